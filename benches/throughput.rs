@@ -0,0 +1,118 @@
+//! Throughput baselines over a synthetic 1M-line corpus: per-line filter matching, highlighting,
+//! ingest (classification + state append) and re-filtering the buffer after a filter change.
+//!
+//! Run with `cargo bench`; the `--bench-ingest FILE` hidden CLI mode measures the same ingest path
+//! end-to-end (file reader included) on real logs.
+
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rtlog::classify::{AlertRule, AlertSeverity, Classifier};
+use rtlog::filter::{line_matches, CompiledFilterSet, FilterRule};
+use rtlog::ui::highlight_line;
+use rtlog::log::LogLine;
+use rtlog::state::AppState;
+
+const CORPUS_LINES: usize = 1_000_000;
+
+/// Deterministic log-like lines (levels, components, request ids, durations)
+fn corpus() -> Vec<Arc<str>> {
+    const LEVELS: [&str; 5] = ["INFO", "DEBUG", "WARN", "ERROR", "INFO"];
+    const COMPONENTS: [&str; 6] = ["api", "db.pool", "auth", "scheduler", "cache", "http.client"];
+    let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+    (0..CORPUS_LINES).map(|i| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let level = LEVELS[(seed % 5) as usize];
+        let component = COMPONENTS[((seed >> 8) % 6) as usize];
+        format!(
+            "2025-09-17 11:{:02}:{:02}.{:03} {:5} [{}] request_id=req-{:06x} handled in {}ms status={}",
+            (i / 60_000) % 60, (i / 1000) % 60, i % 1000, level, component, seed & 0xFF_FFFF, (seed >> 24) % 900, 200 + (seed >> 40) % 4 * 100,
+        ).into()
+    }).collect()
+}
+
+fn rule(pattern: &str, is_regex: bool) -> FilterRule {
+    FilterRule { pattern: pattern.into(), is_regex, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None }
+}
+
+/// A typical filter list: mostly plain substrings plus a few regexes
+fn rules() -> Vec<FilterRule> {
+    vec![
+        rule("timeout", false), rule("db.pool", false), rule("scheduler", false), rule("refused", false),
+        rule(r"status=5\d\d", true), rule(r"handled in [89]\d\dms", true), rule(r"req-00[0-9a-f]{4}", true),
+    ]
+}
+
+fn bench_line_matches(c: &mut Criterion, lines: &[Arc<str>]) {
+    let set = CompiledFilterSet::new(&rules());
+    let mut group = c.benchmark_group("line_matches");
+    group.sample_size(10).throughput(Throughput::Elements(lines.len() as u64));
+    group.bench_function("1M lines, 7 rules", |b| {
+        b.iter(|| lines.iter().filter(|l| line_matches(l, &set)).count())
+    });
+    group.finish();
+}
+
+fn bench_highlight(c: &mut Criterion, lines: &[Arc<str>]) {
+    let set = CompiledFilterSet::new(&rules());
+    let mut group = c.benchmark_group("highlight_line");
+    group.sample_size(10).throughput(Throughput::Elements(lines.len() as u64));
+    group.bench_function("1M lines, 7 rules", |b| {
+        b.iter(|| lines.iter().map(|l| highlight_line(l, &set, None).spans.len()).sum::<usize>())
+    });
+    group.finish();
+}
+
+fn bench_ingest(c: &mut Criterion, lines: &[Arc<str>]) {
+    let classifier = Classifier::new(1, &rules(), &[AlertRule::new("FATAL".into(), false, AlertSeverity::Warn)]);
+    let mut group = c.benchmark_group("ingest");
+    group.sample_size(10).throughput(Throughput::Elements(lines.len() as u64));
+    group.bench_function("classify", |b| {
+        b.iter(|| lines.iter().map(|l| classifier.classify(l).filter_hits.len()).sum::<usize>())
+    });
+    group.bench_function("push_line_for (unclassified)", |b| {
+        b.iter_batched(
+            || {
+                let mut state = AppState::new(None, vec![(AlertSeverity::Warn, "FATAL".into())]);
+                state.set_sources([(None, PathBuf::from("bench.log"))]);
+                state
+            },
+            |mut state| {
+                for l in lines { state.push_line_for(0, LogLine::new(Arc::clone(l))); }
+                black_box(state)
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+fn bench_filter_recompute(c: &mut Criterion, lines: &[Arc<str>]) {
+    let mut group = c.benchmark_group("filter_recompute");
+    group.sample_size(10).throughput(Throughput::Elements(lines.len() as u64));
+    // Toggling a filter rebuilds the rule set and re-selects the matching lines of the buffer
+    group.bench_function("rebuild + rescan 1M lines", |b| {
+        let mut rules = rules();
+        b.iter(|| {
+            rules[0].enabled = !rules[0].enabled;
+            let set = CompiledFilterSet::new(&rules);
+            lines.iter().enumerate().filter(|(_, l)| line_matches(l, &set)).map(|(i, _)| i).collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    let lines = corpus();
+    bench_line_matches(c, &lines);
+    bench_highlight(c, &lines);
+    bench_ingest(c, &lines);
+    bench_filter_recompute(c, &lines);
+}
+
+criterion_group!(throughput, benches);
+criterion_main!(throughput);
@@ -0,0 +1,365 @@
+//! Agent protocol: one viewer tailing the sources of rtlog instances on other machines.
+//!
+//! `rtlog agent --connect VIEWER:PORT` follows its sources without a terminal and streams them
+//! to a viewer started with `--agents PORT`, reconnecting when the connection drops. The
+//! connection carries frames of a 4-byte big-endian length followed by a JSON object:
+//!
+//! - `{"type":"hello","protocol":1,"host":"web1"}` opens the stream
+//! - `{"type":"source","id":0,"name":"app.log","path":"/var/log/app.log","error":null,"waiting":null}`
+//!   announces a source, and again whenever its name, error or waiting state changes
+//! - `{"type":"lines","source":0,"lines":["..."]}` carries a source's new lines
+//!
+//! The viewer shows each remote source as `HOST/NAME`, grouped by host. Agents send the lines
+//! that pass their own filters, after transforms and redaction. There is no authentication or
+//! encryption.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::log::{EventSender, LogLine, LogSource, SourceEvent};
+use crate::net::serve_connections;
+use crate::state::{AppState, LineId};
+
+/// Port agents connect to unless told otherwise
+pub const DEFAULT_PORT: u16 = 7410;
+
+/// Version of the frames below; both ends must speak the same
+const PROTOCOL: u32 = 1;
+
+/// Largest frame accepted
+const MAX_FRAME: usize = 16 * 1024 * 1024;
+
+/// Lines per `lines` frame
+const BATCH_LINES: usize = 1000;
+
+/// Bytes of frames an agent keeps while the viewer is unreachable; older lines are dropped
+const MAX_BACKLOG: usize = 64 * 1024 * 1024;
+
+/// Pause between two connection attempts of an agent
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Frame {
+    Hello { protocol: u32, host: String },
+    Source { id: usize, name: String, path: String, error: Option<String>, waiting: Option<String> },
+    Lines { source: usize, lines: Vec<String> },
+}
+
+impl Frame {
+    /// Length prefix and JSON body
+    fn encode(&self) -> Vec<u8> {
+        let body = serde_json::to_vec(self).unwrap_or_default();
+        let mut out = Vec::with_capacity(4 + body.len());
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+}
+
+/// Next frame from `reader`; None when the peer closed the connection between frames
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Frame>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME { bail!("frame of {} bytes exceeds {}", len, MAX_FRAME); }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Change in an agent's connection to the viewer, for the program running it to report
+#[derive(Debug, Clone, PartialEq)]
+pub enum UplinkEvent {
+    /// The viewer cannot be reached, with the reason; retried, and not reported again until
+    /// connected
+    Unreachable(String),
+    /// Connected, after dropping `dropped` batches of lines the backlog had no room for
+    Connected { dropped: usize },
+    /// The connection failed, with the reason; reconnecting
+    Lost(String),
+}
+
+/// Streams the sources of a headless session to a viewer (`rtlog agent`)
+pub struct AgentClient {
+    frames: mpsc::UnboundedSender<Frame>,
+    /// Last announcement sent per source id
+    announced: HashMap<usize, Frame>,
+    /// Per source id, the first line not yet sent
+    published: HashMap<usize, LineId>,
+    task: JoinHandle<()>,
+}
+
+impl AgentClient {
+    /// Connect to the viewer at `addr` (`HOST:PORT`) in the background, as `host`; the connection
+    /// state is reported on the receiver returned
+    pub fn start(addr: String, host: String) -> (Self, mpsc::UnboundedReceiver<UplinkEvent>) {
+        let (frames, rx) = mpsc::unbounded_channel();
+        let (events, events_rx) = mpsc::unbounded_channel();
+        let uplink = Uplink { addr, host, sources: BTreeMap::new(), backlog: VecDeque::new(), backlog_bytes: 0, dropped: 0, events };
+        let task = tokio::spawn(uplink.run(rx));
+        (Self { frames, announced: HashMap::new(), published: HashMap::new(), task }, events_rx)
+    }
+
+    /// Queue the sources that changed and every source's lines received since the last call
+    pub fn publish(&mut self, state: &AppState) {
+        for src in &state.sources {
+            let path = src.command.clone().unwrap_or_else(|| src.path.display().to_string());
+            let source = Frame::Source { id: src.id, name: src.name.clone(), path, error: src.error.clone(), waiting: src.waiting.clone() };
+            if self.announced.get(&src.id) != Some(&source) {
+                let _ = self.frames.send(source.clone());
+                self.announced.insert(src.id, source);
+            }
+            let end = src.line_id(src.lines.len());
+            let start = self.published.insert(src.id, end).unwrap_or(src.first_id);
+            if end <= start { continue; }
+            // Separators are left to the viewer, which inserts its own
+            let mut lines: Vec<String> = state.shown_lines_rev(src).map(|(i, _)| i)
+                .take_while(|&i| src.line_id(i) >= start)
+                .filter(|&i| !src.is_separator(i))
+                .map(|i| state.redactor.redact(&src.lines[i]).into_owned())
+                .collect();
+            lines.reverse();
+            for batch in lines.chunks(BATCH_LINES) {
+                let _ = self.frames.send(Frame::Lines { source: src.id, lines: batch.to_vec() });
+            }
+        }
+    }
+
+    /// Send what is queued, giving up after `timeout` (e.g. with the viewer unreachable)
+    pub async fn close(self, timeout: Duration) {
+        let Self { frames, mut task, .. } = self;
+        drop(frames);
+        if tokio::time::timeout(timeout, &mut task).await.is_err() { task.abort(); }
+    }
+}
+
+/// The agent's side of the connection, kept across reconnects
+struct Uplink {
+    addr: String,
+    host: String,
+    /// Latest announcement per source, sent again after reconnecting
+    sources: BTreeMap<usize, Vec<u8>>,
+    /// Encoded frames not yet written
+    backlog: VecDeque<Vec<u8>>,
+    backlog_bytes: usize,
+    /// Frames of lines dropped from the backlog since the last report
+    dropped: usize,
+    events: mpsc::UnboundedSender<UplinkEvent>,
+}
+
+impl Uplink {
+    async fn run(mut self, mut frames: mpsc::UnboundedReceiver<Frame>) {
+        let mut open = true;
+        let mut reported = false;
+        while open || !self.backlog.is_empty() {
+            let stream = match TcpStream::connect(&self.addr).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    if !open { return; }
+                    if !std::mem::replace(&mut reported, true) { let _ = self.events.send(UplinkEvent::Unreachable(e.to_string())); }
+                    let retry = tokio::time::sleep(RECONNECT_DELAY);
+                    tokio::pin!(retry);
+                    while open {
+                        tokio::select! {
+                            _ = &mut retry => break,
+                            frame = frames.recv() => match frame {
+                                Some(frame) => self.queue(frame),
+                                None => open = false,
+                            },
+                        }
+                    }
+                    continue;
+                }
+            };
+            reported = false;
+            let _ = self.events.send(UplinkEvent::Connected { dropped: std::mem::take(&mut self.dropped) });
+            match self.stream(stream, &mut frames, &mut open).await {
+                Ok(()) => return,
+                Err(e) => { let _ = self.events.send(UplinkEvent::Lost(e.to_string())); }
+            }
+        }
+    }
+
+    /// Write the hello, the sources and then the frames as they come, until the channel closes
+    /// and everything is written (Ok) or the connection fails
+    async fn stream(&mut self, mut stream: TcpStream, frames: &mut mpsc::UnboundedReceiver<Frame>, open: &mut bool) -> Result<()> {
+        stream.write_all(&Frame::Hello { protocol: PROTOCOL, host: self.host.clone() }.encode()).await?;
+        for source in self.sources.values() { stream.write_all(source).await?; }
+        let mut buf = [0u8; 64];
+        loop {
+            while let Some(frame) = self.backlog.front() {
+                stream.write_all(frame).await?;
+                self.backlog_bytes -= frame.len();
+                self.backlog.pop_front();
+            }
+            if !*open { return Ok(stream.shutdown().await?); }
+            tokio::select! {
+                frame = frames.recv() => match frame {
+                    Some(frame) => self.queue(frame),
+                    None => *open = false,
+                },
+                // The viewer never writes; a read returning means it went away
+                read = stream.read(&mut buf) => match read {
+                    Ok(0) => bail!("closed by the viewer"),
+                    Ok(_) => bail!("unexpected data from the viewer"),
+                    Err(e) => return Err(e.into()),
+                },
+            }
+        }
+    }
+
+    fn queue(&mut self, frame: Frame) {
+        let bytes = frame.encode();
+        if let Frame::Source { id, .. } = frame { self.sources.insert(id, bytes.clone()); }
+        self.backlog_bytes += bytes.len();
+        self.backlog.push_back(bytes);
+        while self.backlog_bytes > MAX_BACKLOG && let Some(old) = self.backlog.pop_front() {
+            self.backlog_bytes -= old.len();
+            self.dropped += 1;
+        }
+    }
+}
+
+/// Accepts agent connections on `addr` until aborted; each remote source becomes a source
+/// `HOST/NAME` (see `SourceEvent::Record`)
+pub struct AgentListener {
+    pub addr: String,
+}
+
+#[async_trait::async_trait]
+impl LogSource for AgentListener {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        let local = listener.local_addr()?;
+        if tx.send((source_id, SourceEvent::Line(LogLine::new(format!("listening for agents on {}", local))))).await.is_err() { return Ok(()); }
+        serve_connections(listener, |sock, peer| {
+            let tx = tx.clone();
+            async move {
+                let mut host = None;
+                let msg = match serve(sock, &mut host, source_id, &tx).await {
+                    Ok(()) => format!("{} disconnected", host.as_deref().unwrap_or("agent")),
+                    Err(e) => format!("{} ({}) dropped: {}", host.as_deref().unwrap_or("agent"), peer, e),
+                };
+                let _ = tx.send((source_id, SourceEvent::Line(LogLine::new(msg)))).await;
+            }
+        }).await;
+        Ok(())
+    }
+}
+
+/// Read one agent's frames until it disconnects; `host` is set once it said hello
+async fn serve(sock: TcpStream, host: &mut Option<String>, source_id: usize, tx: &EventSender) -> Result<()> {
+    let peer = sock.peer_addr()?;
+    let mut reader = tokio::io::BufReader::new(sock);
+    let name = match read_frame(&mut reader).await? {
+        Some(Frame::Hello { protocol, host }) if protocol == PROTOCOL => host,
+        Some(Frame::Hello { protocol, .. }) => bail!("agent speaks protocol {}, this rtlog {}", protocol, PROTOCOL),
+        Some(_) => bail!("expected hello"),
+        None => return Ok(()),
+    };
+    let host = host.insert(name);
+    if tx.send((source_id, SourceEvent::Line(LogLine::new(format!("{} connected from {}", host, peer))))).await.is_err() { return Ok(()); }
+    let mut sources: HashMap<usize, (String, Option<String>, Option<String>)> = HashMap::new();
+    while let Some(frame) = read_frame(&mut reader).await? {
+        match frame {
+            Frame::Source { id, name, error, waiting, .. } => {
+                let tag = format!("{}/{}", host, name);
+                let (old_error, old_waiting) = sources.get(&id).map(|(_, e, w)| (e.clone(), w.clone())).unwrap_or_default();
+                for (new, old) in [(&error, old_error), (&waiting, old_waiting)] {
+                    if let Some(text) = new.as_ref().filter(|&text| Some(text) != old.as_ref())
+                        && tx.send((source_id, SourceEvent::Notice(format!("{}: {}", tag, text)))).await.is_err() { return Ok(()); }
+                }
+                sources.insert(id, (tag, error, waiting));
+            }
+            Frame::Lines { source, lines } => {
+                let Some((tag, ..)) = sources.get(&source) else { bail!("lines for unannounced source {}", source) };
+                for text in lines {
+                    let ev = SourceEvent::Record { tag: tag.clone(), line: LogLine::new(text) };
+                    if tx.send((source_id, ev)).await.is_err() { return Ok(()); }
+                }
+            }
+            Frame::Hello { .. } => bail!("unexpected hello"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::SharedClassifier;
+    use crate::filter::build_filter;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_agent_to_viewer() {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            drop(listener);
+            let (raw_tx, mut rx) = mpsc::channel(64);
+            let viewer = tokio::spawn(AgentListener { addr: addr.clone() }.stream(7, EventSender::new(raw_tx, SharedClassifier::default())));
+            assert!(matches!(rx.recv().await, Some((7, SourceEvent::Line(_)))));
+
+            let mut state = AppState::new(build_filter(Some("disk")).unwrap(), Vec::new());
+            state.set_sources([(None, PathBuf::from("/var/log/app.log"))]);
+            let id = state.sources[0].id;
+            let (mut agent, mut uplink) = AgentClient::start(addr, "web1".into());
+            state.push_line_for(id, LogLine::new("INFO started"));
+            state.push_line_for(id, LogLine::new("ERROR disk full"));
+            // The viewer's own separators stay local; a line of the log shaped like one does not
+            state.insert_marker(false);
+            state.push_line_for(id, LogLine::new("---- MARK 10:00:00 disk check ----"));
+            agent.publish(&state);
+            state.set_source_waiting(id, Some("rotated".into()));
+            state.push_line_for(id, LogLine::new("WARN disk at 90%"));
+            agent.publish(&state);
+            agent.close(Duration::from_secs(5)).await;
+            assert_eq!(uplink.recv().await, Some(UplinkEvent::Connected { dropped: 0 }));
+            assert_eq!(uplink.recv().await, None);
+
+            let mut events = Vec::new();
+            while events.len() < 6 {
+                match rx.recv().await.unwrap().1 {
+                    SourceEvent::Line(line) => events.push(line.text.to_string()),
+                    SourceEvent::Record { tag, line } => events.push(format!("{} {}", tag, line.text)),
+                    SourceEvent::Notice(text) => events.push(text),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            assert!(events[0].starts_with("web1 connected from 127.0.0.1:"));
+            assert_eq!(events[1..], ["web1/app.log ERROR disk full", "web1/app.log ---- MARK 10:00:00 disk check ----", "web1/app.log: rotated", "web1/app.log WARN disk at 90%", "web1 disconnected"]);
+            viewer.abort();
+        });
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let frame = Frame::Lines { source: 3, lines: vec!["a".into(), "b\u{e9}".into()] };
+            let bytes = [frame.encode(), Frame::Hello { protocol: 1, host: "h".into() }.encode()].concat();
+            let mut reader = bytes.as_slice();
+            assert_eq!(read_frame(&mut reader).await.unwrap(), Some(frame));
+            assert!(matches!(read_frame(&mut reader).await.unwrap(), Some(Frame::Hello { .. })));
+            assert_eq!(read_frame(&mut reader).await.unwrap(), None);
+            let mut truncated = &bytes[..5];
+            assert!(read_frame(&mut truncated).await.is_err());
+            let huge = (MAX_FRAME as u32 + 1).to_be_bytes();
+            assert!(read_frame(&mut huge.as_slice()).await.is_err());
+        });
+    }
+}
@@ -0,0 +1,366 @@
+//! Ingest-time line classification.
+//!
+//! Filter hit counting, level detection and alert matching run in the reader tasks (see
+//! `log::EventSender`) rather than on the UI thread, so bursts of input don't stall key handling.
+//! Readers see the rules through a `SharedClassifier` that the state swaps whenever filters change;
+//! every result carries the generation it was computed with, and results from an outdated
+//! generation are recomputed by the state.
+
+use std::sync::{Arc, OnceLock, RwLock};
+
+use regex::{Regex, RegexSet};
+use serde::Deserialize;
+
+use crate::filter::{FilterRule, CompiledFilterSet};
+
+/// Default level patterns: level words as whole words, so "0 errors" or "error_count" don't count
+pub const DEFAULT_ERROR_PATTERN: &str = r"(?i)\b(error|fatal|crit(ical)?|panic|emerg(ency)?)\b";
+pub const DEFAULT_WARN_PATTERN: &str = r"(?i)\bwarn(ing)?\b";
+pub const DEFAULT_INFO_PATTERN: &str = r"(?i)\b(info|notice)\b";
+
+/// Result of classifying one line
+#[derive(Debug, Clone, Default)]
+pub struct LineClass {
+    /// Generation of the classifier that produced this; 0 means not classified
+    pub generation: u64,
+    pub is_error: bool,
+    pub is_warn: bool,
+    pub is_info: bool,
+    /// Highest severity among the alert rules matching the line
+    pub alert: Option<AlertSeverity>,
+    /// Positions of the enabled filter rules matching the line
+    pub filter_hits: Vec<usize>,
+    /// Numeric values of the named groups (`(?P<ms>\d+)`) of the matching filters
+    pub fields: Vec<(Arc<str>, f64)>,
+    /// A separator rtlog inserted (a marker, a `--watch` run, a timestamp gap) rather than a line
+    /// of the input: never classified, shown whatever the filters and counted nowhere
+    pub separator: bool,
+}
+
+impl LineClass {
+    /// Level the line counts as: the most severe one it mentions
+    pub fn level(&self) -> Option<Level> {
+        if self.is_error { Some(Level::Error) } else if self.is_warn { Some(Level::Warn) } else if self.is_info { Some(Level::Info) } else { None }
+    }
+
+    /// The same class counting as `level` only, as read from a structured line's level field
+    pub fn with_level(self, level: Option<Level>) -> Self {
+        Self { is_error: level == Some(Level::Error), is_warn: level == Some(Level::Warn), is_info: level == Some(Level::Info), ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level { Error, Warn, Info }
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self { Level::Error => "error", Level::Warn => "warn", Level::Info => "info" }
+    }
+}
+
+/// Regexes deciding which levels a line mentions; an empty pattern disables its level
+#[derive(Debug, Clone)]
+pub struct LevelRules {
+    set: RegexSet,
+    /// Level of each pattern in `set`
+    levels: Vec<Level>,
+}
+
+impl LevelRules {
+    pub fn new(error: &str, warn: &str, info: &str) -> Result<Self, regex::Error> {
+        let patterns: Vec<(Level, &str)> = [(Level::Error, error), (Level::Warn, warn), (Level::Info, info)]
+            .into_iter().filter(|(_, p)| !p.is_empty()).collect();
+        Ok(Self { set: RegexSet::new(patterns.iter().map(|(_, p)| p))?, levels: patterns.iter().map(|(l, _)| *l).collect() })
+    }
+
+    /// Whether the line mentions the error, warn and info level
+    fn detect(&self, text: &str) -> [bool; 3] {
+        let mut found = [false; 3];
+        for i in self.set.matches(text).iter() { found[self.levels[i] as usize] = true; }
+        found
+    }
+
+    /// The level `text` counts as (see `LineClass::level`)
+    pub fn level(&self, text: &str) -> Option<Level> {
+        let [error, warn, info] = self.detect(text);
+        if error { Some(Level::Error) } else if warn { Some(Level::Warn) } else if info { Some(Level::Info) } else { None }
+    }
+}
+
+/// Level patterns of the sources whose name matches the regex; the first match wins
+pub type LevelOverrides = Vec<(regex::Regex, Arc<LevelRules>)>;
+
+impl Default for LevelRules {
+    fn default() -> Self {
+        static DEFAULT: OnceLock<LevelRules> = OnceLock::new();
+        DEFAULT.get_or_init(|| Self::new(DEFAULT_ERROR_PATTERN, DEFAULT_WARN_PATTERN, DEFAULT_INFO_PATTERN).expect("static patterns")).clone()
+    }
+}
+
+/// Running number of lines per level
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelCounts {
+    pub error: usize,
+    pub warn: usize,
+    pub info: usize,
+}
+
+impl LevelCounts {
+    pub fn count(&mut self, class: &LineClass) {
+        match class.level() {
+            Some(Level::Error) => self.error += 1,
+            Some(Level::Warn) => self.warn += 1,
+            Some(Level::Info) => self.info += 1,
+            None => {}
+        }
+    }
+
+    pub fn add(&mut self, other: &LevelCounts) {
+        self.error += other.error;
+        self.warn += other.warn;
+        self.info += other.info;
+    }
+}
+
+/// Running summary of a numeric field's values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldStats {
+    pub count: usize,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for FieldStats {
+    fn default() -> Self {
+        Self { count: 0, sum: 0.0, min: f64::INFINITY, max: f64::NEG_INFINITY }
+    }
+}
+
+impl FieldStats {
+    pub fn record(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn add(&mut self, other: &FieldStats) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn avg(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.sum / self.count as f64 }
+    }
+}
+
+/// Field summaries by field name
+pub type FieldMap = std::collections::BTreeMap<Arc<str>, FieldStats>;
+
+/// How loud an alert is: banner color, how long it blinks, whether it rings the terminal bell and
+/// whether the banner stays until acknowledged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    #[default]
+    Warn,
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "info" => Some(AlertSeverity::Info),
+            "warn" | "warning" => Some(AlertSeverity::Warn),
+            "critical" | "crit" => Some(AlertSeverity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self { AlertSeverity::Info => "info", AlertSeverity::Warn => "warn", AlertSeverity::Critical => "critical" }
+    }
+
+    /// How long the banner and matching lines blink
+    pub fn blink_ms(self) -> u128 {
+        match self { AlertSeverity::Info => 0, AlertSeverity::Warn => 10_000, AlertSeverity::Critical => 30_000 }
+    }
+
+    /// Ring the terminal bell when raised
+    pub fn sound(self) -> bool { self == AlertSeverity::Critical }
+
+    /// Keep the banner until the user acknowledges it
+    pub fn sticky(self) -> bool { self == AlertSeverity::Critical }
+}
+
+/// An alert pattern with its severity
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub rule: FilterRule,
+    pub severity: AlertSeverity,
+    /// Shown instead of the pattern in the alert history and notices
+    pub name: Option<String>,
+    /// Color of the lines it matches instead of the severity's, as written in the config file
+    pub color: Option<String>,
+}
+
+impl AlertRule {
+    /// Case-insensitive `pattern`, a literal unless `is_regex`, with no name or color
+    pub fn new(pattern: String, is_regex: bool, severity: AlertSeverity) -> Self {
+        let mut rule = FilterRule::simple(pattern, is_regex);
+        rule.ensure_compiled();
+        Self { rule, severity, name: None, color: None }
+    }
+
+    /// Its name, or else its pattern
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.rule.pattern)
+    }
+}
+
+/// Compiled filter and alert rules at one point in time
+#[derive(Debug, Default)]
+pub struct Classifier {
+    pub generation: u64,
+    pub filters: CompiledFilterSet,
+    /// Alert rules by severity (indexed by `AlertSeverity as usize`)
+    alerts: [CompiledFilterSet; 3],
+    /// Level patterns of sources without an override
+    pub levels: Arc<LevelRules>,
+    /// Enabled filters with named groups, by position in the rule list, with the group names
+    fields: Vec<(usize, Regex, Vec<Arc<str>>)>,
+}
+
+impl Classifier {
+    pub fn new(generation: u64, filters: &[FilterRule], alerts: &[AlertRule]) -> Self {
+        let tier = |severity| {
+            let rules: Vec<FilterRule> = alerts.iter().filter(|a| a.severity == severity).map(|a| a.rule.clone()).collect();
+            CompiledFilterSet::new(&rules)
+        };
+        let alerts = [tier(AlertSeverity::Info), tier(AlertSeverity::Warn), tier(AlertSeverity::Critical)];
+        let fields = filters.iter().enumerate()
+            .filter(|(_, r)| r.enabled && r.is_regex && !r.exclude)
+            .filter_map(|(i, r)| {
+                let re = r.compiled.clone().or_else(|| r.compile().ok())?;
+                let names: Vec<Arc<str>> = re.capture_names().flatten().map(Arc::from).collect();
+                (!names.is_empty()).then_some((i, re, names))
+            })
+            .collect();
+        Self { generation, filters: CompiledFilterSet::new(filters), alerts, levels: Arc::default(), fields }
+    }
+
+    /// Enabled regex filters that have named groups
+    pub fn field_regexes(&self) -> impl Iterator<Item = &Regex> {
+        self.fields.iter().map(|(_, re, _)| re)
+    }
+
+    /// Names of the fields, each once, in the order of the filters
+    pub fn field_names(&self) -> Vec<Arc<str>> {
+        let mut out: Vec<Arc<str>> = Vec::new();
+        for name in self.fields.iter().flat_map(|(_, _, names)| names) {
+            if !out.contains(name) { out.push(Arc::clone(name)); }
+        }
+        out
+    }
+
+    pub fn has_field(&self, name: &str) -> bool {
+        self.fields.iter().any(|(_, _, names)| names.iter().any(|n| &**n == name))
+    }
+
+    /// Text of field `name` in `text`, from the first filter with such a group that captures it
+    pub fn field_value<'a>(&self, text: &'a str, name: &str) -> Option<&'a str> {
+        self.fields.iter()
+            .filter(|(_, _, names)| names.iter().any(|n| &**n == name))
+            .find_map(|(_, re, _)| re.captures(text)?.name(name).map(|m| m.as_str()))
+    }
+
+    /// Numeric values of the named groups of the filters in `hits` that match `text`
+    fn fields(&self, text: &str, hits: &[usize]) -> Vec<(Arc<str>, f64)> {
+        let mut values = Vec::new();
+        for (i, re, names) in &self.fields {
+            if !hits.contains(i) { continue; }
+            let Some(caps) = re.captures(text) else { continue; };
+            for name in names {
+                if let Some(value) = caps.name(name).and_then(|m| m.as_str().parse::<f64>().ok()) {
+                    values.push((Arc::clone(name), value));
+                }
+            }
+        }
+        values
+    }
+
+    /// Highest severity of the alert rules matching `text`
+    pub fn alert_severity(&self, text: &str) -> Option<AlertSeverity> {
+        [AlertSeverity::Critical, AlertSeverity::Warn, AlertSeverity::Info].into_iter()
+            .find(|&severity| self.alerts[severity as usize].is_match(text))
+    }
+
+    /// Same rules with other default level patterns
+    pub fn with_levels(self, levels: Arc<LevelRules>) -> Self {
+        Self { levels, ..self }
+    }
+
+    pub fn classify(&self, text: &str) -> LineClass {
+        self.classify_with(text, &self.levels)
+    }
+
+    /// Classify with the level patterns of a source that overrides the defaults
+    pub fn classify_with(&self, text: &str, levels: &LevelRules) -> LineClass {
+        let [is_error, is_warn, is_info] = levels.detect(text);
+        let filter_hits = self.filters.matching_rules(text);
+        let fields = if self.fields.is_empty() { Vec::new() } else { self.fields(text, &filter_hits) };
+        LineClass {
+            generation: self.generation,
+            is_error,
+            is_warn,
+            is_info,
+            alert: self.alert_severity(text),
+            filter_hits,
+            fields,
+            separator: false,
+        }
+    }
+}
+
+/// The current classifier, shared between the state (writer) and the reader tasks
+#[derive(Debug, Clone, Default)]
+pub struct SharedClassifier(Arc<RwLock<Arc<Classifier>>>);
+
+impl SharedClassifier {
+    pub fn get(&self) -> Arc<Classifier> {
+        Arc::clone(&self.0.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn set(&self, classifier: Arc<Classifier>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = classifier;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_line() {
+        let rule = |pattern: &str| FilterRule { pattern: pattern.into(), is_regex: false, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None };
+        let alert = |pattern: &str, severity| AlertRule::new(pattern.into(), false, severity);
+        let classifier = Classifier::new(3, &[rule("db"), rule("timeout")], &[alert("fatal", AlertSeverity::Critical), alert("timeout", AlertSeverity::Info)]);
+        let class = classifier.classify("WARN db: Error while connecting");
+        assert_eq!(class.generation, 3);
+        assert!(class.is_error && class.is_warn && class.alert.is_none());
+        assert_eq!(class.level(), Some(Level::Error));
+        assert_eq!(classifier.classify("INFO warning: retrying").level(), Some(Level::Warn));
+        assert_eq!(classifier.classify("GET /health 200").level(), None);
+        assert_eq!(classifier.classify("done, 0 errors").level(), None);
+        let http = LevelRules::new(r#"" 5\d\d "#, r#"" 4\d\d "#, "").unwrap();
+        assert_eq!(classifier.classify_with(r#""GET / HTTP/1.1" 503 0"#, &http).level(), Some(Level::Error));
+        assert_eq!(classifier.classify_with("INFO started", &http).level(), None);
+        assert_eq!(class.filter_hits, vec![0]);
+        assert_eq!(classifier.classify("FATAL timeout").alert, Some(AlertSeverity::Critical));
+        assert_eq!(classifier.classify("read timeout").alert, Some(AlertSeverity::Info));
+    }
+}
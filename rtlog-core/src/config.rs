@@ -0,0 +1,265 @@
+//! Optional TOML configuration file.
+//!
+//! Loaded from `--config PATH` or, when present, from `$XDG_CONFIG_HOME/rtlog/config.toml`
+//! (fallback `~/.config/rtlog/config.toml`, `%APPDATA%\rtlog\config.toml` on Windows). Settings
+//! here complement the CLI; the CLI layer merges both into the immutable runtime `Config`.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::classify::AlertSeverity;
+use crate::encoding::Encoding;
+use crate::profile::LineFormat;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Commented config file printed by `--print-default-config`: the built-in level patterns,
+/// everything else as commented-out examples
+pub const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    /// Inputs opened in addition to CLI paths (equivalent to `--input NAME=PATH`)
+    pub inputs: Vec<InputEntry>,
+    /// Level classification patterns
+    pub levels: LevelsConfig,
+    /// Alert patterns in addition to `--alert`
+    pub alerts: Vec<AlertEntry>,
+    /// Mailing alerts through an SMTP server
+    pub email: Option<EmailConfig>,
+    /// Rhai scripts rewriting lines before display
+    pub transforms: Vec<TransformEntry>,
+    /// Masking sensitive text on screen and in tee files and emails
+    pub redact: RedactConfig,
+    /// Shortening lines in the log view only
+    pub rewrite: Vec<RewriteRule>,
+    /// How to parse the files matching a glob (`[[sources]]`)
+    pub sources: Vec<SourceProfileEntry>,
+    /// Filters and searches typed in the TUI ignore case unless they have an uppercase letter
+    /// (default true)
+    pub smart_case: Option<bool>,
+    /// Status bar template (see `crate::status`)
+    pub status_bar: Option<String>,
+    /// Columns between tab stops in log lines (default 8)
+    pub tab_width: Option<usize>,
+    /// Filters switched on and off with Alt+1 … Alt+9
+    pub hotkeys: Vec<HotkeyEntry>,
+}
+
+/// `[redact]`: built-in rule sets to enable and custom rules, applied in that order
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Names of built-in rule sets (see `crate::redact::BUILTIN`)
+    pub builtin: Vec<String>,
+    pub rules: Vec<RedactRule>,
+}
+
+/// `[[redact.rules]]`: a regex and what its matches are replaced with (`$1` refers to groups)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedactRule {
+    pub pattern: String,
+    #[serde(default = "default_replacement")]
+    pub replace: String,
+}
+
+fn default_replacement() -> String { crate::redact::DEFAULT_REPLACEMENT.to_string() }
+
+/// `[[rewrite]]`: a regex and what its matches are shown as in the log view (`$1`, `${name}`
+/// refer to groups; empty by default, i.e. the match is hidden)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewriteRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub replace: String,
+}
+
+/// `[[transforms]]`: a Rhai script (inline or from a file) run on every line of the sources
+/// whose name, label, path or command matches `match` (all sources without it)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformEntry {
+    #[serde(rename = "match")]
+    pub pattern: Option<String>,
+    pub script: Option<String>,
+    pub file: Option<PathBuf>,
+}
+
+/// `[[alerts]]`: a case-insensitive pattern (literal unless `regex`), its severity (default
+/// warn) and optionally a name to show instead of the pattern and a color for matching lines
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertEntry {
+    pub name: Option<String>,
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub severity: AlertSeverity,
+    pub color: Option<String>,
+}
+
+/// `[[hotkeys]]`: a case-insensitive filter (literal unless `regex`) that Alt and `key`, 1 to 9,
+/// adds and then disables and re-enables; see `crate::hotkeys`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HotkeyEntry {
+    pub key: u8,
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+}
+
+/// `[[sources]]`: how the lines of the files whose path matches `path` (a glob, matched against
+/// the file name when it has no `/`) are parsed; see `crate::profile`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceProfileEntry {
+    pub path: String,
+    /// Sidebar name of matching files opened without one
+    pub name: Option<String>,
+    #[serde(default)]
+    pub format: LineFormat,
+    /// chrono format of the timestamps (e.g. `%d/%b/%Y:%H:%M:%S %z`), instead of detecting them
+    pub timestamp: Option<String>,
+    /// Regex matching the lines that continue the previous one (stack traces)
+    pub multiline: Option<String>,
+    /// Applied when the line is read, in addition to `[redact]`
+    #[serde(default)]
+    pub redact: RedactConfig,
+    /// Text encoding of the files, instead of telling it from their start
+    pub encoding: Option<Encoding>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputEntry {
+    pub name: Option<String>,
+    pub path: PathBuf,
+}
+
+/// `[levels]`: a regex per level; unset levels keep the built-in pattern, an empty one disables it
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelsConfig {
+    pub error: Option<String>,
+    pub warn: Option<String>,
+    pub info: Option<String>,
+    /// Per-source overrides (`[[levels.source]]`), first match wins
+    pub source: Vec<SourceLevels>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceLevels {
+    /// Regex matched against the source's sidebar name, label, path or command
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Unset levels fall back to the `[levels]` patterns
+    pub error: Option<String>,
+    pub warn: Option<String>,
+    pub info: Option<String>,
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS (port 587)
+    #[default]
+    Starttls,
+    /// TLS from the start (port 465)
+    Tls,
+    /// Unencrypted (port 25), for a relay on the local host or network
+    None,
+}
+
+/// `[email]`: where to mail alerts, which ones and how often
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    /// SMTP server host name
+    pub server: String,
+    /// Defaults to the usual port for `tls`
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    /// Falls back to `$RTLOG_SMTP_PASSWORD`
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Least severe alert that is mailed
+    #[serde(default = "default_min_severity")]
+    pub min_severity: AlertSeverity,
+    /// Minimum time between two emails
+    #[serde(default = "default_min_interval")]
+    pub min_interval_secs: u64,
+}
+
+fn default_min_severity() -> AlertSeverity { AlertSeverity::Critical }
+fn default_min_interval() -> u64 { 300 }
+
+impl FileConfig {
+    /// Load `explicit` (must exist) or the default location (ignored when missing)
+    pub fn load(explicit: Option<&Path>) -> Result<Self> {
+        let path = match explicit {
+            Some(p) => p.to_path_buf(),
+            None => match default_path() {
+                Some(p) if p.is_file() => p,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let text = fs::read_to_string(&path).with_context(|| format!("reading config {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("parsing config {}", path.display()))
+    }
+}
+
+/// Default config file location
+pub fn default_path() -> Option<PathBuf> {
+    let dir = if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        PathBuf::from(dir)
+    } else if let Some(dir) = std::env::var_os("APPDATA").filter(|d| !d.is_empty()) {
+        PathBuf::from(dir)
+    } else {
+        PathBuf::from(std::env::var_os("HOME").filter(|d| !d.is_empty())?).join(".config")
+    };
+    Some(dir.join("rtlog").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::{DEFAULT_ERROR_PATTERN, DEFAULT_INFO_PATTERN, DEFAULT_WARN_PATTERN};
+    use crate::filter::TAB_WIDTH;
+
+    #[test]
+    fn test_default_config() {
+        let config: FileConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.levels.error.as_deref(), Some(DEFAULT_ERROR_PATTERN));
+        assert_eq!(config.levels.warn.as_deref(), Some(DEFAULT_WARN_PATTERN));
+        assert_eq!(config.levels.info.as_deref(), Some(DEFAULT_INFO_PATTERN));
+        assert!(config.inputs.is_empty() && config.alerts.is_empty() && config.email.is_none());
+        assert_eq!(config.smart_case, Some(true));
+        assert!(config.status_bar.is_none());
+        assert_eq!(config.tab_width, Some(TAB_WIDTH));
+        // The commented-out examples are valid too
+        let examples: String = DEFAULT_CONFIG.lines()
+            .map(|l| if l.starts_with("#[") || l.starts_with("#") && l.contains(" = ") { &l[1..] } else { l })
+            .flat_map(|l| [l, "\n"])
+            .collect();
+        let config: FileConfig = toml::from_str(&examples).unwrap();
+        assert_eq!((config.inputs.len(), config.alerts.len(), config.levels.source.len(), config.rewrite.len()), (1, 2, 1, 1));
+        assert_eq!((config.alerts[0].name.as_deref(), config.alerts[0].color.as_deref(), config.alerts[1].regex), (Some("OOM"), Some("magenta"), true));
+        assert_eq!(config.email.map(|e| e.min_interval_secs), Some(300));
+        assert_eq!((config.redact.builtin.len(), config.transforms.len()), (2, 1));
+        assert_eq!(config.hotkeys.iter().map(|h| (h.key, h.regex)).collect::<Vec<_>>(), [(1, true), (2, false)]);
+        assert_eq!(config.sources.iter().map(|s| s.format).collect::<Vec<_>>(), [LineFormat::Json, LineFormat::Plain, LineFormat::Plain]);
+        assert_eq!(config.sources[1].redact.builtin, ["ip"]);
+        assert_eq!(config.sources[2].encoding, Some(Encoding::Utf16Le));
+        assert_eq!(config.status_bar.as_deref(), Some("{levels}{lines} lines  {rate}/s  {filters} filters  {mode}  {source}"));
+    }
+}
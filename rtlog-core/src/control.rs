@@ -0,0 +1,141 @@
+//! Local control socket (`--control PATH`).
+//!
+//! Scripts and editor integrations drive a running viewer through a Unix socket. Each request is
+//! one line of JSON and gets one line of JSON back:
+//!
+//! - `{"cmd":"filter","pattern":"timeout","regex":false}` adds a filter rule (literal and
+//!   case-insensitive unless `regex`)
+//! - `{"cmd":"focus","source":"api.log"}` focuses the source with that sidebar name, label, path
+//!   or command
+//! - `{"cmd":"export","path":"/tmp/api.jsonl","format":"jsonl"}` exports the focused source's
+//!   lines that pass the filters, like `e` (`format` defaults to the export prompt's)
+//! - `{"cmd":"ack"}` acknowledges the alerts, like `a`
+//! - `{"cmd":"status"}` describes the sources, the focused one, the filters and the alerts
+//!
+//! The answer is `{"ok":true}` (with the status fields for `status`) or
+//! `{"ok":false,"error":"..."}`. The socket is created with the permissions of the user's umask
+//! and removed on exit; anyone who can open it controls the viewer.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// Command sent over the control socket
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ControlCommand {
+    Filter { pattern: String, #[serde(default)] regex: bool },
+    Focus { source: String },
+    Export { path: String, format: Option<String> },
+    Ack,
+    Status,
+}
+
+/// A command waiting for the runtime, which owns the state, to carry it out
+#[derive(Debug)]
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    /// Answer: the fields added to `"ok":true`, or the error
+    pub reply: oneshot::Sender<Result<Value, String>>,
+}
+
+/// Listens on the control socket until dropped
+pub struct ControlServer {
+    path: PathBuf,
+    requests: mpsc::Receiver<ControlRequest>,
+    accept_task: JoinHandle<()>,
+}
+
+impl ControlServer {
+    /// Create the socket at `path`, replacing a stale one no process listens on
+    #[cfg(unix)]
+    pub fn bind(path: &Path) -> Result<Self> {
+        use anyhow::Context;
+        use tokio::net::UnixListener;
+        use crate::net::serve_connections;
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                anyhow::bail!("control socket {} is in use by another rtlog", path.display());
+            }
+            std::fs::remove_file(path).with_context(|| format!("removing stale control socket {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(path).with_context(|| format!("creating control socket {}", path.display()))?;
+        let (tx, requests) = mpsc::channel(16);
+        let accept_task = tokio::spawn(serve_connections(listener, move |stream, _| {
+            let requests = tx.clone();
+            async move { let _ = serve(stream, requests).await; }
+        }));
+        Ok(Self { path: path.to_path_buf(), requests, accept_task })
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(_path: &Path) -> Result<Self> {
+        anyhow::bail!("--control needs Unix domain sockets, which this platform lacks")
+    }
+
+    /// Next command to carry out
+    pub async fn next(&mut self) -> Option<ControlRequest> {
+        self.requests.recv().await
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Next request of `server`, or never without one
+pub async fn next_request(server: &mut Option<ControlServer>) -> Option<ControlRequest> {
+    match server {
+        Some(server) => server.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Answer the requests of one connection until it closes
+#[cfg(unix)]
+async fn serve(stream: tokio::net::UnixStream, requests: mpsc::Sender<ControlRequest>) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() { continue; }
+        let answer = match serde_json::from_str::<ControlCommand>(&line) {
+            Ok(command) => {
+                let (reply, answer) = oneshot::channel();
+                requests.send(ControlRequest { command, reply }).await?;
+                answer.await?
+            }
+            Err(e) => Err(format!("invalid command: {}", e)),
+        };
+        let mut answer = match answer {
+            Ok(Value::Object(mut fields)) => { fields.insert("ok".into(), true.into()); Value::Object(fields) }
+            Ok(_) => json!({ "ok": true }),
+            Err(error) => json!({ "ok": false, "error": error }),
+        }.to_string();
+        answer.push('\n');
+        write.write_all(answer.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let parse = |s: &str| serde_json::from_str::<ControlCommand>(s);
+        assert_eq!(parse(r#"{"cmd":"filter","pattern":"timeout"}"#).unwrap(), ControlCommand::Filter { pattern: "timeout".into(), regex: false });
+        assert_eq!(parse(r#"{"cmd":"export","path":"/tmp/a.csv","format":"csv"}"#).unwrap(), ControlCommand::Export { path: "/tmp/a.csv".into(), format: Some("csv".into()) });
+        assert_eq!(parse(r#"{"cmd":"ack"}"#).unwrap(), ControlCommand::Ack);
+        assert!(parse(r#"{"cmd":"focus"}"#).is_err());
+        assert!(parse(r#"{"cmd":"quit"}"#).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Opening the source files that log lines point at (`path:line`, as in stack traces and
+//! compiler output) in the user's editor.
+//!
+//! `--file-pattern` finds the locations in a line: its groups named `file`, `line` and `col`
+//! (optional), or else its first two groups as file and line. The editor is `$VISUAL` or
+//! `$EDITOR`, and the line is passed the way that editor expects.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use regex::{Captures, Regex};
+
+/// Default pattern finding file locations: `path/file.ext:LINE[:COL]` (Rust, Go, Node, gcc, Java
+/// frames like `(Main.java:42)`), .NET's `file.cs:line 42` and Python's `File "x.py", line 42`
+pub const DEFAULT_FILE_PATTERN: &str = r#"(?P<file>[^\s"'()\[\]<>:=,]+\.\w+)(?:", line |:line |:)(?P<line>\d+)(?::(?P<col>\d+))?"#;
+
+/// Editor used without `$VISUAL` and `$EDITOR`
+const FALLBACK_EDITOR: &str = "vi";
+
+/// A line (and column) in a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLocation {
+    pub path: PathBuf,
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+/// The file locations `re` finds in `text`, in order
+pub fn find_locations(text: &str, re: &Regex) -> Vec<FileLocation> {
+    re.captures_iter(text).filter_map(|caps| {
+        let group = |name: &str, index: usize| caps.name(name).or_else(|| unnamed(&caps, re, index)).map(|m| m.as_str());
+        let path = group("file", 1).filter(|p| !p.is_empty())?;
+        let line = group("line", 2)?.parse().ok()?;
+        let column = caps.name("col").and_then(|m| m.as_str().parse().ok());
+        Some(FileLocation { path: PathBuf::from(path), line, column })
+    }).collect()
+}
+
+/// Group `index` of a pattern without named groups
+fn unnamed<'t>(caps: &Captures<'t>, re: &Regex, index: usize) -> Option<regex::Match<'t>> {
+    if re.capture_names().flatten().next().is_some() { return None; }
+    caps.get(index)
+}
+
+/// `location` with a relative path found in the working directory or else in `base` (the
+/// directory of the log file); None when the file is in neither
+pub fn resolve(location: &FileLocation, base: Option<&Path>) -> Option<FileLocation> {
+    let candidates = [Some(location.path.clone()), base.filter(|_| location.path.is_relative()).map(|b| b.join(&location.path))];
+    let path = candidates.into_iter().flatten().find(|p| p.is_file())?;
+    Some(FileLocation { path, ..location.clone() })
+}
+
+/// Command opening `location` in `$VISUAL` or `$EDITOR` (vi without either)
+pub fn editor_command(location: &FileLocation) -> Command {
+    let editor = ["VISUAL", "EDITOR"].iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| FALLBACK_EDITOR.into());
+    command_for(&editor, location)
+}
+
+/// Command running `editor` (a program with its arguments, like `code --wait`) on `location`
+fn command_for(editor: &str, location: &FileLocation) -> Command {
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(FALLBACK_EDITOR);
+    let mut cmd = Command::new(program);
+    cmd.args(words);
+    let name = Path::new(program).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let position = match location.column {
+        Some(column) => format!("{}:{}:{}", location.path.display(), location.line, column),
+        None => format!("{}:{}", location.path.display(), location.line),
+    };
+    match name.as_str() {
+        "code" | "code-insiders" | "codium" | "cursor" => { cmd.arg("--goto").arg(position); }
+        "subl" | "hx" | "helix" | "zed" => { cmd.arg(position); }
+        // vi, vim, nvim, nano, emacs, micro, kak, ...
+        _ => { cmd.arg(format!("+{}", location.line)).arg(&location.path); }
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(text: &str) -> Vec<(String, u32, Option<u32>)> {
+        let re = Regex::new(DEFAULT_FILE_PATTERN).unwrap();
+        find_locations(text, &re).into_iter().map(|l| (l.path.display().to_string(), l.line, l.column)).collect()
+    }
+
+    #[test]
+    fn test_find_locations() {
+        assert_eq!(found("thread 'main' panicked at src/app.rs:42:5:"), [("src/app.rs".into(), 42, Some(5))]);
+        assert_eq!(found("    at handler (/srv/app/index.js:10:3)"), [("/srv/app/index.js".into(), 10, Some(3))]);
+        assert_eq!(found("\tat com.example.Main.run(Main.java:27)"), [("Main.java".into(), 27, None)]);
+        assert_eq!(found(r#"  File "/opt/app/worker.py", line 88, in run"#), [("/opt/app/worker.py".into(), 88, None)]);
+        assert_eq!(found("in Handler.cs:line 12 and util.go:7"), [("Handler.cs".into(), 12, None), ("util.go".into(), 7, None)]);
+        assert!(found("GET /health 200 12ms").is_empty());
+        // Unnamed groups: the first is the file, the second the line
+        let re = Regex::new(r"at (\S+) line (\d+)").unwrap();
+        assert_eq!(find_locations("die at lib/Foo.pm line 9.", &re), [FileLocation { path: "lib/Foo.pm".into(), line: 9, column: None }]);
+    }
+
+    #[test]
+    fn test_editor_commands() {
+        let location = FileLocation { path: "src/app.rs".into(), line: 42, column: Some(5) };
+        let args = |editor: &str| {
+            let cmd = command_for(editor, &location);
+            std::iter::once(cmd.get_program()).chain(cmd.get_args()).map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>()
+        };
+        assert_eq!(args("nvim"), ["nvim", "+42", "src/app.rs"]);
+        assert_eq!(args("code --wait"), ["code", "--wait", "--goto", "src/app.rs:42:5"]);
+        assert_eq!(args("/usr/local/bin/hx"), ["/usr/local/bin/hx", "src/app.rs:42:5"]);
+    }
+}
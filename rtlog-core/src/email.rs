@@ -0,0 +1,171 @@
+//! Mailing alerts through an SMTP server (`[email]` in the config file).
+//!
+//! Raised alerts of at least the configured severity are queued to a sender task without ever
+//! blocking the UI (when the queue is full they are dropped). The task mails them one by one, but
+//! no more often than every `min_interval_secs`: alerts raised in between are collected and sent
+//! together in the next email. Whatever is still queued is sent right away when the app exits.
+//! Alert messages are redacted (see `crate::redact`) before they are queued.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::classify::AlertSeverity;
+use crate::config::{EmailConfig, SmtpTls};
+use crate::redact::Redactor;
+use crate::state::AlertEntry;
+
+/// Alerts listed in one email; the rest are only counted
+const MAX_LISTED: usize = 100;
+/// Environment variable holding the SMTP password when the config file has none
+pub const PASSWORD_ENV: &str = "RTLOG_SMTP_PASSWORD";
+
+/// Handle for queueing alerts to the sender task
+pub struct EmailAlerts {
+    tx: Sender<AlertEntry>,
+    min_severity: AlertSeverity,
+    redactor: Arc<Redactor>,
+    /// Latest failure to send, taken by the UI to show it
+    error: Arc<Mutex<Option<String>>>,
+    task: JoinHandle<()>,
+}
+
+impl EmailAlerts {
+    /// Check the addresses and start the sender task
+    pub fn start(config: &EmailConfig, redactor: Arc<Redactor>) -> Result<Self> {
+        let from: Mailbox = config.from.parse().with_context(|| format!("email: invalid from address {:?}", config.from))?;
+        let to = config.to.iter()
+            .map(|a| a.parse::<Mailbox>().with_context(|| format!("email: invalid recipient {:?}", a)))
+            .collect::<Result<Vec<_>>>()?;
+        anyhow::ensure!(!to.is_empty(), "email: no recipients");
+        let mut transport = match config.tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.server)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.server)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.server),
+        }.timeout(Some(Duration::from_secs(30)));
+        if let Some(port) = config.port { transport = transport.port(port); }
+        if let Some(user) = &config.username {
+            let password = config.password.clone().or_else(|| std::env::var(PASSWORD_ENV).ok())
+                .with_context(|| format!("email: no password for {} (set it in the config file or ${})", user, PASSWORD_ENV))?;
+            transport = transport.credentials(Credentials::new(user.clone(), password));
+        }
+        let (tx, rx) = mpsc::channel(256);
+        let error = Arc::new(Mutex::new(None));
+        let mailer = Mailer { transport: transport.build(), from, to, error: Arc::clone(&error) };
+        let task = tokio::spawn(mailer.run(rx, Duration::from_secs(config.min_interval_secs)));
+        Ok(Self { tx, min_severity: config.min_severity, redactor, error, task })
+    }
+
+    /// Queue `alert` if it is severe enough
+    pub fn send(&self, alert: &AlertEntry) {
+        if alert.severity < self.min_severity { return; }
+        let message = self.redactor.redact(&alert.message).into_owned();
+        let _ = self.tx.try_send(AlertEntry { message, ..alert.clone() });
+    }
+
+    /// The latest failure to send since the last call
+    pub fn take_error(&self) -> Option<String> {
+        self.error.lock().ok()?.take()
+    }
+
+    /// Send what is still queued, then stop the task; returns the last failure to send
+    pub async fn finish(self) -> Option<String> {
+        drop(self.tx);
+        let _ = self.task.await;
+        self.error.lock().ok()?.take()
+    }
+}
+
+struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Mailer {
+    async fn run(self, mut rx: Receiver<AlertEntry>, min_interval: Duration) {
+        let mut last_sent: Option<Instant> = None;
+        while let Some(first) = rx.recv().await {
+            let mut batch = vec![first];
+            let mut more = 0;
+            let mut add = |alert| if batch.len() < MAX_LISTED { batch.push(alert) } else { more += 1 };
+            let mut closed = false;
+            // Collect everything raised until the next email is due
+            if let Some(due) = last_sent.map(|t| t + min_interval) {
+                loop {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(due) => break,
+                        alert = rx.recv() => match alert {
+                            Some(alert) => add(alert),
+                            None => { closed = true; break; }
+                        },
+                    }
+                }
+            }
+            while let Ok(alert) = rx.try_recv() { add(alert); }
+            let (subject, body) = compose(&batch, more);
+            if let Err(e) = self.send(subject, body).await
+                && let Ok(mut error) = self.error.lock()
+            {
+                *error = Some(e.to_string());
+            }
+            last_sent = Some(Instant::now());
+            if closed { break; }
+        }
+    }
+
+    async fn send(&self, subject: String, body: String) -> Result<()> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject).header(ContentType::TEXT_PLAIN);
+        for to in &self.to { builder = builder.to(to.clone()); }
+        self.transport.send(builder.body(body)?).await?;
+        Ok(())
+    }
+}
+
+/// Subject and body of the email for `batch` plus `more` alerts that were not listed
+fn compose(batch: &[AlertEntry], more: usize) -> (String, String) {
+    let highest = batch.iter().map(|a| a.severity).max().unwrap_or_default();
+    let subject = match batch {
+        [alert] => {
+            let message: String = alert.message.chars().take(80).collect();
+            format!("[rtlog] {}: {}", alert.severity.name(), message)
+        }
+        _ => format!("[rtlog] {} alerts, highest {}", batch.len() + more, highest.name()),
+    };
+    let mut body = String::new();
+    for alert in batch {
+        body.push_str(&format!("{}  {:<8}  ", alert.time.format("%Y-%m-%d %H:%M:%S"), alert.severity.name()));
+        if let Some(source) = &alert.source { body.push_str(&format!("{}: ", source)); }
+        body.push_str(&alert.message);
+        body.push('\n');
+    }
+    if more > 0 { body.push_str(&format!("... and {} more\n", more)); }
+    (subject, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose() {
+        let alert = |severity, message: &str| AlertEntry {
+            time: chrono::Local::now(), severity, rule: None, source: Some("app.log".into()), message: message.into(), acknowledged: false,
+        };
+        let (subject, body) = compose(&[alert(AlertSeverity::Critical, "disk full")], 0);
+        assert_eq!(subject, "[rtlog] critical: disk full");
+        assert!(body.ends_with("critical  app.log: disk full\n"));
+        let (subject, body) = compose(&[alert(AlertSeverity::Warn, "slow"), alert(AlertSeverity::Critical, "down")], 3);
+        assert_eq!(subject, "[rtlog] 5 alerts, highest critical");
+        assert_eq!(body.lines().count(), 3);
+    }
+}
@@ -0,0 +1,189 @@
+//! Text encodings of log files, transcoded to UTF-8 as lines are read.
+//!
+//! Files are read as bytes and split into lines in their encoding (in UTF-16 a newline is two
+//! bytes, and a `0x0A` byte may be half of another character), then decoded; bytes that do not
+//! decode become U+FFFD instead of failing the reader. Without `--encoding` (or `encoding` in a
+//! `[[sources]]` entry) the encoding is told from the start of the file: a byte order mark, or
+//! the NUL bytes that ASCII text has in every other position in UTF-16. Anything else is read as
+//! UTF-8; Latin-1 cannot be told apart from it and has to be named.
+
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Bytes looked at to tell the encoding of a file without a byte order mark
+pub const DETECT_BYTES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1: each byte is the code point of the same value
+    Latin1,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    /// Names as in `iconv`, ignoring case, `-` and `_`: `utf-8`, `utf-16le` (or `utf-16`, what
+    /// Windows calls Unicode), `utf-16be`, `latin1` (or `iso-8859-1`)
+    fn from_str(s: &str) -> Result<Self, String> {
+        let name: String = s.chars().filter(|c| !matches!(c, '-' | '_')).collect::<String>().to_ascii_lowercase();
+        match name.as_str() {
+            "utf8" => Ok(Self::Utf8),
+            "utf16le" | "utf16" | "ucs2" => Ok(Self::Utf16Le),
+            "utf16be" => Ok(Self::Utf16Be),
+            "latin1" | "iso88591" | "l1" => Ok(Self::Latin1),
+            _ => Err(format!("unknown encoding '{}' (known: utf-8, utf-16le, utf-16be, latin1)", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Encoding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl Encoding {
+    /// The encoding of a file starting with `head`: by its byte order mark or, without one, by
+    /// where its NUL bytes are; None for anything else (read as UTF-8)
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0xEF, 0xBB, 0xBF]) { return Some(Self::Utf8); }
+        if head.starts_with(&[0xFF, 0xFE]) { return Some(Self::Utf16Le); }
+        if head.starts_with(&[0xFE, 0xFF]) { return Some(Self::Utf16Be); }
+        let pairs: Vec<&[u8]> = head.chunks_exact(2).collect();
+        if pairs.len() < 2 { return None; }
+        // ASCII in UTF-16 is a NUL and the character; a NUL on both sides is binary, not text
+        let nul_at = |i: usize| pairs.iter().filter(|p| p[i] == 0).count();
+        let (even, odd) = (nul_at(0), nul_at(1));
+        match (even, odd) {
+            (0, odd) if odd * 2 >= pairs.len() => Some(Self::Utf16Le),
+            (even, 0) if even * 2 >= pairs.len() => Some(Self::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Length of the first line in `bytes` with its newline, when the newline is in there;
+    /// `offset` is how many bytes of the line were read before (so UTF-16 code units stay
+    /// aligned across reads)
+    fn line_end(self, bytes: &[u8], offset: usize) -> Option<usize> {
+        let newline = match self {
+            Self::Utf8 | Self::Latin1 => return bytes.iter().position(|&b| b == b'\n').map(|i| i + 1),
+            Self::Utf16Le => [b'\n', 0],
+            Self::Utf16Be => [0, b'\n'],
+        };
+        (offset % 2..bytes.len().saturating_sub(1)).step_by(2).find(|&i| bytes[i..i + 2] == newline).map(|i| i + 2)
+    }
+
+    /// Whether `line` (as read by `read_line`) ends with its newline, rather than at the end of
+    /// the input
+    pub fn ends_line(self, line: &[u8]) -> bool {
+        match self {
+            Self::Utf8 | Self::Latin1 => line.ends_with(b"\n"),
+            Self::Utf16Le => line.len().is_multiple_of(2) && line.ends_with(&[b'\n', 0]),
+            Self::Utf16Be => line.len().is_multiple_of(2) && line.ends_with(&[0, b'\n']),
+        }
+    }
+
+    /// `raw` (a line as read, with or without its newline) as text, without the newline, a
+    /// carriage return before it or a byte order mark
+    pub fn decode(self, raw: &[u8]) -> String {
+        let mut text = match self {
+            Self::Utf8 => String::from_utf8_lossy(raw).into_owned(),
+            Self::Latin1 => raw.iter().map(|&b| char::from(b)).collect(),
+            Self::Utf16Le | Self::Utf16Be => {
+                let units = raw.chunks(2).map(|pair| match (self, pair) {
+                    (Self::Utf16Le, [lo, hi]) => u16::from_le_bytes([*lo, *hi]),
+                    (_, [hi, lo]) => u16::from_be_bytes([*hi, *lo]),
+                    // An odd byte at the end of the file does not make a character
+                    _ => 0xFFFD,
+                });
+                char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+            }
+        };
+        if text.ends_with('\n') { text.pop(); }
+        if text.ends_with('\r') { text.pop(); }
+        if text.starts_with('\u{FEFF}') { text.drain(..'\u{FEFF}'.len_utf8()); }
+        text
+    }
+}
+
+/// Read the rest of a line in `encoding` into `line` (the bytes of the line read so far), up to
+/// and including its newline or to the end of the input; returns the number of bytes read
+pub fn read_line<R: BufRead>(reader: &mut R, encoding: Encoding, line: &mut Vec<u8>) -> io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() { return Ok(read); }
+        let end = encoding.line_end(available, line.len());
+        let n = end.unwrap_or(available.len());
+        line.extend_from_slice(&available[..n]);
+        reader.consume(n);
+        read += n;
+        if end.is_some() { return Ok(read); }
+    }
+}
+
+/// `read_line` for async readers
+pub async fn read_line_async<R: AsyncBufRead + Unpin>(reader: &mut R, encoding: Encoding, line: &mut Vec<u8>) -> io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() { return Ok(read); }
+        let end = encoding.line_end(available, line.len());
+        let n = end.unwrap_or(available.len());
+        line.extend_from_slice(&available[..n]);
+        reader.consume(n);
+        read += n;
+        if end.is_some() { return Ok(read); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn lines(bytes: &[u8], encoding: Encoding) -> Vec<String> {
+        // A tiny buffer, so lines and code units are split across reads
+        let mut reader = io::BufReader::with_capacity(3, bytes);
+        let mut out = Vec::new();
+        let mut line = Vec::new();
+        while read_line(&mut reader, encoding, &mut line).unwrap() > 0 {
+            out.push(encoding.decode(&std::mem::take(&mut line)));
+        }
+        out
+    }
+
+    #[test]
+    fn test_detect_and_decode() {
+        let mut bom = vec![0xFF, 0xFE];
+        bom.extend(utf16le("Dienst gestartet\r\nFehler: Zugriff verweigert \u{0A41}\r\nEnde"));
+        assert_eq!(Encoding::detect(&bom), Some(Encoding::Utf16Le));
+        // U+0A41 is 41 0A in UTF-16LE: its 0x0A byte is not a newline
+        assert_eq!(lines(&bom, Encoding::Utf16Le), ["Dienst gestartet", "Fehler: Zugriff verweigert \u{0A41}", "Ende"]);
+        assert_eq!(Encoding::detect(&utf16le("INFO started\n")), Some(Encoding::Utf16Le));
+        let be: Vec<u8> = "ok\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!((Encoding::detect(&be), lines(&be, Encoding::Utf16Be)), (Some(Encoding::Utf16Be), vec!["ok".to_string()]));
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFINFO a\n"), Some(Encoding::Utf8));
+        assert_eq!(lines(b"\xEF\xBB\xBFINFO a\nb", Encoding::Utf8), ["INFO a", "b"]);
+        assert_eq!(Encoding::detect(b"plain ascii text\n"), None);
+        assert_eq!(Encoding::detect(&[0, 0, 0, 0, 1, 2]), None);
+        assert_eq!(lines(b"caf\xE9\n", Encoding::Latin1), ["caf\u{e9}"]);
+        assert_eq!(lines(b"caf\xE9\n", Encoding::Utf8), ["caf\u{FFFD}"]);
+        assert_eq!("UTF_16".parse(), Ok(Encoding::Utf16Le));
+        assert_eq!("ISO-8859-1".parse(), Ok(Encoding::Latin1));
+        assert!("ebcdic".parse::<Encoding>().is_err());
+    }
+}
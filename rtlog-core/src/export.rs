@@ -0,0 +1,132 @@
+//! Writing buffered lines to a file (`e`, `--export-format`).
+//!
+//! Text exports are the lines as they are. JSONL and CSV exports add, per line, the source's
+//! sidebar name, the line number, the parsed timestamp, the level and the named groups of the
+//! enabled regex filters; JSONL puts the groups in a `fields` object (numbers as numbers), CSV
+//! gives each group name its own column. Exported text is redacted like tee files.
+
+use std::io::{self, Write};
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::query::Row;
+use crate::redact::Redactor;
+use crate::timestamp::parse_timestamp;
+
+/// File formats of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Text,
+    Jsonl,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => ExportFormat::Text,
+            "jsonl" | "json" | "ndjson" => ExportFormat::Jsonl,
+            "csv" => ExportFormat::Csv,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self { ExportFormat::Text => "text", ExportFormat::Jsonl => "JSONL", ExportFormat::Csv => "CSV" }
+    }
+
+    /// The format after this one, for cycling in the export prompt
+    pub fn next(self) -> Self {
+        match self { ExportFormat::Text => ExportFormat::Jsonl, ExportFormat::Jsonl => ExportFormat::Csv, ExportFormat::Csv => ExportFormat::Text }
+    }
+}
+
+/// Write `rows` to `out`; `fields` are the regexes whose named groups are exported. Returns the
+/// number of lines written.
+pub fn write<'a>(out: impl Write, format: ExportFormat, rows: impl Iterator<Item = Row<'a>>, fields: &[Regex], redactor: &Redactor) -> io::Result<usize> {
+    let mut out = io::BufWriter::new(out);
+    // Group names in first-seen order; a name used by several filters is one column
+    let mut names: Vec<&str> = Vec::new();
+    for name in fields.iter().flat_map(|re| re.capture_names().flatten()) {
+        if !names.contains(&name) { names.push(name); }
+    }
+    if format == ExportFormat::Csv {
+        let header: Vec<&str> = ["source", "n", "time", "level"].into_iter().chain(names.iter().copied()).chain(["line"]).collect();
+        writeln!(out, "{}", header.iter().map(|h| csv_field(h)).collect::<Vec<_>>().join(","))?;
+    }
+    let mut count = 0;
+    for row in rows {
+        count += 1;
+        let line = redactor.redact(row.line);
+        if format == ExportFormat::Text {
+            writeln!(out, "{}", line)?;
+            continue;
+        }
+        let captures: Vec<Option<regex::Captures>> = fields.iter().map(|re| re.captures(row.line)).collect();
+        let values: Vec<Option<String>> = names.iter().map(|name| {
+            captures.iter().flatten().find_map(|c| c.name(name)).map(|m| redactor.redact(m.as_str()).into_owned())
+        }).collect();
+        let time = parse_timestamp(row.line).map(|t| t.format("%Y-%m-%dT%H:%M:%S%.f").to_string());
+        let level = row.levels.level(row.line).map(|l| l.name());
+        if format == ExportFormat::Jsonl {
+            let mut object = Map::new();
+            object.insert("source".into(), row.source.into());
+            object.insert("n".into(), row.n.into());
+            object.insert("time".into(), time.into());
+            object.insert("level".into(), level.into());
+            let fields: Map<String, Value> = names.iter().zip(values).filter_map(|(name, value)| {
+                let value = value?;
+                let json = value.parse::<f64>().ok().and_then(serde_json::Number::from_f64).map_or(Value::String(value), Value::Number);
+                Some((name.to_string(), json))
+            }).collect();
+            object.insert("fields".into(), fields.into());
+            object.insert("line".into(), line.into_owned().into());
+            writeln!(out, "{}", Value::Object(object))?;
+        } else {
+            let mut record = vec![csv_field(row.source), row.n.to_string(), csv_field(time.as_deref().unwrap_or("")), level.unwrap_or("").to_string()];
+            record.extend(values.iter().map(|v| csv_field(v.as_deref().unwrap_or(""))));
+            record.push(csv_field(&line));
+            writeln!(out, "{}", record.join(","))?;
+        }
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// `s` quoted when it contains a separator, quote or line break
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::LevelRules;
+    use crate::config::RedactConfig;
+
+    #[test]
+    fn test_export_formats() {
+        let levels = LevelRules::new("ERROR", "WARN", "INFO").unwrap();
+        let lines = ["2025-01-02 10:00:01 ERROR user=bob@example.com took 900ms", "plain, \"quoted\" line"];
+        let rows = || lines.iter().enumerate().map(|(i, line)| Row { source: "api", n: i + 1, line, levels: &levels });
+        let fields = [Regex::new(r"took (?P<ms>\d+)ms").unwrap(), Regex::new(r"user=(?P<user>\S+)").unwrap()];
+        let redactor = Redactor::new(&RedactConfig { builtin: vec!["email".into()], rules: Vec::new() }).unwrap();
+        let export = |format| {
+            let mut out = Vec::new();
+            assert_eq!(write(&mut out, format, rows(), &fields, &redactor).unwrap(), 2);
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(export(ExportFormat::Text), "2025-01-02 10:00:01 ERROR user=<email> took 900ms\nplain, \"quoted\" line\n");
+        assert_eq!(export(ExportFormat::Csv), "source,n,time,level,ms,user,line\n\
+            api,1,2025-01-02T10:00:01,error,900,<email>,2025-01-02 10:00:01 ERROR user=<email> took 900ms\n\
+            api,2,,,,,\"plain, \"\"quoted\"\" line\"\n");
+        let jsonl = export(ExportFormat::Jsonl);
+        let first: Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!(first["fields"], serde_json::json!({ "ms": 900.0, "user": "<email>" }));
+        assert_eq!((first["time"].as_str(), first["level"].as_str(), first["n"].as_u64()), (Some("2025-01-02T10:00:01"), Some("error"), Some(1)));
+        let second: Value = serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert_eq!((&second["time"], &second["fields"]), (&Value::Null, &serde_json::json!({})));
+    }
+}
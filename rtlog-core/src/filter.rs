@@ -0,0 +1,643 @@
+//! Filtering utilities.
+//! 
+//! Provides `FilterRule` for user-defined patterns, helpers to compile patterns into regexes,
+//! functions to filter lines and the display-width helpers front ends lay lines out with. This
+//! module is pure and stateless aside from per-rule compiled regex caches, making it easy to test.
+
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use unicode_width::UnicodeWidthChar;
+
+/// Build a single regex from CLI pattern for backward compatibility
+pub fn build_filter(pattern: Option<&str>) -> anyhow::Result<Option<Regex>> {
+    if let Some(p) = pattern {
+        if p.is_empty() { return Ok(None); }
+        let re = RegexBuilder::new(p)
+            .case_insensitive(true)
+            .build()?;
+        Ok(Some(re))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Default pattern for extracting a request/trace ID from a line (first capture group is the ID)
+pub const DEFAULT_CORRELATE_PATTERN: &str = r#"(?i)\b(?:trace[_-]?id|request[_-]?id|req[_-]?id|correlation[_-]?id)["']?\s*[=:]\s*["']?([\w.-]+)"#;
+
+/// Extract a correlation ID from `text`: the first capture group if present, else the whole match
+pub fn extract_correlation_id(text: &str, re: &Regex) -> Option<String> {
+    let caps = re.captures(text)?;
+    let m = caps.get(1).or_else(|| caps.get(0))?;
+    if m.as_str().is_empty() { None } else { Some(m.as_str().to_string()) }
+}
+
+#[derive(Debug, Clone)]
+pub struct FilterRule {
+    pub pattern: String,
+    pub is_regex: bool,
+    /// Match the pattern's characters in order with anything in between (whitespace in the
+    /// pattern is ignored), like fzf; takes precedence over `is_regex`
+    pub fuzzy: bool,
+    pub case_insensitive: bool,
+    pub whole_word: bool,
+    pub whole_line: bool,
+    /// Hide the lines the pattern matches instead of showing them, like `grep -v`
+    pub exclude: bool,
+    pub enabled: bool,
+    // Runtime-only fields for performance and stats
+    pub compiled: Option<Regex>,
+    pub match_count: usize,
+    /// Section of the filter panel the filter is listed in (`g`), switched and collapsed as one
+    pub group: Option<String>,
+}
+
+impl FilterRule {
+    /// Enabled, case-insensitive rule matching `pattern` anywhere, as a literal unless `is_regex`
+    pub fn simple(pattern: String, is_regex: bool) -> Self {
+        Self { pattern, is_regex, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None }
+    }
+
+    /// The flags as shown in the filter list: fuzzy or regex, case-insensitive, whole word, whole
+    /// line, `-` for those off (e.g. `ri--`), after a `!` for an exclude rule
+    pub fn flags(&self) -> String {
+        let exclude = if self.exclude { "!" } else { "" };
+        exclude.chars().chain([
+            if self.fuzzy { 'f' } else if self.is_regex { 'r' } else { '-' },
+            if self.case_insensitive { 'i' } else { '-' },
+            if self.whole_word { 'w' } else { '-' },
+            if self.whole_line { 'x' } else { '-' },
+        ]).collect()
+    }
+
+    /// Final regex source according to flags (case-insensitivity as an inline `(?i)` so the
+    /// same source works in a `RegexSet`)
+    pub fn regex_source(&self) -> String {
+        let mut pat = if self.fuzzy {
+            fuzzy_source(&self.pattern)
+        } else if self.is_regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        if self.whole_line {
+            pat = format!("^(?:{})$", pat);
+        } else if self.whole_word {
+            // Use word boundary \b
+            pat = format!("\\b(?:{})\\b", pat);
+        }
+        if self.case_insensitive { pat = format!("(?i){}", pat); }
+        pat
+    }
+
+    /// Plain case-insensitive substring rule that an Aho-Corasick automaton can match
+    /// (ASCII only: non-ASCII text needs the regex engine's Unicode case folding)
+    pub fn is_simple_literal(&self) -> bool {
+        !self.is_regex && !self.fuzzy && self.case_insensitive && !self.whole_word && !self.whole_line
+            && !self.pattern.is_empty() && self.pattern.is_ascii()
+    }
+
+    /// Compile this rule into a Regex according to flags
+    pub fn compile(&self) -> anyhow::Result<Regex> {
+        Ok(Regex::new(&self.regex_source())?)
+    }
+
+    /// Ensure the compiled regex is available in `compiled`
+    pub fn ensure_compiled(&mut self) {
+        if self.compiled.is_none()
+            && let Ok(re) = self.compile() {
+            self.compiled = Some(re);
+        }
+    }
+}
+
+/// Whether `pattern` has an uppercase letter of its own, for smart case. In a regex, escapes
+/// (`\W`, `\p{Lu}`, `\x{1F}`) and group names and flags do not count.
+pub fn has_uppercase(pattern: &str, is_regex: bool) -> bool {
+    if !is_regex {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('p' | 'P' | 'x' | 'u' | 'U') if chars.peek() == Some(&'{') => {
+                    for c in chars.by_ref() { if c == '}' { break; } }
+                }
+                // One-letter classes (`\pL`) and fixed-width hex codes (`\xAB`, `\u00C9`)
+                Some('p' | 'P') => { chars.next(); }
+                Some(c @ ('x' | 'u' | 'U')) => {
+                    let digits = match c { 'x' => 2, 'u' => 4, _ => 8 };
+                    for _ in 0..digits { chars.next_if(char::is_ascii_hexdigit); }
+                }
+                _ => {}
+            },
+            '(' if chars.peek() == Some(&'?') => {
+                for c in chars.by_ref() { if matches!(c, '>' | ':' | ')') { break; } }
+            }
+            c if c.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Regex matching the characters of a fuzzy `pattern` in order, each in its own group so that
+/// the characters matched can be highlighted rather than everything between them
+fn fuzzy_source(pattern: &str) -> String {
+    let chars: Vec<String> = pattern.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| format!("({})", regex::escape(c.encode_utf8(&mut [0; 4]))))
+        .collect();
+    chars.join(".*?")
+}
+
+/// A match of one rule of a `CompiledFilterSet`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatch {
+    /// Position of the rule in the rule list the set was built from
+    pub rule: usize,
+    /// Byte range of the match in the text
+    pub range: Range<usize>,
+}
+
+/// The enabled rules of a list compiled once and shared by ingest and rendering. A line is shown
+/// when it matches an include rule (or there is none) and no exclude rule (`!pattern`). The
+/// per-line match test is a single pass however many rules there are: plain case-insensitive
+/// substrings go into one Aho-Corasick automaton, everything else into a `RegexSet`. Each rule's
+/// own regex is only run on lines it matches, for the ranges it highlights.
+#[derive(Debug, Clone, Default)]
+pub struct CompiledFilterSet {
+    include: RuleMatcher,
+    exclude: RuleMatcher,
+    /// Positions in the rule list of the exclude rules, in ascending order
+    excluded: Vec<usize>,
+    /// Each enabled rule's regex with its position in the rule list and whether it is fuzzy, in
+    /// list order
+    regexes: Vec<(usize, Regex, bool)>,
+}
+
+impl CompiledFilterSet {
+    /// Build from the enabled rules; rules that fail to compile are left out
+    pub fn new(rules: &[FilterRule]) -> Self {
+        Self::build(rules, None)
+    }
+
+    /// Build with the `RegexSet`s limited to `size_limit` bytes (None: the regex crate's default)
+    fn build(rules: &[FilterRule], size_limit: Option<usize>) -> Self {
+        let regexes: Vec<(usize, Regex, bool)> = rules.iter().enumerate()
+            .filter(|(_, r)| r.enabled)
+            .filter_map(|(i, r)| Some((i, r.compiled.clone().or_else(|| r.compile().ok())?, r.fuzzy)))
+            .collect();
+        let (exclude, include): (Vec<_>, Vec<_>) = regexes.iter().map(|(i, re, _)| (*i, &rules[*i], re)).partition(|(_, r, _)| r.exclude);
+        let excluded = exclude.iter().map(|&(i, _, _)| i).collect();
+        Self { include: RuleMatcher::new(include, size_limit), exclude: RuleMatcher::new(exclude, size_limit), excluded, regexes }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn len(&self) -> usize { self.include.len() + self.exclude.len() }
+
+    /// Whether `text` matches an include rule and no exclude rule
+    pub fn is_match(&self, text: &str) -> bool {
+        self.include.is_match(text) && !self.exclude.is_match(text)
+    }
+
+    /// Whether a line `text` is shown: it matches an include rule, or there is none, and no
+    /// exclude rule
+    pub fn passes(&self, text: &str) -> bool {
+        (self.include.is_empty() || self.include.is_match(text)) && !self.exclude.is_match(text)
+    }
+
+    /// `passes` for a line whose matching rules (from `matching_rules`) are `hits`
+    pub fn passes_hits(&self, hits: &[usize]) -> bool {
+        let is_excluded = |i: &usize| self.excluded.binary_search(i).is_ok();
+        (self.include.is_empty() || hits.iter().any(|i| !is_excluded(i))) && !hits.iter().any(is_excluded)
+    }
+
+    /// Positions (in the original rule list) of the rules matching `text`, include and exclude,
+    /// in ascending order
+    pub fn matching_rules(&self, text: &str) -> Vec<usize> {
+        let mut out = self.include.matching_rules(text);
+        if !self.exclude.is_empty() { out.extend(self.exclude.matching_rules(text)); }
+        out.sort_unstable();
+        out
+    }
+
+    /// Every non-empty match in `text` of every rule, by rule then position; a fuzzy rule's
+    /// matches are the characters it matched
+    pub fn matches(&self, text: &str) -> Vec<RuleMatch> {
+        let hits = self.matching_rules(text);
+        let mut out = Vec::new();
+        for (rule, re, fuzzy) in &self.regexes {
+            if hits.binary_search(rule).is_err() { continue; }
+            if *fuzzy {
+                let chars = re.captures_iter(text).flat_map(|caps| caps.iter().skip(1).flatten().map(|m| m.range()).collect::<Vec<_>>());
+                out.extend(chars.map(|range| RuleMatch { rule: *rule, range }));
+            } else {
+                out.extend(re.find_iter(text).filter(|m| !m.is_empty()).map(|m| RuleMatch { rule: *rule, range: m.range() }));
+            }
+        }
+        out
+    }
+}
+
+/// Which of some rules match, in one pass. Rules the automaton or the set cannot take (too many
+/// or too large patterns) are not dropped: the literals then go into the set, and without a set
+/// each rule's own regex is run
+#[derive(Debug, Clone, Default)]
+struct RuleMatcher {
+    literals: Option<AhoCorasick>,
+    /// Position in the rule list of each automaton pattern
+    literal_index: Vec<usize>,
+    set: Option<RegexSet>,
+    /// Position in the rule list of each set member (or of each regex of `each`)
+    rule_index: Vec<usize>,
+    /// The rules' own regexes when the set could not be built
+    each: Vec<Regex>,
+}
+
+impl RuleMatcher {
+    fn new(rules: Vec<(usize, &FilterRule, &Regex)>, size_limit: Option<usize>) -> Self {
+        let (literal, mut other): (Vec<_>, Vec<_>) = rules.into_iter().partition(|(_, r, _)| r.is_simple_literal());
+        let patterns: Vec<&str> = literal.iter().map(|(_, r, _)| r.pattern.as_str()).collect();
+        let mut literals = None;
+        if !patterns.is_empty() {
+            match AhoCorasick::builder().ascii_case_insensitive(true).match_kind(MatchKind::Standard).build(&patterns) {
+                Ok(ac) => literals = Some(ac),
+                Err(_) => other.extend(literal.iter().copied()),
+            }
+        }
+        let literal_index = if literals.is_some() { literal.iter().map(|&(i, _, _)| i).collect() } else { Vec::new() };
+        let rule_index: Vec<usize> = other.iter().map(|&(i, _, _)| i).collect();
+        let mut builder = RegexSetBuilder::new(other.iter().map(|(_, r, _)| r.regex_source()));
+        if let Some(limit) = size_limit { builder.size_limit(limit); }
+        let (set, each) = match builder.build() {
+            Ok(set) => (Some(set), Vec::new()),
+            Err(_) => (None, other.iter().map(|&(_, _, re)| re.clone()).collect()),
+        };
+        Self { literals, literal_index, set, rule_index, each }
+    }
+
+    fn is_empty(&self) -> bool { self.len() == 0 }
+
+    fn len(&self) -> usize { self.literal_index.len() + self.rule_index.len() }
+
+    fn is_match(&self, text: &str) -> bool {
+        self.literals.as_ref().is_some_and(|ac| ac.is_match(text))
+            || match &self.set {
+                Some(set) => set.is_match(text),
+                None => self.each.iter().any(|re| re.is_match(text)),
+            }
+    }
+
+    /// Positions in the rule list of the rules matching `text`, in no particular order
+    fn matching_rules(&self, text: &str) -> Vec<usize> {
+        let mut out: Vec<usize> = match &self.set {
+            Some(set) => set.matches(text).into_iter().map(|i| self.rule_index[i]).collect(),
+            None => self.each.iter().zip(&self.rule_index).filter(|(re, _)| re.is_match(text)).map(|(_, &i)| i).collect(),
+        };
+        if let Some(ac) = &self.literals {
+            let mut seen = vec![false; self.literal_index.len()];
+            for m in ac.find_overlapping_iter(text) {
+                let p = m.pattern().as_usize();
+                if !seen[p] { seen[p] = true; out.push(self.literal_index[p]); }
+            }
+        }
+        out
+    }
+}
+
+/// Return true if a line `text` passes the rules of the set; if the set is empty, allow all
+pub fn line_matches(text: &str, rules: &CompiledFilterSet) -> bool {
+    rules.passes(text)
+}
+
+/// How a field filter compares a line's value with its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldOp { Eq, Ne, Lt, Le, Gt, Ge }
+
+impl FieldOp {
+    pub fn symbol(self) -> &'static str {
+        match self { Self::Eq => "=", Self::Ne => "!=", Self::Lt => "<", Self::Le => "<=", Self::Gt => ">", Self::Ge => ">=" }
+    }
+}
+
+/// A condition on the value of a field (a named group of a regex filter), such as `ms > 500`:
+/// numbers compare as numbers, anything else as text. Lines without the field fail it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldFilter {
+    pub field: String,
+    pub op: FieldOp,
+    pub value: String,
+}
+
+impl FieldFilter {
+    /// Parse `FIELD OP VALUE`, with `OP` one of `=` `!=` `<` `<=` `>` `>=`
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let input = input.trim();
+        let at = input.find(['=', '!', '<', '>']).ok_or_else(|| anyhow::anyhow!("expected FIELD OP VALUE, e.g. ms > 500"))?;
+        let (field, rest) = (input[..at].trim(), &input[at..]);
+        let op = [FieldOp::Ne, FieldOp::Le, FieldOp::Ge, FieldOp::Eq, FieldOp::Lt, FieldOp::Gt].into_iter()
+            .find(|op| rest.starts_with(op.symbol()))
+            .ok_or_else(|| anyhow::anyhow!("expected FIELD OP VALUE, e.g. ms > 500"))?;
+        let value = rest[op.symbol().len()..].trim().trim_start_matches('=').trim();
+        if field.is_empty() || value.is_empty() || field.contains(char::is_whitespace) {
+            anyhow::bail!("expected FIELD OP VALUE, e.g. ms > 500");
+        }
+        Ok(Self { field: field.to_string(), op, value: value.to_string() })
+    }
+
+    /// Whether a line whose field has `value` (None: the line has no such field) passes
+    pub fn matches(&self, value: Option<&str>) -> bool {
+        let Some(value) = value else { return false; };
+        let ordering = match (value.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(value.cmp(self.value.as_str())),
+        };
+        let Some(ordering) = ordering else { return false; };
+        match self.op {
+            FieldOp::Eq => ordering.is_eq(),
+            FieldOp::Ne => ordering.is_ne(),
+            FieldOp::Lt => ordering.is_lt(),
+            FieldOp::Le => ordering.is_le(),
+            FieldOp::Gt => ordering.is_gt(),
+            FieldOp::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.field, self.op.symbol(), self.value)
+    }
+}
+
+/// Columns between tab stops (log lines use `tab_width` from the config file)
+pub const TAB_WIDTH: usize = 8;
+
+/// Display columns taken by `c`; tabs and other control characters count as none
+pub fn char_width(c: char) -> usize {
+    UnicodeWidthChar::width(c).unwrap_or(0)
+}
+
+/// Display columns taken by `text` (wide CJK and emoji characters take two)
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// `text` with its tabs replaced by spaces up to the next tab stop (every `tab_width` columns),
+/// counting columns from `column`; returns the column after the text as well.
+pub fn expand_tabs_from(text: &str, mut column: usize, tab_width: usize) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\t' {
+            let pad = tab_width - column % tab_width;
+            out.extend(std::iter::repeat_n(' ', pad));
+            column += pad;
+        } else {
+            out.push(c);
+            column += char_width(c);
+        }
+    }
+    (out, column)
+}
+
+/// How a control character other than a tab or newline is shown when control characters are
+/// made visible: `␀` for NUL, caret notation for the others (`^M` for a carriage return, `^[`
+/// for escape, `^?` for delete) and the code in hex for the C1 controls (`<85>`); None for
+/// other characters. The terminal would drop them.
+pub fn control_symbol(c: char) -> Option<String> {
+    match c {
+        '\0' => Some("␀".into()),
+        '\t' | '\n' => None,
+        '\x01'..='\x1f' => Some(format!("^{}", char::from(c as u8 + 0x40))),
+        '\x7f' => Some("^?".into()),
+        '\u{80}'..='\u{9f}' => Some(format!("<{:02X}>", c as u32)),
+        _ => None,
+    }
+}
+
+/// `text` cut to at most `width` display columns, ending in `…` when cut, then padded with
+/// spaces to exactly `width`
+pub fn fit_width(text: &str, width: usize) -> String {
+    let (text, _) = expand_tabs_from(text, 0, TAB_WIDTH);
+    let mut out = String::with_capacity(text.len());
+    let mut used = 0;
+    if display_width(&text) > width {
+        for c in text.chars() {
+            let w = char_width(c);
+            if used + w + 1 > width { break; }
+            out.push(c);
+            used += w;
+        }
+        if width > 0 { out.push('…'); used += 1; }
+    } else {
+        out = text;
+        used = display_width(&out);
+    }
+    out.extend(std::iter::repeat_n(' ', width - used));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_line_matches_any() {
+        let r1 = FilterRule { pattern: "ERROR".into(), is_regex: false, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None };
+        let r2 = FilterRule { pattern: "WARN".into(), is_regex: false, fuzzy: false, case_insensitive: false, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None };
+        let enabled = CompiledFilterSet::new(&[r1, r2]);
+        assert!(line_matches("2025 ERROR something", &enabled));
+        assert!(line_matches("2025 WARN something", &enabled));
+        assert!(!line_matches("2025 info ok", &enabled));
+    }
+
+    #[test]
+    fn test_rule_set_maps_matches_to_rules() {
+        let rule = |pattern: &str, enabled: bool| FilterRule { pattern: pattern.into(), is_regex: false, fuzzy: false, case_insensitive: true, whole_word: true, whole_line: false, exclude: false, enabled, compiled: None, match_count: 0, group: None };
+        let rules = [rule("error", true), rule("warn", false), rule("db", true)];
+        let set = CompiledFilterSet::new(&rules);
+        assert_eq!(set.matching_rules("DB ERROR: warn"), vec![0, 2]);
+        assert!(!set.is_match("dberror"));
+        assert!(line_matches("anything", &CompiledFilterSet::new(&[])));
+    }
+
+    #[test]
+    fn test_rule_set_literal_fast_path() {
+        let literal = |pattern: &str| FilterRule { pattern: pattern.into(), is_regex: false, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None };
+        let regex = FilterRule { pattern: r"id=\d+".into(), is_regex: true, ..literal("") };
+        let rules = [literal("timeout"), regex, literal("Out"), literal("ÉCHEC")];
+        assert!(rules[0].is_simple_literal() && !rules[1].is_simple_literal() && !rules[3].is_simple_literal());
+        let set = CompiledFilterSet::new(&rules);
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.matching_rules("TIMEOUT for id=7"), vec![0, 1, 2]);
+        assert_eq!(set.matching_rules("échec"), vec![3]);
+        assert!(!set.is_match("all good"));
+    }
+
+    #[test]
+    fn test_rule_set_match_ranges() {
+        let mut disabled = FilterRule::simple("db".into(), false);
+        disabled.enabled = false;
+        let rules = [FilterRule::simple("error".into(), false), disabled, FilterRule::simple(r"\d+ms".into(), true), FilterRule::simple("x*".into(), true)];
+        let set = CompiledFilterSet::new(&rules);
+        let ranges: Vec<(usize, Range<usize>)> = set.matches("Error: db 30ms, error again").into_iter().map(|m| (m.rule, m.range)).collect();
+        // By rule then position; empty matches are left out
+        assert_eq!(ranges, [(0, 0..5), (0, 16..21), (2, 10..14)]);
+        assert!(set.matches("all good").is_empty());
+    }
+
+    #[test]
+    fn test_exclude_rules() {
+        let exclude = |pattern: &str, is_regex: bool| FilterRule { exclude: true, ..FilterRule::simple(pattern.into(), is_regex) };
+        let only_exclude = CompiledFilterSet::new(&[exclude("healthz", false), exclude(r"GET /metrics\b", true)]);
+        assert!(line_matches("POST /orders 201", &only_exclude));
+        assert!(!line_matches("GET /healthz 200", &only_exclude) && !line_matches("GET /metrics 200", &only_exclude));
+        let rules = [FilterRule::simple("GET".into(), false), exclude("healthz", false)];
+        let set = CompiledFilterSet::new(&rules);
+        assert!(line_matches("GET /orders", &set) && !line_matches("GET /healthz", &set) && !line_matches("POST /orders", &set));
+        // The hits say the same, for lines classified once at ingest
+        for text in ["GET /orders", "GET /healthz", "POST /orders", "POST /healthz"] {
+            let hits = set.matching_rules(text);
+            assert_eq!(set.passes_hits(&hits), set.passes(text), "{}", text);
+        }
+        assert_eq!(set.matching_rules("GET /healthz"), vec![0, 1]);
+        assert!(!set.is_match("GET /healthz") && set.is_match("GET /"));
+        assert_eq!((set.len(), rules[1].flags()), (2, "!-i--".to_string()));
+    }
+
+    #[test]
+    fn test_rules_kept_without_a_set() {
+        let rules = [FilterRule::simple(r"id=\d+".into(), true), FilterRule::simple("timeout".into(), false), FilterRule::simple(r"\w+ failed".into(), true)];
+        // Too large for the set: the rules' own regexes are run instead of nothing matching
+        let set = CompiledFilterSet::build(&rules, Some(64));
+        assert!(set.include.set.is_none());
+        assert_eq!(set.matching_rules("read timeout for id=7"), vec![0, 1]);
+        assert_eq!(set.matching_rules("login failed"), vec![2]);
+        assert!(set.is_match("id=1") && !set.is_match("all good"));
+        assert_eq!(set.matches("id=42").first().map(|m| m.range.clone()), Some(0..5));
+    }
+
+    #[test]
+    fn test_field_filter() {
+        let slow = FieldFilter::parse("ms > 500").unwrap();
+        assert_eq!(slow, FieldFilter { field: "ms".into(), op: FieldOp::Gt, value: "500".into() });
+        // Numerically, not as text
+        assert!(slow.matches(Some("1200")) && slow.matches(Some("501.5")) && !slow.matches(Some("90")) && !slow.matches(None));
+        let status = FieldFilter::parse("status!=200").unwrap();
+        assert_eq!(status.to_string(), "status != 200");
+        assert!(status.matches(Some("503")) && !status.matches(Some("200")));
+        let user = FieldFilter::parse("user == alice").unwrap();
+        assert!(user.op == FieldOp::Eq && user.matches(Some("alice")) && !user.matches(Some("bob")));
+        assert!(FieldFilter::parse("ms >= 1e3").unwrap().matches(Some("1000")));
+        for bad in ["ms", "ms >", "> 5", "took ms > 5"] {
+            assert!(FieldFilter::parse(bad).unwrap_err().to_string().contains("expected FIELD OP VALUE"), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_has_uppercase() {
+        assert!(has_uppercase("Timeout", false));
+        assert!(!has_uppercase("timeout 500", false));
+        assert!(has_uppercase(r"\\W", false));
+        assert!(!has_uppercase(r"\W+\S\p{Lu}\x{1F600}\xAF\pL (?P<Ms>\d+)(?i:ok)", true));
+        assert!(has_uppercase(r"\xABCD", true));
+        assert!(has_uppercase(r"\bERROR\b", true));
+        assert!(has_uppercase(r"(?P<ms>\d+)Ms", true));
+    }
+
+    #[test]
+    fn test_fuzzy_rule() {
+        let rule = FilterRule { fuzzy: true, ..FilterRule::simple("conn timeout db".into(), false) };
+        assert_eq!(rule.regex_source(), "(?i)(c).*?(o).*?(n).*?(n).*?(t).*?(i).*?(m).*?(e).*?(o).*?(u).*?(t).*?(d).*?(b)");
+        assert!(!rule.is_simple_literal());
+        let set = CompiledFilterSet::new(&[rule]);
+        assert!(set.is_match("Connection to db-1: read timeout after 30s (db pool)"));
+        assert!(!set.is_match("db timeout on connection"));
+        // The characters it matched, not the whole span
+        let ranges: Vec<Range<usize>> = set.matches("conn: timeout (db)").into_iter().map(|m| m.range).collect();
+        assert_eq!(ranges, [0..1, 1..2, 2..3, 3..4, 6..7, 7..8, 8..9, 9..10, 10..11, 11..12, 12..13, 15..16, 16..17]);
+    }
+
+    #[test]
+    fn test_extract_correlation_id() {
+        let re = Regex::new(DEFAULT_CORRELATE_PATTERN).unwrap();
+        assert_eq!(extract_correlation_id("GET /x trace_id=ab12-cd status=200", &re).as_deref(), Some("ab12-cd"));
+        assert_eq!(extract_correlation_id(r#"{"requestId": "r-9", "msg": "ok"}"#, &re).as_deref(), Some("r-9"));
+        assert_eq!(extract_correlation_id("nothing to see", &re), None);
+        let whole = Regex::new(r"req-\d+").unwrap();
+        assert_eq!(extract_correlation_id("handling req-42 now", &whole).as_deref(), Some("req-42"));
+    }
+
+    #[test]
+    fn test_tabs_and_wide_characters() {
+        // The wide characters before the tab take four columns, so it pads to column 8
+        assert_eq!(expand_tabs_from("日本\t错误\tend", 0, TAB_WIDTH), ("日本    错误    end".to_string(), 19));
+        assert_eq!(expand_tabs_from("\tx", 3, TAB_WIDTH).0, "     x");
+        assert_eq!(expand_tabs_from("a\tb\tc", 0, 4).0, "a   b   c");
+        assert_eq!(display_width("a😀b"), 4);
+        assert_eq!(fit_width("日本語テキスト", 7), "日本語…");
+        assert_eq!(fit_width("日本", 6), "日本  ");
+        assert_eq!(fit_width("a\tb", 4), "a  …");
+    }
+
+    #[test]
+    fn test_control_symbols() {
+        let shown: Vec<Option<String>> = ['\0', '\r', '\x1b', '\x7f', '\u{85}', '\t', 'a'].into_iter().map(control_symbol).collect();
+        assert_eq!(shown, [Some("␀".into()), Some("^M".into()), Some("^[".into()), Some("^?".into()), Some("<85>".into()), None, None]);
+    }
+
+    /// Patterns and lines are drawn from a small alphabet so that matches are common; it has
+    /// regex metacharacters, non-word characters and a letter with non-ASCII case folding
+    const PATTERN: &str = "[abAB_.*(é -]{0,4}";
+    const TEXT: &str = "[abAB_.*(é -]{0,16}";
+
+    fn literal_rule() -> impl Strategy<Value = FilterRule> {
+        (PATTERN, any::<bool>(), any::<bool>(), any::<bool>(), any::<bool>()).prop_map(|(pattern, case_insensitive, whole_word, whole_line, enabled)| {
+            FilterRule { case_insensitive, whole_word, whole_line, enabled, ..FilterRule::simple(pattern, false) }
+        })
+    }
+
+    /// Whether a `\b` sees `c` as a word character (None is the edge of the line)
+    fn is_word(c: Option<&char>) -> bool {
+        c.is_some_and(|c| c.is_alphanumeric() || *c == '_')
+    }
+
+    /// Straightforward reading of a literal rule: the pattern occurs in `text` compared character
+    /// by character (folded when case-insensitive), with a word boundary on both sides for a
+    /// whole-word rule, and spans all of `text` for a whole-line rule
+    fn reference_match(rule: &FilterRule, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let pattern: Vec<char> = rule.pattern.chars().collect();
+        let same = |a: char, b: char| if rule.case_insensitive { a.to_lowercase().eq(b.to_lowercase()) } else { a == b };
+        let occurs_at = |i: usize| i + pattern.len() <= text.len() && pattern.iter().zip(&text[i..]).all(|(&p, &t)| same(p, t));
+        let boundary = |i: usize| is_word(i.checked_sub(1).and_then(|j| text.get(j))) != is_word(text.get(i));
+        if rule.whole_line {
+            return text.len() == pattern.len() && occurs_at(0);
+        }
+        (0..=text.len()).any(|i| occurs_at(i) && (!rule.whole_word || (boundary(i) && boundary(i + pattern.len()))))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_compiled_rule_matches_reference(rule in literal_rule(), text in TEXT) {
+            let re = rule.compile().unwrap();
+            prop_assert_eq!(re.is_match(&text), reference_match(&rule, &text), "regex {}", rule.regex_source());
+        }
+
+        #[test]
+        fn prop_rule_set_matches_reference(rules in prop::collection::vec(literal_rule(), 1..5), text in TEXT) {
+            let expected: Vec<usize> = rules.iter().enumerate()
+                .filter(|(_, r)| r.enabled && reference_match(r, &text))
+                .map(|(i, _)| i)
+                .collect();
+            let set = CompiledFilterSet::new(&rules);
+            prop_assert_eq!(set.is_match(&text), !expected.is_empty());
+            prop_assert_eq!(set.matching_rules(&text), expected);
+        }
+    }
+}
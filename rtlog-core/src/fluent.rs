@@ -0,0 +1,253 @@
+//! Fluent forward protocol listener (msgpack over TCP).
+//!
+//! Fluentd / Fluent Bit `forward` outputs can ship records straight into rtlog. Every event is
+//! sent on the channel as `SourceEvent::Record` carrying its tag, so the runtime can create one
+//! source per tag. Supported modes: Message, Forward, PackedForward and CompressedPackedForward
+//! (gzip); `chunk` options are acknowledged. The handshake (shared key auth) is not implemented.
+
+use std::io::{Cursor, Read};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rmpv::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::log::{EventSender, LogLine, LogSource, SourceEvent};
+use crate::net::serve_connections;
+
+/// Default port of the forward protocol
+pub const DEFAULT_PORT: u16 = 24224;
+
+/// Largest undecoded message kept per connection before the peer is dropped
+const MAX_PENDING: usize = 64 * 1024 * 1024;
+
+/// Record keys used as the main text of a line, in order of preference
+const MESSAGE_KEYS: [&str; 3] = ["message", "log", "msg"];
+
+/// Accepts forward-protocol connections on `addr` until aborted
+pub struct FluentListener {
+    pub addr: String,
+}
+
+/// One decoded forward-protocol message
+#[derive(Debug)]
+struct Forward {
+    tag: String,
+    entries: Vec<(DateTime<Utc>, Value)>,
+    chunk: Option<Value>,
+}
+
+#[async_trait::async_trait]
+impl LogSource for FluentListener {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        let local = listener.local_addr()?;
+        if tx.send((source_id, SourceEvent::Line(LogLine::new(format!("listening on {}", local))))).await.is_err() { return Ok(()); }
+        serve_connections(listener, |sock, peer| {
+            let tx = tx.clone();
+            async move {
+                if tx.send((source_id, SourceEvent::Line(LogLine::new(format!("{} connected", peer))))).await.is_err() { return; }
+                let msg = match serve(sock, source_id, &tx).await {
+                    Ok(()) => format!("{} disconnected", peer),
+                    Err(e) => format!("{} dropped: {}", peer, e),
+                };
+                let _ = tx.send((source_id, SourceEvent::Line(LogLine::new(msg)))).await;
+            }
+        }).await;
+        Ok(())
+    }
+}
+
+/// Read messages from one connection until it closes
+async fn serve(mut sock: TcpStream, source_id: usize, tx: &EventSender) -> Result<()> {
+    let mut pending: Vec<u8> = Vec::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let n = sock.read(&mut chunk).await?;
+        if n == 0 {
+            if !pending.is_empty() { bail!("connection closed mid-message"); }
+            return Ok(());
+        }
+        pending.extend_from_slice(&chunk[..n]);
+        let mut consumed = 0;
+        while let Some((value, len)) = next_value(&pending[consumed..])? {
+            consumed += len;
+            let msg = decode_forward(value)?;
+            for (time, record) in &msg.entries {
+                let ev = SourceEvent::Record { tag: msg.tag.clone(), line: LogLine::new(format_record(time, record)) };
+                if tx.send((source_id, ev)).await.is_err() { return Ok(()); }
+            }
+            if let Some(id) = msg.chunk {
+                let mut ack = Vec::new();
+                rmpv::encode::write_value(&mut ack, &Value::Map(vec![(Value::from("ack"), id)]))?;
+                sock.write_all(&ack).await?;
+            }
+        }
+        pending.drain(..consumed);
+        if pending.len() > MAX_PENDING { bail!("message larger than {} bytes", MAX_PENDING); }
+    }
+}
+
+/// Decode one value from the front of `buf`; None when more bytes are needed
+fn next_value(buf: &[u8]) -> Result<Option<(Value, usize)>> {
+    if buf.is_empty() { return Ok(None); }
+    let mut cur = Cursor::new(buf);
+    match rmpv::decode::read_value(&mut cur) {
+        Ok(v) => Ok(Some((v, cur.position() as usize))),
+        Err(rmpv::decode::Error::InvalidMarkerRead(e) | rmpv::decode::Error::InvalidDataRead(e))
+            if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(anyhow!("invalid msgpack: {}", e)),
+    }
+}
+
+/// Interpret a top-level array as Message, Forward or (Compressed)PackedForward mode
+fn decode_forward(value: Value) -> Result<Forward> {
+    let Value::Array(mut parts) = value else { bail!("expected an array") };
+    if parts.len() < 2 { bail!("expected [tag, ...]"); }
+    let tag = parts[0].as_str().ok_or_else(|| anyhow!("tag is not a string"))?.to_string();
+    let second = std::mem::replace(&mut parts[1], Value::Nil);
+    let (entries, option) = match second {
+        Value::Array(items) => {
+            let entries = items.into_iter().map(decode_entry).collect::<Result<Vec<_>>>()?;
+            (entries, parts.get(2))
+        }
+        Value::Binary(bytes) => (decode_packed(bytes, parts.get(2))?, parts.get(2)),
+        Value::String(s) => (decode_packed(s.into_bytes(), parts.get(2))?, parts.get(2)),
+        time => {
+            let record = parts.get(2).cloned().ok_or_else(|| anyhow!("message without record"))?;
+            (vec![(decode_time(&time)?, record)], parts.get(3))
+        }
+    };
+    let chunk = option.and_then(|o| option_value(o, "chunk")).cloned();
+    Ok(Forward { tag, entries, chunk })
+}
+
+/// PackedForward: a concatenated msgpack stream of `[time, record]`, optionally gzip-compressed
+fn decode_packed(bytes: Vec<u8>, option: Option<&Value>) -> Result<Vec<(DateTime<Utc>, Value)>> {
+    let bytes = match option.and_then(|o| option_value(o, "compressed")).and_then(|v| v.as_str()) {
+        Some("gzip") => {
+            let mut out = Vec::new();
+            flate2::read::MultiGzDecoder::new(bytes.as_slice()).read_to_end(&mut out)?;
+            out
+        }
+        Some(other) => bail!("unsupported compression: {}", other),
+        None => bytes,
+    };
+    let mut entries = Vec::new();
+    let mut rest = bytes.as_slice();
+    while !rest.is_empty() {
+        let (value, len) = next_value(rest)?.ok_or_else(|| anyhow!("truncated packed entries"))?;
+        entries.push(decode_entry(value)?);
+        rest = &rest[len..];
+    }
+    Ok(entries)
+}
+
+fn decode_entry(value: Value) -> Result<(DateTime<Utc>, Value)> {
+    match value {
+        Value::Array(mut pair) if pair.len() >= 2 => {
+            let record = pair.swap_remove(1);
+            Ok((decode_time(&pair[0])?, record))
+        }
+        _ => bail!("expected [time, record]"),
+    }
+}
+
+/// Event time: integer seconds, a float, or the EventTime extension (type 0: u32 sec, u32 nsec)
+fn decode_time(value: &Value) -> Result<DateTime<Utc>> {
+    let time = match value {
+        Value::Ext(0, data) if data.len() == 8 => {
+            let secs = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            let nanos = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+            DateTime::from_timestamp(secs as i64, nanos)
+        }
+        Value::F32(_) | Value::F64(_) => {
+            let f = value.as_f64().unwrap_or_default();
+            DateTime::from_timestamp(f.trunc() as i64, (f.fract() * 1e9) as u32)
+        }
+        v => v.as_i64().and_then(|secs| DateTime::from_timestamp(secs, 0)),
+    };
+    time.ok_or_else(|| anyhow!("invalid event time"))
+}
+
+fn option_value<'a>(option: &'a Value, key: &str) -> Option<&'a Value> {
+    option.as_map()?.iter().find(|(k, _)| k.as_str() == Some(key)).map(|(_, v)| v)
+}
+
+/// Render a record as `TIME MESSAGE key=value ...`, where MESSAGE is the first of `MESSAGE_KEYS`
+fn format_record(time: &DateTime<Utc>, record: &Value) -> String {
+    let mut out = time.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let Some(fields) = record.as_map() else {
+        out.push(' ');
+        out.push_str(&value_text(record));
+        return out;
+    };
+    let message = MESSAGE_KEYS.iter().find_map(|key| fields.iter().position(|(k, v)| k.as_str() == Some(key) && v.is_str()));
+    if let Some(i) = message {
+        out.push(' ');
+        out.push_str(fields[i].1.as_str().unwrap_or_default().trim_end_matches(['\r', '\n']));
+    }
+    for (i, (k, v)) in fields.iter().enumerate() {
+        if Some(i) == message { continue; }
+        let text = value_text(v);
+        let quote = text.is_empty() || text.contains([' ', '=', '"']);
+        out.push(' ');
+        out.push_str(&value_text(k));
+        out.push('=');
+        if quote { out.push_str(&format!("{:?}", text)); } else { out.push_str(&text); }
+    }
+    out
+}
+
+/// Plain text of a value: strings and binaries without quotes, everything else as msgpack display
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.as_str().map(str::to_string).unwrap_or_else(|| String::from_utf8_lossy(s.as_bytes()).into_owned()),
+        Value::Binary(b) => String::from_utf8_lossy(b).into_owned(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: &Value) -> Vec<u8> {
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, value).unwrap();
+        buf
+    }
+
+    fn record(pairs: &[(&str, Value)]) -> Value {
+        Value::Map(pairs.iter().map(|(k, v)| (Value::from(*k), v.clone())).collect())
+    }
+
+    #[test]
+    fn test_decode_modes() {
+        let rec = record(&[("log", Value::from("hello\n")), ("level", Value::from("info")), ("n", Value::from(3))]);
+        let message = Value::Array(vec![Value::from("app"), Value::from(1_700_000_000), rec.clone()]);
+        let msg = decode_forward(message).unwrap();
+        assert_eq!(msg.tag, "app");
+        assert_eq!(format_record(&msg.entries[0].0, &msg.entries[0].1), "2023-11-14T22:13:20.000Z hello level=info n=3");
+
+        let entry = Value::Array(vec![Value::Ext(0, vec![0x65, 0x53, 0xF1, 0x00, 0x1D, 0xCD, 0x65, 0x00]), rec]);
+        let option = record(&[("chunk", Value::from("abc"))]);
+        let forward = Value::Array(vec![Value::from("app"), Value::Array(vec![entry.clone(), entry.clone()]), option.clone()]);
+        let msg = decode_forward(forward).unwrap();
+        assert_eq!(msg.entries.len(), 2);
+        assert_eq!(msg.chunk, Some(Value::from("abc")));
+        assert_eq!(msg.entries[0].0.timestamp_subsec_millis(), 500);
+
+        let packed = [encode(&entry), encode(&entry), encode(&entry)].concat();
+        let packed = Value::Array(vec![Value::from("app"), Value::Binary(packed), option]);
+        assert_eq!(decode_forward(packed).unwrap().entries.len(), 3);
+    }
+
+    #[test]
+    fn test_next_value_waits_for_complete_message() {
+        let bytes = encode(&Value::Array(vec![Value::from("tag"), Value::from(1), record(&[("k", Value::from("v"))])]));
+        assert!(next_value(&bytes[..bytes.len() - 1]).unwrap().is_none());
+        assert_eq!(next_value(&bytes).unwrap().unwrap().1, bytes.len());
+    }
+}
@@ -0,0 +1,98 @@
+//! Line volume over time for the histogram view.
+//!
+//! Lines are counted per minute or per hour of their embedded timestamp. Lines without one count
+//! with the previous timestamped line (continuation lines, stack traces); lines before the first
+//! timestamp are not counted. Empty periods between the first and the last bucket are kept as
+//! zero buckets so the x axis is linear in time.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+/// Older buckets are dropped beyond this, so a buffer spanning months stays cheap to draw
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketSize { Minute, Hour }
+
+impl BucketSize {
+    fn duration(self) -> Duration {
+        match self { BucketSize::Minute => Duration::minutes(1), BucketSize::Hour => Duration::hours(1) }
+    }
+
+    /// Start of the bucket containing `ts`
+    fn floor(self, ts: NaiveDateTime) -> NaiveDateTime {
+        let ts = ts.with_nanosecond(0).and_then(|t| t.with_second(0)).unwrap_or(ts);
+        match self { BucketSize::Minute => ts, BucketSize::Hour => ts.with_minute(0).unwrap_or(ts) }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self { BucketSize::Minute => "minute", BucketSize::Hour => "hour" }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bucket {
+    pub start: NaiveDateTime,
+    pub count: u64,
+    /// Index of the first line in this bucket; for an empty bucket the first line after it
+    pub first: usize,
+}
+
+/// Count `lines` (index, text) per bucket of `size`, oldest bucket first; `timestamp` reads a
+/// line's timestamp (`parse_timestamp`, or the source's profile)
+pub fn histogram<'a, I, T>(lines: I, size: BucketSize, timestamp: T) -> Vec<Bucket>
+where
+    I: Iterator<Item = (usize, &'a Arc<str>)>,
+    T: Fn(&str) -> Option<NaiveDateTime>,
+{
+    let mut counts: BTreeMap<NaiveDateTime, (u64, usize)> = BTreeMap::new();
+    let mut current = None;
+    for (i, line) in lines {
+        if let Some(ts) = timestamp(line) { current = Some(size.floor(ts)); }
+        let Some(start) = current else { continue; };
+        let entry = counts.entry(start).or_insert((0, i));
+        entry.0 += 1;
+        entry.1 = entry.1.min(i);
+    }
+    let (Some((&first, _)), Some((&last, _))) = (counts.first_key_value(), counts.last_key_value()) else { return Vec::new(); };
+    let step = size.duration();
+    let span = (last - first).num_seconds() / step.num_seconds();
+    let start = if span >= MAX_BUCKETS as i64 { last - step * (MAX_BUCKETS as i32 - 1) } else { first };
+    let mut buckets = Vec::new();
+    // Filled from the newest (never empty) bucket backwards, so empty buckets can point at the
+    // line after them
+    let mut next_first = 0;
+    let mut t = last;
+    while t >= start {
+        let (count, first) = counts.get(&t).copied().unwrap_or((0, next_first));
+        next_first = first;
+        buckets.push(Bucket { start: t, count, first });
+        t -= step;
+    }
+    buckets.reverse();
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::parse_timestamp;
+
+    #[test]
+    fn test_histogram_buckets() {
+        let lines: Vec<Arc<str>> = [
+            "preamble without time",
+            "2024-01-01 10:00:05 a",
+            "  continuation",
+            "2024-01-01 10:00:50 b",
+            "2024-01-01 10:03:10 c",
+        ].into_iter().map(Arc::from).collect();
+        let buckets = histogram(lines.iter().enumerate(), BucketSize::Minute, parse_timestamp);
+        let counts: Vec<(u32, u64, usize)> = buckets.iter().map(|b| (b.start.minute(), b.count, b.first)).collect();
+        assert_eq!(counts, vec![(0, 3, 1), (1, 0, 4), (2, 0, 4), (3, 1, 4)]);
+        let buckets = histogram(lines.iter().enumerate(), BucketSize::Hour, parse_timestamp);
+        assert_eq!(buckets.iter().map(|b| b.count).collect::<Vec<_>>(), vec![4]);
+    }
+}
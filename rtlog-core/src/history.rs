@@ -0,0 +1,128 @@
+//! Input history for the search overlay and the filter input.
+//!
+//! Behaves like shell history: Up walks back through previous entries, Down walks forward and
+//! finally restores whatever was being typed before navigation started. Entries are persisted as
+//! one line per entry in a plain text file so they survive across sessions.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Maximum number of entries kept per history (oldest are dropped first)
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    // Index into `entries` while navigating; None when editing a fresh line
+    cursor: Option<usize>,
+    // The in-progress input saved when navigation started
+    draft: String,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Load a history from `path`, ignoring a missing or unreadable file
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|s| s.lines().filter(|l| !l.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        Self { entries, cursor: None, draft: String::new(), path }
+    }
+
+    #[allow(dead_code)]
+    pub fn entries(&self) -> &[String] { &self.entries }
+
+    /// Record a submitted entry. Consecutive duplicates are collapsed and the file is rewritten.
+    pub fn push(&mut self, entry: &str) {
+        self.reset();
+        if entry.is_empty() || entry.contains('\n') { return; }
+        if self.entries.last().map(|l| l == entry).unwrap_or(false) { return; }
+        self.entries.push(entry.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+        self.save();
+    }
+
+    /// Step back in history. `current` is the input being edited; returns the text to show.
+    pub fn prev(&mut self, current: &str) -> Option<String> {
+        if self.entries.is_empty() { return None; }
+        let idx = match self.cursor {
+            None => { self.draft = current.to_string(); self.entries.len() - 1 }
+            Some(0) => 0,
+            Some(i) => i - 1,
+        };
+        self.cursor = Some(idx);
+        Some(self.entries[idx].clone())
+    }
+
+    /// Step forward in history, restoring the saved draft after the newest entry.
+    // Paired with `prev`; a history is not an iterator
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<String> {
+        let i = self.cursor?;
+        if i + 1 < self.entries.len() {
+            self.cursor = Some(i + 1);
+            Some(self.entries[i + 1].clone())
+        } else {
+            self.cursor = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    /// Leave navigation mode (e.g. after the input is edited or submitted)
+    pub fn reset(&mut self) {
+        self.cursor = None;
+        self.draft.clear();
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else { return; };
+        if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+        let mut out = self.entries.join("\n");
+        out.push('\n');
+        let _ = fs::write(path, out);
+    }
+}
+
+/// Directory for persisted rtlog state ($XDG_STATE_HOME/rtlog, falling back to ~/.local/state/rtlog)
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("rtlog"));
+    }
+    if let Some(dir) = std::env::var_os("LOCALAPPDATA").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("rtlog"));
+    }
+    std::env::var_os("HOME").filter(|d| !d.is_empty())
+        .map(|h| PathBuf::from(h).join(".local").join("state").join("rtlog"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> History {
+        let mut h = History::load(None);
+        for e in entries { h.push(e); }
+        h
+    }
+
+    #[test]
+    fn test_navigation_restores_draft() {
+        let mut h = history(&["one", "two"]);
+        assert_eq!(h.prev("dra").as_deref(), Some("two"));
+        assert_eq!(h.prev("two").as_deref(), Some("one"));
+        assert_eq!(h.prev("one").as_deref(), Some("one"));
+        assert_eq!(h.next().as_deref(), Some("two"));
+        assert_eq!(h.next().as_deref(), Some("dra"));
+        assert_eq!(h.next(), None);
+    }
+
+    #[test]
+    fn test_push_collapses_duplicates() {
+        let h = history(&["a", "a", "b", "", "a"]);
+        assert_eq!(h.entries(), &["a".to_string(), "b".to_string(), "a".to_string()]);
+    }
+}
@@ -0,0 +1,139 @@
+//! Filters bound to Alt+1 … Alt+9 (`[[hotkeys]]` in the config file, `b`).
+//!
+//! A hotkey adds its filter the first time it is pressed and then disables and re-enables it, so
+//! the searches of an incident (`5\d\d`, `timeout|refused`, a customer's ID) are switched on and
+//! off without typing them again. Keys are bound in the config file or by saving the applied
+//! search with `b`; saved bindings are kept in `$XDG_STATE_HOME/rtlog/hotkeys`, one
+//! `KEY\tFLAGS\tPATTERN` line each (FLAGS as in the filter list, e.g. `ri--`), and win over the
+//! config file's binding of the same key.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::HotkeyEntry;
+use crate::filter::FilterRule;
+
+/// Keys a filter can be bound to, with Alt
+pub const HOTKEYS: std::ops::RangeInclusive<u8> = 1..=9;
+
+#[derive(Debug, Default)]
+pub struct Hotkeys {
+    /// Bound in the config file
+    configured: BTreeMap<u8, FilterRule>,
+    /// Saved with `b`
+    saved: BTreeMap<u8, FilterRule>,
+    path: Option<PathBuf>,
+}
+
+impl Hotkeys {
+    /// Load the bindings saved in `path`, ignoring a missing or unreadable file
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let text = path.as_ref().and_then(|p| fs::read_to_string(p).ok()).unwrap_or_default();
+        let saved = text.lines().filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let key = fields.next()?.parse().ok().filter(|k| HOTKEYS.contains(k))?;
+            let rule = parse_flags(fields.next()?, fields.next()?)?;
+            Some((key, rule))
+        }).collect();
+        Self { configured: BTreeMap::new(), saved, path }
+    }
+
+    /// Bind the config file's `[[hotkeys]]`, replacing the ones bound before
+    pub fn configure(&mut self, entries: &[HotkeyEntry]) -> Result<()> {
+        self.configured = compile(entries)?;
+        Ok(())
+    }
+
+    /// The filter bound to `key`
+    pub fn get(&self, key: u8) -> Option<&FilterRule> {
+        self.saved.get(&key).or_else(|| self.configured.get(&key))
+    }
+
+    /// The key `rule` is bound to, for the filter list
+    pub fn key_of(&self, rule: &FilterRule) -> Option<u8> {
+        HOTKEYS.clone().find(|&key| self.get(key).is_some_and(|bound| same_filter(bound, rule)))
+    }
+
+    /// Bind `rule` to `key` and save the bindings. A pattern with a line break is not bound, as
+    /// the file has one binding a line; one that fails to be written stays bound until the viewer
+    /// quits.
+    pub fn save(&mut self, key: u8, rule: &FilterRule) -> io::Result<()> {
+        if rule.pattern.contains('\n') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "a pattern with a line break cannot be saved"));
+        }
+        self.saved.insert(key, FilterRule { enabled: true, compiled: None, match_count: 0, ..rule.clone() });
+        let Some(path) = &self.path else { return Ok(()); };
+        let mut text = String::new();
+        for (key, rule) in &self.saved {
+            text.push_str(&format!("{}\t{}\t{}\n", key, rule.flags(), rule.pattern));
+        }
+        path.parent().map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(path, text))
+            .map_err(|e| io::Error::new(e.kind(), format!("cannot write {}: {}", path.display(), e)))
+    }
+}
+
+/// Check the `[[hotkeys]]` of a config file: keys 1 to 9, each once, and valid patterns
+pub fn compile(entries: &[HotkeyEntry]) -> Result<BTreeMap<u8, FilterRule>> {
+    let mut bound = BTreeMap::new();
+    for entry in entries {
+        if !HOTKEYS.contains(&entry.key) { bail!("hotkeys: key must be between 1 and 9, not {}", entry.key); }
+        let rule = FilterRule::simple(entry.pattern.clone(), entry.regex);
+        rule.compile().with_context(|| format!("hotkeys: invalid pattern {:?}", entry.pattern))?;
+        if bound.insert(entry.key, rule).is_some() { bail!("hotkeys: key {} is bound twice", entry.key); }
+    }
+    Ok(bound)
+}
+
+/// Whether two filters search the same way, whatever their state
+pub fn same_filter(a: &FilterRule, b: &FilterRule) -> bool {
+    a.pattern == b.pattern && a.flags() == b.flags()
+}
+
+fn parse_flags(flags: &str, pattern: &str) -> Option<FilterRule> {
+    let (exclude, flags) = match flags.strip_prefix('!') { Some(rest) => (true, rest), None => (false, flags) };
+    let flags: Vec<char> = flags.chars().collect();
+    let [kind, case, word, line] = flags[..] else { return None; };
+    let mut rule = FilterRule::simple(pattern.to_string(), kind == 'r');
+    rule.fuzzy = kind == 'f';
+    rule.case_insensitive = case == 'i';
+    rule.whole_word = word == 'w';
+    rule.whole_line = line == 'x';
+    rule.exclude = exclude;
+    Some(rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_over_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hotkeys");
+        let entry = |key: u8, pattern: &str| HotkeyEntry { key, pattern: pattern.into(), regex: true };
+        let mut hotkeys = Hotkeys::load(Some(path.clone()));
+        hotkeys.configure(&[entry(1, "5\\d\\d"), entry(2, "timeout")]).unwrap();
+        let mut rule = FilterRule::simple("Refused".into(), false);
+        rule.case_insensitive = false;
+        hotkeys.save(2, &rule).unwrap();
+        // Not bound: the file has one binding a line
+        assert!(hotkeys.save(3, &FilterRule::simple("a\nb".into(), true)).is_err() && hotkeys.get(3).is_none());
+        let mut loaded = Hotkeys::load(Some(path.clone()));
+        loaded.configure(&[entry(1, "5\\d\\d"), entry(2, "timeout")]).unwrap();
+        assert_eq!(loaded.get(1).map(|r| (r.pattern.as_str(), r.is_regex)), Some(("5\\d\\d", true)));
+        assert_eq!(loaded.get(2).map(|r| (r.pattern.as_str(), r.flags())), Some(("Refused", "----".to_string())));
+        assert_eq!((loaded.get(3).is_none(), loaded.key_of(&rule)), (true, Some(2)));
+        assert!(compile(&[entry(0, "x")]).is_err() && compile(&[entry(1, "(")]).is_err());
+        assert!(compile(&[entry(4, "a"), entry(4, "b")]).unwrap_err().to_string().contains("key 4 is bound twice"));
+        // Bound for the session when the file cannot be written
+        let mut unwritable = Hotkeys::load(Some(path.join("hotkeys")));
+        let err = unwritable.save(5, &rule).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("cannot write {}", path.join("hotkeys").display())), "{}", err);
+        assert!(unwritable.get(5).is_some());
+    }
+}
@@ -0,0 +1,70 @@
+//! rtlog-core: the tailing, filtering and classification engine of the rtlog log viewer, without
+//! a terminal. The `rtlog` binary adds the CLI, the TUI and the headless front ends (reports, web
+//! view) on top; other tools can embed the same engine.
+//!
+//! The main pieces:
+//! - `log`: readers (`LogSource`: files, commands, SSH, HTTP, serial, ...) sending
+//!   `SourceEvent`s through an `EventSender`, which classifies each line on the way
+//! - `filter` and `classify`: filter rules, level patterns and alert rules
+//! - `state`: `AppState`, the buffered sources with their filters, search, alerts and statistics;
+//!   front ends render from it and feed it input
+//! - `config`: the TOML config file
+//!
+//! Feeding lines to a state and reading back the ones that pass the filters:
+//!
+//! ```
+//! use std::path::PathBuf;
+//! use rtlog_core::filter::FilterRule;
+//! use rtlog_core::log::LogLine;
+//! use rtlog_core::state::AppState;
+//!
+//! let mut state = AppState::new(None, Vec::new());
+//! state.set_sources([(None, PathBuf::from("app.log"))]);
+//! state.add_filter(FilterRule::simple("timeout".into(), false));
+//! let id = state.sources[0].id;
+//! for text in ["INFO started", "WARN upstream timeout", "INFO done"] {
+//!     state.push_line_for(id, LogLine::new(text));
+//! }
+//! let src = &state.sources[0];
+//! let shown: Vec<&str> = state.shown_lines(src).into_iter().map(|i| &*src.lines[i]).collect();
+//! assert_eq!(shown, ["WARN upstream timeout"]);
+//! ```
+//!
+//! Readers run as tokio tasks: give each source an id, spawn e.g. `log::stream_file(path,
+//! follow, id, sender)` with an `EventSender` built from the state's `shared_classifier()`, and
+//! apply the events received on the channel to the state.
+
+pub mod agent;
+pub mod classify;
+pub mod config;
+pub mod control;
+pub mod editor;
+pub mod email;
+pub mod encoding;
+pub mod export;
+pub mod filter;
+pub mod fluent;
+pub mod histogram;
+pub mod history;
+pub mod hotkeys;
+pub mod links;
+pub mod log;
+pub mod net;
+pub mod offsets;
+pub mod patterns;
+pub mod plugin;
+pub mod profile;
+pub mod query;
+pub mod redact;
+pub mod replace;
+pub mod replay;
+pub mod rewrite;
+pub mod session;
+pub mod sidebar;
+pub mod state;
+pub mod status;
+pub mod tail;
+pub mod tee;
+pub mod timestamp;
+pub mod transform;
+pub mod watch;
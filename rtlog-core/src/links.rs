@@ -0,0 +1,131 @@
+//! URLs and file paths in log lines, offered by the link chooser (`U`): URLs open in the
+//! browser, log files and directories (and URLs of log files) as new sources, other files with
+//! their default application.
+//!
+//! Only paths that exist are offered, so request paths like `GET /api/users` are left out.
+//! Relative paths are looked up in the working directory, then next to the log file.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Extensions of files opened as sources rather than with their default application
+const LOG_EXTENSIONS: [&str; 7] = ["log", "txt", "out", "err", "json", "jsonl", "ndjson"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// Opened in the browser
+    Url,
+    /// A log file or directory, or the URL of a log file: opened as a new source
+    Log,
+    /// Another file, opened with its default application
+    File,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub kind: LinkKind,
+    /// The URL, or the path as found (relative paths resolved)
+    pub target: String,
+}
+
+fn url_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"\b(?:https?|ftp)://[^\s<>"'`]+"#).unwrap())
+}
+
+fn path_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    // Starting the line or after a separator, so the path part of URLs is not taken
+    RE.get_or_init(|| Regex::new(r#"(?:^|[\s=("'\[,])((?:~|\.\.?)?/[\w.~@%+/-]+|[\w.-]+/[\w.~@%+/-]+)"#).unwrap())
+}
+
+/// The URLs and existing files or directories in `text`, in order and without repeats; `base`
+/// is the directory of the log file
+pub fn find_links(text: &str, base: Option<&Path>) -> Vec<Link> {
+    let mut found: Vec<(usize, Link)> = Vec::new();
+    let mut urls = Vec::new();
+    for m in url_regex().find_iter(text) {
+        let url = trim_trailing(m.as_str());
+        let kind = if is_log_name(url.split(['?', '#']).next().unwrap_or(url)) { LinkKind::Log } else { LinkKind::Url };
+        found.push((m.start(), Link { kind, target: url.to_string() }));
+        urls.push(m.range());
+    }
+    for caps in path_regex().captures_iter(text) {
+        let m = caps.get(1).expect("group 1 always takes part");
+        if urls.iter().any(|url| url.contains(&m.start())) { continue; }
+        let Some(path) = resolve(trim_trailing(m.as_str()), base) else { continue; };
+        let kind = if path.is_dir() || is_log_name(&path.to_string_lossy()) { LinkKind::Log } else { LinkKind::File };
+        found.push((m.start(), Link { kind, target: path.display().to_string() }));
+    }
+    found.sort_by_key(|(start, _)| *start);
+    let mut links: Vec<Link> = Vec::new();
+    for (_, link) in found {
+        if !links.contains(&link) { links.push(link); }
+    }
+    links
+}
+
+/// `text` without the punctuation that ends a sentence or closes a bracket around it (a `)` stays
+/// when the link has the matching `(`, as in Wikipedia URLs)
+fn trim_trailing(text: &str) -> &str {
+    let mut text = text;
+    loop {
+        let trimmed = text.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"', ']', '}', '>']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(inner) if inner.matches('(').count() < trimmed.matches(')').count() => inner,
+            _ => trimmed,
+        };
+        if trimmed.len() == text.len() { return text; }
+        text = trimmed;
+    }
+}
+
+/// Whether a file with this name is a log: a log extension, or `.log` followed by a rotation
+/// suffix (`app.log.1`)
+fn is_log_name(name: &str) -> bool {
+    let name = name.rsplit('/').next().unwrap_or(name).to_ascii_lowercase();
+    let extension = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    LOG_EXTENSIONS.contains(&extension) || name.contains(".log.")
+}
+
+/// Where `path` is: as given (`~` meaning the home directory), or else relative to `base`
+fn resolve(path: &str, base: Option<&Path>) -> Option<PathBuf> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+        None => PathBuf::from(path),
+    };
+    if path.exists() { return Some(path); }
+    base.filter(|_| path.is_relative()).map(|b| b.join(&path)).filter(|p| p.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_links() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        std::fs::write(dir.join("logs/app.log.1"), "").unwrap();
+        std::fs::write(dir.join("dump.bin"), "").unwrap();
+        let line = format!(
+            "see https://example.com/wiki/Foo_(bar). GET /no/such/path 404, rotated to logs/app.log.1; core in {}, log at http://host/x.log?raw=1",
+            dir.join("dump.bin").display(),
+        );
+        let links = find_links(&line, Some(dir));
+        let kinds: Vec<(LinkKind, &str)> = links.iter().map(|l| (l.kind, l.target.as_str())).collect();
+        assert_eq!(kinds, [
+            (LinkKind::Url, "https://example.com/wiki/Foo_(bar)"),
+            (LinkKind::Log, &*dir.join("logs/app.log.1").display().to_string()),
+            (LinkKind::File, &*dir.join("dump.bin").display().to_string()),
+            (LinkKind::Log, "http://host/x.log?raw=1"),
+        ]);
+        // A directory opens as a source too
+        let links = find_links(&format!("path={}/logs", dir.display()), None);
+        assert_eq!(links.iter().map(|l| l.kind).collect::<Vec<_>>(), [LinkKind::Log]);
+        assert_eq!(trim_trailing("https://a.b/c)."), "https://a.b/c");
+    }
+}
@@ -0,0 +1,984 @@
+//! Log ingestion layer: defines a generic interface for streaming log lines from sources.
+//! 
+//! This module follows SOLID principles by introducing an abstraction (`LogSource`) that can be
+//! implemented by different backends (files, sockets, etc.). The application runtime depends on
+//! this interface instead of a concrete file reader.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::time::sleep;
+
+use crate::classify::{LevelRules, LineClass, SharedClassifier};
+use crate::encoding::{read_line_async, Encoding};
+use crate::offsets::ReadOffsets;
+use crate::profile::Profile;
+use crate::tail::Tail;
+use crate::tee::Tee;
+use crate::transform::TransformChain;
+use crate::watch::FileWatch;
+
+/// Events sent from a reader task to the runtime, tagged with the source id on the channel
+#[derive(Debug)]
+pub enum SourceEvent {
+    Line(LogLine),
+    /// The reader failed and stopped
+    Error(String),
+    /// The reader finished normally (EOF without --follow)
+    Closed,
+    /// The input is temporarily unavailable (deleted, not yet created); the reader keeps retrying
+    Waiting(String),
+    /// The input became available again after `Waiting`
+    Reopened,
+    /// A line for the sub-source `tag` of a multiplexing source (e.g. a Fluent listener)
+    Record { tag: String, line: LogLine },
+    /// Something a plugin asked for (see `crate::plugin`)
+    Plugin(crate::plugin::PluginAction),
+    /// A problem worth telling the user about that does not stop the reader
+    Notice(String),
+}
+
+/// One line of input and its ingest-time classification
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    /// Shared (`Arc<str>`) so buffering, snapshots and views never copy the text
+    pub text: Arc<str>,
+    /// Filled in by `EventSender` before the line leaves the reader task
+    pub class: LineClass,
+}
+
+impl LogLine {
+    pub fn new(text: impl Into<Arc<str>>) -> Self {
+        Self { text: text.into(), class: LineClass::default() }
+    }
+
+    /// A separator rtlog inserts itself (see `LineClass::separator`)
+    pub fn separator(text: impl Into<Arc<str>>) -> Self {
+        Self { text: text.into(), class: LineClass { separator: true, ..LineClass::default() } }
+    }
+}
+
+/// Channel sender used by readers. Lines are classified (filter hits, level, alert) here, in the
+/// reader task, so the UI thread only has to store them.
+#[derive(Clone)]
+pub struct EventSender {
+    tx: Sender<(usize, SourceEvent)>,
+    classifier: SharedClassifier,
+    /// Files receiving a copy of every line (`--tee`)
+    tees: Vec<Tee>,
+    /// Level patterns replacing the classifier's defaults for this source
+    levels: Option<Arc<LevelRules>>,
+    /// Scripts rewriting (or dropping) this source's lines before classification
+    transforms: Option<Arc<TransformChain>>,
+    /// Parsing profile of the file (`[[sources]]`)
+    profile: Option<Arc<Profile>>,
+}
+
+impl EventSender {
+    pub fn new(tx: Sender<(usize, SourceEvent)>, classifier: SharedClassifier) -> Self {
+        Self { tx, classifier, tees: Vec::new(), levels: None, transforms: None, profile: None }
+    }
+
+    /// Same channel, additionally copying lines to `tees`
+    pub fn with_tees(&self, tees: Vec<Tee>) -> Self {
+        Self { tees, ..self.clone() }
+    }
+
+    /// Same channel, classifying levels with `levels` instead of the defaults
+    pub fn with_levels(self, levels: Option<Arc<LevelRules>>) -> Self {
+        Self { levels, ..self }
+    }
+
+    /// Same channel, running `transforms` on each line first
+    pub fn with_transforms(self, transforms: Option<Arc<TransformChain>>) -> Self {
+        Self { transforms, ..self }
+    }
+
+    /// Same channel, redacting lines and reading their level as `profile` says
+    pub fn with_profile(self, profile: Option<Arc<Profile>>) -> Self {
+        Self { profile, ..self }
+    }
+
+    pub async fn send(&self, (source_id, mut ev): (usize, SourceEvent)) -> Result<(), SendError<(usize, SourceEvent)>> {
+        if let SourceEvent::Line(line) | SourceEvent::Record { line, .. } = &mut ev
+            && !line.class.separator {
+            // Profile redaction comes first: the original text goes nowhere, tee files included
+            if let Some(profile) = &self.profile && let Cow::Owned(text) = profile.redact(&line.text) {
+                line.text = text.into();
+            }
+            // Tee files get the lines as received
+            for tee in &self.tees { tee.write(&line.text).await; }
+            if let Some(transforms) = &self.transforms {
+                let (text, error) = transforms.apply(&line.text);
+                if let Some(error) = error { self.tx.send((source_id, SourceEvent::Notice(error))).await?; }
+                match text {
+                    Some(text) => line.text = text.into(),
+                    None => return Ok(()),
+                }
+            }
+            let classifier = self.classifier.get();
+            line.class = match &self.levels {
+                Some(levels) => classifier.classify_with(&line.text, levels),
+                None => classifier.classify(&line.text),
+            };
+            if let Some(level) = self.profile.as_ref().and_then(|p| p.level(&line.text)) {
+                line.class = std::mem::take(&mut line.class).with_level(level);
+            }
+        }
+        self.tx.send((source_id, ev)).await
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+/// Generic trait for log sources.
+///
+/// Implementors should continuously send lines to the provided channel.
+#[async_trait::async_trait]
+pub trait LogSource {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()>;
+}
+
+/// How often a followed file is checked for new lines when no change notification comes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Safety-net recheck while waiting for change notifications (e.g. missed events on network filesystems)
+const NOTIFY_RECHECK: Duration = Duration::from_secs(2);
+const RETRY_INITIAL: Duration = Duration::from_millis(500);
+const RETRY_MAX: Duration = Duration::from_secs(10);
+/// Lines a file reader reads on the blocking pool before sending them
+const READ_BATCH: usize = 1024;
+
+/// Concrete file-tail source. If `follow` is true, it behaves like `tail -F`: a file that is
+/// missing, deleted or rotated is reopened (with backoff) instead of ending the reader.
+pub struct FileTail {
+    pub path: PathBuf,
+    pub follow: bool,
+    /// With `--resume`: where the last session stopped reading, and how far this one gets
+    pub offsets: Option<Arc<ReadOffsets>>,
+    /// Told from the start of the file when None
+    pub encoding: Option<Encoding>,
+}
+
+#[async_trait::async_trait]
+impl LogSource for FileTail {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        #[cfg(unix)]
+        if is_fifo(&self.path).await { return self.stream_fifo(source_id, &tx).await; }
+        // Change notifications replace polling when the platform supports them
+        let watch = self.follow.then(|| FileWatch::new(&self.path));
+        let mut tail = Tail::new(self.path.clone(), self.follow);
+        tail.tracked = self.offsets.as_ref().map(|offsets| offsets.track(&self.path));
+        tail.encoding = self.encoding;
+        let mut backoff = RETRY_INITIAL;
+        loop {
+            let (read, events, more) = tokio::task::spawn_blocking(move || {
+                let mut events = Vec::new();
+                let more = tail.read(&mut events, READ_BATCH);
+                (tail, events, more)
+            }).await?;
+            tail = read;
+            for ev in events {
+                match ev {
+                    // Reported by `stream_and_report`
+                    SourceEvent::Closed => return Ok(()),
+                    SourceEvent::Error(e) => anyhow::bail!(e),
+                    ev => if tx.send((source_id, ev)).await.is_err() { return Ok(()); },
+                }
+            }
+            if more { continue; }
+            if tx.is_closed() { return Ok(()); }
+            let Some(watch) = &watch else { return Ok(()); };
+            if tail.is_waiting() {
+                watch.wait(backoff, backoff).await;
+                backoff = (backoff * 2).min(RETRY_MAX);
+            } else {
+                backoff = RETRY_INITIAL;
+                watch.wait(NOTIFY_RECHECK, POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl FileTail {
+    /// Read a named pipe. EOF only means the current writer closed its end: with `follow` the
+    /// pipe is reopened so a restarted writer keeps streaming, without it the reader ends there.
+    async fn stream_fifo(&self, source_id: usize, tx: &EventSender) -> Result<()> {
+        use tokio::net::unix::pipe;
+        let encoding = self.encoding.unwrap_or_default();
+        if !self.follow {
+            // Blocking open: waits for a writer, so EOF is the end of what it wrote
+            let mut reader = BufReader::new(File::open(&self.path).await?);
+            let mut raw = Vec::new();
+            while read_line_async(&mut reader, encoding, &mut raw).await? > 0 {
+                if !send_raw_line(tx, source_id, encoding, &mut raw).await { break; }
+            }
+            return Ok(());
+        }
+        loop {
+            // Non-blocking open: succeeds without a writer, reads then report EOF until one connects
+            let receiver = pipe::OpenOptions::new().open_receiver(&self.path)?;
+            let mut reader = BufReader::new(receiver);
+            let mut raw = Vec::new();
+            while read_line_async(&mut reader, encoding, &mut raw).await? > 0 {
+                if !send_raw_line(tx, source_id, encoding, &mut raw).await { return Ok(()); }
+            }
+            if tx.is_closed() { return Ok(()); }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    tokio::fs::metadata(path).await.is_ok_and(|md| md.file_type().is_fifo())
+}
+
+/// Strip the line terminator and send; returns false when the receiver is gone
+pub(crate) async fn send_line(tx: &EventSender, source_id: usize, buf: &mut String) -> bool {
+    if buf.ends_with('\n') { buf.pop(); }
+    if buf.ends_with('\r') { buf.pop(); }
+    let line = LogLine::new(buf.as_str());
+    buf.clear();
+    tx.send((source_id, SourceEvent::Line(line))).await.is_ok()
+}
+
+/// Decode a line read as bytes in `encoding` and send it; returns false when the receiver is gone
+async fn send_raw_line(tx: &EventSender, source_id: usize, encoding: Encoding, raw: &mut Vec<u8>) -> bool {
+    let line = LogLine::new(encoding.decode(raw));
+    raw.clear();
+    tx.send((source_id, SourceEvent::Line(line))).await.is_ok()
+}
+
+/// Whether `a` and `b` describe the same file (true where that cannot be told)
+#[cfg(unix)]
+pub fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Without inode numbers (the file index is not stable Rust on Windows), a file put in the
+/// place of a rotated one is told by its later creation time
+#[cfg(not(unix))]
+pub fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    match (a.created(), b.created()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => true,
+    }
+}
+
+/// Runs a shell command and streams its combined stdout/stderr lines until it exits.
+/// The child is killed when the reader task is aborted (e.g. the source is closed).
+pub struct CommandSource {
+    pub command: String,
+    /// Lines written to the command's stdin (closed afterwards); without them stdin is null
+    pub input: Option<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl LogSource for CommandSource {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        let script = format!("{} 2>&1", self.command);
+        let mut cmd = if cfg!(windows) {
+            let mut c = Command::new("cmd");
+            c.arg("/C").arg(script);
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.arg("-c").arg(script);
+            c
+        };
+        let stdin = if self.input.is_some() { Stdio::piped() } else { Stdio::null() };
+        let mut child = cmd.stdin(stdin).stdout(Stdio::piped()).kill_on_drop(true).spawn()?;
+        // Fed concurrently so a command that writes before reading everything cannot deadlock
+        if let (Some(lines), Some(mut stdin)) = (self.input, child.stdin.take()) {
+            tokio::spawn(async move {
+                for line in lines {
+                    // The command may exit without reading all of its input
+                    if stdin.write_all(line.as_bytes()).await.is_err() || stdin.write_all(b"\n").await.is_err() { break; }
+                }
+            });
+        }
+        let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("no stdout"))?;
+        let mut reader = BufReader::new(stdout);
+        let mut buf = String::new();
+        while reader.read_line(&mut buf).await? > 0 {
+            if !send_line(&tx, source_id, &mut buf).await { return Ok(()); }
+        }
+        let status = child.wait().await?;
+        if !status.success() { anyhow::bail!("command {}", status); }
+        Ok(())
+    }
+}
+
+/// Separator lines before each `--watch` run start with this (and end with " ----")
+pub const RUN_PREFIX: &str = "---- RUN ";
+
+/// Runs a shell command repeatedly like `watch`: each run's combined output follows a separator
+/// line with the time and the command, and the next run starts `interval` after one finishes.
+pub struct WatchCommand {
+    pub command: String,
+    pub interval: Duration,
+}
+
+#[async_trait::async_trait]
+impl LogSource for WatchCommand {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        loop {
+            let separator = format!("{}{} $ {} ----", RUN_PREFIX, chrono::Local::now().format("%H:%M:%S"), self.command);
+            if tx.send((source_id, SourceEvent::Line(LogLine::separator(separator)))).await.is_err() { return Ok(()); }
+            let run = CommandSource { command: self.command.clone(), input: None };
+            // A failing run is shown in its block; the next one is tried anyway
+            if let Err(e) = run.stream(source_id, tx.clone()).await {
+                let mut line = format!("({})", e);
+                if !send_line(&tx, source_id, &mut line).await { return Ok(()); }
+            }
+            if tx.is_closed() { return Ok(()); }
+            sleep(self.interval).await;
+        }
+    }
+}
+
+/// Follows a file on a remote host by running `tail` (or `cat` without follow) through the system
+/// `ssh` client. With `follow`, a dropped connection is retried with backoff like a missing file.
+/// Authentication must work non-interactively (keys or an agent); password prompts are disabled.
+pub struct SshTail {
+    /// `[user@]host` as understood by ssh (ports and options come from ~/.ssh/config)
+    pub target: String,
+    pub path: String,
+    pub follow: bool,
+}
+
+/// How long a new ssh session must stay up before it counts as connected
+const SSH_SETTLE: Duration = Duration::from_secs(3);
+
+#[async_trait::async_trait]
+impl LogSource for SshTail {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        let path = shell_quote(&self.path);
+        let mut backoff = RETRY_INITIAL;
+        let mut waiting = false;
+        loop {
+            // Lines written while disconnected are not replayed: reconnects resume at the end
+            let remote = if self.follow { format!("exec tail -n 0 -F -- {}", path) } else { format!("exec cat -- {}", path) };
+            let mut child = Command::new("ssh")
+                .args(["-o", "BatchMode=yes", "-o", "ServerAliveInterval=15", "-o", "ServerAliveCountMax=3", "--"])
+                .arg(&self.target)
+                .arg(remote)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()?;
+            let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("no stdout"))?;
+            let stderr = child.stderr.take().ok_or_else(|| anyhow::anyhow!("no stderr"))?;
+            let last_err = tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                let mut last = String::new();
+                while let Ok(Some(l)) = lines.next_line().await {
+                    if !l.trim().is_empty() { last = l; }
+                }
+                last
+            });
+            let mut reader = BufReader::new(stdout);
+            let mut buf = String::new();
+            let settle = sleep(SSH_SETTLE);
+            tokio::pin!(settle);
+            let mut settled = false;
+            loop {
+                tokio::select! {
+                    n = reader.read_line(&mut buf) => {
+                        if n? == 0 { break; }
+                        settled = true;
+                        backoff = RETRY_INITIAL;
+                        if waiting {
+                            waiting = false;
+                            if tx.send((source_id, SourceEvent::Reopened)).await.is_err() { return Ok(()); }
+                        }
+                        if !send_line(&tx, source_id, &mut buf).await { return Ok(()); }
+                    }
+                    _ = &mut settle, if !settled => {
+                        settled = true;
+                        backoff = RETRY_INITIAL;
+                        if waiting {
+                            waiting = false;
+                            if tx.send((source_id, SourceEvent::Reopened)).await.is_err() { return Ok(()); }
+                        }
+                    }
+                }
+            }
+            let status = child.wait().await?;
+            let last_err = last_err.await.unwrap_or_default();
+            if status.success() { return Ok(()); }
+            // ssh itself exits with 255 on connection and authentication failures
+            let reason = if last_err.is_empty() { format!("ssh {}", status) } else { last_err };
+            if !self.follow || status.code() != Some(255) { anyhow::bail!("{}", reason); }
+            if !waiting {
+                waiting = true;
+                if tx.send((source_id, SourceEvent::Waiting(format!("{}: {}", self.target, reason)))).await.is_err() { return Ok(()); }
+            }
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(RETRY_MAX);
+        }
+    }
+}
+
+/// Quote a string for a POSIX shell (the remote side of an ssh session)
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Shell command streaming the macOS unified log (`log stream`), optionally filtered by an
+/// NSPredicate such as `subsystem == "com.example.app"`. Run it as a `CommandSource`.
+pub fn oslog_command(predicate: &str) -> String {
+    let mut cmd = "log stream --style compact".to_string();
+    if !predicate.trim().is_empty() {
+        cmd.push_str(" --predicate ");
+        cmd.push_str(&shell_quote(predicate));
+    }
+    cmd
+}
+
+/// Split an ssh source spec `[user@]host:/path` into target and remote path. It splits at the
+/// last `:` outside brackets, so an IPv6 host is written in brackets (`[::1]:/var/log/app.log`),
+/// which the target is given without, as ssh takes it
+pub fn parse_ssh_spec(spec: &str) -> Option<(String, String)> {
+    let mut depth = 0usize;
+    let mut split = None;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.checked_sub(1)?,
+            ':' if depth == 0 => split = Some(i),
+            _ => {}
+        }
+    }
+    let (target, path) = spec.split_at(split?);
+    let path = &path[1..];
+    let (user, host) = match target.rsplit_once('@') {
+        Some((user, host)) => (Some(user), host),
+        None => (None, target),
+    };
+    let host = match host.strip_prefix('[') {
+        Some(inner) => inner.strip_suffix(']')?,
+        None if host.contains(']') => return None,
+        None => host,
+    };
+    if depth > 0 || host.is_empty() || user == Some("") || path.is_empty() { return None; }
+    let target = match user { Some(user) => format!("{}@{}", user, host), None => host.to_string() };
+    Some((target, path.to_string()))
+}
+
+/// Polls a URL and appends what was added since the last poll, using `Range: bytes=N-` requests.
+/// Without `follow` the body is fetched once. With it, reading starts at the end of what the
+/// first poll finds, like a followed file. Servers that ignore ranges are handled by skipping
+/// the bytes already seen; a body shorter than before is treated as truncation and re-read.
+/// Failed polls are retried with backoff, `HTTP_RETRIES` times in a row before giving up.
+pub struct HttpPoll {
+    pub url: String,
+    pub follow: bool,
+    pub interval: Duration,
+}
+
+/// Failed polls in a row after which an HTTP source gives up
+const HTTP_RETRIES: u32 = 10;
+
+#[async_trait::async_trait]
+impl LogSource for HttpPoll {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("rtlog/", env!("CARGO_PKG_VERSION")))
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        if !self.follow {
+            let body = client.get(&self.url).send().await?.error_for_status()?.bytes().await?;
+            let mut pending = body.to_vec();
+            if !pending.is_empty() && !pending.ends_with(b"\n") { pending.push(b'\n'); }
+            send_complete_lines(&tx, source_id, &mut pending).await;
+            return Ok(());
+        }
+
+        // Unknown until the first successful poll, which starts at the end
+        let mut offset: Option<u64> = None;
+        // Bytes after the last newline, held back until the line is complete
+        let mut pending: Vec<u8> = Vec::new();
+        let mut failures = 0;
+        let mut backoff = self.interval;
+        loop {
+            let poll = match offset {
+                Some(offset) => self.poll_once(&client, offset).await,
+                None => self.length(&client).await.map(Poll::Start),
+            };
+            match poll {
+                Ok(Poll::Start(len)) => offset = Some(len),
+                Ok(Poll::Data(body)) => {
+                    offset = offset.map(|o| o + body.len() as u64);
+                    pending.extend_from_slice(&body);
+                    if !send_complete_lines(&tx, source_id, &mut pending).await { return Ok(()); }
+                }
+                Ok(Poll::Unchanged) => {}
+                Ok(Poll::Truncated) => { offset = Some(0); pending.clear(); continue; }
+                Err(e) => {
+                    failures += 1;
+                    if failures == HTTP_RETRIES { return Err(e.context(format!("{}: gave up after {} failed polls", self.url, HTTP_RETRIES))); }
+                    let reason = format!("{}: {} (retry {} of {})", self.url, e, failures, HTTP_RETRIES - 1);
+                    if tx.send((source_id, SourceEvent::Waiting(reason))).await.is_err() { return Ok(()); }
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(RETRY_MAX.max(self.interval));
+                    continue;
+                }
+            }
+            if failures > 0 {
+                (failures, backoff) = (0, self.interval);
+                if tx.send((source_id, SourceEvent::Reopened)).await.is_err() { return Ok(()); }
+            }
+            if tx.is_closed() { return Ok(()); }
+            sleep(self.interval).await;
+        }
+    }
+}
+
+/// Outcome of one HTTP poll
+#[derive(Debug, PartialEq)]
+enum Poll {
+    /// Length of the resource when following starts
+    Start(u64),
+    /// New bytes past the previous offset
+    Data(Vec<u8>),
+    Unchanged,
+    /// The resource is now shorter than what was already read
+    Truncated,
+}
+
+impl HttpPoll {
+    /// Length of the resource, asked for with a one-byte range
+    async fn length(&self, client: &reqwest::Client) -> Result<u64> {
+        let resp = client.get(&self.url).header(reqwest::header::RANGE, "bytes=0-0").send().await?;
+        let (status, range) = (resp.status(), content_range(&resp));
+        let body = resp.bytes().await?;
+        resource_length(status, range.as_deref(), body.len())
+    }
+
+    async fn poll_once(&self, client: &reqwest::Client, offset: u64) -> Result<Poll> {
+        let resp = client.get(&self.url).header(reqwest::header::RANGE, format!("bytes={}-", offset)).send().await?;
+        let (status, range) = (resp.status(), content_range(&resp));
+        let body = resp.bytes().await?;
+        poll_outcome(status, range.as_deref(), &body, offset)
+    }
+}
+
+fn content_range(resp: &reqwest::Response) -> Option<String> {
+    resp.headers().get(reqwest::header::CONTENT_RANGE)?.to_str().ok().map(str::to_string)
+}
+
+/// Total length in a `Content-Range` header (`bytes 0-0/1234`, `bytes */1234`)
+fn range_total(content_range: Option<&str>) -> Option<u64> {
+    content_range?.rsplit('/').next()?.parse().ok()
+}
+
+/// Length of a resource from the answer to `Range: bytes=0-0`: the range's total, or the whole
+/// body from a server that ignores ranges
+fn resource_length(status: reqwest::StatusCode, content_range: Option<&str>, body_len: usize) -> Result<u64> {
+    use reqwest::StatusCode;
+    match status {
+        // Empty: no byte to give
+        StatusCode::RANGE_NOT_SATISFIABLE => Ok(range_total(content_range).unwrap_or(0)),
+        StatusCode::PARTIAL_CONTENT => range_total(content_range).ok_or_else(|| anyhow::anyhow!("HTTP 206 without the length in Content-Range")),
+        status if status.is_success() => Ok(body_len as u64),
+        status => anyhow::bail!("HTTP {}", status),
+    }
+}
+
+/// What the answer to `Range: bytes=OFFSET-` means for a reader at `offset`
+fn poll_outcome(status: reqwest::StatusCode, content_range: Option<&str>, body: &[u8], offset: u64) -> Result<Poll> {
+    use reqwest::StatusCode;
+    Ok(match status {
+        // `Content-Range: bytes */LEN` tells whether the resource shrank
+        StatusCode::RANGE_NOT_SATISFIABLE => match range_total(content_range) {
+            Some(len) if len < offset => Poll::Truncated,
+            _ => Poll::Unchanged,
+        },
+        StatusCode::PARTIAL_CONTENT if body.is_empty() => Poll::Unchanged,
+        StatusCode::PARTIAL_CONTENT => Poll::Data(body.to_vec()),
+        // Range ignored: the full body came back
+        status if status.is_success() => match (body.len() as u64).cmp(&offset) {
+            std::cmp::Ordering::Less => Poll::Truncated,
+            std::cmp::Ordering::Equal => Poll::Unchanged,
+            std::cmp::Ordering::Greater => Poll::Data(body[offset as usize..].to_vec()),
+        },
+        status => anyhow::bail!("HTTP {}", status),
+    })
+}
+
+/// Send every newline-terminated line in `pending` (decoded lossily) and keep the incomplete tail.
+/// Returns false when the receiver is gone.
+async fn send_complete_lines(tx: &EventSender, source_id: usize, pending: &mut Vec<u8>) -> bool {
+    let Some(last_nl) = pending.iter().rposition(|&b| b == b'\n') else { return true };
+    let rest = pending.split_off(last_nl + 1);
+    let mut complete = std::mem::replace(pending, rest);
+    complete.pop();
+    for raw in complete.split(|&b| b == b'\n') {
+        let mut line = String::from_utf8_lossy(raw).into_owned();
+        if !send_line(tx, source_id, &mut line).await { return false; }
+    }
+    true
+}
+
+/// Reads newline-delimited output from a serial device (e.g. a board's console on /dev/ttyUSB0).
+/// An unplugged device is reopened with backoff; with `follow` the device may also be missing at start.
+pub struct SerialSource {
+    pub path: String,
+    pub baud: u32,
+    pub follow: bool,
+}
+
+/// Baud rate used when `--serial` has no `:BAUD` suffix
+pub const DEFAULT_BAUD: u32 = 115_200;
+
+#[async_trait::async_trait]
+impl LogSource for SerialSource {
+    async fn stream(self, source_id: usize, tx: EventSender) -> Result<()> {
+        use tokio_serial::SerialPortBuilderExt;
+        let mut backoff = RETRY_INITIAL;
+        let mut opened_once = false;
+        let mut waiting = false;
+        loop {
+            let reason = match tokio_serial::new(&self.path, self.baud).open_native_async() {
+                Ok(port) => {
+                    opened_once = true;
+                    backoff = RETRY_INITIAL;
+                    if waiting {
+                        waiting = false;
+                        if tx.send((source_id, SourceEvent::Reopened)).await.is_err() { return Ok(()); }
+                    }
+                    let mut reader = BufReader::new(port);
+                    let mut raw = Vec::new();
+                    loop {
+                        match reader.read_until(b'\n', &mut raw).await {
+                            Ok(0) => break format!("{}: device closed", self.path),
+                            Ok(_) => {
+                                let mut line = String::from_utf8_lossy(&raw).into_owned();
+                                raw.clear();
+                                if !send_line(&tx, source_id, &mut line).await { return Ok(()); }
+                            }
+                            Err(e) => break format!("{}: {}", self.path, e),
+                        }
+                    }
+                }
+                Err(e) if opened_once || self.follow => format!("{}: {}", self.path, e),
+                Err(e) => anyhow::bail!("{}: {}", self.path, e),
+            };
+            if !waiting {
+                waiting = true;
+                if tx.send((source_id, SourceEvent::Waiting(reason))).await.is_err() { return Ok(()); }
+            }
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(RETRY_MAX);
+        }
+    }
+}
+
+/// Split a serial spec `PATH[:BAUD]` (e.g. `/dev/ttyUSB0:115200`, `COM3`)
+pub fn parse_serial_spec(spec: &str) -> Option<(String, u32)> {
+    let (path, baud) = match spec.rsplit_once(':') {
+        Some((path, baud)) if baud.chars().all(|c| c.is_ascii_digit()) => (path, baud.parse().ok().filter(|&b| b > 0)?),
+        _ => (spec, DEFAULT_BAUD),
+    };
+    if path.is_empty() { return None; }
+    Some((path.to_string(), baud))
+}
+
+/// Run any source to completion, reporting the outcome on the channel as
+/// `SourceEvent::Closed` or `SourceEvent::Error`. `label` names the input in error messages.
+pub async fn stream_and_report<S: LogSource + Send>(source: S, label: &Path, source_id: usize, tx: EventSender) -> Result<()> {
+    let res = source.stream(source_id, tx.clone()).await;
+    let ev = match &res {
+        Ok(()) => SourceEvent::Closed,
+        Err(e) => SourceEvent::Error(describe_error(e, label)),
+    };
+    let _ = tx.send((source_id, ev)).await;
+    res
+}
+
+/// Backwards-compatible helper that streams a file using the new `FileTail` implementor.
+/// Reader failures are reported on the channel as `SourceEvent::Error` as well as returned.
+pub async fn stream_file(path: PathBuf, follow: bool, source_id: usize, tx: EventSender) -> Result<()> {
+    stream_and_report(FileTail { path: path.clone(), follow, offsets: None, encoding: None }, &path, source_id, tx).await
+}
+
+/// Helper that runs a shell command as a source (see `CommandSource`).
+pub async fn stream_command(command: String, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&command);
+    stream_and_report(CommandSource { command, input: None }, &label, source_id, tx).await
+}
+
+/// Helper that reruns a shell command every `interval` as a source (see `WatchCommand`).
+pub async fn stream_watch(command: String, interval: Duration, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&command);
+    stream_and_report(WatchCommand { command, interval }, &label, source_id, tx).await
+}
+
+/// Helper that runs a shell command with `input` on its stdin as a source (see `CommandSource`).
+pub async fn stream_pipe(command: String, input: Vec<String>, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&command);
+    stream_and_report(CommandSource { command, input: Some(input) }, &label, source_id, tx).await
+}
+
+/// Helper that follows a remote file over ssh (see `SshTail`); `spec` is `[user@]host:/path`.
+pub async fn stream_ssh(spec: String, follow: bool, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&spec);
+    let (target, path) = parse_ssh_spec(&spec).ok_or_else(|| anyhow::anyhow!("invalid ssh source: {}", spec))?;
+    stream_and_report(SshTail { target, path, follow }, &label, source_id, tx).await
+}
+
+/// Helper that polls a URL as a source (see `HttpPoll`).
+pub async fn stream_http(url: String, follow: bool, interval: Duration, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&url);
+    stream_and_report(HttpPoll { url, follow, interval }, &label, source_id, tx).await
+}
+
+/// Helper that runs a Fluent forward listener (see `crate::fluent::FluentListener`).
+pub async fn stream_fluent(addr: String, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&addr);
+    stream_and_report(crate::fluent::FluentListener { addr }, &label, source_id, tx).await
+}
+
+/// Helper that accepts `rtlog agent` connections (see `crate::agent::AgentListener`).
+pub async fn stream_agents(addr: String, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&addr);
+    stream_and_report(crate::agent::AgentListener { addr }, &label, source_id, tx).await
+}
+
+/// Helper that runs a plugin process (see `crate::plugin::PluginProcess`).
+pub async fn stream_plugin(command: String, events: Receiver<String>, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&command);
+    stream_and_report(crate::plugin::PluginProcess { command, events }, &label, source_id, tx).await
+}
+
+/// Helper that reads a serial device as a source (see `SerialSource`); `spec` is `PATH[:BAUD]`.
+pub async fn stream_serial(spec: String, follow: bool, source_id: usize, tx: EventSender) -> Result<()> {
+    let label = PathBuf::from(&spec);
+    let (path, baud) = parse_serial_spec(&spec).ok_or_else(|| anyhow::anyhow!("invalid serial source: {}", spec))?;
+    stream_and_report(SerialSource { path, baud, follow }, &label, source_id, tx).await
+}
+
+/// Human-readable reader error, e.g. "not found: /var/log/app.log"
+pub(crate) fn describe_error(e: &anyhow::Error, path: &Path) -> String {
+    match e.downcast_ref::<std::io::Error>() {
+        Some(io) => describe_io_error(io, path),
+        None => e.to_string(),
+    }
+}
+
+/// Short reason why `path` cannot be read, for the sidebar and alerts
+pub fn describe_io_error(e: &std::io::Error, path: &Path) -> String {
+    use std::io::ErrorKind;
+    match e.kind() {
+        ErrorKind::NotFound => format!("not found: {}", path.display()),
+        ErrorKind::PermissionDenied => format!("permission denied: {}", path.display()),
+        _ => format!("{}: {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use reqwest::StatusCode;
+    use tokio::io::AsyncReadExt;
+    use tokio::sync::mpsc;
+    use crate::classify::SharedClassifier;
+
+    /// Serve `body` at a local URL the way a file server does, with `Range: bytes=N-M` requests
+    /// answered by 206 or 416; 503 while the body is None. Returns the URL and the request count
+    async fn serve(body: Arc<Mutex<Option<Vec<u8>>>>) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/app.log", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let count = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(n) if n > 0 => request.extend_from_slice(&buf[..n]),
+                        _ => break,
+                    }
+                }
+                count.fetch_add(1, Ordering::SeqCst);
+                let request = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                let range = request.lines().find_map(|l| l.strip_prefix("range: bytes=")).and_then(|r| r.trim().split_once('-'));
+                let body = body.lock().unwrap().clone();
+                let (status, headers, content) = match (body, range) {
+                    (None, _) => ("503 Service Unavailable", String::new(), Vec::new()),
+                    (Some(body), Some((start, end))) => {
+                        let (start, len) = (start.parse::<usize>().unwrap(), body.len());
+                        let end = end.parse::<usize>().map_or(len, |e| (e + 1).min(len));
+                        if start >= len {
+                            ("416 Range Not Satisfiable", format!("Content-Range: bytes */{}\r\n", len), Vec::new())
+                        } else {
+                            ("206 Partial Content", format!("Content-Range: bytes {}-{}/{}\r\n", start, end - 1, len), body[start..end].to_vec())
+                        }
+                    }
+                    (Some(body), None) => ("200 OK", String::new(), body),
+                };
+                let head = format!("HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n", status, headers, content.len());
+                let _ = stream.write_all(&[head.into_bytes(), content].concat()).await;
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    fn test_http_follow_from_end() {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let body = Arc::new(Mutex::new(Some(b"old 1\nold 2\n".to_vec())));
+            let (url, requests) = serve(Arc::clone(&body)).await;
+            let (raw_tx, mut events) = mpsc::channel(8);
+            let poll = HttpPoll { url, follow: true, interval: Duration::from_millis(10) };
+            let reader = tokio::spawn(poll.stream(1, EventSender::new(raw_tx, SharedClassifier::default())));
+            while requests.load(Ordering::SeqCst) == 0 { sleep(Duration::from_millis(5)).await; }
+            // Only what is added after the first poll, the incomplete line once it is complete
+            body.lock().unwrap().as_mut().unwrap().extend_from_slice(b"new 1\nnew");
+            assert!(matches!(events.recv().await, Some((1, SourceEvent::Line(line))) if &*line.text == "new 1"));
+            body.lock().unwrap().as_mut().unwrap().extend_from_slice(b" 2\n");
+            assert!(matches!(events.recv().await, Some((1, SourceEvent::Line(line))) if &*line.text == "new 2"));
+            // Shorter than what was read: truncated, read again from the start
+            *body.lock().unwrap() = Some(b"fresh\n".to_vec());
+            assert!(matches!(events.recv().await, Some((1, SourceEvent::Line(line))) if &*line.text == "fresh"));
+            reader.abort();
+        });
+    }
+
+    #[test]
+    fn test_http_retries_are_limited() {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let (url, requests) = serve(Arc::new(Mutex::new(None))).await;
+            let (raw_tx, mut events) = mpsc::channel(16);
+            let poll = HttpPoll { url, follow: true, interval: Duration::from_millis(1) };
+            let reader = tokio::spawn(poll.stream(1, EventSender::new(raw_tx, SharedClassifier::default())));
+            assert!(matches!(events.recv().await, Some((1, SourceEvent::Waiting(reason))) if reason.ends_with("HTTP 503 Service Unavailable (retry 1 of 9)")));
+            let err = reader.await.unwrap().unwrap_err();
+            assert!(format!("{:#}", err).contains("gave up after 10 failed polls"), "{:#}", err);
+            assert_eq!(requests.load(Ordering::SeqCst), 10);
+        });
+    }
+
+    /// The next event of source 1 other than a line, or the text of its next line
+    async fn next_event(events: &mut mpsc::Receiver<(usize, SourceEvent)>) -> String {
+        match events.recv().await {
+            Some((1, SourceEvent::Line(line))) => line.text.to_string(),
+            Some((1, SourceEvent::Waiting(_))) => "<waiting>".into(),
+            Some((1, SourceEvent::Reopened)) => "<reopened>".into(),
+            other => format!("{:?}", other.map(|(_, ev)| std::mem::discriminant(&ev))),
+        }
+    }
+
+    #[test]
+    fn test_follow_truncation_rotation_and_deletion() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("app.log");
+        std::fs::write(&path, "before\n").unwrap();
+        let append = |text: &str| std::io::Write::write_all(&mut std::fs::OpenOptions::new().append(true).open(&path).unwrap(), text.as_bytes()).unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let (raw_tx, mut events) = mpsc::channel(8);
+            let reader = tokio::spawn(stream_file(path.clone(), true, 1, EventSender::new(raw_tx, SharedClassifier::default())));
+            // Followed from the end, once the reader has opened the file
+            sleep(Duration::from_millis(200)).await;
+            append("one\n");
+            assert_eq!(next_event(&mut events).await, "one");
+            // Truncated in place (copytruncate): read again from the start
+            std::fs::write(&path, "two\n").unwrap();
+            assert_eq!(next_event(&mut events).await, "two");
+            // Rotated: the new file, put in place in one step, is read from its start
+            std::fs::hard_link(&path, dir.join("app.log.1")).unwrap();
+            std::fs::write(dir.join("app.log.new"), "three\n").unwrap();
+            std::fs::rename(dir.join("app.log.new"), &path).unwrap();
+            assert_eq!(next_event(&mut events).await, "three");
+            // Deleted: waiting, with backoff, until it is back
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(next_event(&mut events).await, "<waiting>");
+            std::fs::write(&path, "four\n").unwrap();
+            assert_eq!(next_event(&mut events).await, "<reopened>");
+            assert_eq!(next_event(&mut events).await, "four");
+            reader.abort();
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fifo_without_follow_ends() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("app.pipe");
+        assert!(std::process::Command::new("mkfifo").arg(&path).status().unwrap().success());
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let (raw_tx, mut events) = mpsc::channel(8);
+            let tx = EventSender::new(raw_tx, SharedClassifier::default());
+            let reader = tokio::spawn(stream_file(path.clone(), false, 1, tx));
+            let writer = std::thread::spawn({ let path = path.clone(); move || std::fs::write(path, "one\ntwo\n").unwrap() });
+            for expected in ["one", "two"] {
+                assert!(matches!(events.recv().await, Some((1, SourceEvent::Line(line))) if &*line.text == expected));
+            }
+            // The writer closing its end ends the source
+            assert!(matches!(events.recv().await, Some((1, SourceEvent::Closed))));
+            reader.await.unwrap().unwrap();
+            writer.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn test_http_poll_outcome() {
+        let partial = StatusCode::PARTIAL_CONTENT;
+        assert_eq!(poll_outcome(partial, Some("bytes 10-14/15"), b"more\n", 10).unwrap(), Poll::Data(b"more\n".to_vec()));
+        assert_eq!(poll_outcome(partial, Some("bytes 10-9/10"), b"", 10).unwrap(), Poll::Unchanged);
+        let unsatisfiable = StatusCode::RANGE_NOT_SATISFIABLE;
+        assert_eq!(poll_outcome(unsatisfiable, Some("bytes */10"), b"", 10).unwrap(), Poll::Unchanged);
+        assert_eq!(poll_outcome(unsatisfiable, Some("bytes */4"), b"", 10).unwrap(), Poll::Truncated);
+        assert_eq!(poll_outcome(unsatisfiable, None, b"", 10).unwrap(), Poll::Unchanged);
+        // A server ignoring the range sends the whole body every time
+        assert_eq!(poll_outcome(StatusCode::OK, None, b"line 1\nline 2\n", 7).unwrap(), Poll::Data(b"line 2\n".to_vec()));
+        assert_eq!(poll_outcome(StatusCode::OK, None, b"line 1\n", 7).unwrap(), Poll::Unchanged);
+        assert_eq!(poll_outcome(StatusCode::OK, None, b"x\n", 7).unwrap(), Poll::Truncated);
+        assert_eq!(poll_outcome(StatusCode::NOT_FOUND, None, b"", 0).unwrap_err().to_string(), "HTTP 404 Not Found");
+
+        assert_eq!(resource_length(partial, Some("bytes 0-0/1234"), 1).unwrap(), 1234);
+        assert_eq!(resource_length(unsatisfiable, Some("bytes */0"), 0).unwrap(), 0);
+        assert_eq!(resource_length(StatusCode::OK, None, 42).unwrap(), 42);
+        assert!(resource_length(partial, None, 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_ssh_spec() {
+        let parse = |spec: &str| parse_ssh_spec(spec).map(|(target, path)| format!("{} {}", target, path));
+        assert_eq!(parse("web1:/var/log/app.log").as_deref(), Some("web1 /var/log/app.log"));
+        assert_eq!(parse("deploy@web1:logs/app.log").as_deref(), Some("deploy@web1 logs/app.log"));
+        // IPv6 hosts in brackets, given to ssh without them
+        assert_eq!(parse("[::1]:/var/log/app.log").as_deref(), Some("::1 /var/log/app.log"));
+        assert_eq!(parse("deploy@[fe80::1%eth0]:/var/log/app.log").as_deref(), Some("deploy@fe80::1%eth0 /var/log/app.log"));
+        // The last colon outside brackets splits
+        assert_eq!(parse("fe80::1:/var/log/app.log").as_deref(), Some("fe80::1 /var/log/app.log"));
+        for bad in ["web1", "web1:", ":/var/log", "deploy@:/var/log", "[::1/var/log", "[::1]", "[]:/var/log", "::1]:/var/log", "we[b1:/x"] {
+            assert_eq!(parse(bad), None, "{}", bad);
+        }
+    }
+}
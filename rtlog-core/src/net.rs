@@ -0,0 +1,65 @@
+//! The accept loop shared by the servers (`--fluent`, `--agent-listen`, `--control`, `rtlog serve`).
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+
+/// Pause after a failed accept, doubled while it keeps failing (e.g. out of file descriptors)
+const ACCEPT_RETRY_INITIAL: Duration = Duration::from_millis(10);
+const ACCEPT_RETRY_MAX: Duration = Duration::from_secs(1);
+
+/// A listening socket
+pub trait Listener {
+    type Stream: Send + 'static;
+    type Addr: Send + 'static;
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Stream, Self::Addr)>> + Send;
+}
+
+impl Listener for TcpListener {
+    type Stream = tokio::net::TcpStream;
+    type Addr = std::net::SocketAddr;
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Stream, Self::Addr)>> + Send {
+        TcpListener::accept(self)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Stream = tokio::net::UnixStream;
+    type Addr = tokio::net::unix::SocketAddr;
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Stream, Self::Addr)>> + Send {
+        tokio::net::UnixListener::accept(self)
+    }
+}
+
+/// Accept connections on `listener` and serve each with `handler` in its own task, until dropped.
+/// Failed accepts are retried after a growing pause rather than in a busy loop.
+pub async fn serve_connections<L, F, Fut>(listener: L, mut handler: F)
+where
+    L: Listener,
+    F: FnMut(L::Stream, L::Addr) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    // Connection tasks live in the set so dropping the loop drops them as well
+    let mut conns = JoinSet::new();
+    let mut backoff = ACCEPT_RETRY_INITIAL;
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, addr)) => {
+                    backoff = ACCEPT_RETRY_INITIAL;
+                    conns.spawn(handler(stream, addr));
+                }
+                Err(_) => {
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(ACCEPT_RETRY_MAX);
+                }
+            },
+            Some(_) = conns.join_next(), if !conns.is_empty() => {}
+        }
+    }
+}
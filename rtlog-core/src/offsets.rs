@@ -0,0 +1,152 @@
+//! Read offsets of followed files, kept between runs for `--resume`.
+//!
+//! A followed file is normally read from its end, so lines written while rtlog was not running
+//! are never seen. With `--resume` each reader records how far it got, the offsets are written to
+//! a positions file when rtlog exits (`$XDG_STATE_HOME/rtlog/offsets`, one `OFFSET\tFILE-ID\tPATH`
+//! line per file), and the next session starts each file there instead. A file that was replaced
+//! (rotated) or truncated meanwhile is read from its start; files without a saved offset still
+//! start at their end.
+
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+
+use crate::history::state_dir;
+
+/// Device and inode of a file, telling a rotated file from the one read before (zeros where
+/// that cannot be told)
+type FileId = (u64, u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Offset {
+    file: FileId,
+    offset: u64,
+}
+
+/// The positions file, with the offsets recorded by this session's readers
+#[derive(Debug)]
+pub struct ReadOffsets {
+    path: Option<PathBuf>,
+    saved: HashMap<PathBuf, Offset>,
+    /// Offsets reached in this session, written over the saved ones by `save`
+    reached: Mutex<HashMap<PathBuf, Offset>>,
+}
+
+impl ReadOffsets {
+    /// Load the offsets saved in `path`, ignoring a missing or unreadable file
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let saved = path.as_deref().map(read_offsets).unwrap_or_default();
+        Self { path, saved, reached: Mutex::default() }
+    }
+
+    /// The positions file in the state directory
+    pub fn default_path() -> Option<PathBuf> {
+        state_dir().map(|dir| dir.join("offsets"))
+    }
+
+    /// Offsets of the file at `path`; relative paths are made absolute so sessions started in
+    /// other directories find them
+    pub fn track(self: &Arc<Self>, path: &Path) -> TrackedFile {
+        let key = fs::canonicalize(path).or_else(|_| std::path::absolute(path)).unwrap_or_else(|_| path.to_path_buf());
+        TrackedFile { offsets: Arc::clone(self), key }
+    }
+
+    /// Write the offsets reached to the positions file, keeping the other files' entries (other
+    /// sessions may have saved them since this one started); returns how many files were saved
+    pub fn save(&self) -> Result<usize> {
+        let reached = self.reached.lock().expect("offsets lock").clone();
+        let Some(path) = self.path.as_deref().filter(|_| !reached.is_empty()) else { return Ok(0); };
+        let mut all = read_offsets(path);
+        all.extend(reached.iter().map(|(key, offset)| (key.clone(), *offset)));
+        let mut entries: Vec<_> = all.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut out = String::new();
+        for (key, Offset { file: (dev, ino), offset }) in entries {
+            let key = key.to_string_lossy();
+            if key.contains(['\t', '\n']) { continue; }
+            out.push_str(&format!("{}\t{}:{}\t{}\n", offset, dev, ino, key));
+        }
+        if let Some(dir) = path.parent() { fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?; }
+        // Written aside and renamed, so a session exiting at the same time never reads half a file
+        let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+        fs::write(&tmp, out).and_then(|()| fs::rename(&tmp, path)).with_context(|| format!("cannot write {}", path.display()))?;
+        Ok(reached.len())
+    }
+}
+
+/// A followed file's entry in the `ReadOffsets`
+#[derive(Debug, Clone)]
+pub struct TrackedFile {
+    offsets: Arc<ReadOffsets>,
+    key: PathBuf,
+}
+
+impl TrackedFile {
+    /// Where to start reading the file opened with metadata `md`: the saved offset, or 0 when
+    /// the file was replaced or truncated since; None without a saved offset
+    pub fn start(&self, md: &Metadata) -> Option<u64> {
+        let saved = self.offsets.saved.get(&self.key)?;
+        Some(if saved.file == file_id(md) && saved.offset <= md.len() { saved.offset } else { 0 })
+    }
+
+    /// Record that the file opened with metadata `md` was read up to `offset`
+    pub fn record(&self, md: &Metadata, offset: u64) {
+        let offset = Offset { file: file_id(md), offset };
+        self.offsets.reached.lock().expect("offsets lock").insert(self.key.clone(), offset);
+    }
+}
+
+#[cfg(unix)]
+fn file_id(md: &Metadata) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    (md.dev(), md.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_md: &Metadata) -> FileId {
+    (0, 0)
+}
+
+/// The entries of a positions file, skipping lines that do not parse
+fn read_offsets(path: &Path) -> HashMap<PathBuf, Offset> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    text.lines().filter_map(|line| {
+        let mut fields = line.splitn(3, '\t');
+        let offset = fields.next()?.parse().ok()?;
+        let (dev, ino) = fields.next()?.split_once(':')?;
+        let file = (dev.parse().ok()?, ino.parse().ok()?);
+        Some((PathBuf::from(fields.next()?), Offset { file, offset }))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets_saved_and_resumed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (log, positions) = (dir.join("app.log"), dir.join("offsets"));
+        fs::write(&log, "one\ntwo\n").unwrap();
+        fs::write(&positions, "12\t1:2\t/elsewhere/other.log\nnot an entry\n").unwrap();
+        let offsets = Arc::new(ReadOffsets::load(Some(positions.clone())));
+        let tracked = offsets.track(&log);
+        // Never read before: starts at the end like without --resume
+        assert_eq!(tracked.start(&fs::metadata(&log).unwrap()), None);
+        tracked.record(&fs::metadata(&log).unwrap(), 4);
+        assert_eq!(offsets.save().unwrap(), 1);
+        let saved = fs::read_to_string(&positions).unwrap();
+        assert!(saved.starts_with("12\t1:2\t/elsewhere/other.log\n4\t"), "{}", saved);
+        // The next session starts where this one stopped
+        let next = Arc::new(ReadOffsets::load(Some(positions)));
+        fs::write(&log, "one\ntwo\nthree\n").unwrap();
+        assert_eq!(next.track(&log).start(&fs::metadata(&log).unwrap()), Some(4));
+        // Truncated meanwhile: from the start
+        fs::write(&log, "1\n").unwrap();
+        assert_eq!(next.track(&log).start(&fs::metadata(&log).unwrap()), Some(0));
+    }
+}
@@ -0,0 +1,108 @@
+//! Clustering lines by message template ("top talkers").
+//!
+//! A line's template is the line with its variable parts masked: every word containing a digit
+//! (counts, durations, IDs, UUIDs, IP addresses, the timestamp) becomes `<*>`, so
+//! `user 42 logged in from 10.0.0.7` and `user 7 logged in from 10.0.0.9` count as one pattern.
+//! Words are runs of alphanumerics, `_`, `-` and `.`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::timestamp::parse_timestamp;
+
+/// Placeholder for a masked word
+pub const WILDCARD: &str = "<*>";
+
+/// Number of patterns kept, most frequent first
+const TOP_N: usize = 200;
+
+/// One message template and how often it occurred
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternCount {
+    pub template: String,
+    pub count: usize,
+    /// Index of the newest line with this template
+    pub last: usize,
+}
+
+/// Template of `line`: words containing a digit are replaced by `<*>`
+pub fn template(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut word_start = None;
+    for (i, c) in line.char_indices() {
+        let in_word = c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+        match (in_word, word_start) {
+            (true, None) => word_start = Some(i),
+            (false, Some(start)) => {
+                push_word(&mut out, &line[start..i]);
+                word_start = None;
+                out.push(c);
+            }
+            (false, None) => out.push(c),
+            (true, Some(_)) => {}
+        }
+    }
+    if let Some(start) = word_start { push_word(&mut out, &line[start..]); }
+    out
+}
+
+fn push_word(out: &mut String, word: &str) {
+    out.push_str(if word.bytes().any(|b| b.is_ascii_digit()) { WILDCARD } else { word });
+}
+
+/// Count the templates of `lines` (index, text), keeping the `TOP_N` most frequent. With a
+/// `window`, only lines whose embedded timestamp lies within `window` of the newest timestamp
+/// are counted (lines without one count with their predecessors); `None` counts everything.
+/// Returns the patterns and the number of lines counted.
+pub fn top_patterns<'a, I>(lines: I, window: Option<Duration>) -> (Vec<PatternCount>, usize)
+where
+    I: DoubleEndedIterator<Item = (usize, &'a Arc<str>)>,
+{
+    let mut counts: HashMap<String, PatternCount> = HashMap::new();
+    let mut total = 0;
+    let mut newest = None;
+    // Newest first, so the window can stop at the first line older than its start
+    for (i, line) in lines.rev() {
+        if let Some(window) = window
+            && let Some(ts) = parse_timestamp(line)
+        {
+            let newest = *newest.get_or_insert(ts);
+            if newest.signed_duration_since(ts).to_std().is_ok_and(|age| age > window) { break; }
+        }
+        total += 1;
+        counts.entry(template(line))
+            .and_modify(|p| p.count += 1)
+            .or_insert_with_key(|t| PatternCount { template: t.clone(), count: 1, last: i });
+    }
+    let mut top: Vec<PatternCount> = counts.into_values().collect();
+    top.sort_by(|a, b| b.count.cmp(&a.count).then(b.last.cmp(&a.last)));
+    top.truncate(TOP_N);
+    (top, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_and_top_patterns() {
+        assert_eq!(template("2024-01-01 10:00:05 user 42 logged in from 10.0.0.7"), "<*> <*>:<*>:<*> user <*> logged in from <*>");
+        assert_eq!(template("req=ab12-ff took 35ms (retry)"), "req=<*> took <*> (retry)");
+        let lines: Vec<Arc<str>> = [
+            "2024-01-01 10:00:00 user 1 logged in",
+            "2024-01-01 10:00:30 cache miss key=a1",
+            "2024-01-01 10:04:00 user 2 logged in",
+            "2024-01-01 10:05:00 cache miss key=b2",
+            "2024-01-01 10:05:10 cache miss key=c3",
+        ].into_iter().map(Arc::from).collect();
+        let (top, total) = top_patterns(lines.iter().enumerate(), None);
+        assert_eq!(total, 5);
+        assert_eq!((top[0].template.as_str(), top[0].count, top[0].last), ("<*> <*>:<*>:<*> cache miss key=<*>", 3, 4));
+        assert_eq!(top[1].count, 2);
+        // Only the last two minutes before the newest line (10:05:10)
+        let (top, total) = top_patterns(lines.iter().enumerate(), Some(Duration::from_secs(120)));
+        assert_eq!(total, 3);
+        assert_eq!(top.iter().map(|p| p.count).collect::<Vec<_>>(), vec![2, 1]);
+    }
+}
@@ -0,0 +1,163 @@
+//! External plugin processes (`--plugin CMD`).
+//!
+//! A plugin is a program started through the shell that talks newline-delimited JSON. rtlog
+//! writes one event per line to its stdin:
+//!
+//! - `{"event":"line","source":"app.log","index":41,"line":"...","level":"error"}` for every line
+//!   received (`index` numbers the source's lines from 0 and keeps counting when the buffer is
+//!   cleared, `level` may be null)
+//! - `{"event":"alert","severity":"critical","source":"app.log","message":"..."}` for every alert
+//! - `{"event":"filters","filters":[{"pattern":"timeout","regex":false,"exclude":false,"enabled":true}]}` at start
+//!   and whenever the filter list changes
+//!
+//! and reads actions from its stdout:
+//!
+//! - `{"action":"annotate","source":"app.log","index":41,"text":"retry of #40"}` attaches a note
+//!   to a line
+//! - `{"action":"filter","pattern":"timeout","regex":false}` adds a filter rule
+//! - `{"action":"line","line":"...","tag":"slow"}` appends a derived line to the plugin's own
+//!   source, or to a sub-source per `tag`
+//!
+//! Output that is not a valid action, and anything the plugin writes to stderr, is shown as-is in
+//! the plugin's source. Lines of plugin sources are not sent to plugins. Events are queued
+//! without blocking the UI and dropped while a plugin falls behind by more than `QUEUE` events.
+
+use std::process::Stdio;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::process::Command;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::log::{EventSender, LogLine, LogSource, SourceEvent};
+
+/// Events queued per plugin before further ones are dropped
+const QUEUE: usize = 4096;
+
+/// Event sent to plugins
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum PluginEvent<'a> {
+    Line { source: &'a str, index: usize, line: &'a str, level: Option<&'static str> },
+    Alert { severity: &'static str, source: Option<&'a str>, message: &'a str },
+    Filters { filters: Vec<FilterInfo<'a>> },
+}
+
+#[derive(Debug, Serialize)]
+pub struct FilterInfo<'a> {
+    pub pattern: &'a str,
+    pub regex: bool,
+    pub exclude: bool,
+    pub enabled: bool,
+}
+
+/// Action requested by a plugin
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase", deny_unknown_fields)]
+pub enum PluginAction {
+    /// Attach `text` to line `index` of the source named `source`
+    Annotate { source: String, index: usize, text: String },
+    /// Add a filter rule (literal and case-insensitive unless `regex`)
+    Filter { pattern: String, #[serde(default)] regex: bool },
+    /// Append a derived line to the plugin's source, or to its sub-source `tag`
+    Line { line: String, tag: Option<String> },
+}
+
+/// Handle for sending events to one plugin process
+#[derive(Debug)]
+pub struct Plugin {
+    tx: Sender<String>,
+}
+
+impl Plugin {
+    /// A handle and the receiving end to pass to `PluginProcess`
+    pub fn channel() -> (Self, Receiver<String>) {
+        let (tx, rx) = mpsc::channel(QUEUE);
+        (Self { tx }, rx)
+    }
+
+    /// Queue `event`; dropped if the plugin is behind or gone
+    pub fn send(&self, event: &PluginEvent) {
+        if let Ok(json) = serde_json::to_string(event) { let _ = self.tx.try_send(json); }
+    }
+}
+
+/// Runs a plugin program, feeding it the queued events and turning its output into source events
+pub struct PluginProcess {
+    pub command: String,
+    pub events: Receiver<String>,
+}
+
+#[async_trait::async_trait]
+impl LogSource for PluginProcess {
+    async fn stream(mut self, source_id: usize, tx: EventSender) -> Result<()> {
+        let mut cmd = if cfg!(windows) {
+            let mut c = Command::new("cmd");
+            c.arg("/C").arg(&self.command);
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.arg("-c").arg(&self.command);
+            c
+        };
+        let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true).spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("no stdin"))?;
+        let mut stdout = BufReader::new(child.stdout.take().ok_or_else(|| anyhow::anyhow!("no stdout"))?).lines();
+        let mut stderr = BufReader::new(child.stderr.take().ok_or_else(|| anyhow::anyhow!("no stderr"))?).lines();
+        let mut stdin = Some(BufWriter::new(stdin));
+        let (mut out_done, mut err_done) = (false, false);
+        while !(out_done && err_done) {
+            tokio::select! {
+                event = self.events.recv(), if stdin.is_some() => {
+                    let (Some(event), Some(w)) = (event, stdin.as_mut()) else {
+                        // No more events: the plugin sees end of input
+                        stdin = None;
+                        continue;
+                    };
+                    let mut res = w.write_all(event.as_bytes()).await;
+                    if res.is_ok() { res = w.write_all(b"\n").await; }
+                    // Flush once the queue is drained, so bursts are written in large chunks
+                    if res.is_ok() && self.events.is_empty() { res = w.flush().await; }
+                    // The plugin stopped reading (closed stdin or exited); keep reading its output
+                    if res.is_err() { stdin = None; }
+                }
+                line = stdout.next_line(), if !out_done => match line? {
+                    Some(line) => {
+                        let ev = match serde_json::from_str::<PluginAction>(&line) {
+                            Ok(PluginAction::Line { line, tag: Some(tag) }) => SourceEvent::Record { tag, line: LogLine::new(line) },
+                            Ok(PluginAction::Line { line, tag: None }) => SourceEvent::Line(LogLine::new(line)),
+                            Ok(action) => SourceEvent::Plugin(action),
+                            Err(_) => SourceEvent::Line(LogLine::new(line)),
+                        };
+                        if tx.send((source_id, ev)).await.is_err() { return Ok(()); }
+                    }
+                    None => out_done = true,
+                },
+                line = stderr.next_line(), if !err_done => match line? {
+                    Some(line) => { if tx.send((source_id, SourceEvent::Line(LogLine::new(line)))).await.is_err() { return Ok(()); } }
+                    None => err_done = true,
+                },
+            }
+        }
+        let status = child.wait().await?;
+        if !status.success() { anyhow::bail!("plugin {}", status); }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol() {
+        let event = PluginEvent::Line { source: "app.log", index: 3, line: "disk \"full\"", level: Some("error") };
+        assert_eq!(serde_json::to_string(&event).unwrap(), r#"{"event":"line","source":"app.log","index":3,"line":"disk \"full\"","level":"error"}"#);
+        let action: PluginAction = serde_json::from_str(r#"{"action":"filter","pattern":"timeout"}"#).unwrap();
+        assert_eq!(action, PluginAction::Filter { pattern: "timeout".into(), regex: false });
+        let action: PluginAction = serde_json::from_str(r#"{"action":"annotate","source":"a","index":1,"text":"t"}"#).unwrap();
+        assert!(matches!(action, PluginAction::Annotate { index: 1, .. }));
+        assert!(serde_json::from_str::<PluginAction>(r#"{"action":"explode"}"#).is_err());
+    }
+}
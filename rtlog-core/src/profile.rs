@@ -0,0 +1,277 @@
+//! Per-source parsing profiles (`[[sources]]` in the config file).
+//!
+//! A profile applies to the files whose path matches its glob (the first matching profile wins)
+//! and says how to read their lines: structured formats (JSON, logfmt) take the level and the
+//! timestamp from fields instead of scanning the text, a chrono format replaces timestamp
+//! detection, a multi-line regex joins continuation lines (stack traces) to the line they
+//! continue, and redaction rules mask what only these files contain. A profile can also name the
+//! source in the sidebar.
+//!
+//! Profile redaction runs in the reader, before anything else sees the line: unlike `[redact]`,
+//! the original text never enters rtlog, so `R` cannot reveal it and tee files get it masked.
+
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::classify::Level;
+use crate::config::SourceProfileEntry;
+use crate::encoding::Encoding;
+use crate::redact::Redactor;
+use crate::timestamp::{is_valid_format, parse_timestamp, parse_timestamp_with};
+
+/// Fields holding the level in structured lines, first found wins
+const LEVEL_KEYS: [&str; 4] = ["level", "lvl", "severity", "loglevel"];
+/// Fields holding the timestamp in structured lines, first found wins
+const TIME_KEYS: [&str; 4] = ["time", "ts", "timestamp", "@timestamp"];
+
+/// How a source's lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineFormat {
+    /// Free text: levels by the level patterns, timestamps detected
+    #[default]
+    Plain,
+    /// One JSON object per line
+    Json,
+    /// `key=value` pairs, values quoted when they contain spaces
+    Logfmt,
+}
+
+/// A compiled `[[sources]]` entry
+#[derive(Debug)]
+pub struct Profile {
+    glob: Regex,
+    /// The glob has no `/`, so it is matched against file names
+    by_name: bool,
+    /// Sidebar name of matching sources without a label
+    pub name: Option<String>,
+    pub format: LineFormat,
+    /// chrono format of the timestamps, instead of detecting them
+    pub timestamp: Option<String>,
+    /// Text encoding of the files, instead of telling it from their start
+    pub encoding: Option<Encoding>,
+    /// Lines matching it continue the line before
+    multiline: Option<Regex>,
+    redactor: Redactor,
+}
+
+impl Profile {
+    pub fn new(entry: &SourceProfileEntry) -> Result<Self> {
+        let context = || format!("[[sources]] {}", entry.path);
+        Ok(Self {
+            glob: glob_regex(&entry.path).with_context(context)?,
+            by_name: !entry.path.contains('/'),
+            name: entry.name.clone(),
+            format: entry.format,
+            timestamp: match &entry.timestamp {
+                Some(format) if !is_valid_format(format) => bail!("[[sources]] {}: invalid timestamp format '{}'", entry.path, format),
+                format => format.clone(),
+            },
+            encoding: entry.encoding,
+            multiline: entry.multiline.as_deref().map(Regex::new).transpose().with_context(context)?,
+            redactor: Redactor::new(&entry.redact).with_context(context)?,
+        })
+    }
+
+    /// Whether the profile applies to the file at `path`, as given or made absolute
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.by_name {
+            return path.file_name().is_some_and(|name| self.glob.is_match(&name.to_string_lossy()));
+        }
+        self.glob.is_match(&path.to_string_lossy())
+            || std::path::absolute(path).is_ok_and(|abs| self.glob.is_match(&abs.to_string_lossy()))
+    }
+
+    /// Whether `text` continues the line before it
+    pub fn continues(&self, text: &str) -> bool {
+        self.multiline.as_ref().is_some_and(|re| re.is_match(text))
+    }
+
+    /// `text` with the profile's redaction rules applied
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.redactor.redact(text)
+    }
+
+    /// Level named by the level field of a structured line (`None` inside for levels below info,
+    /// like debug); `None` when the line has no such field and the level patterns decide
+    pub fn level(&self, text: &str) -> Option<Option<Level>> {
+        let value = self.field(text, &LEVEL_KEYS)?;
+        Some(level_named(&value))
+    }
+
+    /// Timestamp of `text`: from the time field of a structured line, else found in the text,
+    /// in `format` when there is one (the profile's, unless `--timestamp-format` overrides it)
+    pub fn timestamp(&self, text: &str, format: Option<&str>) -> Option<NaiveDateTime> {
+        let parse = |s: &str| match format {
+            Some(format) => parse_timestamp_with(s, format),
+            None => parse_timestamp(s),
+        };
+        if let Some(value) = self.field(text, &TIME_KEYS) {
+            return parse(&value).or_else(|| epoch_timestamp(&value));
+        }
+        parse(text)
+    }
+
+    /// Value of the first of `keys` present in a structured line
+    fn field(&self, text: &str, keys: &[&str]) -> Option<Cow<'_, str>> {
+        match self.format {
+            LineFormat::Plain => None,
+            LineFormat::Json => {
+                let Ok(Value::Object(object)) = serde_json::from_str::<Value>(text) else { return None; };
+                keys.iter().find_map(|k| match object.get(*k)? {
+                    Value::String(s) => Some(Cow::Owned(s.clone())),
+                    Value::Number(n) => Some(Cow::Owned(n.to_string())),
+                    _ => None,
+                })
+            }
+            LineFormat::Logfmt => keys.iter().find_map(|k| logfmt_field(text, k)).map(Cow::Owned),
+        }
+    }
+}
+
+/// The `[[sources]]` profiles in config order
+#[derive(Debug, Default, Clone)]
+pub struct Profiles(Vec<Arc<Profile>>);
+
+impl Profiles {
+    pub fn compile(entries: &[SourceProfileEntry]) -> Result<Self> {
+        Ok(Self(entries.iter().map(|e| Profile::new(e).map(Arc::new)).collect::<Result<_>>()?))
+    }
+
+    /// The first profile applying to the file at `path`
+    pub fn for_path(&self, path: &Path) -> Option<Arc<Profile>> {
+        self.0.iter().find(|p| p.matches(path)).cloned()
+    }
+}
+
+/// `glob` as a regex matching whole paths: `*` and `?` stay within a directory, `**` crosses
+/// directories and `[...]` (`[!...]` negated) is a character class
+pub fn glob_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `dir/**/x` also matches `dir/x`
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                let (negated, class) = match class.strip_prefix('!') { Some(rest) => ("^", rest), None => ("", class.as_str()) };
+                re.push_str(&format!("[{}{}]", negated, class.replace('\\', "\\\\").replace('[', "\\[")));
+            }
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    Regex::new(&re)
+}
+
+/// Level a structured line's level field names; None for levels below info
+fn level_named(value: &str) -> Option<Level> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "error" | "err" | "fatal" | "crit" | "critical" | "panic" | "emerg" | "emergency" | "alert" | "severe" => Some(Level::Error),
+        "warn" | "warning" => Some(Level::Warn),
+        "info" | "information" | "notice" => Some(Level::Info),
+        _ => None,
+    }
+}
+
+/// A number of seconds or, when too large for that, milliseconds since the epoch (UTC)
+fn epoch_timestamp(value: &str) -> Option<NaiveDateTime> {
+    let n: f64 = value.trim().parse().ok()?;
+    let millis = if n.abs() < 1e11 { n * 1000.0 } else { n };
+    DateTime::from_timestamp_millis(millis as i64).map(|t| t.naive_utc())
+}
+
+/// Value of `key` in a logfmt line, unquoted
+fn logfmt_field(text: &str, key: &str) -> Option<String> {
+    let mut rest = text;
+    while !rest.is_empty() {
+        rest = rest.trim_start();
+        let end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let (name, after) = rest.split_at(end);
+        let Some(after) = after.strip_prefix('=') else {
+            rest = after;
+            continue;
+        };
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => { if let Some((_, escaped)) = chars.next() { value.push(escaped); } }
+                        '"' => { end = i + 1; break; }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                (after[..end].to_string(), &after[end..])
+            }
+        };
+        if name == key { return Some(value); }
+        rest = next;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactConfig;
+
+    fn profile(path: &str, format: LineFormat) -> Profile {
+        Profile::new(&SourceProfileEntry { path: path.into(), name: None, format, timestamp: None, multiline: None, redact: RedactConfig::default(), encoding: None }).unwrap()
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        let nginx = profile("/var/log/nginx/*.log", LineFormat::Plain);
+        assert!(nginx.matches(Path::new("/var/log/nginx/access.log")));
+        assert!(!nginx.matches(Path::new("/var/log/nginx/old/access.log")));
+        let deep = profile("/srv/**/app-?.json", LineFormat::Json);
+        assert!(deep.matches(Path::new("/srv/app-1.json")) && deep.matches(Path::new("/srv/a/b/app-2.json")));
+        assert!(!deep.matches(Path::new("/srv/app-10.json")));
+        // Without a slash the glob is matched against the file name
+        let by_name = profile("*.[!t]xt", LineFormat::Plain);
+        assert!(by_name.matches(Path::new("logs/a.jxt")) && !by_name.matches(Path::new("logs/a.txt")));
+    }
+
+    #[test]
+    fn test_structured_fields() {
+        let json = profile("*", LineFormat::Json);
+        let line = r#"{"ts":"2025-09-17T11:59:52Z","level":"WARN","msg":"error budget at 80%"}"#;
+        assert_eq!(json.level(line), Some(Some(Level::Warn)));
+        assert_eq!(json.timestamp(line, None).map(|t| t.to_string()).as_deref(), Some("2025-09-17 11:59:52"));
+        assert_eq!(json.level(r#"{"level":"debug","msg":"error"}"#), Some(None));
+        assert_eq!(json.level("plain error line"), None);
+        assert_eq!(json.timestamp(r#"{"time":1758110392}"#, None).map(|t| t.to_string()).as_deref(), Some("2025-09-17 11:59:52"));
+        let logfmt = profile("*", LineFormat::Logfmt);
+        let line = r#"time=2025-09-17T11:59:52Z msg="level=info in the message" lvl=error"#;
+        assert_eq!(logfmt.level(line), Some(Some(Level::Error)));
+        assert_eq!(logfmt_field(line, "msg").as_deref(), Some("level=info in the message"));
+        assert!(logfmt.timestamp(line, None).is_some());
+        let line = "time=\"17/Sep/2025 11:59:52\" lvl=info";
+        assert_eq!(logfmt.timestamp(line, Some("%d/%b/%Y %H:%M:%S")).map(|t| t.to_string()).as_deref(), Some("2025-09-17 11:59:52"));
+    }
+}
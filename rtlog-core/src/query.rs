@@ -0,0 +1,744 @@
+//! SQL-like queries over the buffered lines (`Q`).
+//!
+//! `SELECT level, count(*) FROM * WHERE line LIKE '%timeout%' GROUP BY level ORDER BY 2 DESC`
+//!
+//! Every buffered line is a row with the columns `line` (also `msg`), `level` (`error`, `warn`,
+//! `info` or NULL), `source`, `n` (line number), `time` and `minute` (from the line's timestamp,
+//! NULL without one), plus the named groups of the enabled regex filters (`(?P<ms>\d+)ms` adds
+//! `ms`). `FROM` takes the focused source (the default), `*` for every source, or a source name.
+//! Conditions compare numerically when both sides are numbers; `LIKE` is case-insensitive.
+//! Aggregates are `count(*)`, `count(col)`, `sum`, `avg`, `min` and `max`; `min`/`max` of text
+//! compare alphabetically, `sum`/`avg` skip values that are not numbers.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::classify::LevelRules;
+use crate::timestamp::parse_timestamp;
+
+/// Rows returned when the query has no `LIMIT`
+pub const MAX_ROWS: usize = 10_000;
+
+/// Columns every row has
+const BUILTIN_COLUMNS: [&str; 8] = ["line", "msg", "message", "level", "source", "n", "time", "minute"];
+
+/// A cell value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Num(f64),
+    Text(String),
+}
+
+impl Value {
+    fn as_num(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Text(s) => s.trim().parse().ok(),
+            Value::Null => None,
+        }
+    }
+
+    /// NULL first, then numbers (numerically), then text
+    fn compare(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) => Ordering::Less,
+            (_, Value::Null) => Ordering::Greater,
+            _ => match (self.as_num(), other.as_num()) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => self.to_string().cmp(&other.to_string()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("NULL"),
+            Value::Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Num(n) => write!(f, "{:.2}", n),
+            Value::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+/// Which sources a query reads
+#[derive(Debug, Clone, PartialEq)]
+pub enum FromClause {
+    Focused,
+    All,
+    Source(String),
+}
+
+/// One line offered to a query
+pub struct Row<'a> {
+    pub source: &'a str,
+    /// 1-based line number
+    pub n: usize,
+    pub line: &'a str,
+    /// Level patterns of the line's source
+    pub levels: &'a LevelRules,
+}
+
+/// Output of a query
+#[derive(Debug, Default)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// Lines that satisfied `WHERE`
+    pub matched: usize,
+    /// More rows than `MAX_ROWS` (without `LIMIT`)
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Agg { Count, Sum, Avg, Min, Max }
+
+#[derive(Debug)]
+enum Item {
+    Star,
+    Column { name: String, alias: Option<String> },
+    /// `arg` is `None` for `count(*)`
+    Agg { func: Agg, arg: Option<String>, alias: Option<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op { Eq, Ne, Lt, Le, Gt, Ge }
+
+#[derive(Debug)]
+enum Operand {
+    Column(String),
+    Literal(Value),
+}
+
+#[derive(Debug)]
+enum Cond {
+    Cmp(Operand, Op, Operand),
+    Like { operand: Operand, pattern: Regex, negated: bool },
+    IsNull { operand: Operand, negated: bool },
+    And(Box<Cond>, Box<Cond>),
+    Or(Box<Cond>, Box<Cond>),
+    Not(Box<Cond>),
+}
+
+#[derive(Debug)]
+enum OrderKey {
+    /// 1-based output column
+    Position(usize),
+    Name(String),
+}
+
+/// A parsed query
+#[derive(Debug)]
+pub struct Query {
+    pub from: FromClause,
+    items: Vec<Item>,
+    filter: Option<Cond>,
+    group_by: Vec<String>,
+    order_by: Vec<(OrderKey, bool)>,
+    limit: Option<usize>,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Parser { tokens: tokenize(text)?, pos: 0 }.query()
+    }
+
+    /// Run the query over `rows`; `fields` are the regexes whose named groups are columns
+    pub fn run<'a>(&self, rows: impl Iterator<Item = Row<'a>>, fields: &[Regex]) -> Result<QueryResult, String> {
+        let mut binder = Binder { fields, columns: Vec::new() };
+        let filter = self.filter.as_ref().map(|c| binder.cond(c)).transpose()?;
+        let grouped = !self.group_by.is_empty() || self.items.iter().any(|i| matches!(i, Item::Agg { .. }));
+        let (headers, outputs) = if grouped { self.grouped_outputs(&mut binder)? } else { self.plain_outputs(&mut binder)? };
+        let group_keys = self.group_by.iter().map(|g| binder.column(g)).collect::<Result<Vec<_>, _>>()?;
+        let order = self.order_by.iter().map(|(key, desc)| {
+            let index = match key {
+                OrderKey::Position(p) if (1..=headers.len()).contains(p) => p - 1,
+                OrderKey::Position(p) => return Err(format!("ORDER BY {}: there are {} columns", p, headers.len())),
+                OrderKey::Name(name) => headers.iter().position(|h| h.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| format!("ORDER BY {}: not a selected column", name))?,
+            };
+            Ok((index, *desc))
+        }).collect::<Result<Vec<_>, String>>()?;
+        let cap = self.limit.unwrap_or(MAX_ROWS);
+        // Without sorting, rows past the cap are only counted
+        let sorted = !order.is_empty();
+
+        let mut result = QueryResult { columns: headers, ..Default::default() };
+        let mut groups: HashMap<Vec<String>, usize> = HashMap::new();
+        let mut group_rows: Vec<(Vec<Value>, Vec<AggState>)> = Vec::new();
+        let mut values = Vec::with_capacity(binder.columns.len());
+        for row in rows {
+            binder.load(&row, &mut values);
+            if let Some(cond) = &filter && !cond.eval(&values) { continue; }
+            result.matched += 1;
+            if grouped {
+                let key: Vec<Value> = group_keys.iter().map(|&i| values[i].clone()).collect();
+                let id = *groups.entry(key.iter().map(Value::to_string).collect()).or_insert_with(|| {
+                    group_rows.push((key, outputs.iter().map(|_| AggState::default()).collect()));
+                    group_rows.len() - 1
+                });
+                for (state, output) in group_rows[id].1.iter_mut().zip(&outputs) {
+                    if let Output::Agg(_, arg) = output { state.add(arg.map(|i| &values[i])); }
+                }
+            } else if sorted || result.rows.len() <= cap {
+                result.rows.push(outputs.iter().map(|o| match o {
+                    Output::Column(i) => values[*i].clone(),
+                    Output::GroupKey(_) | Output::Agg(..) => Value::Null,
+                }).collect());
+            }
+        }
+        if grouped {
+            result.rows = group_rows.into_iter().map(|(key, states)| {
+                outputs.iter().zip(states).map(|(o, state)| match o {
+                    Output::GroupKey(k) => key[*k].clone(),
+                    Output::Agg(func, _) => state.finish(*func),
+                    Output::Column(_) => Value::Null,
+                }).collect()
+            }).collect();
+        }
+        if !order.is_empty() {
+            result.rows.sort_by(|a, b| {
+                order.iter().map(|&(i, desc)| {
+                    let o = a[i].compare(&b[i]);
+                    if desc { o.reverse() } else { o }
+                }).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+            });
+        }
+        if result.rows.len() > cap {
+            result.rows.truncate(cap);
+            result.truncated = self.limit.is_none();
+        }
+        Ok(result)
+    }
+
+    fn plain_outputs(&self, binder: &mut Binder) -> Result<(Vec<String>, Vec<Output>), String> {
+        let mut headers = Vec::new();
+        let mut outputs = Vec::new();
+        for item in &self.items {
+            match item {
+                Item::Star => for name in ["source", "n", "level", "line"] {
+                    headers.push(name.to_string());
+                    outputs.push(Output::Column(binder.column(name)?));
+                },
+                Item::Column { name, alias } => {
+                    headers.push(alias.clone().unwrap_or_else(|| name.clone()));
+                    outputs.push(Output::Column(binder.column(name)?));
+                }
+                Item::Agg { .. } => unreachable!("aggregates make the query grouped"),
+            }
+        }
+        Ok((headers, outputs))
+    }
+
+    fn grouped_outputs(&self, binder: &mut Binder) -> Result<(Vec<String>, Vec<Output>), String> {
+        let mut headers = Vec::new();
+        let mut outputs = Vec::new();
+        for item in &self.items {
+            match item {
+                Item::Star => return Err("* cannot be combined with GROUP BY or aggregates".into()),
+                Item::Column { name, alias } => {
+                    let k = self.group_by.iter().position(|g| g == name)
+                        .ok_or_else(|| format!("{} must be in GROUP BY or inside an aggregate", name))?;
+                    headers.push(alias.clone().unwrap_or_else(|| name.clone()));
+                    outputs.push(Output::GroupKey(k));
+                }
+                Item::Agg { func, arg, alias } => {
+                    let index = arg.as_ref().map(|a| binder.column(a)).transpose()?;
+                    let name = format!("{}({})", func.name(), arg.as_deref().unwrap_or("*"));
+                    headers.push(alias.clone().unwrap_or(name));
+                    outputs.push(Output::Agg(*func, index));
+                }
+            }
+        }
+        Ok((headers, outputs))
+    }
+}
+
+/// What an output column holds, by index into the bound columns or group key
+enum Output {
+    Column(usize),
+    GroupKey(usize),
+    Agg(Agg, Option<usize>),
+}
+
+impl Agg {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "count" => Agg::Count,
+            "sum" => Agg::Sum,
+            "avg" => Agg::Avg,
+            "min" => Agg::Min,
+            "max" => Agg::Max,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self { Agg::Count => "count", Agg::Sum => "sum", Agg::Avg => "avg", Agg::Min => "min", Agg::Max => "max" }
+    }
+}
+
+#[derive(Default)]
+struct AggState {
+    count: usize,
+    numbers: usize,
+    sum: f64,
+    min: Option<Value>,
+    max: Option<Value>,
+}
+
+impl AggState {
+    /// Count a row; `value` is `None` for `count(*)`
+    fn add(&mut self, value: Option<&Value>) {
+        let Some(value) = value else { self.count += 1; return; };
+        if *value == Value::Null { return; }
+        self.count += 1;
+        if let Some(n) = value.as_num() { self.numbers += 1; self.sum += n; }
+        if self.min.as_ref().is_none_or(|m| value.compare(m).is_lt()) { self.min = Some(value.clone()); }
+        if self.max.as_ref().is_none_or(|m| value.compare(m).is_gt()) { self.max = Some(value.clone()); }
+    }
+
+    fn finish(self, func: Agg) -> Value {
+        match func {
+            Agg::Count => Value::Num(self.count as f64),
+            Agg::Sum if self.numbers > 0 => Value::Num(self.sum),
+            Agg::Avg if self.numbers > 0 => Value::Num(self.sum / self.numbers as f64),
+            Agg::Min => self.min.unwrap_or(Value::Null),
+            Agg::Max => self.max.unwrap_or(Value::Null),
+            Agg::Sum | Agg::Avg => Value::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Column {
+    Line,
+    Level,
+    Source,
+    N,
+    Time,
+    Minute,
+    /// Named group of `fields[regex]`
+    Field { regex: usize, name: String },
+}
+
+/// Resolves column names to slots and fills the slots for each row
+struct Binder<'r> {
+    fields: &'r [Regex],
+    columns: Vec<Column>,
+}
+
+impl Binder<'_> {
+    fn column(&mut self, name: &str) -> Result<usize, String> {
+        let column = match name.to_ascii_lowercase().as_str() {
+            "line" | "msg" | "message" => Column::Line,
+            "level" => Column::Level,
+            "source" => Column::Source,
+            "n" => Column::N,
+            "time" => Column::Time,
+            "minute" => Column::Minute,
+            _ => {
+                let regex = self.fields.iter().position(|re| re.capture_names().flatten().any(|n| n == name))
+                    .ok_or_else(|| {
+                        let mut known: Vec<&str> = BUILTIN_COLUMNS.to_vec();
+                        known.extend(self.fields.iter().flat_map(|re| re.capture_names().flatten()));
+                        format!("unknown column {} (columns: {})", name, known.join(", "))
+                    })?;
+                Column::Field { regex, name: name.to_string() }
+            }
+        };
+        Ok(match self.columns.iter().position(|c| *c == column) {
+            Some(i) => i,
+            None => { self.columns.push(column); self.columns.len() - 1 }
+        })
+    }
+
+    fn operand(&mut self, operand: &Operand) -> Result<Bound, String> {
+        Ok(match operand {
+            Operand::Column(name) => Bound::Slot(self.column(name)?),
+            Operand::Literal(v) => Bound::Value(v.clone()),
+        })
+    }
+
+    fn cond(&mut self, cond: &Cond) -> Result<BoundCond, String> {
+        Ok(match cond {
+            Cond::Cmp(a, op, b) => BoundCond::Cmp(self.operand(a)?, *op, self.operand(b)?),
+            Cond::Like { operand, pattern, negated } => BoundCond::Like(self.operand(operand)?, pattern.clone(), *negated),
+            Cond::IsNull { operand, negated } => BoundCond::IsNull(self.operand(operand)?, *negated),
+            Cond::And(a, b) => BoundCond::And(Box::new(self.cond(a)?), Box::new(self.cond(b)?)),
+            Cond::Or(a, b) => BoundCond::Or(Box::new(self.cond(a)?), Box::new(self.cond(b)?)),
+            Cond::Not(c) => BoundCond::Not(Box::new(self.cond(c)?)),
+        })
+    }
+
+    /// The values of the bound columns for `row`
+    fn load(&self, row: &Row, values: &mut Vec<Value>) {
+        values.clear();
+        let mut time = None;
+        let mut captures: Vec<Option<Option<regex::Captures>>> = (0..self.fields.len()).map(|_| None).collect();
+        for column in &self.columns {
+            let value = match column {
+                Column::Line => Value::Text(row.line.to_string()),
+                Column::Level => row.levels.level(row.line).map_or(Value::Null, |l| Value::Text(l.name().to_string())),
+                Column::Source => Value::Text(row.source.to_string()),
+                Column::N => Value::Num(row.n as f64),
+                Column::Time | Column::Minute => {
+                    let ts = *time.get_or_insert_with(|| parse_timestamp(row.line));
+                    let format = if *column == Column::Time { "%Y-%m-%d %H:%M:%S" } else { "%Y-%m-%d %H:%M" };
+                    ts.map_or(Value::Null, |t| Value::Text(t.format(format).to_string()))
+                }
+                Column::Field { regex, name } => {
+                    let caps = captures[*regex].get_or_insert_with(|| self.fields[*regex].captures(row.line));
+                    caps.as_ref().and_then(|c| c.name(name)).map_or(Value::Null, |m| Value::Text(m.as_str().to_string()))
+                }
+            };
+            values.push(value);
+        }
+    }
+}
+
+enum Bound {
+    Slot(usize),
+    Value(Value),
+}
+
+impl Bound {
+    fn get<'v>(&'v self, values: &'v [Value]) -> &'v Value {
+        match self { Bound::Slot(i) => &values[*i], Bound::Value(v) => v }
+    }
+}
+
+enum BoundCond {
+    Cmp(Bound, Op, Bound),
+    Like(Bound, Regex, bool),
+    IsNull(Bound, bool),
+    And(Box<BoundCond>, Box<BoundCond>),
+    Or(Box<BoundCond>, Box<BoundCond>),
+    Not(Box<BoundCond>),
+}
+
+impl BoundCond {
+    fn eval(&self, values: &[Value]) -> bool {
+        match self {
+            BoundCond::Cmp(a, op, b) => {
+                let (a, b) = (a.get(values), b.get(values));
+                // Comparisons with NULL are never true
+                if *a == Value::Null || *b == Value::Null { return false; }
+                let o = a.compare(b);
+                match op { Op::Eq => o.is_eq(), Op::Ne => o.is_ne(), Op::Lt => o.is_lt(), Op::Le => o.is_le(), Op::Gt => o.is_gt(), Op::Ge => o.is_ge() }
+            }
+            BoundCond::Like(a, re, negated) => match a.get(values) {
+                Value::Null => false,
+                v => re.is_match(&v.to_string()) != *negated,
+            },
+            BoundCond::IsNull(a, negated) => (*a.get(values) == Value::Null) != *negated,
+            BoundCond::And(a, b) => a.eval(values) && b.eval(values),
+            BoundCond::Or(a, b) => a.eval(values) || b.eval(values),
+            BoundCond::Not(c) => !c.eval(values),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Sym(&'static str),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() { chars.next(); continue; }
+        if c == '\'' || c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    // A doubled quote stands for itself
+                    Some((_, q)) if q == c => {
+                        if chars.peek().is_some_and(|&(_, n)| n == c) { chars.next(); s.push(c); } else { break; }
+                    }
+                    Some((_, ch)) => s.push(ch),
+                    None => return Err("unterminated string".into()),
+                }
+            }
+            tokens.push(Token::Str(s));
+            continue;
+        }
+        let negative_number = c == '-' && text[start + 1..].starts_with(|d: char| d.is_ascii_digit());
+        if c.is_ascii_digit() || negative_number {
+            chars.next();
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, d)) = chars.peek() {
+                if !(d.is_ascii_digit() || d == '.') { break; }
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Num(text[start..end].parse().map_err(|_| format!("invalid number {}", &text[start..end]))?));
+            continue;
+        }
+        if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek() {
+                if !(d.is_alphanumeric() || matches!(d, '_' | '.' | '-')) { break; }
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Ident(text[start..end].to_string()));
+            continue;
+        }
+        let rest = &text[start..];
+        let sym = ["!=", "<>", "<=", ">=", ",", "(", ")", "*", "=", "<", ">"].into_iter().find(|s| rest.starts_with(s))
+            .ok_or_else(|| format!("unexpected {:?}", c))?;
+        for _ in 0..sym.len() { chars.next(); }
+        tokens.push(Token::Sym(if sym == "<>" { "!=" } else { sym }));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos) }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn at_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s.eq_ignore_ascii_case(kw))
+    }
+
+    fn keyword(&mut self, kw: &str) -> bool {
+        let found = self.at_keyword(kw);
+        if found { self.pos += 1; }
+        found
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> Result<(), String> {
+        if self.keyword(kw) { Ok(()) } else { Err(format!("expected {}", kw)) }
+    }
+
+    fn symbol(&mut self, sym: &str) -> bool {
+        let found = self.peek() == Some(&Token::Sym(match sym { "," => ",", "(" => "(", ")" => ")", "*" => "*", _ => "" }));
+        if found { self.pos += 1; }
+        found
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Ident(s)) => Ok(s),
+            Some(t) => Err(format!("expected a name, found {}", describe(&t))),
+            None => Err("expected a name".into()),
+        }
+    }
+
+    fn query(mut self) -> Result<Query, String> {
+        self.expect_keyword("select")?;
+        let mut items = vec![self.item()?];
+        while self.symbol(",") { items.push(self.item()?); }
+        let from = if self.keyword("from") {
+            match self.next() {
+                Some(Token::Sym("*")) => FromClause::All,
+                Some(Token::Ident(s) | Token::Str(s)) => FromClause::Source(s),
+                _ => return Err("expected * or a source name after FROM".into()),
+            }
+        } else {
+            FromClause::Focused
+        };
+        let filter = if self.keyword("where") { Some(self.or_cond()?) } else { None };
+        let mut group_by = Vec::new();
+        if self.keyword("group") {
+            self.expect_keyword("by")?;
+            group_by.push(self.ident()?);
+            while self.symbol(",") { group_by.push(self.ident()?); }
+        }
+        let mut order_by = Vec::new();
+        if self.keyword("order") {
+            self.expect_keyword("by")?;
+            loop {
+                let key = match self.next() {
+                    Some(Token::Num(n)) if n >= 1.0 && n.fract() == 0.0 => OrderKey::Position(n as usize),
+                    Some(Token::Ident(name)) if self.symbol("(") => {
+                        // An aggregate, referred to by its column header
+                        let arg = if self.symbol("*") { "*".to_string() } else { self.ident()? };
+                        if !self.symbol(")") { return Err("expected )".into()); }
+                        OrderKey::Name(format!("{}({})", name.to_ascii_lowercase(), arg))
+                    }
+                    Some(Token::Ident(name)) => OrderKey::Name(name),
+                    _ => return Err("expected a column after ORDER BY".into()),
+                };
+                let desc = if self.keyword("desc") { true } else { self.keyword("asc"); false };
+                order_by.push((key, desc));
+                if !self.symbol(",") { break; }
+            }
+        }
+        let limit = if self.keyword("limit") {
+            match self.next() {
+                Some(Token::Num(n)) if n >= 0.0 && n.fract() == 0.0 => Some(n as usize),
+                _ => return Err("expected a number after LIMIT".into()),
+            }
+        } else {
+            None
+        };
+        if let Some(t) = self.peek() { return Err(format!("unexpected {}", describe(t))); }
+        Ok(Query { from, items, filter, group_by, order_by, limit })
+    }
+
+    fn item(&mut self) -> Result<Item, String> {
+        if self.symbol("*") { return Ok(Item::Star); }
+        let name = self.ident()?;
+        let item = if self.symbol("(") {
+            let func = Agg::parse(&name).ok_or_else(|| format!("unknown function {}", name))?;
+            let arg = if self.symbol("*") {
+                if func != Agg::Count { return Err(format!("{}(*) is not supported", func.name())); }
+                None
+            } else {
+                Some(self.ident()?)
+            };
+            if !self.symbol(")") { return Err("expected )".into()); }
+            Item::Agg { func, arg, alias: None }
+        } else {
+            Item::Column { name, alias: None }
+        };
+        let alias = if self.keyword("as") { Some(self.ident()?) } else { None };
+        Ok(match item {
+            Item::Agg { func, arg, .. } => Item::Agg { func, arg, alias },
+            Item::Column { name, .. } => Item::Column { name, alias },
+            Item::Star => Item::Star,
+        })
+    }
+
+    fn or_cond(&mut self) -> Result<Cond, String> {
+        let mut cond = self.and_cond()?;
+        while self.keyword("or") { cond = Cond::Or(Box::new(cond), Box::new(self.and_cond()?)); }
+        Ok(cond)
+    }
+
+    fn and_cond(&mut self) -> Result<Cond, String> {
+        let mut cond = self.not_cond()?;
+        while self.keyword("and") { cond = Cond::And(Box::new(cond), Box::new(self.not_cond()?)); }
+        Ok(cond)
+    }
+
+    fn not_cond(&mut self) -> Result<Cond, String> {
+        if self.keyword("not") { return Ok(Cond::Not(Box::new(self.not_cond()?))); }
+        if self.symbol("(") {
+            let cond = self.or_cond()?;
+            if !self.symbol(")") { return Err("expected )".into()); }
+            return Ok(cond);
+        }
+        let operand = self.operand()?;
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Cond::IsNull { operand, negated });
+        }
+        let negated = self.keyword("not");
+        if self.keyword("like") {
+            let Some(Token::Str(pattern)) = self.next() else { return Err("expected a quoted pattern after LIKE".into()); };
+            return Ok(Cond::Like { operand, pattern: like_regex(&pattern)?, negated });
+        }
+        if negated { return Err("expected LIKE after NOT".into()); }
+        let op = match self.next() {
+            Some(Token::Sym("=")) => Op::Eq,
+            Some(Token::Sym("!=")) => Op::Ne,
+            Some(Token::Sym("<")) => Op::Lt,
+            Some(Token::Sym("<=")) => Op::Le,
+            Some(Token::Sym(">")) => Op::Gt,
+            Some(Token::Sym(">=")) => Op::Ge,
+            _ => return Err("expected a comparison (=, !=, <, <=, >, >=, LIKE, IS NULL)".into()),
+        };
+        Ok(Cond::Cmp(operand, op, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Ident(s)) if s.eq_ignore_ascii_case("null") => Ok(Operand::Literal(Value::Null)),
+            Some(Token::Ident(s)) => Ok(Operand::Column(s)),
+            Some(Token::Str(s)) => Ok(Operand::Literal(Value::Text(s))),
+            Some(Token::Num(n)) => Ok(Operand::Literal(Value::Num(n))),
+            Some(t) => Err(format!("expected a column or value, found {}", describe(&t))),
+            None => Err("expected a column or value".into()),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(s) => s.clone(),
+        Token::Str(s) => format!("'{}'", s),
+        Token::Num(n) => n.to_string(),
+        Token::Sym(s) => s.to_string(),
+    }
+}
+
+/// `%` matches any run of characters, `_` any one character; case-insensitive
+fn like_regex(pattern: &str) -> Result<Regex, String> {
+    let mut re = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '%' => re.push_str(".*"),
+            '_' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    RegexBuilder::new(&re).case_insensitive(true).dot_matches_new_line(true).build().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(query: &str, lines: &[(&str, &str)]) -> Result<QueryResult, String> {
+        let levels = LevelRules::default();
+        let fields = [Regex::new(r"took (?P<ms>\d+)ms").unwrap()];
+        let rows = lines.iter().enumerate().map(|(i, (source, line))| Row { source, n: i + 1, line, levels: &levels });
+        Query::parse(query)?.run(rows, &fields)
+    }
+
+    fn cells(result: &QueryResult) -> Vec<Vec<String>> {
+        result.rows.iter().map(|r| r.iter().map(Value::to_string).collect()).collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let lines = [
+            ("api", "2025-01-02 10:00:01 ERROR upstream timeout took 900ms"),
+            ("api", "2025-01-02 10:00:02 INFO GET / took 12ms"),
+            ("db", "2025-01-02 10:01:00 WARN slow query took 300ms"),
+            ("api", "2025-01-02 10:01:30 ERROR Timeout again"),
+        ];
+        let r = run("SELECT level, count(*) WHERE msg LIKE '%timeout%' GROUP BY level", &lines).unwrap();
+        assert_eq!((r.columns.clone(), cells(&r), r.matched), (vec!["level".into(), "count(*)".into()], vec![vec!["error".into(), "2".into()]], 2));
+        let r = run("select source, avg(ms) as avg_ms, max(ms), count(ms) group by source order by avg_ms desc", &lines).unwrap();
+        assert_eq!(cells(&r), [["api", "456", "900", "2"], ["db", "300", "300", "1"]]);
+        let r = run("SELECT n, ms FROM * WHERE ms > 100 AND NOT (source = 'db' OR level IS NULL) LIMIT 5", &lines).unwrap();
+        assert_eq!(cells(&r), [["1", "900"]]);
+        let r = run("SELECT minute, count(*) GROUP BY minute ORDER BY 2 DESC, minute", &lines).unwrap();
+        assert_eq!(cells(&r), [["2025-01-02 10:00", "2"], ["2025-01-02 10:01", "2"]]);
+        let r = run("SELECT * LIMIT 1", &lines).unwrap();
+        assert_eq!(r.columns, ["source", "n", "level", "line"]);
+        assert_eq!(r.rows.len(), 1);
+        assert!(run("SELECT line, count(*)", &lines).unwrap_err().contains("GROUP BY"));
+        assert!(run("SELECT nope", &lines).unwrap_err().contains("unknown column nope"));
+        assert!(run("SELECT line WHERE", &lines).is_err());
+    }
+}
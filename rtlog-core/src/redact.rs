@@ -0,0 +1,89 @@
+//! Masking sensitive text (`--redact`, `[redact]` in the config file).
+//!
+//! Redaction rules are regexes with a replacement (`$1` refers to groups). They apply to what is
+//! shown on screen (unless revealed with `R`) and to what leaves rtlog: tee files, exports and
+//! emailed alerts. Filters, search and alerts still match the original text. Built-in rule sets
+//! cover email addresses (`email`), IPv4/IPv6 addresses (`ip`) and credentials (`token`: bearer
+//! tokens, JWTs and `password=`/`api_key:`-style values).
+
+use std::borrow::Cow;
+
+use anyhow::{Context, Result};
+
+use crate::config::RedactConfig;
+use crate::replace::ReplaceRules;
+
+/// Replacement of rules that do not set one
+pub const DEFAULT_REPLACEMENT: &str = "***";
+
+/// Names of the built-in rule sets
+pub const BUILTIN: [&str; 3] = ["email", "ip", "token"];
+
+/// Patterns and replacements of a built-in rule set
+fn builtin(name: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    Some(match name {
+        "email" => &[(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}", "<email>")],
+        "ip" => &[
+            (r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b", "<ip>"),
+            // Full form, or compressed with at least one group before `::` (not `std::fs`-like paths)
+            (r"\b(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}\b|\b(?:[0-9A-Fa-f]{1,4}:){1,7}:(?:[0-9A-Fa-f]{1,4}(?::[0-9A-Fa-f]{1,4})*\b)?", "<ip>"),
+        ],
+        "token" => &[
+            (r"(?i)\b(bearer\s+)[A-Za-z0-9._~+/=-]{8,}", "${1}***"),
+            (r"\beyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*", "***"),
+            (r#"(?i)\b((?:api[_-]?key|access[_-]?key|secret|token|password|passwd|pwd)["']?\s*[=:]\s*["']?)[^\s"'&,;]+"#, "${1}***"),
+        ],
+        _ => return None,
+    })
+}
+
+/// Compiled redaction rules
+#[derive(Debug, Default)]
+pub struct Redactor(ReplaceRules);
+
+impl Redactor {
+    /// Compile the built-in sets named in `config` followed by its own rules
+    pub fn new(config: &RedactConfig) -> Result<Self> {
+        let mut specs: Vec<(String, String)> = Vec::new();
+        for name in &config.builtin {
+            let rules = builtin(name).with_context(|| format!("unknown redaction rule set {:?} (expected one of {})", name, BUILTIN.join(", ")))?;
+            specs.extend(rules.iter().map(|(p, r)| (p.to_string(), r.to_string())));
+        }
+        specs.extend(config.rules.iter().map(|r| (r.pattern.clone(), r.replace.clone())));
+        Ok(Self(ReplaceRules::new(specs, "redaction")?))
+    }
+
+    /// Whether there are no rules
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `text` with the sensitive parts masked; borrowed when there were none
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.0.apply(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactRule;
+
+    #[test]
+    fn test_redact() {
+        let config = RedactConfig {
+            builtin: BUILTIN.iter().map(|s| s.to_string()).collect(),
+            rules: vec![RedactRule { pattern: r"(card=)\d{12}(\d{4})".into(), replace: "${1}************$2".into() }],
+        };
+        let r = Redactor::new(&config).unwrap();
+        assert_eq!(r.redact("login bob.smith@example.co.uk from 10.0.0.7 ok"), "login <email> from <ip> ok");
+        assert_eq!(r.redact("peer fe80::1ff:fe23:4567:890a up"), "peer <ip> up");
+        assert_eq!(r.redact("Authorization: Bearer abc.def-123456"), "Authorization: Bearer ***");
+        assert_eq!(r.redact(r#"{"password": "hunter2", "user": "x"}"#), r#"{"password": "***", "user": "x"}"#);
+        assert_eq!(r.redact("pay card=4111111111111111"), "pay card=************1111");
+        // Times, versions and paths are left alone
+        let plain = "12:34:56 v1.2.3 std::fs::read 999.1.1.1";
+        assert!(matches!(r.redact(plain), Cow::Borrowed(_)));
+        assert!(Redactor::new(&RedactConfig { builtin: vec!["phone".into()], rules: Vec::new() }).is_err());
+    }
+}
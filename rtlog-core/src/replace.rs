@@ -0,0 +1,58 @@
+//! Ordered regex replacement rules, the engine behind redaction (`redact`) and display rewrites
+//! (`rewrite`).
+
+use std::borrow::Cow;
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexSet};
+
+/// Compiled regexes, each with a replacement template (`$1`, `${name}` refer to groups)
+#[derive(Debug, Default)]
+pub struct ReplaceRules {
+    rules: Vec<(Regex, String)>,
+    /// All patterns, to skip lines no rule touches cheaply
+    set: Option<RegexSet>,
+}
+
+impl ReplaceRules {
+    /// Compile `(pattern, replacement)` pairs; `kind` names the rules in errors ("redaction")
+    pub fn new(specs: impl IntoIterator<Item = (String, String)>, kind: &str) -> Result<Self> {
+        let rules = specs.into_iter()
+            .map(|(p, r)| Ok((Regex::new(&p).with_context(|| format!("invalid {} pattern {:?}", kind, p))?, r)))
+            .collect::<Result<Vec<_>>>()?;
+        if rules.is_empty() { return Ok(Self::default()); }
+        let set = RegexSet::new(rules.iter().map(|(re, _)| re.as_str()))?;
+        Ok(Self { rules, set: Some(set) })
+    }
+
+    /// Whether there are no rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// `text` with every rule applied in order; borrowed when nothing matched
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let Some(set) = &self.set else { return Cow::Borrowed(text); };
+        if !set.is_match(text) { return Cow::Borrowed(text); }
+        let mut out = Cow::Borrowed(text);
+        for (re, replacement) in &self.rules {
+            if let Cow::Owned(s) = re.replace_all(&out, replacement.as_str()) { out = Cow::Owned(s); }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_in_order() {
+        let rules = ReplaceRules::new([("a+".to_string(), "b".to_string()), ("b(c)".to_string(), "<$1>".to_string())], "test").unwrap();
+        assert_eq!(rules.apply("aaac xc"), "<c> xc");
+        assert!(matches!(rules.apply("xyz"), Cow::Borrowed(_)));
+        assert!(ReplaceRules::new(Vec::new(), "test").unwrap().is_empty());
+        let err = ReplaceRules::new([("(".to_string(), String::new())], "rewrite").unwrap_err();
+        assert_eq!(err.to_string(), "invalid rewrite pattern \"(\"");
+    }
+}
@@ -0,0 +1,262 @@
+//! Replaying a captured log file as if it were live (`--replay`).
+//!
+//! Lines are re-emitted with the gaps between their embedded timestamps, scaled by a speed factor
+//! (`2x` plays twice as fast), or at a fixed rate (`50/s`) ignoring timestamps. Lines without a
+//! timestamp follow the previous line of their file immediately. Several files are played as one
+//! recording, merged by timestamp, so lines keep their order across sources. The UI pauses, steps
+//! and changes the speed through the `ReplayControl`.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::log::{describe_error, send_line, EventSender, SourceEvent};
+use crate::timestamp::parse_timestamp;
+
+/// Quiet periods in the recording are shortened to at most this (after scaling)
+const MAX_GAP: Duration = Duration::from_secs(5);
+
+/// Replay pacing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// Multiplier on the recorded gaps between timestamps
+    Factor(f64),
+    /// Fixed number of lines per second
+    Rate(f64),
+}
+
+impl Speed {
+    /// Parse `2x`, `0.5x` or `100/s`
+    pub fn parse(s: &str) -> Option<Self> {
+        let (value, speed): (&str, fn(f64) -> Speed) = match s.trim() {
+            s if s.ends_with('x') => (&s[..s.len() - 1], Speed::Factor),
+            s if s.ends_with("/s") => (&s[..s.len() - 2], Speed::Rate),
+            _ => return None,
+        };
+        value.trim().parse::<f64>().ok().filter(|v| v.is_finite() && *v > 0.0).map(speed)
+    }
+
+    /// Same pacing, `by` times faster
+    fn scaled(self, by: f64) -> Self {
+        match self { Speed::Factor(f) => Speed::Factor(f * by), Speed::Rate(r) => Speed::Rate(r * by) }
+    }
+
+    /// Real time to wait before a line that came `gap` after the previous one
+    fn delay(self, gap: Duration) -> Duration {
+        let secs = match self { Speed::Factor(f) => gap.as_secs_f64() / f, Speed::Rate(r) => 1.0 / r };
+        Duration::try_from_secs_f64(secs).unwrap_or(MAX_GAP).min(MAX_GAP)
+    }
+}
+
+impl std::fmt::Display for Speed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self { Speed::Factor(x) => write!(f, "{}x", x), Speed::Rate(r) => write!(f, "{}/s", r) }
+    }
+}
+
+/// Pause, step and speed shared by the UI and all replay readers
+#[derive(Clone)]
+pub struct ReplayControl {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    paused: AtomicBool,
+    /// Lines that may still be emitted while paused
+    steps: AtomicUsize,
+    speed: Mutex<Speed>,
+    /// Wakes waiting readers after any change
+    changed: Notify,
+}
+
+impl ReplayControl {
+    pub fn new(speed: Speed) -> Self {
+        Self { inner: Arc::new(Inner { paused: AtomicBool::new(false), steps: AtomicUsize::new(0), speed: Mutex::new(speed), changed: Notify::new() }) }
+    }
+
+    pub fn is_paused(&self) -> bool { self.inner.paused.load(Ordering::SeqCst) }
+
+    pub fn speed(&self) -> Speed { *self.inner.speed.lock().unwrap_or_else(|e| e.into_inner()) }
+
+    /// Pause or resume; returns true when now paused
+    pub fn toggle_pause(&self) -> bool {
+        let paused = !self.inner.paused.fetch_xor(true, Ordering::SeqCst);
+        self.inner.steps.store(0, Ordering::SeqCst);
+        self.inner.changed.notify_waiters();
+        paused
+    }
+
+    /// Let one more line through while paused
+    pub fn step(&self) {
+        self.inner.steps.fetch_add(1, Ordering::SeqCst);
+        self.inner.changed.notify_waiters();
+    }
+
+    /// Multiply the speed by `by` (e.g. 2.0 or 0.5) and return the new speed
+    pub fn scale_speed(&self, by: f64) -> Speed {
+        let speed = {
+            let mut speed = self.inner.speed.lock().unwrap_or_else(|e| e.into_inner());
+            *speed = speed.scaled(by);
+            *speed
+        };
+        self.inner.changed.notify_waiters();
+        speed
+    }
+
+    fn take_step(&self) -> bool {
+        self.inner.steps.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok()
+    }
+
+    /// Wait until a line recorded `gap` after the previous one (due at `prev`) is due, and
+    /// return when it was due. Pacing from the previous due time rather than from now keeps
+    /// high fixed rates accurate despite timer granularity.
+    pub(crate) async fn wait_turn(&self, mut prev: Instant, gap: Duration) -> Instant {
+        loop {
+            // Register for change notifications before looking at the state, so none is missed
+            let changed = self.inner.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            if self.is_paused() {
+                if self.take_step() { return Instant::now(); }
+                changed.await;
+                // Pacing restarts from the moment of resuming
+                prev = Instant::now();
+                continue;
+            }
+            let due = prev + self.speed().delay(gap);
+            if Instant::now() >= due { return due; }
+            tokio::select! {
+                _ = tokio::time::sleep_until(due) => return due,
+                _ = changed => {}
+            }
+        }
+    }
+}
+
+/// A `--replay` file and the source its lines go to
+pub struct ReplayFile {
+    pub path: PathBuf,
+    pub source_id: usize,
+    pub tx: EventSender,
+}
+
+/// A file being replayed, with its next line read ahead
+struct Pending {
+    file: ReplayFile,
+    reader: BufReader<File>,
+    line: String,
+    /// Timestamp of the next line, or else of the last timestamped line before it
+    at: Option<NaiveDateTime>,
+}
+
+impl Pending {
+    /// Read the next line; false at the end of the file, which is then reported closed
+    async fn advance(&mut self) -> bool {
+        self.line.clear();
+        let mut raw = Vec::new();
+        let event = match self.reader.read_until(b'\n', &mut raw).await {
+            Ok(0) => SourceEvent::Closed,
+            Ok(_) => {
+                self.line = String::from_utf8_lossy(&raw).into_owned();
+                self.at = parse_timestamp(&self.line).or(self.at);
+                return true;
+            }
+            Err(e) => SourceEvent::Error(describe_error(&e.into(), &self.file.path)),
+        };
+        let _ = self.file.tx.send((self.file.source_id, event)).await;
+        false
+    }
+}
+
+/// Re-emit the lines of `files` in the order of their timestamps, paced by `control`; each file
+/// is reported closed at its end. Returns once all ended or the viewer is gone.
+pub async fn replay(files: Vec<ReplayFile>, control: ReplayControl) {
+    let mut pending = Vec::new();
+    for file in files {
+        match File::open(&file.path).await {
+            Ok(f) => {
+                let mut next = Pending { file, reader: BufReader::new(f), line: String::new(), at: None };
+                if next.advance().await { pending.push(next); }
+            }
+            Err(e) => { let _ = file.tx.send((file.source_id, SourceEvent::Error(describe_error(&e.into(), &file.path)))).await; }
+        }
+    }
+    let mut last_ts = None;
+    let mut clock = Instant::now();
+    // The earliest next line goes first, the first file's on a tie; lines before any timestamp
+    // of their file right away
+    while let Some(i) = (0..pending.len()).min_by_key(|&i| (pending[i].at, i)) {
+        let next = &mut pending[i];
+        let gap = match (next.at, last_ts) {
+            (Some(ts), Some(prev)) => ts.signed_duration_since(prev).to_std().unwrap_or_default(),
+            _ => Duration::ZERO,
+        };
+        if next.at.is_some() { last_ts = last_ts.max(next.at); }
+        // At a fixed rate every line waits its turn, timestamped or not
+        let paced = gap > Duration::ZERO || matches!(control.speed(), Speed::Rate(_));
+        if paced || control.is_paused() { clock = control.wait_turn(clock, gap).await; }
+        if !send_line(&next.file.tx, next.file.source_id, &mut next.line).await { return; }
+        if !next.advance().await { pending.remove(i); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::SharedClassifier;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_speed_parse_and_delay() {
+        assert_eq!(Speed::parse("2x"), Some(Speed::Factor(2.0)));
+        assert_eq!(Speed::parse("100/s"), Some(Speed::Rate(100.0)));
+        assert_eq!(Speed::parse("0x"), None);
+        assert_eq!(Speed::parse("fast"), None);
+        assert_eq!(Speed::Factor(2.0).delay(Duration::from_secs(3)), Duration::from_millis(1500));
+        assert_eq!(Speed::Factor(1.0).delay(Duration::from_secs(60)), MAX_GAP);
+        assert_eq!(Speed::Rate(4.0).delay(Duration::ZERO), Duration::from_millis(250));
+        assert_eq!(Speed::Factor(1.0).scaled(0.5).to_string(), "0.5x");
+    }
+
+    #[test]
+    fn test_files_merged_by_timestamp() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("a.log"), "2024-05-01 10:00:00 a1\n2024-05-01 10:00:02 a2\n").unwrap();
+        std::fs::write(dir.join("b.log"), "2024-05-01 10:00:01 b1\n  at b1\n2024-05-01 10:00:03 b2\n").unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let events = rt.block_on(async {
+            let (raw_tx, mut rx) = mpsc::channel(16);
+            let tx = EventSender::new(raw_tx, SharedClassifier::default());
+            let files = ["a.log", "b.log", "missing.log"].into_iter().enumerate()
+                .map(|(id, name)| ReplayFile { path: dir.join(name), source_id: id, tx: tx.clone() }).collect();
+            drop(tx);
+            let start = Instant::now();
+            replay(files, ReplayControl::new(Speed::Factor(100.0))).await;
+            // Three seconds of recording a hundred times as fast
+            assert!(start.elapsed() >= Duration::from_millis(30));
+            let mut events = Vec::new();
+            while let Some((id, ev)) = rx.recv().await {
+                events.push(match ev {
+                    SourceEvent::Line(line) => format!("{} {}", id, line.text),
+                    SourceEvent::Closed => format!("{} closed", id),
+                    SourceEvent::Error(e) => format!("{} error: {}", id, e),
+                    _ => format!("{} other", id),
+                });
+            }
+            events
+        });
+        assert_eq!(events[0], format!("2 error: not found: {}", dir.join("missing.log").display()));
+        assert_eq!(events[1..], [
+            "0 2024-05-01 10:00:00 a1", "1 2024-05-01 10:00:01 b1", "1   at b1", "0 2024-05-01 10:00:02 a2", "0 closed",
+            "1 2024-05-01 10:00:03 b2", "1 closed",
+        ]);
+    }
+}
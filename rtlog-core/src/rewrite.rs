@@ -0,0 +1,71 @@
+//! Display rewrites (`[[rewrite]]` in the config file, `D`).
+//!
+//! A rewrite rule is a regex and a replacement template (`$1`, `${name}` refer to groups), used
+//! to shorten what the log view shows, e.g. a long logger path down to its last segment. Only the
+//! screen is affected: the buffered lines, filters, search, exports, tee files and piped lines
+//! all keep the text as received.
+
+use std::borrow::Cow;
+
+use anyhow::Result;
+
+use crate::config::RewriteRule;
+use crate::replace::ReplaceRules;
+
+/// Compiled rewrite rules
+#[derive(Debug, Default)]
+pub struct Rewriter(ReplaceRules);
+
+impl Rewriter {
+    pub fn new(rules: &[RewriteRule]) -> Result<Self> {
+        Ok(Self(ReplaceRules::new(rules.iter().map(|r| (r.pattern.clone(), r.replace.clone())), "rewrite")?))
+    }
+
+    /// Whether there are no rules
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `text` shortened for the log view; borrowed when no rule matched
+    pub fn rewrite<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.0.apply(text)
+    }
+}
+
+/// Groups a replacement template refers to: numbers or names after `$`, or in `${...}`
+pub fn group_refs(replacement: &str) -> Vec<&str> {
+    let mut refs = Vec::new();
+    let mut rest = replacement;
+    while let Some(at) = rest.find('$') {
+        rest = &rest[at + 1..];
+        if let Some(braced) = rest.strip_prefix('{') && let Some(end) = braced.find('}') {
+            refs.push(&braced[..end]);
+            rest = &braced[end + 1..];
+        } else if let Some(escaped) = rest.strip_prefix('$') {
+            rest = escaped;
+        } else {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            if end > 0 { refs.push(&rest[..end]); }
+            rest = &rest[end..];
+        }
+    }
+    refs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite() {
+        let rules = [
+            RewriteRule { pattern: r"\[(?:[\w$]+\.)+(?P<class>\w+)\]".into(), replace: "[${class}]".into() },
+            RewriteRule { pattern: r"^(\d{4}-\d\d-\d\d )".into(), replace: String::new() },
+        ];
+        let r = Rewriter::new(&rules).unwrap();
+        assert_eq!(r.rewrite("2025-01-02 10:00:01 INFO [com.example.billing.internal.InvoiceService] sent"), "10:00:01 INFO [InvoiceService] sent");
+        assert!(matches!(r.rewrite("nothing to shorten"), Cow::Borrowed(_)));
+        assert!(Rewriter::new(&[RewriteRule { pattern: "(".into(), replace: String::new() }]).is_err());
+        assert_eq!(group_refs("[${class}] $1_x $$2 ${2}s $"), ["class", "1_x", "2"]);
+    }
+}
@@ -0,0 +1,233 @@
+//! Recording a viewer session and playing it back (`--record`, `--play`).
+//!
+//! A recording is a JSON-lines file: a header naming the sources, then every line the sources
+//! delivered and every key pressed, each with the milliseconds since the recording started:
+//!
+//! ```text
+//! {"rtlog_session":1,"sources":[{"name":"app.log","path":"/var/log/app.log"}]}
+//! {"ms":120,"source":"app.log","line":"ERROR disk full"}
+//! {"ms":950,"key":"/"}
+//! ```
+//!
+//! Playing it back creates the same sources, sends the lines again and presses the keys with the
+//! recorded gaps, paced like `--replay` (so `--speed` and the replay keys apply). Keys are kept as
+//! text (`j`, `<Enter>`, `<C-d>`); the UI turns them back into key presses. A source first seen
+//! after the recording started (opened with `o`, or a tag of a Fluent listener) is named by its
+//! lines and is there from the start of the playback.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+use crate::log::{EventSender, LogLine, SourceEvent};
+use crate::replay::{ReplayControl, Speed};
+
+/// Format of the recordings written, in the header's `rtlog_session`
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedSource {
+    /// Sidebar name, which the recorded lines refer to
+    pub name: String,
+    /// Input path (empty for commands), for sidebar groups
+    #[serde(default)]
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    rtlog_session: u32,
+    sources: Vec<RecordedSource>,
+}
+
+/// Something recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Entry {
+    /// A line of `source`, or of its sub-source `tag`
+    Line {
+        source: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+        line: String,
+    },
+    Key { key: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Timed {
+    ms: u64,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+/// The `--record` file being written
+#[derive(Debug)]
+pub struct SessionRecorder {
+    out: BufWriter<File>,
+    started: std::time::Instant,
+}
+
+impl SessionRecorder {
+    /// Create `path` (replacing it) and write the header listing `sources`
+    pub fn create(path: &Path, sources: Vec<RecordedSource>) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("cannot create {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        serde_json::to_writer(&mut out, &Header { rtlog_session: VERSION, sources })?;
+        out.write_all(b"\n").with_context(|| format!("cannot write {}", path.display()))?;
+        Ok(Self { out, started: std::time::Instant::now() })
+    }
+
+    pub fn line(&mut self, source: &str, tag: Option<&str>, line: &str) -> io::Result<()> {
+        self.write(Entry::Line { source: source.to_string(), tag: tag.map(str::to_string), line: line.to_string() })
+    }
+
+    /// Record a key press; written through right away, so a recording cut short by a crash still
+    /// has the keys leading up to it
+    pub fn key(&mut self, key: &str) -> io::Result<()> {
+        self.write(Entry::Key { key: key.to_string() })?;
+        self.out.flush()
+    }
+
+    fn write(&mut self, entry: Entry) -> io::Result<()> {
+        let ms = self.started.elapsed().as_millis() as u64;
+        serde_json::to_writer(&mut self.out, &Timed { ms, entry })?;
+        self.out.write_all(b"\n")
+    }
+}
+
+/// A recording read back for `--play`
+#[derive(Debug)]
+pub struct Recording {
+    /// The sources of the header, then those first seen in the lines
+    pub sources: Vec<RecordedSource>,
+    /// When each entry was recorded, from the start
+    pub entries: Vec<(Duration, Entry)>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+        let header = lines.next().and_then(|(_, line)| serde_json::from_str::<Header>(line).ok());
+        let Some(Header { mut sources, .. }) = header.filter(|h| h.rtlog_session == VERSION) else {
+            bail!("{} is not a session recorded with --record", path.display());
+        };
+        let mut entries = Vec::new();
+        while let Some((n, line)) = lines.next() {
+            let timed = match serde_json::from_str::<Timed>(line) {
+                Ok(timed) => timed,
+                // The last entry of a recording cut short may be half written
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => bail!("{}:{}: {}", path.display(), n + 1, e),
+            };
+            if let Entry::Line { source, .. } = &timed.entry
+                && !sources.iter().any(|s| &s.name == source) {
+                sources.push(RecordedSource { name: source.clone(), path: PathBuf::from(source) });
+            }
+            entries.push((Duration::from_millis(timed.ms), timed.entry));
+        }
+        Ok(Self { sources, entries })
+    }
+}
+
+/// A recorded key for the UI to press; `done` is dropped once it was, so the lines recorded after
+/// it are never shown before it
+pub struct PlayedKey {
+    pub key: String,
+    pub done: oneshot::Sender<()>,
+}
+
+/// Send the recorded lines to the sources with the ids in `sources` (by name) and the keys to
+/// `keys`, paced by `control`; returns at the end of the recording or once the viewer is gone
+pub async fn play(entries: Vec<(Duration, Entry)>, sources: HashMap<String, usize>, tx: EventSender, keys: mpsc::Sender<PlayedKey>, control: ReplayControl) {
+    let mut clock = Instant::now();
+    let mut last = Duration::ZERO;
+    for (at, entry) in entries {
+        let gap = at.saturating_sub(std::mem::replace(&mut last, at));
+        let paced = gap > Duration::ZERO || matches!(control.speed(), Speed::Rate(_));
+        if paced || control.is_paused() { clock = control.wait_turn(clock, gap).await; }
+        match entry {
+            Entry::Line { source, tag, line } => {
+                let Some(&id) = sources.get(&source) else { continue; };
+                let line = LogLine::new(line);
+                let ev = match tag { Some(tag) => SourceEvent::Record { tag, line }, None => SourceEvent::Line(line) };
+                if tx.send((id, ev)).await.is_err() { return; }
+            }
+            Entry::Key { key } => {
+                let (done, pressed) = oneshot::channel();
+                if keys.send(PlayedKey { key, done }).await.is_err() { return; }
+                let _ = pressed.await;
+            }
+        }
+    }
+}
+
+/// The next key of the playback, if any: like `mpsc::Receiver::recv`, but never ready without a
+/// playback. None once the playback ended, after which there is no playback any more
+pub async fn next_key(keys: &mut Option<mpsc::Receiver<PlayedKey>>) -> Option<PlayedKey> {
+    let Some(rx) = keys else { return std::future::pending().await; };
+    let key = rx.recv().await;
+    if key.is_none() { *keys = None; }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::SharedClassifier;
+
+    #[test]
+    fn test_record_and_play() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.rt");
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let mut recorder = SessionRecorder::create(&path, vec![RecordedSource { name: "app.log".into(), path: "/var/log/app.log".into() }]).unwrap();
+        recorder.line("app.log", None, "INFO started").unwrap();
+        recorder.key("<C-d>").unwrap();
+        recorder.line("fluent :24224", Some("web"), "GET / 200").unwrap();
+        drop(recorder);
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with("{\"rtlog_session\":1,\"sources\":[{\"name\":\"app.log\",\"path\":\"/var/log/app.log\"}]}\n{\"ms\":"), "{}", written);
+        // Cut short in the middle of an entry
+        fs::write(&path, written + "{\"ms\":20").unwrap();
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(recording.sources.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["app.log", "fluent :24224"]);
+        assert_eq!(recording.entries.iter().map(|(_, e)| e.clone()).collect::<Vec<_>>(), [
+            Entry::Line { source: "app.log".into(), tag: None, line: "INFO started".into() },
+            Entry::Key { key: "<C-d>".into() },
+            Entry::Line { source: "fluent :24224".into(), tag: Some("web".into()), line: "GET / 200".into() },
+        ]);
+        fs::write(&path, "INFO not a recording\n").unwrap();
+        assert!(Recording::load(&path).unwrap_err().to_string().contains("is not a session recorded with --record"));
+
+        rt.block_on(async {
+            let mut entries = recording.entries;
+            entries[1].0 = Duration::from_millis(1500);
+            entries[2].0 = Duration::from_millis(1500);
+            let (raw_tx, mut lines) = mpsc::channel(8);
+            let (keys_tx, keys) = mpsc::channel(1);
+            let sources = HashMap::from([("app.log".to_string(), 1), ("fluent :24224".to_string(), 2)]);
+            let player = tokio::spawn(play(entries, sources, EventSender::new(raw_tx, SharedClassifier::default()), keys_tx, ReplayControl::new(Speed::Factor(100.0))));
+            assert!(matches!(lines.recv().await, Some((1, SourceEvent::Line(line))) if &*line.text == "INFO started"));
+            let start = Instant::now();
+            let mut keys = Some(keys);
+            let key = next_key(&mut keys).await.unwrap();
+            // A hundred times as fast, and the line recorded with the key only once it was pressed
+            assert!(key.key == "<C-d>" && start.elapsed() >= Duration::from_millis(15));
+            tokio::task::yield_now().await;
+            assert!(lines.try_recv().is_err());
+            drop(key.done);
+            assert!(matches!(lines.recv().await, Some((2, SourceEvent::Record { tag, .. })) if tag == "web"));
+            assert!(next_key(&mut keys).await.is_none() && keys.is_none());
+            player.await.unwrap();
+        });
+    }
+}
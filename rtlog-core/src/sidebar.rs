@@ -0,0 +1,108 @@
+//! Sidebar ordering: the sort order of sources (`O`) and the pinned sources (`*`).
+//!
+//! Both are saved to a small text file in the state directory whenever they change, so a
+//! favourite set of sources stays on top from one run to the next. Each line is `sort ORDER` or
+//! `pin NAME`, NAME being the source's sidebar name.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// Orders of the sources within a sidebar group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceSort {
+    /// The order the inputs were given in
+    #[default]
+    Input,
+    Name,
+    /// Most recent line first
+    Activity,
+    /// Largest share of error lines first
+    Errors,
+}
+
+impl SourceSort {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "input" => SourceSort::Input,
+            "name" => SourceSort::Name,
+            "activity" => SourceSort::Activity,
+            "errors" => SourceSort::Errors,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self { SourceSort::Input => "input", SourceSort::Name => "name", SourceSort::Activity => "activity", SourceSort::Errors => "errors" }
+    }
+
+    /// The order after this one, for cycling with `O`
+    pub fn next(self) -> Self {
+        match self { SourceSort::Input => SourceSort::Name, SourceSort::Name => SourceSort::Activity, SourceSort::Activity => SourceSort::Errors, SourceSort::Errors => SourceSort::Input }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SidebarPrefs {
+    pub sort: SourceSort,
+    /// Sidebar names of the pinned sources
+    pub pinned: BTreeSet<String>,
+    path: Option<PathBuf>,
+}
+
+impl SidebarPrefs {
+    /// Load the preferences from `path`, ignoring a missing or unreadable file
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut prefs = Self { path, ..Self::default() };
+        let text = prefs.path.as_ref().and_then(|p| fs::read_to_string(p).ok()).unwrap_or_default();
+        for line in text.lines() {
+            match line.split_once(' ') {
+                Some(("sort", order)) => prefs.sort = SourceSort::parse(order).unwrap_or_default(),
+                Some(("pin", name)) if !name.is_empty() => { prefs.pinned.insert(name.to_string()); }
+                _ => {}
+            }
+        }
+        prefs
+    }
+
+    /// Pin `name`, or unpin it when pinned; returns whether it is pinned now
+    pub fn toggle_pin(&mut self, name: &str) -> bool {
+        let pinned = !self.pinned.remove(name) && self.pinned.insert(name.to_string());
+        self.save();
+        pinned
+    }
+
+    pub fn cycle_sort(&mut self) -> SourceSort {
+        self.sort = self.sort.next();
+        self.save();
+        self.sort
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else { return; };
+        if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+        let mut text = format!("sort {}\n", self.sort.name());
+        for name in self.pinned.iter().filter(|n| !n.contains('\n')) { text.push_str(&format!("pin {}\n", name)); }
+        let _ = fs::write(path, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefs_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sidebar");
+        let mut prefs = SidebarPrefs::load(Some(path.clone()));
+        assert_eq!((prefs.sort, prefs.pinned.len()), (SourceSort::Input, 0));
+        assert!(prefs.toggle_pin("api 2.log"));
+        assert!(prefs.toggle_pin("db.log"));
+        assert!(!prefs.toggle_pin("db.log"));
+        assert_eq!(prefs.cycle_sort(), SourceSort::Name);
+        let loaded = SidebarPrefs::load(Some(path.clone()));
+        assert_eq!(loaded.sort, SourceSort::Name);
+        assert_eq!(loaded.pinned.iter().collect::<Vec<_>>(), ["api 2.log"]);
+    }
+}
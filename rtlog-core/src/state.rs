@@ -0,0 +1,3596 @@
+//!
+//! Core application state container.
+//! 
+//! Holds sources, filters, search, alerts, selection, and stats. The UI reads from this struct and
+//! the runtime mutates it in response to user input and incoming log lines. Methods are kept small
+//! and cohesive to ease testing and future extraction into submodules.
+
+use crate::classify::{AlertRule, AlertSeverity, Classifier, FieldMap, Level, LevelCounts, LevelOverrides, LevelRules, LineClass, SharedClassifier};
+use crate::control::ControlCommand;
+use crate::editor::{find_locations, resolve, FileLocation};
+use crate::email::EmailAlerts;
+use crate::encoding::Encoding;
+use crate::export::{self, ExportFormat};
+use crate::filter::{extract_correlation_id, has_uppercase, line_matches, FieldFilter, FilterRule, CompiledFilterSet, TAB_WIDTH};
+use crate::histogram::{histogram, Bucket, BucketSize};
+use crate::log::LogLine;
+use crate::offsets::ReadOffsets;
+use crate::patterns::{top_patterns, PatternCount};
+use crate::plugin::{FilterInfo, Plugin, PluginAction, PluginEvent};
+use crate::profile::{Profile, Profiles};
+use crate::query::{FromClause, Query, QueryResult, Row};
+use crate::redact::Redactor;
+use crate::rewrite::{group_refs, Rewriter};
+use crate::sidebar::{SidebarPrefs, SourceSort};
+use crate::status::StatusTemplate;
+use crate::replay::ReplayControl;
+use crate::session::SessionRecorder;
+use crate::history::History;
+use crate::hotkeys::{same_filter, Hotkeys, HOTKEYS};
+use crate::links::{find_links, Link};
+use crate::transform::{TransformChain, Transforms};
+use crate::timestamp::{convert_timestamp, format_gap, parse_time_of_day, parse_timestamp, parse_timestamp_with, DisplayZone};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterFocus { #[default] Input, List }
+
+/// Kinds of single-line prompts shown as a popup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind { Goto, OpenSource, Shell, ConfirmClear, MuteAlert, Query, Export, Pipe, Replace, Hotkey, FilterGroup, Screenshot, FieldFilter }
+
+#[derive(Debug)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+    pub error: Option<String>,
+}
+
+/// A row of the filter list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterRow {
+    /// The filter at this index
+    Filter(usize),
+    /// A group's header, with the indices of its filters
+    Group { name: String, members: Vec<usize>, collapsed: bool },
+}
+
+/// Temporary cross-source view of all lines containing one request/trace ID
+#[derive(Debug)]
+pub struct Correlation {
+    pub id: String,
+    /// (source index, line id) pairs, ordered by timestamp when parsing is enabled
+    pub hits: Vec<(usize, LineId)>,
+    pub selected: usize,
+}
+
+/// Time windows the patterns panel cycles through (minutes; `None` is the whole buffer)
+const PATTERN_WINDOWS: [Option<u64>; 5] = [None, Some(1), Some(5), Some(15), Some(60)];
+
+/// Most frequent message templates of the focused source ("top talkers"), recounted about once
+/// a second while open
+#[derive(Debug)]
+pub struct PatternView {
+    /// Index into `PATTERN_WINDOWS`
+    window: usize,
+    pub patterns: Vec<PatternCount>,
+    /// Lines counted (within the window)
+    pub total: usize,
+    pub selected: usize,
+    /// Source the counts belong to
+    source_id: Option<usize>,
+    computed_ms: u128,
+}
+
+impl PatternView {
+    /// Window length in minutes, `None` for the whole buffer
+    pub fn window_minutes(&self) -> Option<u64> { PATTERN_WINDOWS[self.window] }
+}
+
+/// Overview of every source (`--summary`, `I`), shown in place of the log panel and recounted
+/// about once a second while sources are still being read
+#[derive(Debug)]
+pub struct SummaryView {
+    pub sources: Vec<SourceSummary>,
+    /// Sources still being read
+    pub loading: usize,
+    pub scroll: usize,
+    computed_ms: u128,
+}
+
+/// Patterns listed per source in the summary
+const SUMMARY_PATTERNS: usize = 3;
+
+#[derive(Debug)]
+pub struct SourceSummary {
+    pub name: String,
+    pub lines: usize,
+    pub levels: LevelCounts,
+    /// First and last timestamp found in the lines
+    pub span: Option<(chrono::NaiveDateTime, chrono::NaiveDateTime)>,
+    pub top: Vec<PatternCount>,
+}
+
+impl SourceSummary {
+    fn of(src: &Source) -> Self {
+        let lines = || src.lines.iter().enumerate().filter(|&(i, _)| !src.is_separator(i));
+        let first = lines().find_map(|(_, l)| src.timestamp(l));
+        let last = lines().rev().find_map(|(_, l)| src.timestamp(l));
+        let (mut top, _) = top_patterns(lines(), None);
+        top.truncate(SUMMARY_PATTERNS);
+        Self { name: src.name.clone(), lines: lines().count(), levels: src.levels, span: first.zip(last), top }
+    }
+}
+
+/// URLs and file paths found in the selected line (`U`), to pick one to open
+#[derive(Debug)]
+pub struct LinkChooser {
+    pub links: Vec<Link>,
+    pub selected: usize,
+}
+
+/// Results of the last SQL-like query (`Q`), shown in place of the log panel
+#[derive(Debug)]
+pub struct QueryView {
+    pub text: String,
+    pub result: QueryResult,
+    pub selected: usize,
+    pub elapsed_ms: u128,
+}
+
+/// Line volume of the focused source per minute or hour, shown above the log panel and recounted
+/// about once a second while open
+#[derive(Debug)]
+pub struct HistogramView {
+    pub size: BucketSize,
+    pub buckets: Vec<Bucket>,
+    /// Bucket under the cursor; stays on the newest bucket until moved
+    pub selected: usize,
+    follow: bool,
+    source_id: Option<usize>,
+    computed_ms: u128,
+}
+
+/// Values kept per field for the windowed aggregate, oldest dropped first
+const FIELD_WINDOW_VALUES: usize = 50_000;
+
+/// Count, median, 95th percentile and maximum of a field's recent values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldAggregate {
+    pub count: usize,
+    pub p50: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl FieldAggregate {
+    fn of(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() { return None; }
+        values.sort_unstable_by(f64::total_cmp);
+        // Nearest rank
+        let rank = |p: f64| values[((p * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1];
+        Some(Self { count: values.len(), p50: rank(0.5), p95: rank(0.95), max: values[values.len() - 1] })
+    }
+}
+
+/// Raised alerts kept for the alert history panel, oldest dropped first
+const ALERT_HISTORY: usize = 500;
+/// Minutes an alert pattern is muted for when the mute prompt is left empty
+const DEFAULT_MUTE_MINUTES: u64 = 10;
+
+/// One raised alert, as listed in the alert history panel
+#[derive(Debug, Clone)]
+pub struct AlertEntry {
+    pub time: chrono::DateTime<chrono::Local>,
+    pub severity: AlertSeverity,
+    /// Index into `alert_rules` of the pattern that matched; `None` for reader failures
+    pub rule: Option<usize>,
+    /// Name of the source the matching line came from
+    pub source: Option<String>,
+    pub message: String,
+    pub acknowledged: bool,
+}
+
+/// An alert pattern muted until `until_ms`: matching lines raise no banner, bell or history entry
+#[derive(Debug, Clone, Copy)]
+pub struct AlertMute {
+    pub until_ms: u128,
+    /// Alerts swallowed while muted
+    pub suppressed: usize,
+}
+
+/// Severity of a status-line message; also decides how long it stays visible
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageLevel { Info, Warn, Error }
+
+impl MessageLevel {
+    fn ttl_ms(self) -> u128 {
+        match self { MessageLevel::Info => 3000, MessageLevel::Warn => 5000, MessageLevel::Error => 8000 }
+    }
+}
+
+/// Transient feedback for a user action ("filter added", "no match", ...), shown in the status bar
+#[derive(Debug)]
+pub struct StatusMessage {
+    pub level: MessageLevel,
+    pub text: String,
+    /// Epoch millis after which the message is no longer shown
+    pub deadline_ms: u128,
+}
+
+/// Marker lines inserted by the user start with this (and end with " ----")
+pub const MARK_PREFIX: &str = "---- MARK ";
+
+/// Separators rtlog inserts where the timestamps of a source jump (`--gap`) start with this
+pub const GAP_PREFIX: &str = "---- GAP ";
+
+/// Reversible user action, most recent last on the undo stack
+#[derive(Debug)]
+enum UndoAction {
+    /// Filter appended at `index`
+    Added { index: usize },
+    /// Filter removed from `index`
+    Removed { index: usize, rule: FilterRule },
+    /// Filter at `index` enabled or disabled
+    Toggled { index: usize },
+    /// Filters at `indices`, all of `group`, switched together
+    GroupToggled { group: String, indices: Vec<usize> },
+    /// Buffer of source `source_id` cleared
+    Cleared { source_id: usize, lines: Vec<Arc<str>>, levels: LevelCounts, fields: FieldMap, annotations: BTreeMap<LineId, String> },
+}
+
+/// Oldest undo entries are dropped beyond this
+const UNDO_LIMIT: usize = 100;
+
+/// Most context lines shown around filter matches
+const MAX_FILTER_CONTEXT: usize = 50;
+
+/// Sidebar group holding the pinned sources, above all others
+pub const PINNED_GROUP: &str = "★ pinned";
+
+/// Sidebar group of sources (by parent directory or a user-defined `--group` rule)
+#[derive(Debug)]
+pub struct SourceGroup {
+    pub name: String,
+    pub members: Vec<usize>,
+    pub collapsed: bool,
+}
+
+/// Number of a line in its source's stream, counting from the first line received. Unlike the
+/// index into `Source::lines` it stays the same when older lines are dropped (clearing) or put
+/// back (undo), so selections, notes and search results refer to lines by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct LineId(pub usize);
+
+/// Lines that pull the focus to their source in focus-follow mode
+#[derive(Debug, Clone, Default)]
+pub enum FocusFollow {
+    /// Lines raising an alert that is not muted
+    #[default]
+    Alerts,
+    /// Lines matching the regex
+    Pattern(regex::Regex),
+}
+
+#[derive(Debug, Default)]
+pub struct Source {
+    /// Stable id used to tag reader events; unlike the index it survives removal of other sources
+    pub id: usize,
+    /// Display name: the user-assigned label, or the file name disambiguated by parent directories
+    pub name: String,
+    pub label: Option<String>,
+    pub group: usize,
+    /// Shell command for command sources (path is empty then)
+    pub command: Option<String>,
+    /// Static copy made by the freeze action; no reader ever writes to it
+    pub snapshot: bool,
+    /// Parsing profile of the file, whose name (if any) stands in for a missing label
+    pub profile: Option<Arc<Profile>>,
+    #[allow(dead_code)]
+    pub path: PathBuf,
+    pub lines: Vec<Arc<str>>,
+    /// Id of `lines[0]`
+    pub first_id: LineId,
+    /// Lines rtlog inserted as separators (markers, `--watch` runs, gaps); the log view always
+    /// shows these, whatever the filters, and they count towards no stats or alerts
+    pub separators: BTreeSet<LineId>,
+    /// Lines among `lines` that pass the filters (separators included)
+    pub shown: usize,
+    /// Lines per level among `lines`
+    pub levels: LevelCounts,
+    /// Summaries of the filters' named groups among `lines`
+    pub fields: FieldMap,
+    /// Notes attached to lines by plugins
+    pub annotations: BTreeMap<LineId, String>,
+    /// Lines come from a plugin (its own output or derived lines), so they are not sent to plugins
+    pub from_plugin: bool,
+    /// Output of a command the user piped lines into: it restates other lines, so it raises no
+    /// alerts, stays out of the global stats and is not sent to plugins
+    pub derived: bool,
+    pub scroll_offset: usize,
+    pub auto_scroll: bool,
+    pub selected_log: Option<LineId>,
+    /// Where the range selection (`V`) started; the range runs from it to `selected_log`
+    pub range_anchor: Option<LineId>,
+    // Activity indicators for the sidebar
+    pub unread: usize,
+    pub error: Option<String>,
+    pub closed: bool,
+    /// Set while a followed input is unavailable and being retried
+    pub waiting: Option<String>,
+    /// When the last line arrived, as a count of lines received by all sources
+    pub last_active: u64,
+    /// Timestamp of the last line that had one, while gaps are detected (`--gap`)
+    last_timestamp: Option<chrono::NaiveDateTime>,
+    /// chrono format of the timestamps (`--timestamp-format` or the profile's), instead of
+    /// detecting them
+    pub timestamp_format: Option<String>,
+    /// Lines whose timestamp `timestamp_format` parsed, and those it did not
+    pub timestamps_parsed: usize,
+    pub timestamps_failed: usize,
+    rate_sec: u64,
+    rate_cur: u32,
+    rate_prev: u32,
+}
+
+impl Source {
+    /// Id of the line at `index` in `lines` (or of the next line, for `lines.len()`)
+    pub fn line_id(&self, index: usize) -> LineId {
+        LineId(self.first_id.0 + index)
+    }
+
+    /// Index in `lines` of the line `id`, unless it is no longer buffered
+    pub fn line_index(&self, id: LineId) -> Option<usize> {
+        id.0.checked_sub(self.first_id.0).filter(|&i| i < self.lines.len())
+    }
+
+    /// Whether the line at `index` in `lines` is a separator rtlog inserted
+    pub fn is_separator(&self, index: usize) -> bool {
+        self.separators.contains(&self.line_id(index))
+    }
+
+    /// Index in `lines` of the selected line
+    pub fn selected_index(&self) -> Option<usize> {
+        self.selected_log.and_then(|id| self.line_index(id))
+    }
+
+    /// Timestamp of `text`, a line of this source, read as its profile says
+    pub fn timestamp(&self, text: &str) -> Option<chrono::NaiveDateTime> {
+        let format = self.timestamp_format.as_deref();
+        match (&self.profile, format) {
+            (Some(profile), _) => profile.timestamp(text, format),
+            (None, Some(format)) => parse_timestamp_with(text, format),
+            (None, None) => parse_timestamp(text),
+        }
+    }
+
+    /// Names the source can be referred to by (`--tee SOURCE=`, level overrides): sidebar name,
+    /// path, label and command
+    pub fn names(&self) -> Vec<String> {
+        let mut names = vec![self.name.clone(), self.path.to_string_lossy().into_owned()];
+        names.extend(self.label.clone());
+        names.extend(self.command.clone());
+        names
+    }
+
+    /// Lines received during the last complete second
+    pub fn lines_per_sec(&self) -> u32 {
+        let now = current_epoch_sec();
+        if now == self.rate_sec { self.rate_prev } else if now == self.rate_sec + 1 { self.rate_cur } else { 0 }
+    }
+
+    fn count_rate(&mut self) {
+        let now = current_epoch_sec();
+        if now != self.rate_sec {
+            self.rate_prev = if now == self.rate_sec + 1 { self.rate_cur } else { 0 };
+            self.rate_cur = 0;
+            self.rate_sec = now;
+        }
+        self.rate_cur = self.rate_cur.saturating_add(1);
+    }
+}
+
+#[derive(Default)]
+pub struct AppState {
+    // Multiple sources
+    pub sources: Vec<Source>,
+    pub focused: usize,
+    pub groups: Vec<SourceGroup>,
+    /// Sort order and pinned sources of the sidebar (`O`, `*`)
+    pub sidebar: SidebarPrefs,
+    /// Filters bound to Alt+1 … Alt+9 (`[[hotkeys]]`, `b`)
+    pub hotkeys: Hotkeys,
+    /// Lines received by all sources, for ordering them by activity
+    lines_received: u64,
+    group_rules: Vec<(String, regex::Regex)>,
+    next_source_id: usize,
+    /// (multiplexing source id, tag) -> id of the source created for that tag
+    tag_routes: HashMap<(usize, String), usize>,
+
+    // Filter system (global)
+    pub filters: Vec<FilterRule>,
+    /// Compiled filter and alert rules; rebuilt (new generation) whenever `filters` changes
+    classifier: Arc<Classifier>,
+    /// Same classifier as seen by the reader tasks
+    shared_classifier: SharedClassifier,
+    /// Per-source level patterns; the first whose regex matches one of a source's names applies
+    level_overrides: LevelOverrides,
+    /// Line transform scripts, handed to the readers of matching sources
+    pub transforms: Transforms,
+    /// Parsing profiles (`[[sources]]`), looked up for the files added as sources
+    pub profiles: Profiles,
+    pub filter_panel_open: bool,
+    pub filter_input: String,
+    pub input_is_regex: bool,
+    pub input_fuzzy: bool,
+    pub input_case_insensitive: bool,
+    pub input_whole_word: bool,
+    pub input_whole_line: bool,
+    pub filter_focus: FilterFocus,
+    pub selected_filter: usize,
+    /// The header of the selected filter's group is selected in the filter list, rather than
+    /// the filter
+    pub filter_header: bool,
+    /// Filter groups listed with their header only
+    pub collapsed_filter_groups: BTreeSet<String>,
+    pub filter_history: History,
+
+    // Search overlay (global, affects highlighting and jump)
+    pub search_open: bool,
+    pub search_input: String,
+    pub search_is_regex: bool,
+    pub search_case_insensitive: bool,
+    /// Whether searches look through all lines, including those the filters hide, rather than
+    /// only the lines the log panel shows
+    pub search_all_lines: bool,
+    /// With the i flag on, typed filters and searches with an uppercase letter match case
+    pub smart_case: bool,
+    /// What the status bar shows after the notices
+    pub status_template: StatusTemplate,
+    pub search_compiled: Option<regex::Regex>,
+    pub search_history: History,
+    /// Replacement for the search matches while previewing a substitution (`s`): the log panel
+    /// shows only the lines the search matches, with the replacement applied, and exports write
+    /// them that way
+    pub replace_preview: Option<String>,
+
+    // Generic popup prompt (goto, ...)
+    pub prompt: Option<Prompt>,
+    pub parse_timestamps: bool,
+    /// `--timestamp-format [SOURCE=]FORMAT`: for the sources with that name, label or path, or
+    /// for every source without a profile format (None)
+    pub timestamp_formats: Vec<(Option<String>, String)>,
+    /// `--encoding [SOURCE=]ENCODING`, looked up like `timestamp_formats`
+    pub encodings: Vec<(Option<String>, Encoding)>,
+
+    /// Finds `path:line` locations in lines for opening them in the editor (`--file-pattern`)
+    pub file_regex: Option<regex::Regex>,
+    pub link_chooser: Option<LinkChooser>,
+
+    // Correlation view
+    pub correlate_regex: Option<regex::Regex>,
+    pub correlation: Option<Correlation>,
+    pub patterns: Option<PatternView>,
+    pub summary: Option<SummaryView>,
+    pub query: Option<QueryView>,
+    /// Text of the last query run, offered again when the prompt reopens
+    last_query: String,
+    pub histogram: Option<HistogramView>,
+
+    // Alerts
+    pub alert_rules: Vec<AlertRule>,
+    pub alert_deadline_ms: u128, // epoch millis until which alert banner is visible
+    pub alert_blink_deadline_ms: u128, // epoch millis until which blinking is active
+    pub alert_message: Option<String>,
+    pub alert_severity: AlertSeverity,
+    /// Critical alerts raised since the last acknowledgement; the banner stays while non-zero
+    pub alert_unacked: usize,
+    /// A raised alert wants the terminal bell; taken by the runtime after drawing
+    pub bell: bool,
+    /// Text to put on the system clipboard; taken by the runtime after drawing
+    pub clipboard: Option<String>,
+    pub alert_history: VecDeque<AlertEntry>,
+    /// Muted alert patterns by index into `alert_rules`; expired entries are ignored
+    pub alert_mutes: HashMap<usize, AlertMute>,
+    /// Alert history panel, with the cursor counted from the newest entry
+    pub alert_panel: Option<usize>,
+    /// Mails raised alerts (`[email]` in the config file)
+    pub email: Option<EmailAlerts>,
+    /// Read offsets of followed files, saved when the session ends (`--resume`)
+    pub offsets: Option<Arc<ReadOffsets>>,
+    /// Plugin processes by the id of their source (`--plugin`)
+    plugins: Vec<(usize, Plugin)>,
+    /// Masks sensitive text on screen; shared with the tee writers and the email sink
+    pub redactor: Arc<Redactor>,
+    /// Show the original text despite `redactor` (`R`)
+    pub revealed: bool,
+    /// Shortens log lines on screen (`[[rewrite]]`)
+    pub rewriter: Rewriter,
+    /// Show log lines without the rewrites (`D`)
+    pub rewrites_off: bool,
+    /// Zone log timestamps are converted to on screen (`--tz`)
+    pub display_zone: Option<DisplayZone>,
+    /// Longer timestamp jumps between consecutive lines of a source get a separator (`--gap`)
+    pub gap_threshold: Option<chrono::TimeDelta>,
+    /// Format of the next export (`--export-format`; Tab cycles it in the export prompt)
+    pub export_format: ExportFormat,
+    /// The range selection also takes the lines the filters hide between its ends (`h`)
+    pub range_hidden: bool,
+
+    // Status-line feedback; a newer message replaces the current one
+    pub message: Option<StatusMessage>,
+
+    undo_stack: Vec<UndoAction>,
+
+    // Pause/step/speed of the `--replay` readers, if any
+    pub replay: Option<ReplayControl>,
+    // The `--record` file, receiving every line and key press
+    pub recorder: Option<SessionRecorder>,
+
+    // Context/details view (per focused source)
+    pub context_panel_open: bool,
+    pub context_radius: usize,
+    /// Lines the context panel is scrolled past the window around the selection (J/K); reset
+    /// when the selection moves
+    pub context_scroll: isize,
+
+    /// Filters only highlight and hide nothing (`v`)
+    pub show_all: bool,
+    /// Count typed before a navigation key, vim style (`25j`, `100G`)
+    pub pending_count: Option<usize>,
+    /// Visible heights a page scroll moves (`--page-step`)
+    pub page_step: f64,
+    /// What moves the focus by itself while `focus_follow_on` (`--focus-follow`, `L`)
+    pub focus_follow: FocusFollow,
+    pub focus_follow_on: bool,
+    /// Lines shown (dimmed) before and after each filter match (`-C`, +/-)
+    pub filter_context: usize,
+    /// Long lines are cut at the panel edge instead of wrapped (`W`)
+    pub no_wrap: bool,
+    /// Conditions on the fields of the filters' named groups (`=`) that shown lines all meet
+    pub field_filters: Vec<FieldFilter>,
+    /// The fields in columns before the text of each line (`t`)
+    pub column_view: bool,
+    /// Columns scrolled right while not wrapping
+    pub h_scroll: u16,
+    /// Only the log lines and the status bar: no sidebar, stats panel or borders (`Z`)
+    pub compact: bool,
+    /// Columns between tab stops in log lines (`tab_width` in the config file)
+    pub tab_width: usize,
+    /// Tabs and control characters shown as symbols (`T`)
+    pub show_controls: bool,
+    /// Show `terminal_title` in the terminal's title bar
+    pub set_terminal_title: bool,
+    /// `--exit-on-match`: the session ends at the first line matching it
+    pub exit_pattern: Option<regex::Regex>,
+    /// Source name and text of the first line that matched `exit_pattern`
+    pub exit_match: Option<String>,
+
+    // Stats: rolling counts per second for last N seconds (global)
+    pub err_buckets: VecDeque<u16>,
+    pub warn_buckets: VecDeque<u16>,
+    pub bucket_epoch_sec: u64,
+    /// Recent field values (all sources) with the second they arrived, for the windowed aggregate
+    field_window: BTreeMap<Arc<str>, VecDeque<(u64, f64)>>,
+    /// Field aggregated next to the sparklines (`f` cycles); the first one when unset
+    aggregate_field: Option<Arc<str>>,
+}
+
+const SPARK_WINDOW: usize = 60;
+
+impl AppState {
+    pub fn new(initial_cli_regex: Option<regex::Regex>, alert_patterns: Vec<(AlertSeverity, String)>) -> Self {
+        let now_sec = current_epoch_sec();
+        let mut s = Self {
+            sources: Vec::new(),
+            focused: 0,
+            groups: Vec::new(),
+            sidebar: SidebarPrefs::default(),
+            hotkeys: Hotkeys::default(),
+            lines_received: 0,
+            group_rules: Vec::new(),
+            next_source_id: 0,
+            tag_routes: HashMap::new(),
+            filters: Vec::new(),
+            classifier: Arc::default(),
+            shared_classifier: SharedClassifier::default(),
+            level_overrides: Vec::new(),
+            transforms: Transforms::default(),
+            profiles: Profiles::default(),
+            filter_panel_open: false,
+            filter_input: String::new(),
+            input_is_regex: false,
+            input_fuzzy: false,
+            input_case_insensitive: true,
+            input_whole_word: false,
+            input_whole_line: false,
+            filter_focus: FilterFocus::Input,
+            selected_filter: 0,
+            filter_header: false,
+            collapsed_filter_groups: BTreeSet::new(),
+            filter_history: History::default(),
+            search_open: false,
+            search_input: String::new(),
+            search_is_regex: false,
+            search_case_insensitive: true,
+            search_all_lines: false,
+            smart_case: true,
+            status_template: StatusTemplate::default(),
+            search_compiled: None,
+            search_history: History::default(),
+            replace_preview: None,
+            prompt: None,
+            parse_timestamps: false,
+            timestamp_formats: Vec::new(),
+            encodings: Vec::new(),
+            correlate_regex: None,
+            file_regex: None,
+            link_chooser: None,
+            correlation: None,
+            patterns: None,
+            summary: None,
+            query: None,
+            last_query: String::new(),
+            histogram: None,
+            // alerts
+            alert_rules: Vec::new(),
+            alert_deadline_ms: 0,
+            alert_blink_deadline_ms: 0,
+            alert_message: None,
+            alert_severity: AlertSeverity::default(),
+            alert_unacked: 0,
+            bell: false,
+            clipboard: None,
+            alert_history: VecDeque::new(),
+            alert_mutes: HashMap::new(),
+            alert_panel: None,
+            email: None,
+            offsets: None,
+            plugins: Vec::new(),
+            redactor: Arc::default(),
+            revealed: false,
+            rewriter: Rewriter::default(),
+            rewrites_off: false,
+            display_zone: None,
+            gap_threshold: None,
+            export_format: ExportFormat::default(),
+            range_hidden: false,
+            message: None,
+            undo_stack: Vec::new(),
+            replay: None,
+            recorder: None,
+            // context
+            context_panel_open: false,
+            context_radius: 3,
+            context_scroll: 0,
+            show_all: false,
+            pending_count: None,
+            page_step: 1.0,
+            focus_follow: FocusFollow::Alerts,
+            focus_follow_on: false,
+            filter_context: 0,
+            no_wrap: false,
+            field_filters: Vec::new(),
+            column_view: false,
+            compact: false,
+            tab_width: TAB_WIDTH,
+            show_controls: false,
+            set_terminal_title: true,
+            exit_pattern: None,
+            exit_match: None,
+            h_scroll: 0,
+            // stats
+            err_buckets: VecDeque::from(vec![0; SPARK_WINDOW]),
+            warn_buckets: VecDeque::from(vec![0; SPARK_WINDOW]),
+            bucket_epoch_sec: now_sec.saturating_sub(SPARK_WINDOW as u64 - 1),
+            field_window: BTreeMap::new(),
+            aggregate_field: None,
+        };
+        if let Some(re) = initial_cli_regex {
+            // We don't have the original pattern; store the regex string
+            let rule = FilterRule { pattern: re.as_str().to_string(), is_regex: true, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: Some(re), match_count: 0, group: None };
+            s.filters.push(rule);
+        }
+        // Initialize alert rules from patterns (treated as plain, case-insensitive substrings)
+        for (severity, p) in alert_patterns {
+            s.alert_rules.push(AlertRule::new(p, false, severity));
+        }
+        s.rebuild_classifier();
+        s
+    }
+
+    /// Load the sidebar preferences, saved filter bindings and input histories kept in `dir`
+    /// (`history::state_dir`), which they are saved back to from then on. `new` leaves them empty
+    /// and unsaved, so states built for tests and reports never touch the user's files
+    pub fn load_saved(&mut self, dir: &Path) {
+        self.sidebar = SidebarPrefs::load(Some(dir.join("sidebar")));
+        self.hotkeys = Hotkeys::load(Some(dir.join("hotkeys")));
+        self.filter_history = History::load(Some(dir.join("filter_history")));
+        self.search_history = History::load(Some(dir.join("search_history")));
+    }
+
+    /// Replace all sources (optional label, path); ids are assigned in order starting at 0
+    pub fn set_sources<I: IntoIterator<Item = (Option<String>, PathBuf)>>(&mut self, inputs: I) {
+        self.sources.clear();
+        self.next_source_id = 0;
+        for (label, path) in inputs { self.push_source(label, path, None); }
+        self.focused = 0;
+        self.regroup();
+    }
+
+    /// Add a source at runtime and return its id (use it to tag the reader's events)
+    pub fn add_source(&mut self, label: Option<String>, path: PathBuf, command: Option<String>) -> usize {
+        let id = self.push_source(label, path, command);
+        self.regroup();
+        id
+    }
+
+    fn push_source(&mut self, label: Option<String>, path: PathBuf, command: Option<String>) -> usize {
+        let id = self.next_source_id;
+        self.next_source_id += 1;
+        let profile = if command.is_none() { self.profiles.for_path(&path) } else { None };
+        let label = label.or_else(|| profile.as_ref().and_then(|p| p.name.clone()));
+        self.sources.push(Source { id, label, path, command, profile, auto_scroll: true, ..Default::default() });
+        id
+    }
+
+    /// Recompute display names so sources sharing a file name stay distinguishable
+    fn assign_names(&mut self) {
+        let auto: Vec<usize> = (0..self.sources.len()).filter(|&i| self.sources[i].label.is_none()).collect();
+        let paths: Vec<&Path> = auto.iter().map(|&i| self.sources[i].path.as_path()).collect();
+        let names = disambiguated_names(&paths);
+        for (i, name) in auto.into_iter().zip(names) { self.sources[i].name = name; }
+        for src in self.sources.iter_mut() {
+            if let Some(label) = &src.label { src.name = label.clone(); }
+        }
+        // Formats given for a source refer to it by name, so they are looked up again with the names
+        for i in 0..self.sources.len() {
+            self.sources[i].timestamp_format = self.timestamp_format_for(&self.sources[i]);
+        }
+    }
+
+    /// Timestamp format of `src`: `--timestamp-format` naming it, else its profile's, else the
+    /// global `--timestamp-format`
+    fn timestamp_format_for(&self, src: &Source) -> Option<String> {
+        let names = src.names();
+        let named = self.timestamp_formats.iter().find(|(source, _)| source.as_ref().is_some_and(|s| names.contains(s)));
+        let global = || self.timestamp_formats.iter().find(|(source, _)| source.is_none());
+        named.map(|(_, format)| format.clone())
+            .or_else(|| src.profile.as_ref().and_then(|p| p.timestamp.clone()))
+            .or_else(|| global().map(|(_, format)| format.clone()))
+    }
+
+    /// Encoding to read `src`'s file in: `--encoding` for its name, its profile's, then
+    /// `--encoding` for all sources; None to tell it from the start of the file
+    pub fn encoding_for(&self, src: &Source) -> Option<Encoding> {
+        let names = src.names();
+        let named = self.encodings.iter().find(|(source, _)| source.as_ref().is_some_and(|s| names.contains(s)));
+        let global = || self.encodings.iter().find(|(source, _)| source.is_none());
+        named.map(|(_, encoding)| *encoding)
+            .or_else(|| src.profile.as_ref().and_then(|p| p.encoding))
+            .or_else(|| global().map(|(_, encoding)| *encoding))
+    }
+
+    /// Clone the focused source's buffer into a new read-only snapshot source named `NAME @HH:MM:SS`
+    /// and focus it. Live ingestion into the original source continues.
+    pub fn freeze_focused(&mut self) -> Option<usize> {
+        let src = self.current_source()?;
+        let stamp = chrono::Local::now().format("%H:%M:%S");
+        let label = format!("{} @{}", src.name, stamp);
+        let msg = format!("snapshot created: {} ({} lines)", label, src.lines.len());
+        let snapshot = Source {
+            label: Some(label),
+            path: src.path.clone(),
+            lines: src.lines.clone(),
+            first_id: src.first_id,
+            separators: src.separators.clone(),
+            shown: src.shown,
+            levels: src.levels,
+            fields: src.fields.clone(),
+            profile: src.profile.clone(),
+            timestamps_parsed: src.timestamps_parsed,
+            timestamps_failed: src.timestamps_failed,
+            scroll_offset: src.scroll_offset,
+            selected_log: src.selected_log,
+            range_anchor: src.range_anchor,
+            auto_scroll: false,
+            snapshot: true,
+            closed: true,
+            ..Default::default()
+        };
+        let id = self.next_source_id;
+        self.next_source_id += 1;
+        self.notify(MessageLevel::Info, msg);
+        self.sources.push(Source { id, ..snapshot });
+        self.regroup();
+        self.set_focus(self.sources.len() - 1);
+        Some(id)
+    }
+
+    /// Append a `---- MARK HH:MM:SS ----` line to the focused source, or to every source with
+    /// `all`, to separate "before" and "after" while reproducing an issue. Markers bypass
+    /// classification, so they don't count towards stats or trigger alerts.
+    pub fn insert_marker(&mut self, all: bool) {
+        let text: Arc<str> = format!("{}{} ----", MARK_PREFIX, chrono::Local::now().format("%H:%M:%S")).into();
+        let focused = self.focused;
+        let mut count = 0;
+        for (i, src) in self.sources.iter_mut().enumerate().filter(|(i, _)| all || *i == focused) {
+            src.separators.insert(src.line_id(src.lines.len()));
+            src.lines.push(Arc::clone(&text));
+            src.shown += 1;
+            if i != focused { src.unread += 1; }
+            if src.auto_scroll { src.scroll_offset = 0; }
+            count += 1;
+        }
+        if count > 0 { self.notify(MessageLevel::Info, format!("marker added to {} source(s)", count)); }
+    }
+
+    /// Pause or resume the replay
+    pub fn toggle_replay_pause(&mut self) {
+        let Some(replay) = &self.replay else { return; };
+        let msg = if replay.toggle_pause() { "replay paused (. steps one line)".to_string() } else { format!("replay resumed at {}", replay.speed()) };
+        self.notify(MessageLevel::Info, msg);
+    }
+
+    /// Emit the next replayed line while paused
+    pub fn replay_step(&mut self) {
+        let Some(replay) = &self.replay else { return; };
+        if replay.is_paused() { replay.step(); } else { self.notify(MessageLevel::Warn, "replay is not paused (p pauses)"); }
+    }
+
+    /// Make the replay `by` times faster (< 1 slows it down)
+    pub fn scale_replay_speed(&mut self, by: f64) {
+        let Some(replay) = &self.replay else { return; };
+        let speed = replay.scale_speed(by);
+        self.notify(MessageLevel::Info, format!("replay speed {}", speed));
+    }
+
+    /// Write a line `source_id` delivered (of its sub-source `tag`) to the `--record` file,
+    /// redacted like tee files
+    pub fn record_line(&mut self, source_id: usize, tag: Option<&str>, text: &str) {
+        let Some(idx) = self.index_of(source_id) else { return; };
+        let Some(recorder) = &mut self.recorder else { return; };
+        let res = recorder.line(&self.sources[idx].name, tag, &self.redactor.redact(text));
+        self.stop_recording_on(res);
+    }
+
+    /// Write a key press (`j`, `<C-d>`) to the `--record` file
+    pub fn record_key(&mut self, key: &str) {
+        let Some(recorder) = &mut self.recorder else { return; };
+        let res = recorder.key(key);
+        self.stop_recording_on(res);
+    }
+
+    fn stop_recording_on(&mut self, res: std::io::Result<()>) {
+        if let Err(e) = res {
+            self.recorder = None;
+            self.notify(MessageLevel::Error, format!("recording stopped: {}", e));
+        }
+    }
+
+    /// Remove the focused source and return its id so the runtime can stop the reader
+    pub fn remove_focused_source(&mut self) -> Option<usize> {
+        if self.focused >= self.sources.len() { return None; }
+        let src = self.sources.remove(self.focused);
+        self.plugins.retain(|(id, _)| *id != src.id);
+        self.notify(MessageLevel::Info, format!("closed {}", src.name));
+        // Correlation hits hold source indices, which just shifted
+        self.correlation = None;
+        let next = self.focused.min(self.sources.len().saturating_sub(1));
+        self.set_focus(next);
+        self.regroup();
+        Some(src.id)
+    }
+
+    fn index_of(&self, source_id: usize) -> Option<usize> {
+        self.sources.iter().position(|s| s.id == source_id)
+    }
+
+    /// Set user grouping rules (first regex matching the path wins) and regroup
+    pub fn group_sources(&mut self, rules: &[(String, regex::Regex)]) {
+        self.group_rules = rules.to_vec();
+        self.regroup();
+    }
+
+    /// Assign each source to the first user rule whose regex matches its path, else to its parent
+    /// directory (command sources go to "commands"). Pinned sources form a group of their own,
+    /// listed first. Collapsed state is kept by group name.
+    fn regroup(&mut self) {
+        self.assign_names();
+        let mut groups: Vec<SourceGroup> = Vec::new();
+        for (i, src) in self.sources.iter().enumerate() {
+            let path = src.command.clone().unwrap_or_else(|| src.path.to_string_lossy().into_owned());
+            let pinned = self.sidebar.pinned.contains(&src.name);
+            let name = pinned.then(|| PINNED_GROUP.to_string())
+                .or_else(|| self.group_rules.iter().find(|(_, re)| re.is_match(&path)).map(|(n, _)| n.clone()))
+                .unwrap_or_else(|| match src.path.parent().map(|p| p.display().to_string()) {
+                    _ if src.snapshot => "snapshots".to_string(),
+                    _ if src.from_plugin => "plugins".to_string(),
+                    _ if src.command.is_some() => "commands".to_string(),
+                    Some(dir) if !dir.is_empty() => dir,
+                    _ => ".".to_string(),
+                });
+            let gi = match groups.iter().position(|g| g.name == name) {
+                Some(gi) => gi,
+                None => {
+                    let collapsed = self.groups.iter().any(|g| g.name == name && g.collapsed);
+                    groups.push(SourceGroup { name, members: Vec::new(), collapsed });
+                    groups.len() - 1
+                }
+            };
+            groups[gi].members.push(i);
+        }
+        if let Some(gi) = groups.iter().position(|g| g.name == PINNED_GROUP) {
+            let pinned = groups.remove(gi);
+            groups.insert(0, pinned);
+        }
+        for (gi, g) in groups.iter().enumerate() {
+            for &i in &g.members { self.sources[i].group = gi; }
+        }
+        self.groups = groups;
+    }
+
+    pub fn current_source(&self) -> Option<&Source> { self.sources.get(self.focused) }
+    pub fn current_source_mut(&mut self) -> Option<&mut Source> { self.sources.get_mut(self.focused) }
+
+    pub fn push_line_for(&mut self, source_id: usize, line: LogLine) {
+        // Lines classified by a reader before the rules last changed are classified again here
+        let LogLine { text: line, class } = line;
+        let Some(idx) = self.index_of(source_id) else { return; };
+        let marker = class.separator;
+        let class = if marker || class.generation == self.classifier.generation { class } else {
+            let src = &self.sources[idx];
+            let class = match self.level_rules_for(src) {
+                Some(levels) => self.classifier.classify_with(&line, &levels),
+                None => self.classifier.classify(&line),
+            };
+            match src.profile.as_ref().and_then(|p| p.level(&line)) {
+                Some(level) => class.with_level(level),
+                None => class,
+            }
+        };
+        // Separators count for nothing, derived lines only for their own source
+        if !marker && self.sources[idx].profile.as_ref().is_some_and(|p| p.continues(&line)) && self.join_continuation(idx, &line, &class) { return; }
+        let quiet = marker || self.sources[idx].derived;
+        let shown = marker || self.classifier.filters.passes_hits(&class.filter_hits) && self.passes_field_filters(&line);
+        // Update stats globally first to avoid borrow conflicts
+        let mut alerted = false;
+        if !marker { self.lines_received += 1; }
+        if !quiet {
+            self.update_buckets_for_now();
+            self.count_class(&class);
+            if !class.fields.is_empty() { self.record_field_values(&class.fields); }
+            if class.alert.is_some() { alerted = self.alert_for_line(source_id, &line); }
+        }
+        // Timestamps are read for gaps and, with a format, to count the lines it does not parse
+        let src = &self.sources[idx];
+        let timestamp = if marker || self.gap_threshold.is_none() && src.timestamp_format.is_none() { None } else { src.timestamp(&line) };
+        if !quiet && self.exit_match.is_none() && self.exit_pattern.as_ref().is_some_and(|re| re.is_match(&line)) {
+            self.exit_match = Some(format!("{}: {}", src.name, line));
+        }
+        let refocus = self.focus_follow_on && !quiet && idx != self.focused && match &self.focus_follow {
+            FocusFollow::Alerts => alerted,
+            FocusFollow::Pattern(re) => re.is_match(&line),
+        };
+        if let Some(src) = self.sources.get_mut(idx) {
+            if !marker && src.timestamp_format.is_some() {
+                if timestamp.is_some() { src.timestamps_parsed += 1; } else { src.timestamps_failed += 1; }
+            }
+            if let Some(ts) = timestamp.filter(|_| self.gap_threshold.is_some()) {
+                if let (Some(last), Some(threshold)) = (src.last_timestamp, self.gap_threshold) && ts - last > threshold {
+                    src.separators.insert(src.line_id(src.lines.len()));
+                    src.lines.push(format!("{}{} ----", GAP_PREFIX, format_gap(ts - last)).into());
+                    src.shown += 1;
+                }
+                src.last_timestamp = Some(ts);
+            }
+            if let Some(corr) = self.correlation.as_mut() && line.contains(corr.id.as_str()) {
+                let follow = corr.selected + 1 >= corr.hits.len();
+                corr.hits.push((idx, src.line_id(src.lines.len())));
+                if follow { corr.selected = corr.hits.len() - 1; }
+            }
+            if !src.from_plugin && !quiet && !self.plugins.is_empty() {
+                let event = PluginEvent::Line { source: &src.name, index: src.line_id(src.lines.len()).0, line: &line[..], level: class.level().map(|l| l.name()) };
+                for (_, plugin) in &self.plugins { plugin.send(&event); }
+            }
+            if marker { src.separators.insert(src.line_id(src.lines.len())); }
+            src.lines.push(line);
+            if shown { src.shown += 1; }
+            if !marker {
+                src.last_active = self.lines_received;
+                src.levels.count(&class);
+                for (name, value) in &class.fields { src.fields.entry(Arc::clone(name)).or_default().record(*value); }
+            }
+            src.count_rate();
+            if idx != self.focused { src.unread += 1; }
+            if src.auto_scroll { src.scroll_offset = 0; }
+        }
+        if refocus { self.set_focus(idx); }
+    }
+
+    /// Append `line`, which continues the previous one by the source's multi-line pattern, to
+    /// that line; false when there is no line to continue. Its alert is raised, but it adds no
+    /// line to the stats.
+    fn join_continuation(&mut self, idx: usize, line: &str, class: &LineClass) -> bool {
+        let src = &self.sources[idx];
+        let Some(prev) = src.lines.last().filter(|_| !src.is_separator(src.lines.len() - 1)).cloned() else { return false; };
+        let joined: Arc<str> = format!("{}\n{}", prev, line).into();
+        let (was_shown, shown) = (self.passes_filters(&prev), self.passes_filters(&joined));
+        if class.alert.is_some() && !self.sources[idx].derived {
+            let source_id = self.sources[idx].id;
+            self.alert_for_line(source_id, line);
+        }
+        let src = &mut self.sources[idx];
+        if let Some(last) = src.lines.last_mut() { *last = joined; }
+        src.shown = src.shown + shown as usize - was_shown as usize;
+        if src.auto_scroll { src.scroll_offset = 0; }
+        true
+    }
+
+    /// Append a line for `tag` of the multiplexing source `source_id`, creating a source for the
+    /// tag on first use. Tag sources sit next to their parent in the sidebar and have no reader.
+    pub fn push_tagged_line(&mut self, source_id: usize, tag: &str, line: LogLine) {
+        let key = (source_id, tag.to_string());
+        let id = match self.tag_routes.get(&key) {
+            // A closed tag source is recreated when its tag shows up again
+            Some(&id) if self.index_of(id).is_some() => id,
+            _ => {
+                let Some(parent) = self.index_of(source_id).map(|i| &self.sources[i]) else { return; };
+                let path = parent.path.parent().map(|dir| dir.join(tag)).unwrap_or_else(|| PathBuf::from(tag));
+                let from_plugin = parent.from_plugin;
+                let id = self.add_source(Some(tag.to_string()), path, None);
+                if from_plugin { self.mark_plugin_source(id); }
+                self.tag_routes.insert(key, id);
+                id
+            }
+        };
+        self.push_line_for(id, line);
+    }
+
+    /// Record a reader failure and surface it as an alert
+    pub fn set_source_error(&mut self, source_id: usize, err: String) {
+        let Some(src) = self.index_of(source_id).and_then(|i| self.sources.get_mut(i)) else { return; };
+        let msg = format!("{}: {}", src.name, err);
+        src.error = Some(err);
+        self.raise_alert(AlertSeverity::Warn, msg);
+    }
+
+    pub fn set_source_waiting(&mut self, source_id: usize, reason: Option<String>) {
+        if let Some(src) = self.index_of(source_id).and_then(|i| self.sources.get_mut(i)) { src.waiting = reason; }
+    }
+
+    pub fn set_source_closed(&mut self, source_id: usize) {
+        if let Some(src) = self.index_of(source_id).and_then(|i| self.sources.get_mut(i)) { src.closed = true; }
+    }
+
+    /// Mark the sources whose readers were stopped before their end (`--duration`,
+    /// `--until-idle`) as closed, saying why
+    pub fn stop_reading(&mut self, reason: &str) {
+        for src in self.sources.iter_mut().filter(|s| s.error.is_none()) {
+            src.closed = true;
+            src.waiting = None;
+        }
+        self.notify(MessageLevel::Warn, format!("stopped reading: {}", reason));
+    }
+
+    /// Lines received by all sources so far
+    pub fn lines_received(&self) -> u64 {
+        self.lines_received
+    }
+
+    pub fn failed_source_count(&self) -> usize {
+        self.sources.iter().filter(|s| s.error.is_some()).count()
+    }
+
+    /// Change focus, clearing the newly focused source's unread counter
+    pub fn set_focus(&mut self, idx: usize) {
+        if idx >= self.sources.len() { return; }
+        self.focused = idx;
+        self.sources[idx].unread = 0;
+    }
+
+    fn count_class(&mut self, class: &LineClass) {
+        // Per-filter match counts
+        for &i in &class.filter_hits {
+            if let Some(rule) = self.filters.get_mut(i) { rule.match_count = rule.match_count.saturating_add(1); }
+        }
+        if class.is_error { self.bump_bucket(true); }
+        if class.is_warn { self.bump_bucket(false); }
+    }
+
+    fn bump_bucket(&mut self, is_error: bool) {
+        if is_error {
+            if let Some(back) = self.err_buckets.back_mut() { *back = back.saturating_add(1); }
+        } else {
+            if let Some(back) = self.warn_buckets.back_mut() { *back = back.saturating_add(1); }
+        }
+    }
+
+    fn update_buckets_for_now(&mut self) {
+        let now = current_epoch_sec();
+        if now <= self.bucket_epoch_sec { return; }
+        // Advance buckets to 'now', pushing zeros (a gap longer than the window clears it)
+        for _ in 0..(now - self.bucket_epoch_sec).min(SPARK_WINDOW as u64) {
+            // move window forward by 1 second
+            if self.err_buckets.len() == SPARK_WINDOW { self.err_buckets.pop_front(); }
+            if self.warn_buckets.len() == SPARK_WINDOW { self.warn_buckets.pop_front(); }
+            self.err_buckets.push_back(0);
+            self.warn_buckets.push_back(0);
+        }
+        self.bucket_epoch_sec = now;
+    }
+
+    fn record_field_values(&mut self, values: &[(Arc<str>, f64)]) {
+        let now = current_epoch_sec();
+        for (name, value) in values {
+            let recent = self.field_window.entry(Arc::clone(name)).or_default();
+            while recent.front().is_some_and(|&(t, _)| t + (SPARK_WINDOW as u64) <= now) || recent.len() >= FIELD_WINDOW_VALUES { recent.pop_front(); }
+            recent.push_back((now, *value));
+        }
+    }
+
+    /// The aggregated field and its values' summary over the sparkline window (`None` without
+    /// values in the window)
+    pub fn field_aggregate(&self) -> Option<(&str, Option<FieldAggregate>)> {
+        let (name, recent) = match &self.aggregate_field {
+            Some(name) => self.field_window.get_key_value(name)?,
+            None => self.field_window.iter().next()?,
+        };
+        let since = current_epoch_sec().saturating_sub(SPARK_WINDOW as u64 - 1);
+        let values = recent.iter().filter(|&&(t, _)| t >= since).map(|&(_, v)| v).collect();
+        Some((name, FieldAggregate::of(values)))
+    }
+
+    /// Aggregate the next field (in name order) next to the sparklines
+    pub fn cycle_aggregate_field(&mut self) {
+        let current = self.field_aggregate().map(|(name, _)| name.to_string());
+        let next = match current {
+            Some(current) => self.field_window.keys().find(|k| ***k > *current).or_else(|| self.field_window.keys().next()).cloned(),
+            None => None,
+        };
+        match next {
+            Some(name) => {
+                self.notify(MessageLevel::Info, format!("aggregating field {}", name));
+                self.aggregate_field = Some(name);
+            }
+            None => self.notify(MessageLevel::Info, r"no fields yet: add a regex filter with a named group, e.g. (?P<ms>\d+)ms"),
+        }
+    }
+
+    /// Enabled filters for the per-line match test
+    pub fn filter_match_set(&self) -> &CompiledFilterSet { &self.classifier.filters }
+
+    /// Handle through which reader tasks classify lines with the current rules
+    pub fn shared_classifier(&self) -> SharedClassifier { self.shared_classifier.clone() }
+
+    /// Use `default` level patterns, and `overrides` for sources with a matching name
+    pub fn set_level_rules(&mut self, default: LevelRules, overrides: LevelOverrides) {
+        self.level_overrides = overrides;
+        self.publish_classifier(Arc::new(default));
+    }
+
+    /// Level patterns overriding the defaults for `src`, if any
+    pub fn level_rules_for(&self, src: &Source) -> Option<Arc<LevelRules>> {
+        let names = src.names();
+        self.level_overrides.iter().find(|(re, _)| names.iter().any(|n| re.is_match(n))).map(|(_, rules)| Arc::clone(rules))
+    }
+
+    /// Level of `text`, a line of `src`, by the source's level patterns
+    pub fn line_level(&self, src: &Source, text: &str) -> Option<Level> {
+        if let Some(level) = src.profile.as_ref().and_then(|p| p.level(text)) { return level; }
+        match self.level_rules_for(src) {
+            Some(levels) => levels.level(text),
+            None => self.classifier.levels.level(text),
+        }
+    }
+
+    /// Transform scripts for `src`, if any apply
+    pub fn transforms_for(&self, src: &Source) -> Option<Arc<TransformChain>> {
+        self.transforms.for_source(&src.names())
+    }
+
+    fn rebuild_classifier(&mut self) {
+        self.publish_classifier(Arc::clone(&self.classifier.levels));
+        for (_, plugin) in &self.plugins { plugin.send(&self.filters_event()); }
+    }
+
+    fn filters_event(&self) -> PluginEvent<'_> {
+        let filters = self.filters.iter().map(|f| FilterInfo { pattern: &f.pattern, regex: f.is_regex, exclude: f.exclude, enabled: f.enabled }).collect();
+        PluginEvent::Filters { filters }
+    }
+
+    /// Compile the current rules as a new generation and hand it to the readers
+    fn publish_classifier(&mut self, levels: Arc<LevelRules>) {
+        let generation = self.classifier.generation + 1;
+        self.classifier = Arc::new(Classifier::new(generation, &self.filters, &self.alert_rules).with_levels(levels));
+        self.shared_classifier.set(Arc::clone(&self.classifier));
+        self.recount_shown();
+    }
+
+    /// Recount the lines each source shows after the filters changed
+    fn recount_shown(&mut self) {
+        let shown: Vec<usize> = self.sources.iter().map(|src| {
+            src.lines.iter().enumerate().filter(|&(i, l)| src.is_separator(i) || self.passes_filters(l)).count()
+        }).collect();
+        for (src, shown) in self.sources.iter_mut().zip(shown) { src.shown = shown; }
+    }
+
+    pub fn add_filter_from_input(&mut self) {
+        if self.filter_input.is_empty() { return; }
+        // A leading `!` hides what the rest matches
+        let (exclude, pattern) = match self.filter_input.strip_prefix('!') {
+            Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+            _ => (false, self.filter_input.clone()),
+        };
+        self.add_filter(FilterRule {
+            pattern,
+            is_regex: self.input_is_regex,
+            fuzzy: self.input_fuzzy,
+            case_insensitive: self.input_ignores_case(),
+            whole_word: self.input_whole_word,
+            whole_line: self.input_whole_line,
+            exclude,
+            enabled: true,
+            compiled: None,
+            match_count: 0,
+            group: None,
+        });
+        self.filter_history.push(&self.filter_input);
+        self.filter_input.clear();
+    }
+
+    /// Whether the filter being typed will ignore case: the i flag, unless smart case finds an
+    /// uppercase letter in it
+    pub fn input_ignores_case(&self) -> bool {
+        self.input_case_insensitive && !(self.smart_case && has_uppercase(&self.filter_input, self.input_is_regex && !self.input_fuzzy))
+    }
+
+    /// Append `rule`, undoably; an invalid pattern is kept (disabled in effect) and reported
+    pub fn add_filter(&mut self, mut rule: FilterRule) {
+        match rule.compile() {
+            Ok(re) => {
+                rule.compiled = Some(re);
+                self.notify(MessageLevel::Info, format!("{} added: {}", if rule.exclude { "exclude filter" } else { "filter" }, rule.pattern));
+            }
+            Err(e) => self.notify(MessageLevel::Error, format!("invalid filter pattern (ignored): {}", last_line(&e))),
+        }
+        self.filters.push(rule);
+        self.rebuild_classifier();
+        self.push_undo(UndoAction::Added { index: self.filters.len() - 1 });
+    }
+
+    /// Send events to `plugin`, whose output goes to source `source_id`; it starts with the
+    /// current filters
+    pub fn add_plugin(&mut self, source_id: usize, plugin: Plugin) {
+        self.mark_plugin_source(source_id);
+        plugin.send(&self.filters_event());
+        self.plugins.push((source_id, plugin));
+    }
+
+    fn mark_plugin_source(&mut self, source_id: usize) {
+        if let Some(src) = self.index_of(source_id).map(|i| &mut self.sources[i]) { src.from_plugin = true; }
+        self.regroup();
+    }
+
+    /// Carry out a command received on the control socket; Ok holds the fields of the answer
+    pub fn apply_control(&mut self, command: ControlCommand) -> Result<serde_json::Value, String> {
+        match command {
+            ControlCommand::Filter { pattern, regex } => {
+                let rule = FilterRule::simple(pattern, regex);
+                rule.compile().map_err(|e| format!("invalid filter pattern: {}", last_line(&e)))?;
+                self.add_filter(rule);
+            }
+            ControlCommand::Focus { source } => {
+                let idx = self.sources.iter().position(|s| s.names().contains(&source)).ok_or_else(|| format!("no source named {}", source))?;
+                self.set_focus(idx);
+            }
+            ControlCommand::Export { path, format } => {
+                let format = match format {
+                    Some(f) => ExportFormat::parse(&f).ok_or_else(|| format!("unknown export format {} (text, jsonl or csv)", f))?,
+                    None => self.export_format,
+                };
+                let prompt_format = std::mem::replace(&mut self.export_format, format);
+                let res = self.export_visible(&path);
+                self.export_format = prompt_format;
+                res?;
+            }
+            ControlCommand::Ack => self.acknowledge_alerts(),
+            ControlCommand::Status => {
+                let sources: Vec<serde_json::Value> = self.sources.iter().map(|s| serde_json::json!({
+                    "name": s.name, "lines": s.lines.len(), "shown": s.shown, "unread": s.unread, "error": s.error,
+                })).collect();
+                let filters: Vec<serde_json::Value> = self.filters.iter().map(|f| serde_json::json!({
+                    "pattern": f.pattern, "regex": f.is_regex, "fuzzy": f.fuzzy, "exclude": f.exclude, "enabled": f.enabled, "matches": f.match_count,
+                })).collect();
+                return Ok(serde_json::json!({
+                    "sources": sources,
+                    "focused": self.current_source().map(|s| &s.name),
+                    "filters": filters,
+                    "unacked_alerts": self.unacked_alerts(),
+                }));
+            }
+        }
+        Ok(serde_json::Value::Null)
+    }
+
+    /// Carry out an action requested by a plugin
+    pub fn apply_plugin_action(&mut self, action: PluginAction) {
+        match action {
+            PluginAction::Annotate { source, index, text } => {
+                let src = self.sources.iter_mut().find(|s| s.names().contains(&source));
+                if let Some(src) = src && src.line_index(LineId(index)).is_some() { src.annotations.insert(LineId(index), text); }
+            }
+            PluginAction::Filter { pattern, regex } => self.add_filter(FilterRule::simple(pattern, regex)),
+            // Turned into source lines by the plugin reader
+            PluginAction::Line { .. } => {}
+        }
+    }
+
+    pub fn filter_history_prev(&mut self) {
+        if let Some(entry) = self.filter_history.prev(&self.filter_input) { self.filter_input = entry; }
+    }
+    pub fn filter_history_next(&mut self) {
+        if let Some(entry) = self.filter_history.next() { self.filter_input = entry; }
+    }
+
+    pub fn remove_selected_filter(&mut self) {
+        if self.filters.is_empty() { return; }
+        if self.group_header_selected() { return self.notify(MessageLevel::Warn, "select a filter of the group to delete it"); }
+        if self.selected_filter >= self.filters.len() { self.selected_filter = self.filters.len()-1; }
+        let rule = self.filters.remove(self.selected_filter);
+        self.rebuild_classifier();
+        self.notify(MessageLevel::Info, format!("filter removed: {} (u to undo)", rule.pattern));
+        self.push_undo(UndoAction::Removed { index: self.selected_filter, rule });
+        if self.selected_filter >= self.filters.len() && !self.filters.is_empty() {
+            self.selected_filter = self.filters.len()-1;
+        }
+    }
+
+    pub fn toggle_selected_filter(&mut self) {
+        if self.group_header_selected() { return self.toggle_selected_group(); }
+        let Some(rule) = self.filters.get_mut(self.selected_filter) else { return; };
+        rule.enabled = !rule.enabled;
+        let msg = format!("filter {}: {}", if rule.enabled { "enabled" } else { "disabled" }, rule.pattern);
+        self.rebuild_classifier();
+        self.notify(MessageLevel::Info, msg);
+        self.push_undo(UndoAction::Toggled { index: self.selected_filter });
+    }
+
+    /// The rows of the filter list: the filters without a group, then each group's header
+    /// followed by its filters unless it is collapsed
+    pub fn filter_rows(&self) -> Vec<FilterRow> {
+        let mut rows: Vec<FilterRow> = (0..self.filters.len()).filter(|&i| self.filters[i].group.is_none()).map(FilterRow::Filter).collect();
+        let mut groups: Vec<&str> = Vec::new();
+        for name in self.filters.iter().filter_map(|f| f.group.as_deref()) {
+            if !groups.contains(&name) { groups.push(name); }
+        }
+        for name in groups {
+            let members: Vec<usize> = (0..self.filters.len()).filter(|&i| self.filters[i].group.as_deref() == Some(name)).collect();
+            let collapsed = self.collapsed_filter_groups.contains(name);
+            rows.push(FilterRow::Group { name: name.to_string(), members: members.clone(), collapsed });
+            if !collapsed { rows.extend(members.into_iter().map(FilterRow::Filter)); }
+        }
+        rows
+    }
+
+    /// Whether the selection is on the header of the selected filter's group; a filter in a
+    /// collapsed group is selected through its header
+    fn group_header_selected(&self) -> bool {
+        let group = self.filters.get(self.selected_filter).and_then(|f| f.group.as_ref());
+        group.is_some_and(|g| self.filter_header || self.collapsed_filter_groups.contains(g))
+    }
+
+    /// Position of the selection in `filter_rows`
+    pub fn selected_filter_row(&self, rows: &[FilterRow]) -> Option<usize> {
+        let group = self.filters.get(self.selected_filter)?.group.as_deref();
+        let header = self.group_header_selected();
+        rows.iter().position(|row| match row {
+            FilterRow::Filter(i) => !header && *i == self.selected_filter,
+            FilterRow::Group { name, .. } => header && Some(name.as_str()) == group,
+        })
+    }
+
+    /// Move the selection in the filter list by `delta` rows
+    fn move_filter_selection(&mut self, delta: isize) {
+        let rows = self.filter_rows();
+        let Some(pos) = self.selected_filter_row(&rows) else { return; };
+        let pos = pos.saturating_add_signed(delta).min(rows.len().saturating_sub(1));
+        match &rows[pos] {
+            FilterRow::Filter(i) => (self.selected_filter, self.filter_header) = (*i, false),
+            FilterRow::Group { members, .. } => (self.selected_filter, self.filter_header) = (members[0], true),
+        }
+    }
+
+    /// Enable every filter of the selected group, or disable them all when some are enabled
+    pub fn toggle_selected_group(&mut self) {
+        let Some(group) = self.filters.get(self.selected_filter).and_then(|f| f.group.clone()) else { return; };
+        let members = || self.filters.iter().enumerate().filter(|(_, f)| f.group.as_ref() == Some(&group));
+        let enable = !members().any(|(_, f)| f.enabled);
+        let indices: Vec<usize> = members().filter(|(_, f)| f.enabled != enable).map(|(i, _)| i).collect();
+        for &i in &indices { self.filters[i].enabled = enable; }
+        self.rebuild_classifier();
+        self.notify(MessageLevel::Info, format!("group {} {} ({} filters)", group, if enable { "enabled" } else { "disabled" }, indices.len()));
+        self.push_undo(UndoAction::GroupToggled { group, indices });
+    }
+
+    /// Collapse the selected filter's group to its header, or expand it
+    pub fn toggle_filter_group_collapsed(&mut self) {
+        let Some(group) = self.filters.get(self.selected_filter).and_then(|f| f.group.clone()) else { return; };
+        if !self.collapsed_filter_groups.remove(&group) {
+            self.collapsed_filter_groups.insert(group);
+            self.filter_header = true;
+        }
+    }
+
+    /// Ask for the group of the selected filter
+    pub fn open_filter_group_prompt(&mut self) {
+        let Some(rule) = self.filters.get(self.selected_filter).filter(|_| !self.group_header_selected()) else {
+            return self.notify(MessageLevel::Warn, "select a filter to group it");
+        };
+        let group = rule.group.clone().unwrap_or_default();
+        self.open_prompt(PromptKind::FilterGroup);
+        if let Some(prompt) = self.prompt.as_mut() { prompt.input = group; }
+    }
+
+    /// Move the selected filter to the group named `name` (out of its group when empty)
+    fn set_filter_group(&mut self, name: &str) -> Result<(), String> {
+        let rule = self.filters.get_mut(self.selected_filter).ok_or("no filter selected")?;
+        let name = name.trim();
+        rule.group = (!name.is_empty()).then(|| name.to_string());
+        let msg = match &rule.group {
+            Some(group) => format!("filter {} in group {} (Space on the header switches the group)", rule.pattern, group),
+            None => format!("filter {} ungrouped", rule.pattern),
+        };
+        if let Some(group) = &rule.group { self.collapsed_filter_groups.remove(group); }
+        self.notify(MessageLevel::Info, msg);
+        Ok(())
+    }
+
+    /// Add the filter bound to Alt+`key`, or disable or re-enable it once added
+    pub fn toggle_hotkey(&mut self, key: u8) {
+        let Some(rule) = self.hotkeys.get(key).cloned() else {
+            return self.notify(MessageLevel::Warn, format!("nothing bound to Alt+{} (b saves the applied search to a key)", key));
+        };
+        match self.filters.iter().position(|f| same_filter(f, &rule)) {
+            Some(index) => {
+                (self.selected_filter, self.filter_header) = (index, false);
+                self.toggle_selected_filter();
+            }
+            None => self.add_filter(rule),
+        }
+    }
+
+    /// The applied search as a filter, to bind to a hotkey
+    pub fn search_as_filter(&self) -> Option<FilterRule> {
+        self.search_compiled.as_ref()?;
+        let mut rule = FilterRule::simple(self.search_input.clone(), self.search_is_regex);
+        rule.case_insensitive = self.search_ignores_case();
+        Some(rule)
+    }
+
+    /// Ask for the key to bind the applied search to
+    pub fn open_hotkey_prompt(&mut self) {
+        if self.search_as_filter().is_none() { return self.notify(MessageLevel::Warn, "apply a search (?) first: b binds it to Alt and a digit"); }
+        self.open_prompt(PromptKind::Hotkey);
+    }
+
+    /// Bind the applied search to the key typed in the hotkey prompt
+    fn save_hotkey(&mut self, input: &str) -> Result<(), String> {
+        let key = input.trim().parse().ok().filter(|k| HOTKEYS.contains(k)).ok_or("enter a digit from 1 to 9")?;
+        let rule = self.search_as_filter().ok_or("no search applied")?;
+        match self.hotkeys.save(key, &rule) {
+            Ok(()) => self.notify(MessageLevel::Info, format!("Alt+{} now filters for {}", key, rule.pattern)),
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => self.notify(MessageLevel::Error, format!("Alt+{} not bound: {}", key, e)),
+            Err(e) => self.notify(MessageLevel::Error, format!("Alt+{} filters for {} until rtlog quits: {}", key, rule.pattern, e)),
+        }
+        Ok(())
+    }
+
+    fn push_undo(&mut self, action: UndoAction) {
+        if self.undo_stack.len() == UNDO_LIMIT { self.undo_stack.remove(0); }
+        self.undo_stack.push(action);
+    }
+
+    /// Drop the focused source's lines and reset its counters; the reader keeps appending.
+    /// Filter match counts no longer include the dropped lines. Undoable.
+    pub fn clear_focused_source(&mut self) {
+        let Some(src) = self.sources.get_mut(self.focused) else { return; };
+        // Lines received from now on keep numbering after the dropped ones
+        src.first_id = src.line_id(src.lines.len());
+        let lines = std::mem::take(&mut src.lines);
+        let levels = std::mem::take(&mut src.levels);
+        let fields = std::mem::take(&mut src.fields);
+        let annotations = std::mem::take(&mut src.annotations);
+        src.shown = 0;
+        src.scroll_offset = 0;
+        src.selected_log = None;
+        src.unread = 0;
+        (src.rate_cur, src.rate_prev) = (0, 0);
+        let (source_id, name) = (src.id, src.name.clone());
+        self.adjust_match_counts(&lines, false);
+        // Correlation hits hold line indices, which are gone now
+        if self.correlation.as_ref().is_some_and(|c| c.hits.iter().any(|&(si, _)| si == self.focused)) { self.correlation = None; }
+        self.notify(MessageLevel::Info, format!("cleared {} ({} lines, u to undo)", name, lines.len()));
+        self.push_undo(UndoAction::Cleared { source_id, lines, levels, fields, annotations });
+    }
+
+    /// Add (or remove) the filter hits of `lines` to the per-filter match counts
+    fn adjust_match_counts(&mut self, lines: &[Arc<str>], add: bool) {
+        let mut counts = vec![0usize; self.filters.len()];
+        for line in lines {
+            for i in self.classifier.filters.matching_rules(line) { counts[i] += 1; }
+        }
+        for (rule, n) in self.filters.iter_mut().zip(counts) {
+            rule.match_count = if add { rule.match_count.saturating_add(n) } else { rule.match_count.saturating_sub(n) };
+        }
+    }
+
+    /// Revert the most recent filter change (add, delete, toggle) or buffer clear
+    pub fn undo(&mut self) {
+        let Some(action) = self.undo_stack.pop() else {
+            self.notify(MessageLevel::Warn, "nothing to undo");
+            return;
+        };
+        let msg = match action {
+            UndoAction::Added { index } => {
+                if index >= self.filters.len() { return; }
+                let rule = self.filters.remove(index);
+                self.selected_filter = self.selected_filter.min(self.filters.len().saturating_sub(1));
+                format!("undo: removed filter {}", rule.pattern)
+            }
+            UndoAction::Removed { index, rule } => {
+                let index = index.min(self.filters.len());
+                let msg = format!("undo: restored filter {}", rule.pattern);
+                self.filters.insert(index, rule);
+                self.selected_filter = index;
+                msg
+            }
+            UndoAction::Toggled { index } => {
+                let Some(rule) = self.filters.get_mut(index) else { return; };
+                rule.enabled = !rule.enabled;
+                (self.selected_filter, self.filter_header) = (index, false);
+                format!("undo: filter {}: {}", if rule.enabled { "enabled" } else { "disabled" }, rule.pattern)
+            }
+            UndoAction::GroupToggled { group, indices } => {
+                for (i, rule) in self.filters.iter_mut().enumerate() {
+                    if indices.contains(&i) { rule.enabled = !rule.enabled; }
+                }
+                format!("undo: group {} switched back", group)
+            }
+            UndoAction::Cleared { source_id, lines, levels, fields, mut annotations } => {
+                let Some(idx) = self.index_of(source_id) else {
+                    self.notify(MessageLevel::Warn, "undo: the cleared source was closed");
+                    return;
+                };
+                self.adjust_match_counts(&lines, true);
+                let first = self.sources[idx].first_id.0 - lines.len();
+                let separators = &self.sources[idx].separators;
+                let shown = lines.iter().enumerate().filter(|&(i, l)| separators.contains(&LineId(first + i)) || self.passes_filters(l)).count();
+                let src = &mut self.sources[idx];
+                src.shown += shown;
+                // Lines received since the clear come after the restored ones
+                let restored = lines.len();
+                let newer = std::mem::replace(&mut src.lines, lines);
+                src.lines.extend(newer);
+                src.first_id = LineId(src.first_id.0 - restored);
+                annotations.append(&mut src.annotations);
+                src.annotations = annotations;
+                src.levels.add(&levels);
+                for (name, stats) in &fields { src.fields.entry(Arc::clone(name)).or_default().add(stats); }
+                self.correlation = None;
+                // Only the restored match counts changed; the rules did not
+                self.notify(MessageLevel::Info, format!("undo: restored {} lines of {}", self.sources[idx].lines.len(), self.sources[idx].name));
+                return;
+            }
+        };
+        self.rebuild_classifier();
+        self.notify(MessageLevel::Info, msg);
+    }
+
+    pub fn move_selection_up(&mut self) {
+        self.move_filter_selection(-1);
+    }
+    pub fn move_selection_down(&mut self) {
+        self.move_filter_selection(1);
+    }
+
+    /// Select the bottom line of the log panel when nothing is selected. The selection is a line
+    /// index, so it stays on its record while new lines arrive.
+    pub fn ensure_log_selection(&mut self) {
+        let Some(src) = self.current_source() else { return; };
+        if src.selected_index().is_some() { return; }
+        // scroll_offset counts the lines the panel shows, not buffer lines
+        let sel = self.shown_lines_rev(src).map(|(i, _)| i).nth(src.scroll_offset).map(|i| src.line_id(i));
+        if let Some(src) = self.current_source_mut() { src.selected_log = sel; }
+    }
+
+    pub fn move_log_selection_up(&mut self) {
+        self.move_log_selection_by(-1);
+    }
+    pub fn move_log_selection_down(&mut self) {
+        self.move_log_selection_by(1);
+    }
+
+    /// Move the selection `delta` shown lines down (up when negative), stopping at either end
+    pub fn move_log_selection_by(&mut self, delta: isize) {
+        self.ensure_log_selection();
+        self.context_scroll = 0;
+        let Some(src) = self.current_source() else { return; };
+        let Some(sel) = src.selected_index() else { return; };
+        // Step over the lines the filters hide, which would take the highlight with them
+        let shown = self.shown_lines_rev(src).map(|(i, _)| i);
+        let target = if delta < 0 {
+            shown.filter(|&i| i < sel).take(delta.unsigned_abs()).last()
+        } else {
+            let below: Vec<usize> = shown.take_while(|&i| i > sel).collect();
+            below.len().checked_sub(delta as usize).map_or(below.first(), |k| below.get(k)).copied()
+        };
+        let target = target.map(|i| src.line_id(i));
+        if let Some(src) = self.current_source_mut() && target.is_some() { src.selected_log = target; }
+    }
+
+    /// Start a range selection at the selected line (`V`), or drop the current one
+    pub fn toggle_range(&mut self) {
+        self.ensure_log_selection();
+        self.range_hidden = false;
+        if let Some(src) = self.current_source_mut() {
+            src.range_anchor = if src.range_anchor.is_some() { None } else { src.selected_log };
+        }
+    }
+
+    /// Take the lines the filters hide between the ends of the range selection, or leave them
+    /// out again
+    pub fn toggle_range_hidden(&mut self) {
+        if self.current_source().is_some_and(|s| s.range_anchor.is_some()) { self.range_hidden = !self.range_hidden; }
+    }
+
+    /// First and last index in `src.lines` of its range selection
+    pub fn range_bounds(&self, src: &Source) -> Option<(usize, usize)> {
+        // An anchor cleared away since leaves the range starting at the oldest line
+        let anchor = src.line_index(src.range_anchor?).unwrap_or(0);
+        let sel = src.selected_index()?;
+        Some((anchor.min(sel), anchor.max(sel)))
+    }
+
+    /// Indices of the lines in `src`'s range selection, oldest first: the ones the log panel
+    /// shows between its ends, or all of them with `range_hidden`. None without a range.
+    pub fn range_lines(&self, src: &Source) -> Option<Vec<usize>> {
+        let (first, last) = self.range_bounds(src)?;
+        if self.range_hidden { return Some((first..=last).collect()); }
+        let mut lines: Vec<usize> = self.shown_lines_rev(src).map(|(i, _)| i)
+            .skip_while(|&i| i > last)
+            .take_while(|&i| i >= first)
+            .collect();
+        lines.reverse();
+        Some(lines)
+    }
+
+    /// Put the range selection, or else the selected line, on the clipboard as displayed
+    /// (redacted unless revealed) and end the range selection
+    pub fn copy_selection(&mut self) {
+        self.ensure_log_selection();
+        let Some(src) = self.current_source() else { return; };
+        let lines = self.range_lines(src).unwrap_or_else(|| src.selected_index().into_iter().collect());
+        if lines.is_empty() { return self.notify(MessageLevel::Warn, "nothing to copy"); }
+        let text: Vec<Cow<str>> = lines.iter().map(|&i| self.display_text(&src.lines[i])).collect();
+        self.clipboard = Some(text.join("\n"));
+        self.notify(MessageLevel::Info, format!("copied {} lines to the clipboard", lines.len()));
+        if let Some(src) = self.current_source_mut() { src.range_anchor = None; }
+    }
+
+    /// Add a digit to the pending count
+    pub fn push_count_digit(&mut self, digit: u32) {
+        self.pending_count = Some(self.pending_count.unwrap_or(0).saturating_mul(10).saturating_add(digit as usize));
+    }
+
+    /// Scroll the context panel by `delta` lines without moving the selection
+    pub fn scroll_context(&mut self, delta: isize) {
+        let total = self.current_source().map_or(0, |s| s.lines.len()) as isize;
+        self.context_scroll = (self.context_scroll + delta).clamp(-total, total);
+    }
+
+    /// Lines to scroll for `halves` half pages of a log panel `height` lines tall
+    pub fn page_lines(&self, height: usize, halves: usize) -> usize {
+        ((height * halves) as f64 / 2.0 * self.page_step).round().max(1.0) as usize
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        if let Some(src) = self.current_source_mut() {
+            src.auto_scroll = false;
+            let max_offset = src.lines.len().saturating_sub(1);
+            src.scroll_offset = (src.scroll_offset + n).min(max_offset);
+        }
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        if let Some(src) = self.current_source_mut() {
+            if src.scroll_offset == 0 { return; }
+            src.scroll_offset = src.scroll_offset.saturating_sub(n);
+            if src.scroll_offset == 0 {
+                src.auto_scroll = true;
+            }
+        }
+    }
+
+    pub fn scroll_top(&mut self) {
+        if let Some(src) = self.current_source_mut() {
+            src.auto_scroll = false;
+            src.scroll_offset = src.lines.len().saturating_sub(1);
+        }
+    }
+
+    pub fn scroll_bottom(&mut self) {
+        if let Some(src) = self.current_source_mut() {
+            src.scroll_offset = 0;
+            src.auto_scroll = true;
+        }
+    }
+
+    /// Switch between wrapping long lines and cutting them off (scrollable sideways)
+    pub fn toggle_wrap(&mut self) {
+        self.no_wrap = !self.no_wrap;
+        self.h_scroll = 0;
+        self.notify(MessageLevel::Info, if self.no_wrap { "line wrap off (←/→ scroll)" } else { "line wrap on" });
+    }
+
+    /// Switch between the full layout and only the log lines with the status bar
+    pub fn toggle_compact(&mut self) {
+        self.compact = !self.compact;
+        self.notify(MessageLevel::Info, if self.compact { "compact mode on (Z)" } else { "compact mode off" });
+    }
+
+    /// Switch between showing tabs and control characters as symbols and leaving them out
+    pub fn toggle_controls(&mut self) {
+        self.show_controls = !self.show_controls;
+        self.notify(MessageLevel::Info, if self.show_controls { "tabs and control characters shown (T)" } else { "tabs and control characters hidden" });
+    }
+
+    /// Scroll unwrapped lines sideways by `delta` columns
+    pub fn scroll_horizontal(&mut self, delta: i16) {
+        if self.no_wrap { self.h_scroll = self.h_scroll.saturating_add_signed(delta); }
+    }
+
+    /// Whether the log panel shows the line `text`: it passes the filters, or all lines are
+    /// revealed. Separators are shown either way
+    pub fn is_shown(&self, text: &str) -> bool {
+        self.show_all || self.passes_filters(text)
+    }
+
+    fn passes_filters(&self, text: &str) -> bool {
+        line_matches(text, self.filter_match_set()) && self.previews(text) && self.passes_field_filters(text)
+    }
+
+    /// Whether the fields of `text` meet every field filter; those on a field of no enabled
+    /// filter are left out until it is enabled again
+    fn passes_field_filters(&self, text: &str) -> bool {
+        self.field_filters.iter().all(|f| !self.classifier.has_field(&f.field) || f.matches(self.classifier.field_value(text, &f.field)))
+    }
+
+    /// Add the field filter `input` (`ms > 500`); an empty one removes them all
+    fn add_field_filter(&mut self, input: &str) -> Result<(), String> {
+        if input.trim().is_empty() {
+            if !self.field_filters.is_empty() {
+                self.field_filters.clear();
+                self.recount_shown();
+                self.notify(MessageLevel::Info, "field filters removed");
+            }
+            return Ok(());
+        }
+        let filter = FieldFilter::parse(input).map_err(|e| e.to_string())?;
+        let names = self.classifier.field_names();
+        if !names.iter().any(|n| **n == filter.field) {
+            let known: Vec<&str> = names.iter().map(|n| &**n).collect();
+            return Err(if known.is_empty() {
+                "no fields: add a regex filter with named groups, e.g. (?P<ms>\\d+)ms".to_string()
+            } else {
+                format!("no field {} (fields: {})", filter.field, known.join(", "))
+            });
+        }
+        self.notify(MessageLevel::Info, format!("field filter {} (= with nothing removes the field filters)", filter));
+        self.field_filters.push(filter);
+        self.recount_shown();
+        Ok(())
+    }
+
+    /// Switch the fields' columns before the lines on or off
+    pub fn toggle_column_view(&mut self) {
+        self.column_view = !self.column_view;
+        let msg = match (self.column_view, self.classifier.field_names().is_empty()) {
+            (false, _) => "column view off",
+            (true, false) => "column view on (t)",
+            (true, true) => "column view on (t), no fields yet: add a regex filter with named groups",
+        };
+        self.notify(MessageLevel::Info, msg);
+    }
+
+    /// Whether the substitution preview keeps `text`: it matches the search, or there is no
+    /// preview
+    fn previews(&self, text: &str) -> bool {
+        self.replace_preview.is_none() || self.search_compiled.as_ref().is_none_or(|re| re.is_match(text))
+    }
+
+    /// `text` with the search matches replaced while previewing a substitution
+    pub fn replaced<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match (&self.replace_preview, &self.search_compiled) {
+            (Some(replacement), Some(re)) => re.replace_all(text, replacement.as_str()),
+            _ => Cow::Borrowed(text),
+        }
+    }
+
+    /// Ask for a replacement of the applied search's matches to preview, or end the preview
+    pub fn toggle_replace_preview(&mut self) {
+        if self.replace_preview.take().is_some() {
+            self.notify(MessageLevel::Info, "replace preview off");
+        } else if self.search_compiled.is_none() {
+            self.notify(MessageLevel::Warn, "apply a search (?) first: s previews replacing its matches");
+        } else {
+            self.open_prompt(PromptKind::Replace);
+        }
+    }
+
+    /// Preview replacing the search matches with `replacement` (`$1`, `${name}` refer to groups)
+    fn preview_replacement(&mut self, replacement: &str) -> Result<(), String> {
+        let re = self.search_compiled.as_ref().ok_or("no search applied")?;
+        let known = |group: &str| group.parse::<usize>().map_or_else(|_| re.capture_names().flatten().any(|n| n == group), |i| i < re.captures_len());
+        if let Some(unknown) = group_refs(replacement).into_iter().find(|g| !known(g)) {
+            return Err(format!("no group {} in the search", unknown));
+        }
+        self.replace_preview = Some(replacement.to_string());
+        self.notify(MessageLevel::Info, "previewing the replacement: matching lines only, exports write them replaced (s: stop)");
+        Ok(())
+    }
+
+    /// Indices of `src`'s lines the log panel shows, newest first, each with whether it is only
+    /// context: the lines passing the filters and the `filter_context` lines before and after
+    /// each filter match
+    pub fn shown_lines_rev<'a>(&'a self, src: &'a Source) -> impl Iterator<Item = (usize, bool)> + 'a {
+        let radius = if self.show_all { 0 } else { self.filter_context };
+        let filters = self.filter_match_set();
+        let matches = move |i: usize| self.show_all || (line_matches(&src.lines[i], filters) && self.previews(&src.lines[i]) && self.passes_field_filters(&src.lines[i]));
+        let mut i = src.lines.len();
+        // Whether the current line and the `radius` older ones match, the current one first
+        let mut window: VecDeque<bool> = (0..=radius).map_while(|k| i.checked_sub(k + 1)).map(matches).collect();
+        let mut hits = window.iter().filter(|&&m| m).count();
+        let mut newer_match: Option<usize> = None;
+        std::iter::from_fn(move || loop {
+            i = i.checked_sub(1)?;
+            let own = window.pop_front().unwrap_or(false);
+            if own { hits -= 1; }
+            let near = hits > 0 || newer_match.is_some_and(|j| j - i <= radius);
+            if own { newer_match = Some(i); }
+            if let Some(older) = i.checked_sub(radius + 1) {
+                let m = matches(older);
+                if m { hits += 1; }
+                window.push_back(m);
+            }
+            if own || src.is_separator(i) { return Some((i, false)); }
+            if near { return Some((i, true)); }
+        })
+    }
+
+    /// Indices of the lines the log panel shows, oldest first
+    pub fn shown_lines(&self, src: &Source) -> Vec<usize> {
+        let mut shown: Vec<usize> = self.shown_lines_rev(src).map(|(i, _)| i).collect();
+        shown.reverse();
+        shown
+    }
+
+    /// Change the number of context lines around filter matches by `delta`
+    pub fn adjust_filter_context(&mut self, delta: isize) {
+        self.filter_context = self.filter_context.saturating_add_signed(delta).min(MAX_FILTER_CONTEXT);
+        self.notify(MessageLevel::Info, format!("filter context: {} line(s) around matches (+/-)", self.filter_context));
+    }
+
+    /// Reveal the lines the filters hide, keeping the matches highlighted, or hide them again
+    pub fn toggle_show_all(&mut self) {
+        self.show_all = !self.show_all;
+        self.notify(MessageLevel::Info, if self.show_all { "showing all lines, filter matches highlighted (v)" } else { "filters hide non-matching lines again" });
+    }
+
+    pub fn toggle_auto_scroll(&mut self) {
+        if let Some(src) = self.current_source_mut() {
+            if src.auto_scroll {
+                src.auto_scroll = false;
+            } else {
+                src.scroll_offset = 0;
+                src.auto_scroll = true;
+            }
+        }
+    }
+
+    /// Pause every source if any is following, otherwise make them all follow again
+    pub fn toggle_auto_scroll_all(&mut self) {
+        let follow = !self.sources.iter().any(|s| s.auto_scroll);
+        for src in &mut self.sources {
+            src.auto_scroll = follow;
+            if follow { src.scroll_offset = 0; }
+        }
+        self.notify(MessageLevel::Info, if follow { "all sources following" } else { "all sources paused" });
+    }
+
+    /// Switch focus-follow on or off
+    pub fn toggle_focus_follow(&mut self) {
+        self.focus_follow_on = !self.focus_follow_on;
+        let msg = match (self.focus_follow_on, &self.focus_follow) {
+            (false, _) => "focus-follow off".to_string(),
+            (true, FocusFollow::Alerts) => "focus-follow: alerts".to_string(),
+            (true, FocusFollow::Pattern(re)) => format!("focus-follow: /{}/", re.as_str()),
+        };
+        self.notify(MessageLevel::Info, msg);
+    }
+
+    /// Sources in sidebar order (grouped)
+    pub fn sidebar_order(&self) -> Vec<usize> {
+        if self.groups.is_empty() { return self.sorted_sources((0..self.sources.len()).collect()); }
+        (0..self.groups.len()).flat_map(|gi| self.group_members(gi)).collect()
+    }
+
+    /// Members of the group `gi` in the sidebar's sort order
+    pub fn group_members(&self, gi: usize) -> Vec<usize> {
+        self.groups.get(gi).map_or_else(Vec::new, |g| self.sorted_sources(g.members.clone()))
+    }
+
+    fn sorted_sources(&self, mut members: Vec<usize>) -> Vec<usize> {
+        let sources = &self.sources;
+        match self.sidebar.sort {
+            SourceSort::Input => {}
+            SourceSort::Name => members.sort_by_cached_key(|&i| sources[i].name.to_lowercase()),
+            SourceSort::Activity => members.sort_by_key(|&i| std::cmp::Reverse(sources[i].last_active)),
+            SourceSort::Errors => {
+                let share = |i: usize| sources[i].levels.error as f64 / sources[i].lines.len().max(1) as f64;
+                members.sort_by(|&a, &b| share(b).total_cmp(&share(a)));
+            }
+        }
+        members
+    }
+
+    /// Sort the sidebar by the next order (input, name, activity, errors)
+    pub fn cycle_source_sort(&mut self) {
+        let sort = self.sidebar.cycle_sort();
+        self.notify(MessageLevel::Info, format!("sources sorted by {}", sort.name()));
+    }
+
+    /// Pin the focused source to the top of the sidebar, or unpin it
+    pub fn toggle_pin_source(&mut self) {
+        let Some(name) = self.current_source().map(|s| s.name.clone()) else { return; };
+        let pinned = self.sidebar.toggle_pin(&name);
+        self.regroup();
+        self.notify(MessageLevel::Info, format!("{} {}", if pinned { "pinned" } else { "unpinned" }, name));
+    }
+
+    /// Move focus to the next/previous source in sidebar order, skipping collapsed groups
+    fn step_source(&mut self, forward: bool) {
+        let order = self.sidebar_order();
+        let Some(pos) = order.iter().position(|&i| i == self.focused) else { return; };
+        let n = order.len();
+        for step in 1..n {
+            let idx = if forward { (pos + step) % n } else { (pos + n - step) % n };
+            let cand = order[idx];
+            let collapsed = self.groups.get(self.sources[cand].group).map(|g| g.collapsed).unwrap_or(false);
+            if !collapsed { self.set_focus(cand); return; }
+        }
+    }
+
+    pub fn focus_next_source(&mut self) {
+        if self.sources.is_empty() { return; }
+        self.step_source(true);
+    }
+    pub fn focus_prev_source(&mut self) {
+        if self.sources.is_empty() { return; }
+        self.step_source(false);
+    }
+
+    /// Focus the first source of the next/previous group
+    pub fn focus_group(&mut self, forward: bool) {
+        let Some(src) = self.current_source() else { return; };
+        let n = self.groups.len();
+        if n == 0 { return; }
+        let gi = if forward { (src.group + 1) % n } else { (src.group + n - 1) % n };
+        if let Some(&first) = self.group_members(gi).first() { self.set_focus(first); }
+    }
+
+    pub fn toggle_group_collapsed(&mut self) {
+        let Some(gi) = self.current_source().map(|s| s.group) else { return; };
+        if let Some(g) = self.groups.get_mut(gi) { g.collapsed = !g.collapsed; }
+    }
+}
+
+impl AppState {
+    /// Highest severity of the alert rules matching `text`
+    pub fn alert_severity(&self, text: &str) -> Option<AlertSeverity> { self.classifier.alert_severity(text) }
+
+    /// Names of the fields of the enabled filters' named groups
+    pub fn field_names(&self) -> Vec<Arc<str>> { self.classifier.field_names() }
+
+    /// Text of field `name` in `text`
+    pub fn field_value<'a>(&self, text: &'a str, name: &str) -> Option<&'a str> { self.classifier.field_value(text, name) }
+
+    /// Lines per level over all sources
+    pub fn level_counts(&self) -> LevelCounts {
+        let mut total = LevelCounts::default();
+        for src in &self.sources { total.add(&src.levels); }
+        total
+    }
+
+    /// Alert banner, blinking or a status message still on screen, so the view changes without
+    /// new events
+    pub fn animating(&self) -> bool {
+        let message = self.message.as_ref().map_or(0, |m| m.deadline_ms);
+        current_epoch_millis() < self.alert_deadline_ms.max(self.alert_blink_deadline_ms).max(message)
+    }
+
+    /// Show `text` in the status bar for a while (longer for warnings and errors)
+    pub fn notify(&mut self, level: MessageLevel, text: impl Into<String>) {
+        let deadline_ms = current_epoch_millis() + level.ttl_ms();
+        self.message = Some(StatusMessage { level, text: text.into(), deadline_ms });
+    }
+
+    /// `text` as shown on screen: redacted unless revealed
+    pub fn display_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.revealed { Cow::Borrowed(text) } else { self.redactor.redact(text) }
+    }
+
+    /// A log line as shown on screen: the search matches replaced while previewing a
+    /// substitution, its timestamp in the display zone, rewritten unless
+    /// rewrites are off, then redacted unless revealed
+    pub fn display_log_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = self.replaced(text);
+        if let Some(zone) = self.display_zone && let Cow::Owned(t) = convert_timestamp(&text, zone) { text = Cow::Owned(t); }
+        if !self.rewrites_off && let Cow::Owned(t) = self.rewriter.rewrite(&text) { text = Cow::Owned(t); }
+        match text {
+            Cow::Borrowed(text) => self.display_text(text),
+            Cow::Owned(text) => Cow::Owned(self.display_text(&text).into_owned()),
+        }
+    }
+
+    /// Switch between rewritten and received log lines on screen
+    pub fn toggle_rewrites(&mut self) {
+        if self.rewriter.is_empty() {
+            self.notify(MessageLevel::Info, "No rewrite rules ([[rewrite]] in the config file)");
+            return;
+        }
+        self.rewrites_off = !self.rewrites_off;
+        self.notify(MessageLevel::Info, if self.rewrites_off { "Showing lines as received (D to rewrite)" } else { "Lines rewritten for display" });
+    }
+
+    /// Switch between redacted and original text on screen
+    pub fn toggle_reveal(&mut self) {
+        if self.redactor.is_empty() {
+            self.notify(MessageLevel::Info, "No redaction rules (--redact or [redact] in the config file)");
+            return;
+        }
+        self.revealed = !self.revealed;
+        if self.revealed {
+            self.notify(MessageLevel::Warn, "Showing unredacted text (R to hide)");
+        } else {
+            self.notify(MessageLevel::Info, "Sensitive text redacted");
+        }
+    }
+
+    /// The status message, unless it has expired
+    pub fn current_message(&self) -> Option<&StatusMessage> {
+        self.message.as_ref().filter(|m| current_epoch_millis() < m.deadline_ms)
+    }
+
+    /// Show the alert banner with `msg`: for 3 seconds, or until acknowledged for a critical alert.
+    /// An unacknowledged critical alert is only replaced by another critical one.
+    pub fn raise_alert(&mut self, severity: AlertSeverity, msg: String) {
+        self.raise_rule_alert(severity, None, None, msg);
+        self.send_alert_to_plugins();
+    }
+
+    /// Tell the plugins about the newest alert in the history
+    fn send_alert_to_plugins(&self) {
+        let Some(entry) = self.alert_history.back() else { return; };
+        let event = PluginEvent::Alert { severity: entry.severity.name(), source: entry.source.as_deref(), message: &entry.message };
+        for (_, plugin) in &self.plugins { plugin.send(&event); }
+    }
+
+    fn raise_rule_alert(&mut self, severity: AlertSeverity, rule: Option<usize>, source: Option<String>, mut msg: String) {
+        // Keep a short message extract for display
+        if msg.len() > 120 {
+            let mut cut = 120;
+            while !msg.is_char_boundary(cut) { cut -= 1; }
+            msg.truncate(cut);
+        }
+        let entry = AlertEntry { time: chrono::Local::now(), severity, rule, source, message: msg.clone(), acknowledged: false };
+        if let Some(email) = &self.email { email.send(&entry); }
+        if self.alert_history.len() == ALERT_HISTORY { self.alert_history.pop_front(); }
+        self.alert_history.push_back(entry);
+        if self.alert_unacked > 0 && !severity.sticky() { return; }
+        let now = current_epoch_millis();
+        self.alert_deadline_ms = now + 3000; // 3 seconds banner visibility
+        self.alert_blink_deadline_ms = now + severity.blink_ms();
+        self.alert_severity = severity;
+        if severity.sticky() { self.alert_unacked += 1; }
+        if severity.sound() { self.bell = true; }
+        self.alert_message = Some(msg);
+    }
+
+    /// Raise the alert `text` matches, if any rule is not muted; returns whether one was raised
+    fn alert_for_line(&mut self, source_id: usize, text: &str) -> bool {
+        let now = current_epoch_millis();
+        let mut best: Option<(usize, AlertSeverity)> = None;
+        let mut muted = Vec::new();
+        for (i, a) in self.alert_rules.iter().enumerate() {
+            if !a.rule.compiled.as_ref().is_some_and(|re| re.is_match(text)) { continue; }
+            if self.alert_mutes.get(&i).is_some_and(|m| now < m.until_ms) { muted.push(i); continue; }
+            if best.is_none_or(|(_, s)| a.severity > s) { best = Some((i, a.severity)); }
+        }
+        match best {
+            Some((i, severity)) => {
+                let source = self.index_of(source_id).map(|idx| &self.sources[idx]);
+                // Alerts on plugin output are not sent back to plugins, which could loop
+                let to_plugins = !source.is_some_and(|s| s.from_plugin);
+                let source = source.map(|s| s.name.clone());
+                self.raise_rule_alert(severity, Some(i), source, text.trim().to_string());
+                if to_plugins { self.send_alert_to_plugins(); }
+                true
+            }
+            None => {
+                for i in muted {
+                    if let Some(m) = self.alert_mutes.get_mut(&i) { m.suppressed += 1; }
+                }
+                false
+            }
+        }
+    }
+
+    /// Replace the alert rules (e.g. after the config file changed). History entries and mutes
+    /// follow their rule to its new position when the same pattern is still there, and lose it
+    /// otherwise.
+    pub fn set_alert_rules(&mut self, rules: Vec<AlertRule>) {
+        let moved: Vec<Option<usize>> = self.alert_rules.iter().map(|old| {
+            rules.iter().position(|new| new.rule.pattern == old.rule.pattern && new.rule.is_regex == old.rule.is_regex)
+        }).collect();
+        for entry in &mut self.alert_history {
+            entry.rule = entry.rule.and_then(|i| moved.get(i).copied().flatten());
+        }
+        self.alert_mutes = std::mem::take(&mut self.alert_mutes).into_iter()
+            .filter_map(|(i, mute)| Some((moved.get(i).copied().flatten()?, mute)))
+            .collect();
+        self.alert_rules = rules;
+        self.rebuild_classifier();
+    }
+
+    /// Color configured for the rule that wins the alert `text` raises, if it has one
+    pub fn alert_color(&self, text: &str) -> Option<&str> {
+        if self.alert_rules.iter().all(|a| a.color.is_none()) { return None; }
+        let mut best: Option<&AlertRule> = None;
+        for a in &self.alert_rules {
+            if !a.rule.compiled.as_ref().is_some_and(|re| re.is_match(text)) { continue; }
+            if best.is_none_or(|b| a.severity > b.severity) { best = Some(a); }
+        }
+        best?.color.as_deref()
+    }
+
+    /// Whether the alert banner is on screen
+    pub fn alert_visible(&self) -> bool {
+        self.alert_unacked > 0 || current_epoch_millis() < self.alert_deadline_ms
+    }
+
+    /// Alerts in the history not acknowledged yet
+    pub fn unacked_alerts(&self) -> usize {
+        self.alert_history.iter().rev().take_while(|e| !e.acknowledged).count()
+    }
+
+    /// Title for the terminal window or tmux pane, so a pane in the background shows trouble: the
+    /// unacknowledged alerts and failed sources, then the focused source
+    pub fn terminal_title(&self) -> String {
+        let mut title = String::from("rtlog");
+        let alerts = self.unacked_alerts();
+        if alerts > 0 { title.push_str(&format!(" ⚠ {} alert{}", alerts, if alerts == 1 { "" } else { "s" })); }
+        let failed = self.failed_source_count();
+        if failed > 0 { title.push_str(&format!(" ✖ {} failed", failed)); }
+        if let Some(src) = self.current_source() { title.push_str(&format!(" - {}", src.name)); }
+        title
+    }
+
+    /// Acknowledge all alerts: stop blinking and dismiss the banner; they stay in the history
+    pub fn acknowledge_alerts(&mut self) {
+        let n = self.unacked_alerts();
+        if n == 0 { return; }
+        for entry in self.alert_history.iter_mut().rev().take(n) { entry.acknowledged = true; }
+        self.alert_unacked = 0;
+        (self.alert_deadline_ms, self.alert_blink_deadline_ms) = (0, 0);
+        self.notify(MessageLevel::Info, format!("acknowledged {} alert(s)", n));
+    }
+
+    /// Mute, if any, of the alert pattern `rule` that has not expired yet
+    pub fn alert_mute(&self, rule: usize) -> Option<&AlertMute> {
+        self.alert_mutes.get(&rule).filter(|m| current_epoch_millis() < m.until_ms)
+    }
+
+    /// Open or close the alert history panel
+    pub fn toggle_alert_panel(&mut self) {
+        self.alert_panel = if self.alert_panel.is_some() { None } else { Some(0) };
+    }
+    /// Move the alert history cursor (`up` is towards newer entries, which are listed first)
+    pub fn alert_panel_move(&mut self, up: bool) {
+        let last = self.alert_history.len().saturating_sub(1);
+        if let Some(sel) = self.alert_panel.as_mut() {
+            *sel = if up { sel.saturating_sub(1) } else { (*sel + 1).min(last) };
+        }
+    }
+    /// Entry under the alert history cursor
+    pub fn selected_alert(&self) -> Option<&AlertEntry> {
+        self.alert_panel.and_then(|sel| self.alert_history.iter().rev().nth(sel))
+    }
+
+    /// Ask for how long to mute the pattern of the selected alert
+    pub fn open_mute_prompt(&mut self) {
+        match self.selected_alert() {
+            Some(AlertEntry { rule: Some(_), .. }) => self.open_prompt(PromptKind::MuteAlert),
+            Some(_) => self.notify(MessageLevel::Warn, "not raised by an alert pattern"),
+            None => {}
+        }
+    }
+    /// Mute the pattern of the selected alert for `minutes` (empty: the default)
+    fn mute_selected_alert(&mut self, minutes: &str) -> Result<(), String> {
+        let minutes = match minutes.trim() {
+            "" => DEFAULT_MUTE_MINUTES,
+            m => m.parse::<u64>().ok().filter(|&m| m > 0).ok_or("not a number of minutes")?,
+        };
+        let rule = self.selected_alert().and_then(|e| e.rule).ok_or("no alert pattern selected")?;
+        let until_ms = current_epoch_millis() + minutes as u128 * 60_000;
+        self.alert_mutes.insert(rule, AlertMute { until_ms, suppressed: 0 });
+        let label = self.alert_rules[rule].label();
+        self.notify(MessageLevel::Info, format!("muted alert '{}' for {}m", label, minutes));
+        Ok(())
+    }
+    /// Lift the mute of the selected alert's pattern
+    pub fn unmute_selected_alert(&mut self) {
+        let Some(rule) = self.selected_alert().and_then(|e| e.rule) else { return; };
+        if self.alert_mutes.remove(&rule).is_some() {
+            let msg = format!("unmuted alert '{}'", self.alert_rules[rule].label());
+            self.notify(MessageLevel::Info, msg);
+        }
+    }
+
+    pub fn open_search(&mut self) {
+        self.search_open = true;
+        self.search_input.clear();
+    }
+    pub fn close_search(&mut self) {
+        self.search_open = false;
+    }
+    pub fn search_push_char(&mut self, c: char) {
+        self.search_history.reset();
+        self.search_input.push(c);
+    }
+    pub fn search_pop_char(&mut self) {
+        self.search_history.reset();
+        self.search_input.pop();
+    }
+    pub fn search_history_prev(&mut self) {
+        if let Some(entry) = self.search_history.prev(&self.search_input) { self.search_input = entry; }
+    }
+    pub fn search_history_next(&mut self) {
+        if let Some(entry) = self.search_history.next() { self.search_input = entry; }
+    }
+    /// Whether the search ignores case: the i flag, unless smart case finds an uppercase letter
+    pub fn search_ignores_case(&self) -> bool {
+        self.search_case_insensitive && !(self.smart_case && has_uppercase(&self.search_input, self.search_is_regex))
+    }
+    pub fn apply_search(&mut self) {
+        if self.search_input.is_empty() {
+            self.search_compiled = None;
+            return;
+        }
+        self.search_history.push(&self.search_input);
+        // Build regex from search_input and flags
+        let pat = if self.search_is_regex { self.search_input.clone() } else { regex::escape(&self.search_input) };
+        let mut builder = regex::RegexBuilder::new(&pat);
+        builder.case_insensitive(self.search_ignores_case());
+        self.search_compiled = match builder.build() {
+            Ok(re) => Some(re),
+            Err(e) => { self.notify(MessageLevel::Error, format!("invalid search regex: {}", last_line(&e))); None }
+        };
+        // Jump to first match from top of visible window
+        let _ = self.jump_next_match();
+    }
+    /// Switch the search between the lines the log panel shows and all lines
+    pub fn toggle_search_scope(&mut self) {
+        self.search_all_lines = !self.search_all_lines;
+    }
+    pub fn jump_next_match(&mut self) -> Option<usize> {
+        self.jump_match(true)
+    }
+    pub fn jump_prev_match(&mut self) -> Option<usize> {
+        self.jump_match(false)
+    }
+    /// Select the next (or previous) jump target after the selected line, wrapping around. Only
+    /// the lines the log panel shows are searched unless the search covers all lines; a match the
+    /// filters hide is then revealed by showing all lines
+    fn jump_match(&mut self, forward: bool) -> Option<usize> {
+        let src = self.current_source()?;
+        if src.lines.is_empty() { return None; }
+        let start = src.selected_index().unwrap_or(src.lines.len() - 1);
+        let shown = self.shown_lines(src);
+        let candidates: Vec<usize> = if self.search_all_lines { (0..src.lines.len()).collect() } else { shown.clone() };
+        let targets: Vec<usize> = candidates.into_iter().filter(|&i| self.is_jump_target(&src.lines[i])).collect();
+        let idx = if forward {
+            targets.iter().find(|&&i| i > start).or(targets.first())
+        } else {
+            targets.iter().rev().find(|&&i| i < start).or(targets.last())
+        }.copied()?;
+        if shown.binary_search(&idx).is_err() {
+            self.show_all = true;
+            self.notify(MessageLevel::Info, "showing all lines for a match the filters hide (v)");
+        }
+        self.jump_to(idx);
+        Some(idx)
+    }
+    /// Lines `n`/`N` stop at: search matches or, with no search while the context panel is open,
+    /// filter matches, to page through them in the panel
+    fn is_jump_target(&self, text: &str) -> bool {
+        if self.search_input.is_empty() && self.context_panel_open {
+            let filters = self.filter_match_set();
+            !filters.is_empty() && filters.is_match(text)
+        } else {
+            self.line_matches_search(text)
+        }
+    }
+    fn line_matches_search(&self, text: &str) -> bool {
+        if let Some(re) = &self.search_compiled {
+            re.is_match(text)
+        } else if !self.search_input.is_empty() {
+            if self.search_ignores_case() { text.to_ascii_lowercase().contains(&self.search_input.to_ascii_lowercase()) } else { text.contains(&self.search_input) }
+        } else { false }
+    }
+    /// The first location in the selected line whose file exists, a relative path looked up in
+    /// the working directory and then next to the source's file
+    pub fn selected_file_location(&self) -> Result<FileLocation, String> {
+        let re = self.file_regex.as_ref().ok_or("no file pattern configured")?;
+        let src = self.current_source().ok_or("no source focused")?;
+        let line = src.selected_index().and_then(|i| src.lines.get(i)).ok_or("no line selected")?;
+        let found = find_locations(line, re);
+        let first = found.first().ok_or("no file:line in the selected line")?;
+        let base = src.path.parent();
+        found.iter().find_map(|location| resolve(location, base)).ok_or_else(|| format!("{}: no such file", first.path.display()))
+    }
+
+    /// Offer the URLs and file paths in the selected line to open
+    pub fn open_link_chooser(&mut self) {
+        self.ensure_log_selection();
+        let Some(src) = self.current_source() else { return; };
+        let Some(line) = src.selected_index().and_then(|i| src.lines.get(i)) else { return; };
+        let links = find_links(line, src.path.parent());
+        if links.is_empty() { return self.notify(MessageLevel::Warn, "no URL or file path in the selected line"); }
+        self.link_chooser = Some(LinkChooser { links, selected: 0 });
+    }
+    pub fn close_link_chooser(&mut self) {
+        self.link_chooser = None;
+    }
+    pub fn link_chooser_move(&mut self, up: bool) {
+        if let Some(c) = self.link_chooser.as_mut() {
+            if up { c.selected = c.selected.saturating_sub(1); }
+            else if c.selected + 1 < c.links.len() { c.selected += 1; }
+        }
+    }
+    /// The link under the chooser's cursor
+    pub fn chosen_link(&self) -> Option<&Link> {
+        self.link_chooser.as_ref().and_then(|c| c.links.get(c.selected))
+    }
+    /// Close the chooser, returning the link under its cursor
+    pub fn take_chosen_link(&mut self) -> Option<Link> {
+        let c = self.link_chooser.take()?;
+        c.links.into_iter().nth(c.selected)
+    }
+    /// Put the link under the chooser's cursor on the clipboard (redacted unless revealed)
+    pub fn copy_chosen_link(&mut self) {
+        let Some(link) = self.take_chosen_link() else { return; };
+        self.clipboard = Some(self.display_text(&link.target).into_owned());
+        self.notify(MessageLevel::Info, "copied link to the clipboard");
+    }
+
+    /// Extract an ID from the selected line and open a view of all lines containing it
+    pub fn correlate_selected(&mut self) -> Result<(), String> {
+        self.ensure_log_selection();
+        let re = self.correlate_regex.as_ref().ok_or("no correlate pattern configured")?;
+        let src = self.current_source().ok_or("no source focused")?;
+        let line = src.selected_index().and_then(|i| src.lines.get(i)).ok_or("no line selected")?;
+        let id = extract_correlation_id(line, re).ok_or("no ID found in selected line")?;
+        let mut hits: Vec<(usize, LineId)> = Vec::new();
+        for (si, s) in self.sources.iter().enumerate() {
+            hits.extend(s.lines.iter().enumerate().filter(|(_, l)| l.contains(id.as_str())).map(|(li, _)| (si, s.line_id(li))));
+        }
+        if self.parse_timestamps {
+            hits.sort_by_key(|&(si, id)| self.sources[si].line_index(id).and_then(|li| self.sources[si].timestamp(&self.sources[si].lines[li])));
+        }
+        let selected = hits.len().saturating_sub(1);
+        self.correlation = Some(Correlation { id, hits, selected });
+        Ok(())
+    }
+    pub fn close_correlation(&mut self) {
+        self.correlation = None;
+    }
+    pub fn correlation_move(&mut self, up: bool) {
+        if let Some(c) = self.correlation.as_mut() {
+            if up { c.selected = c.selected.saturating_sub(1); }
+            else if c.selected + 1 < c.hits.len() { c.selected += 1; }
+        }
+    }
+    /// Close the correlation view and focus/select the line under its cursor
+    pub fn correlation_jump(&mut self) {
+        let Some(c) = self.correlation.take() else { return; };
+        if let Some(&(si, id)) = c.hits.get(c.selected)
+            && let Some(li) = self.sources.get(si).and_then(|s| s.line_index(id)) {
+            self.set_focus(si);
+            self.jump_to(li);
+        }
+    }
+
+    /// Open or close the summary of all sources
+    pub fn toggle_summary(&mut self) {
+        if self.summary.take().is_some() { return; }
+        self.summary = Some(SummaryView { sources: Vec::new(), loading: 0, scroll: 0, computed_ms: 0 });
+        self.refresh_summary();
+    }
+    /// Recount the summary if it is over a second old and sources were still being read
+    pub fn refresh_summary(&mut self) {
+        let Some(view) = self.summary.as_mut() else { return; };
+        let now = current_epoch_millis();
+        if view.computed_ms > 0 && (view.loading == 0 || now < view.computed_ms + 1000) { return; }
+        view.sources = self.sources.iter().map(SourceSummary::of).collect();
+        view.loading = self.sources.iter().filter(|s| !s.closed && s.error.is_none() && !s.snapshot && !s.from_plugin && !s.derived).count();
+        view.computed_ms = now;
+    }
+    pub fn summary_scroll(&mut self, up: bool) {
+        if let Some(v) = self.summary.as_mut() {
+            v.scroll = if up { v.scroll.saturating_sub(1) } else { v.scroll + 1 };
+        }
+    }
+
+    /// Open or close the top patterns panel for the focused source
+    pub fn toggle_patterns(&mut self) {
+        if self.patterns.take().is_some() { return; }
+        self.patterns = Some(PatternView { window: 0, patterns: Vec::new(), total: 0, selected: 0, source_id: None, computed_ms: 0 });
+        self.refresh_patterns();
+    }
+    /// Recount the patterns panel if the focused source changed or the counts are over a second old
+    pub fn refresh_patterns(&mut self) {
+        let source_id = self.current_source().map(|s| s.id);
+        let Some(view) = self.patterns.as_mut() else { return; };
+        let now = current_epoch_millis();
+        if view.source_id == source_id && now < view.computed_ms + 1000 { return; }
+        let window = view.window_minutes().map(|m| std::time::Duration::from_secs(m * 60));
+        let (patterns, total) = match self.sources.get(self.focused) {
+            Some(src) => top_patterns(src.lines.iter().enumerate().filter(|&(i, _)| !src.is_separator(i)), window),
+            None => top_patterns(std::iter::empty(), window),
+        };
+        if view.source_id != source_id { view.selected = 0; }
+        view.selected = view.selected.min(patterns.len().saturating_sub(1));
+        view.patterns = patterns;
+        view.total = total;
+        view.source_id = source_id;
+        view.computed_ms = now;
+    }
+    /// Switch the patterns panel to the next time window
+    pub fn cycle_patterns_window(&mut self) {
+        let Some(view) = self.patterns.as_mut() else { return; };
+        view.window = (view.window + 1) % PATTERN_WINDOWS.len();
+        view.computed_ms = 0;
+        self.refresh_patterns();
+    }
+    pub fn patterns_move(&mut self, up: bool) {
+        if let Some(v) = self.patterns.as_mut() {
+            if up { v.selected = v.selected.saturating_sub(1); }
+            else if v.selected + 1 < v.patterns.len() { v.selected += 1; }
+        }
+    }
+    /// Close the patterns panel and select the newest line of the pattern under its cursor
+    pub fn patterns_jump(&mut self) {
+        let Some(v) = self.patterns.take() else { return; };
+        // Counted lines may be gone (cleared buffer) or belong to another source by now
+        let current = self.current_source().map(|s| (s.id, s.lines.len()));
+        if let Some(p) = v.patterns.get(v.selected)
+            && let (Some(id), Some((cur, len))) = (v.source_id, current)
+            && id == cur && p.last < len
+        {
+            self.jump_to(p.last);
+        }
+    }
+
+    /// Cycle the histogram: per minute, per hour, hidden
+    pub fn cycle_histogram(&mut self) {
+        let size = match self.histogram.take().map(|h| h.size) {
+            None => BucketSize::Minute,
+            Some(BucketSize::Minute) => BucketSize::Hour,
+            Some(BucketSize::Hour) => return,
+        };
+        self.histogram = Some(HistogramView { size, buckets: Vec::new(), selected: 0, follow: true, source_id: None, computed_ms: 0 });
+        self.refresh_histogram();
+    }
+    /// Recount the histogram if the focused source changed or the counts are over a second old
+    pub fn refresh_histogram(&mut self) {
+        let source_id = self.current_source().map(|s| s.id);
+        let Some(view) = self.histogram.as_mut() else { return; };
+        let now = current_epoch_millis();
+        if view.source_id == source_id && now < view.computed_ms + 1000 { return; }
+        view.buckets = match self.sources.get(self.focused) {
+            Some(src) => histogram(src.lines.iter().enumerate().filter(|&(i, _)| !src.is_separator(i)), view.size, |l| src.timestamp(l)),
+            None => Vec::new(),
+        };
+        if view.source_id != source_id { view.follow = true; }
+        let last = view.buckets.len().saturating_sub(1);
+        view.selected = if view.follow { last } else { view.selected.min(last) };
+        view.source_id = source_id;
+        view.computed_ms = now;
+    }
+    /// Move the histogram cursor by `delta` buckets and show the first line of that bucket
+    pub fn histogram_move(&mut self, delta: isize) {
+        let Some(view) = self.histogram.as_mut() else { return; };
+        let Some(last) = view.buckets.len().checked_sub(1) else { return; };
+        view.selected = view.selected.saturating_add_signed(delta).min(last);
+        view.follow = false;
+        let first = view.buckets[view.selected].first;
+        if self.current_source().is_some_and(|s| first < s.lines.len()) { self.jump_to(first); }
+    }
+
+    pub fn open_prompt(&mut self, kind: PromptKind) {
+        let input = if kind == PromptKind::Query { self.last_query.clone() } else { String::new() };
+        self.prompt = Some(Prompt { kind, input, error: None });
+    }
+    pub fn close_prompt(&mut self) {
+        self.prompt = None;
+    }
+    pub fn prompt_push_char(&mut self, c: char) {
+        if let Some(p) = self.prompt.as_mut() { p.input.push(c); p.error = None; }
+    }
+    pub fn prompt_pop_char(&mut self) {
+        if let Some(p) = self.prompt.as_mut() { p.input.pop(); p.error = None; }
+    }
+    pub fn set_prompt_error(&mut self, err: String) {
+        if let Some(p) = self.prompt.as_mut() { p.error = Some(err); }
+    }
+    /// Run the action for the open prompt; on failure the prompt stays open showing the error
+    pub fn submit_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else { return; };
+        let res = match prompt.kind {
+            PromptKind::Goto => self.goto(&prompt.input).map(|_| ()),
+            PromptKind::ConfirmClear => { self.clear_focused_source(); Ok(()) }
+            PromptKind::MuteAlert => self.mute_selected_alert(&prompt.input),
+            PromptKind::Query => self.run_query(&prompt.input),
+            PromptKind::Export => self.export_selection(&prompt.input),
+            PromptKind::Replace => self.preview_replacement(&prompt.input),
+            PromptKind::Hotkey => self.save_hotkey(&prompt.input),
+            PromptKind::FilterGroup => self.set_filter_group(&prompt.input),
+            PromptKind::FieldFilter => self.add_field_filter(&prompt.input),
+            // Handled by the runtime, which owns the reader tasks
+            PromptKind::OpenSource | PromptKind::Shell | PromptKind::Pipe => Ok(()),
+            // Handled by the runtime, which knows the terminal's size
+            PromptKind::Screenshot => Ok(()),
+        };
+        if let Err(e) = res {
+            self.prompt = Some(Prompt { error: Some(e), ..prompt });
+        }
+    }
+
+    /// Mark `source_id` as the output of a pipe command (see `Source::derived`)
+    pub fn mark_derived_source(&mut self, source_id: usize) {
+        if let Some(src) = self.index_of(source_id).and_then(|i| self.sources.get_mut(i)) { src.derived = true; }
+    }
+
+    /// Whether piping sends the context window rather than all visible lines
+    pub fn pipe_context(&self) -> bool {
+        self.context_panel_open && self.current_source().is_some_and(|s| s.selected_index().is_some())
+    }
+
+    /// Lines to pipe into a command, as displayed (redacted unless revealed): the range
+    /// selection, the context window around the selected line while the context panel is open,
+    /// otherwise every line passing the filters
+    pub fn pipe_input(&self) -> Vec<String> {
+        let Some(src) = self.current_source() else { return Vec::new(); };
+        let lines: Vec<&str> = match (self.range_lines(src), src.selected_index()) {
+            (Some(range), _) => range.into_iter().map(|i| &*src.lines[i]).collect(),
+            (None, Some(sel)) if self.pipe_context() => {
+                let from = sel.saturating_sub(self.context_radius);
+                let to = (sel + self.context_radius + 1).min(src.lines.len());
+                src.lines[from.min(to)..to].iter().map(|l| &**l).collect()
+            }
+            _ => self.shown_lines(src).into_iter().map(|i| &*src.lines[i]).collect(),
+        };
+        lines.into_iter().map(|l| self.display_text(l).into_owned()).collect()
+    }
+
+    /// Write the focused source's lines that pass the filters (what the log panel shows) to
+    /// `path` in `export_format`
+    pub fn export_visible(&mut self, path: &str) -> Result<(), String> {
+        let src = self.current_source().ok_or("no source focused")?;
+        self.export_lines(path, self.shown_lines(src))
+    }
+
+    /// Export the range selection like `export_visible`, ending it, or the visible lines without
+    /// one
+    pub fn export_selection(&mut self, path: &str) -> Result<(), String> {
+        let src = self.current_source().ok_or("no source focused")?;
+        let Some(lines) = self.range_lines(src) else { return self.export_visible(path); };
+        self.export_lines(path, lines)?;
+        if let Some(src) = self.current_source_mut() { src.range_anchor = None; }
+        Ok(())
+    }
+
+    /// Write the lines of every source that pass the filters to `path` in `export_format`, source
+    /// after source; returns the number of lines written
+    pub fn export_all(&self, path: &str) -> Result<usize, String> {
+        let parts = self.sources.iter().enumerate().map(|(idx, src)| (idx, self.shown_lines(src))).collect();
+        self.write_export(path, parts)
+    }
+
+    /// Write the focused source's `lines` (indices, oldest first) to `path` in `export_format`
+    fn export_lines(&mut self, path: &str, lines: Vec<usize>) -> Result<(), String> {
+        let count = self.write_export(path, vec![(self.focused, lines)])?;
+        self.notify(MessageLevel::Info, format!("exported {} lines to {} ({})", count, path.trim(), self.export_format.name()));
+        Ok(())
+    }
+
+    /// Write the `lines` (indices, oldest first) of the sources at the given indices to `path`
+    fn write_export(&self, path: &str, parts: Vec<(usize, Vec<usize>)>) -> Result<usize, String> {
+        let path = path.trim();
+        if path.is_empty() { return Err("enter a file path".into()); }
+        if parts.iter().any(|(idx, _)| *idx >= self.sources.len()) { return Err("no source focused".into()); }
+        let levels: Vec<Arc<LevelRules>> = parts.iter()
+            .map(|(idx, _)| self.level_rules_for(&self.sources[*idx]).unwrap_or_else(|| Arc::clone(&self.classifier.levels)))
+            .collect();
+        // Replaced while previewing a substitution
+        let texts: Vec<Vec<Cow<str>>> = parts.iter().map(|(idx, lines)| lines.iter().map(|&i| self.replaced(&self.sources[*idx].lines[i])).collect()).collect();
+        let rows = parts.iter().zip(&levels).zip(&texts).flat_map(|(((idx, lines), levels), texts)| {
+            let src = &self.sources[*idx];
+            lines.iter().zip(texts).map(move |(&i, text)| Row { source: &src.name, n: i + 1, line: text, levels })
+        });
+        let fields: Vec<regex::Regex> = self.classifier.field_regexes().cloned().collect();
+        let file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        export::write(file, self.export_format, rows, &fields, &self.redactor).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Run a SQL-like query over the buffered lines and show its results
+    pub fn run_query(&mut self, text: &str) -> Result<(), String> {
+        let started = std::time::Instant::now();
+        let query = Query::parse(text)?;
+        let sources: Vec<&Source> = match &query.from {
+            FromClause::Focused => self.current_source().into_iter().collect(),
+            FromClause::All => self.sources.iter().collect(),
+            FromClause::Source(name) => {
+                let src = self.sources.iter().find(|s| s.names().iter().any(|n| n == name))
+                    .ok_or_else(|| format!("no source named {}", name))?;
+                vec![src]
+            }
+        };
+        let levels: Vec<Arc<LevelRules>> = sources.iter()
+            .map(|s| self.level_rules_for(s).unwrap_or_else(|| Arc::clone(&self.classifier.levels)))
+            .collect();
+        let rows = sources.iter().zip(&levels).flat_map(|(src, levels)| {
+            src.lines.iter().enumerate().map(move |(i, line)| Row { source: &src.name, n: i + 1, line, levels })
+        });
+        let fields: Vec<regex::Regex> = self.classifier.field_regexes().cloned().collect();
+        let result = query.run(rows, &fields)?;
+        self.last_query = text.trim().to_string();
+        self.query = Some(QueryView { text: self.last_query.clone(), result, selected: 0, elapsed_ms: started.elapsed().as_millis() });
+        Ok(())
+    }
+    pub fn close_query(&mut self) {
+        self.query = None;
+    }
+    pub fn query_move(&mut self, up: bool) {
+        if let Some(v) = self.query.as_mut() {
+            if up { v.selected = v.selected.saturating_sub(1); }
+            else if v.selected + 1 < v.result.rows.len() { v.selected += 1; }
+        }
+    }
+
+    /// Jump the focused source to a 1-based line number or, with timestamp parsing enabled,
+    /// to the first line at or after a timestamp (a bare time of day uses the selected line's date).
+    /// Select line `n` (1-based, clamped to the buffer) of the focused source
+    pub fn goto_line(&mut self, n: usize) -> Option<usize> {
+        let len = self.current_source()?.lines.len();
+        if len == 0 { return None; }
+        let idx = n.clamp(1, len) - 1;
+        self.jump_to(idx);
+        Some(idx)
+    }
+
+    pub fn goto(&mut self, target: &str) -> Result<usize, String> {
+        let target = target.trim();
+        let src = self.current_source().ok_or("no source focused")?;
+        if src.lines.is_empty() { return Err("source is empty".into()); }
+        if let Ok(n) = target.parse::<usize>() {
+            return self.goto_line(n).ok_or_else(|| "source is empty".into());
+        }
+        if !self.parse_timestamps {
+            return Err("not a line number (start with -t to jump by time)".into());
+        }
+        let wanted = if let Some(ts) = parse_timestamp(target).or_else(|| src.timestamp(target)) {
+            ts
+        } else if let Some(time) = parse_time_of_day(target) {
+            let from = src.selected_index().unwrap_or(src.lines.len() - 1);
+            let date = src.lines[..=from].iter().rev().chain(src.lines[from + 1..].iter())
+                .find_map(|l| src.timestamp(l))
+                .ok_or("no timestamps found in source")?
+                .date();
+            date.and_time(time)
+        } else {
+            return Err("expected a line number or timestamp".into());
+        };
+        let idx = src.lines.iter()
+            .position(|l| src.timestamp(l).is_some_and(|ts| ts >= wanted))
+            .ok_or_else(|| format!("no line at or after {}", wanted))?;
+        self.jump_to(idx);
+        Ok(idx)
+    }
+
+    fn jump_to(&mut self, idx: usize) {
+        self.context_scroll = 0;
+        if let Some(src) = self.current_source_mut() {
+            src.selected_log = Some(src.line_id(idx));
+            src.auto_scroll = false;
+            // Adjust scroll so that idx is visible near bottom of viewport when possible
+            let viewport = 20usize; // rough guess; actual height determined in UI, but this keeps it visible
+            let total = src.lines.len();
+            let from_bottom = total.saturating_sub(idx + 1);
+            // scroll_offset is number of lines from bottom hidden
+            src.scroll_offset = from_bottom.saturating_sub(viewport/2);
+        }
+    }
+}
+
+/// File names, with just enough trailing parent directories appended to tell duplicates apart,
+/// e.g. `app.log (api)` and `app.log (web)`
+pub fn disambiguated_names(paths: &[&Path]) -> Vec<String> {
+    fn base(p: &Path) -> String {
+        p.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| p.display().to_string())
+    }
+    fn parents(p: &Path, depth: usize) -> Vec<String> {
+        let comps: Vec<String> = p.parent().into_iter()
+            .flat_map(|d| d.components())
+            .filter(|c| matches!(c, std::path::Component::Normal(_)))
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        comps[comps.len().saturating_sub(depth)..].to_vec()
+    }
+    paths.iter().map(|p| {
+        let name = base(p);
+        let others: Vec<&&Path> = paths.iter().filter(|q| *q != p && base(q) == name).collect();
+        if others.is_empty() { return name; }
+        let max_depth = parents(p, usize::MAX).len();
+        let depth = (1..=max_depth).find(|&d| others.iter().all(|q| parents(q, d) != parents(p, d))).unwrap_or(max_depth);
+        if depth == 0 { name } else { format!("{} ({})", name, parents(p, depth).join("/")) }
+    }).collect()
+}
+
+/// Last line of an error message; regex errors put the actual reason after a multi-line excerpt
+fn last_line(err: &dyn std::fmt::Display) -> String {
+    let msg = err.to_string();
+    let last = msg.lines().last().unwrap_or_default().trim();
+    last.strip_prefix("error: ").unwrap_or(last).to_string()
+}
+
+fn current_epoch_sec() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn current_epoch_millis() -> u128 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_message_expires() {
+        let mut state = AppState::default();
+        state.notify(MessageLevel::Warn, "no match");
+        assert_eq!(state.current_message().map(|m| (m.level, m.text.as_str())), Some((MessageLevel::Warn, "no match")));
+        assert!(state.animating());
+        if let Some(m) = state.message.as_mut() { m.deadline_ms = 0; }
+        assert!(state.current_message().is_none());
+        assert!(!state.animating());
+    }
+
+    #[test]
+    fn test_anchors_and_whole_line() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        let id = state.sources[0].id;
+        for text in ["foo", "foo started", "stopped foo", "bar", "bar stopped", "stopped bar"] {
+            state.push_line_for(id, LogLine::new(text));
+        }
+        let shown = |state: &AppState| -> Vec<String> {
+            let src = &state.sources[0];
+            state.shown_lines(src).into_iter().map(|i| src.lines[i].to_string()).collect()
+        };
+        // Anchors in a regex bind to their branch of the alternation
+        state.filter_input = "^foo|bar$".into();
+        state.input_is_regex = true;
+        state.add_filter_from_input();
+        assert_eq!(shown(&state), ["foo", "foo started", "bar", "stopped bar"]);
+        state.search_input = "^foo|bar$".into();
+        state.search_is_regex = true;
+        state.apply_search();
+        assert!(state.line_matches_search("stopped bar") && !state.line_matches_search("stopped foo"));
+        // The search stops at the lines shown, or at hidden ones too when it covers all lines
+        state.search_input = "stopped".into();
+        state.search_is_regex = false;
+        state.apply_search();
+        assert_eq!(state.sources[0].selected_index(), Some(5));
+        assert_eq!(state.jump_prev_match(), Some(5));
+        state.toggle_search_scope();
+        assert_eq!((state.jump_prev_match(), state.show_all), (Some(4), true));
+        state.show_all = false;
+        // The whole-line flag applies to the pattern as a whole
+        state.remove_selected_filter();
+        state.filter_input = "foo|bar".into();
+        state.input_whole_line = true;
+        state.add_filter_from_input();
+        assert_eq!(shown(&state), ["foo", "bar"]);
+    }
+
+    #[test]
+    fn test_smart_case() {
+        let mut state = AppState { input_case_insensitive: true, search_case_insensitive: true, smart_case: true, ..AppState::default() };
+        let case = |state: &mut AppState, pattern: &str| {
+            state.filter_input = pattern.into();
+            state.add_filter_from_input();
+            state.filters.last().unwrap().case_insensitive
+        };
+        assert!(case(&mut state, "timeout"));
+        assert!(!case(&mut state, "Timeout"));
+        state.input_is_regex = true;
+        assert!(case(&mut state, r"\d+\Sms"));
+        state.input_case_insensitive = false;
+        assert!(!case(&mut state, "timeout"));
+        state.input_case_insensitive = true;
+        state.smart_case = false;
+        assert!(case(&mut state, "Timeout"));
+        state.smart_case = true;
+        state.search_input = "Disk".into();
+        assert!(!state.search_ignores_case());
+        state.search_input = "disk".into();
+        assert!(state.search_ignores_case());
+    }
+
+    #[test]
+    fn test_undo_filter_changes() {
+        let mut state = AppState::default();
+        for pattern in ["error", "timeout"] {
+            state.filter_input = pattern.into();
+            state.add_filter_from_input();
+        }
+        state.selected_filter = 0;
+        state.toggle_selected_filter();
+        state.remove_selected_filter();
+        assert_eq!(state.filters.len(), 1);
+        state.undo();
+        assert_eq!(state.filters[0].pattern, "error");
+        assert!(!state.filters[0].enabled);
+        state.undo();
+        assert!(state.filters[0].enabled);
+        state.undo();
+        assert_eq!(state.filters.iter().map(|f| f.pattern.as_str()).collect::<Vec<_>>(), ["error"]);
+        assert_eq!(state.filter_match_set().len(), 1);
+    }
+
+    #[test]
+    fn test_clear_source_and_undo() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        state.filter_input = "error".into();
+        state.add_filter_from_input();
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        push(&mut state, "error one");
+        push(&mut state, "info two");
+        state.clear_focused_source();
+        assert!(state.sources[0].lines.is_empty());
+        assert_eq!(state.filters[0].match_count, 0);
+        assert_eq!(state.sources[0].levels, LevelCounts::default());
+        push(&mut state, "error three");
+        state.undo();
+        assert_eq!((state.sources[0].levels.error, state.sources[0].levels.info), (2, 1));
+        let lines: Vec<&str> = state.sources[0].lines.iter().map(|l| &**l).collect();
+        assert_eq!(lines, ["error one", "info two", "error three"]);
+        assert_eq!(state.filters[0].match_count, 2);
+        assert_eq!(state.sources[0].shown, 2);
+    }
+
+    #[test]
+    fn test_shown_counts_and_show_all() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        push(&mut state, "error one");
+        push(&mut state, "info two");
+        assert_eq!(state.sources[0].shown, 2);
+        state.filter_input = "error".into();
+        state.add_filter_from_input();
+        push(&mut state, "info three");
+        state.insert_marker(false);
+        assert_eq!((state.sources[0].shown, state.sources[0].lines.len()), (2, 4));
+        assert!(!state.is_shown("info two"));
+        state.toggle_show_all();
+        assert!(state.is_shown("info two"));
+        assert_eq!(state.pipe_input().len(), 4);
+        state.toggle_show_all();
+        state.selected_filter = 0;
+        state.toggle_selected_filter();
+        assert_eq!(state.sources[0].shown, 4);
+    }
+
+    #[test]
+    fn test_filter_context_lines() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        let id = state.sources[0].id;
+        for text in ["a", "error 1", "b", "c", "d", "error 2", "e"] {
+            state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        }
+        state.filter_input = "error".into();
+        state.add_filter_from_input();
+        let shown = |state: &AppState| state.shown_lines_rev(&state.sources[0]).collect::<Vec<_>>();
+        assert_eq!(shown(&state), [(5, false), (1, false)]);
+        state.adjust_filter_context(1);
+        assert_eq!(shown(&state), [(6, true), (5, false), (4, true), (2, true), (1, false), (0, true)]);
+        state.adjust_filter_context(1);
+        assert_eq!(shown(&state).len(), 7);
+        assert_eq!(state.pipe_input(), ["a", "error 1", "b", "c", "d", "error 2", "e"]);
+        state.adjust_filter_context(-5);
+        assert_eq!(state.filter_context, 0);
+    }
+
+    #[test]
+    fn test_selection_tracks_its_line() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        for text in ["error 1", "info 2", "error 3", "info 4"] { push(&mut state, text); }
+        state.filter_input = "error".into();
+        state.add_filter_from_input();
+        // The bottom line of the panel is the last match, not the hidden last line
+        state.ensure_log_selection();
+        assert_eq!(state.sources[0].selected_index(), Some(2));
+        push(&mut state, "error 5");
+        assert_eq!(state.sources[0].selected_index(), Some(2));
+        state.move_log_selection_up();
+        assert_eq!(state.sources[0].selected_index(), Some(0));
+        state.move_log_selection_down();
+        state.move_log_selection_down();
+        assert_eq!(state.sources[0].selected_index(), Some(4));
+        state.clear_focused_source();
+        push(&mut state, "error 6");
+        state.ensure_log_selection();
+        state.undo();
+        assert_eq!(state.sources[0].lines[state.sources[0].selected_index().unwrap()].as_ref(), "error 6");
+    }
+
+    #[test]
+    fn test_counted_selection_moves() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        let id = state.sources[0].id;
+        for n in 1..=10 { state.push_line_for(id, LogLine { text: format!("line {}", n).into(), class: LineClass::default() }); }
+        for digit in [1, 2] { state.push_count_digit(digit); }
+        assert_eq!(state.pending_count, Some(12));
+        assert_eq!(state.goto_line(5), Some(4));
+        state.move_log_selection_by(-3);
+        assert_eq!(state.sources[0].selected_index(), Some(1));
+        state.move_log_selection_by(-3);
+        assert_eq!(state.sources[0].selected_index(), Some(0));
+        state.move_log_selection_by(4);
+        assert_eq!(state.sources[0].selected_index(), Some(4));
+        state.move_log_selection_by(25);
+        assert_eq!(state.sources[0].selected_index(), Some(9));
+    }
+
+    #[test]
+    fn test_page_lines() {
+        let mut state = AppState::new(None, Vec::new());
+        assert_eq!((state.page_lines(30, 2), state.page_lines(30, 1)), (30, 15));
+        state.page_step = 0.5;
+        assert_eq!((state.page_lines(30, 2), state.page_lines(1, 1)), (15, 1));
+    }
+
+    #[test]
+    fn test_toggle_auto_scroll_all() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("a.log")), (None, PathBuf::from("b.log"))]);
+        state.toggle_auto_scroll();
+        state.toggle_auto_scroll_all();
+        assert!(state.sources.iter().all(|s| !s.auto_scroll));
+        state.sources[1].scroll_offset = 4;
+        state.toggle_auto_scroll_all();
+        assert!(state.sources.iter().all(|s| s.auto_scroll && s.scroll_offset == 0));
+    }
+
+    #[test]
+    fn test_sidebar_sort_and_pins() {
+        let mut state = AppState::new(None, Vec::new());
+        // Not the user's saved sidebar
+        state.sidebar = SidebarPrefs::default();
+        state.set_sources([(None, PathBuf::from("b.log")), (None, PathBuf::from("a.log")), (None, PathBuf::from("c.log"))]);
+        let ids: Vec<usize> = state.sources.iter().map(|s| s.id).collect();
+        let push = |state: &mut AppState, i: usize, text: &str| state.push_line_for(ids[i], LogLine { text: text.into(), class: LineClass::default() });
+        push(&mut state, 2, "ERROR x");
+        push(&mut state, 0, "fine");
+        push(&mut state, 1, "ERROR y");
+        push(&mut state, 1, "fine");
+        assert_eq!(state.sidebar_order(), [0, 1, 2]);
+        state.cycle_source_sort();
+        assert_eq!(state.sidebar_order(), [1, 0, 2]);
+        state.cycle_source_sort();
+        assert_eq!(state.sidebar_order(), [1, 0, 2]);
+        state.cycle_source_sort();
+        assert_eq!(state.sidebar_order(), [2, 1, 0]);
+        state.set_focus(0);
+        state.toggle_pin_source();
+        assert_eq!((state.groups[0].name.as_str(), state.sidebar_order()), (PINNED_GROUP, vec![0, 2, 1]));
+        state.focus_group(true);
+        assert_eq!(state.focused, 2);
+        state.set_focus(0);
+        state.toggle_pin_source();
+        assert_eq!(state.groups.len(), 1);
+    }
+
+    #[test]
+    fn test_focus_follow() {
+        let mut state = AppState::new(None, vec![(AlertSeverity::Warn, "ERROR".into())]);
+        state.set_sources([(None, PathBuf::from("a.log")), (None, PathBuf::from("b.log")), (None, PathBuf::from("c.log"))]);
+        let ids: Vec<usize> = state.sources.iter().map(|s| s.id).collect();
+        let push = |state: &mut AppState, i: usize, text: &str| state.push_line_for(ids[i], LogLine { text: text.into(), class: LineClass::default() });
+        push(&mut state, 1, "ERROR before");
+        assert_eq!(state.focused, 0);
+        state.toggle_focus_follow();
+        push(&mut state, 2, "quiet line");
+        push(&mut state, 1, "ERROR again");
+        assert_eq!((state.focused, state.sources[1].unread), (1, 0));
+        state.focus_follow = FocusFollow::Pattern(regex::Regex::new("deploy").unwrap());
+        push(&mut state, 0, "ERROR not followed now");
+        push(&mut state, 2, "deploy started");
+        assert_eq!(state.focused, 2);
+        state.toggle_focus_follow();
+        push(&mut state, 0, "deploy again");
+        assert_eq!(state.focused, 2);
+    }
+
+    #[test]
+    fn test_exit_on_match() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("a.log"))]);
+        state.exit_pattern = Some(regex::Regex::new("(?i)panic").unwrap());
+        let id = state.sources[0].id;
+        state.push_line_for(id, LogLine::new("INFO started"));
+        state.push_line_for(id, LogLine::separator(format!("{}12:00:00 $ ./panic.sh ----", crate::log::RUN_PREFIX)));
+        assert!(state.exit_match.is_none());
+        state.push_line_for(id, LogLine::new("thread main PANICKED"));
+        state.push_line_for(id, LogLine::new("panic again"));
+        assert_eq!(state.exit_match.as_deref(), Some("a.log: thread main PANICKED"));
+    }
+
+    #[test]
+    fn test_context_panel_scroll_and_match_paging() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        let id = state.sources[0].id;
+        for text in ["error 1", "a", "b", "error 2", "c"] {
+            state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        }
+        state.filter_input = "error".into();
+        state.add_filter_from_input();
+        state.sources[0].selected_log = Some(LineId(0));
+        state.context_panel_open = true;
+        state.scroll_context(2);
+        state.scroll_context(-20);
+        assert_eq!(state.context_scroll, -5);
+        // Without a search, n pages through the filter matches and recentres the panel
+        assert_eq!(state.jump_next_match(), Some(3));
+        assert_eq!(state.context_scroll, 0);
+        assert_eq!(state.jump_next_match(), Some(0));
+        state.context_panel_open = false;
+        assert_eq!(state.jump_next_match(), None);
+    }
+
+    #[test]
+    fn test_exclude_filter() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("api.log"))]);
+        state.filter_input = "!healthz".into();
+        state.add_filter_from_input();
+        assert!(state.filters[0].exclude && state.filters[0].pattern == "healthz");
+        let id = state.sources[0].id;
+        for text in ["GET /healthz 200", "GET /orders 200", "POST /orders 500"] {
+            state.push_line_for(id, LogLine::new(text));
+        }
+        assert_eq!((state.shown_lines(&state.sources[0]), state.sources[0].shown), (vec![1, 2], 2));
+        state.add_filter(FilterRule::simple("500".into(), false));
+        assert_eq!((state.shown_lines(&state.sources[0]), state.sources[0].shown), (vec![2], 1));
+    }
+
+    #[test]
+    fn test_field_filters() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("api.log"))]);
+        state.add_filter(FilterRule::simple(r"took (?P<ms>\d+)ms".into(), true));
+        let id = state.sources[0].id;
+        for text in ["GET / took 12ms", "GET /slow took 950ms", "GET /other"] {
+            state.push_line_for(id, LogLine::new(text));
+        }
+        state.open_prompt(PromptKind::FieldFilter);
+        state.prompt.as_mut().unwrap().input = "ms >= 100".into();
+        state.submit_prompt();
+        assert_eq!(state.shown_lines(&state.sources[0]), [1]);
+        assert_eq!(state.sources[0].shown, 1);
+        state.push_line_for(id, LogLine::new("GET /a took 5ms"));
+        state.push_line_for(id, LogLine::new("GET /b took 500ms"));
+        assert_eq!((state.shown_lines(&state.sources[0]), state.sources[0].shown), (vec![1, 4], 2));
+        // Left out while no enabled filter has the field
+        state.toggle_selected_filter();
+        assert_eq!(state.sources[0].shown, 5);
+        state.toggle_selected_filter();
+        state.open_prompt(PromptKind::FieldFilter);
+        state.submit_prompt();
+        assert!(state.field_filters.is_empty() && state.sources[0].shown == 4);
+    }
+
+    #[test]
+    fn test_field_stats_from_named_groups() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("api.log"))]);
+        state.input_is_regex = true;
+        state.filter_input = r"took (?P<ms>\d+(\.\d+)?)ms".into();
+        state.add_filter_from_input();
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        push(&mut state, "GET / took 12ms");
+        push(&mut state, "GET /slow took 250.5ms");
+        push(&mut state, "GET /other");
+        let ms = state.sources[0].fields["ms"];
+        assert_eq!((ms.count, ms.min, ms.max), (2, 12.0, 250.5));
+        assert_eq!(ms.avg(), 131.25);
+        state.clear_focused_source();
+        assert!(state.sources[0].fields.is_empty());
+        push(&mut state, "POST / took 4ms");
+        state.undo();
+        assert_eq!((state.sources[0].fields["ms"].count, state.sources[0].fields["ms"].min), (3, 4.0));
+    }
+
+    #[test]
+    fn test_field_aggregate() {
+        let a = FieldAggregate::of((1..=100).rev().map(f64::from).collect()).unwrap();
+        assert_eq!(a, FieldAggregate { count: 100, p50: 50.0, p95: 95.0, max: 100.0 });
+        assert_eq!(FieldAggregate::of(vec![7.0]).unwrap().p95, 7.0);
+        assert!(FieldAggregate::of(Vec::new()).is_none());
+
+        let mut state = AppState::default();
+        state.record_field_values(&[("ms".into(), 3.0), ("bytes".into(), 10.0)]);
+        state.record_field_values(&[("ms".into(), 5.0)]);
+        // Fields in name order, the first one until another is chosen
+        assert_eq!(state.field_aggregate().map(|(name, a)| (name, a.unwrap().count)), Some(("bytes", 1)));
+        state.cycle_aggregate_field();
+        assert_eq!(state.field_aggregate().map(|(name, a)| (name, a.unwrap().p50)), Some(("ms", 3.0)));
+        state.cycle_aggregate_field();
+        assert_eq!(state.field_aggregate().unwrap().0, "bytes");
+    }
+
+    #[test]
+    fn test_critical_alert_until_acknowledged() {
+        let mut state = AppState::default();
+        state.raise_alert(AlertSeverity::Critical, "disk full".into());
+        state.raise_alert(AlertSeverity::Warn, "slow query".into());
+        assert_eq!((state.alert_unacked, state.alert_message.as_deref()), (1, Some("disk full")));
+        assert!(std::mem::take(&mut state.bell));
+        state.alert_deadline_ms = 0;
+        assert!(state.alert_visible());
+        state.acknowledge_alerts();
+        assert!(!state.alert_visible());
+        state.raise_alert(AlertSeverity::Info, "deploy started".into());
+        assert!(state.alert_visible() && !state.bell);
+    }
+
+    #[test]
+    fn test_terminal_title() {
+        let mut state = AppState::default();
+        assert_eq!(state.terminal_title(), "rtlog");
+        state.set_sources([(None, PathBuf::from("app.log")), (None, PathBuf::from("db.log"))]);
+        state.raise_alert(AlertSeverity::Warn, "slow query".into());
+        // A failing source raises an alert too
+        let db = state.sources[1].id;
+        state.set_source_error(db, "permission denied".into());
+        assert_eq!(state.terminal_title(), "rtlog ⚠ 2 alerts ✖ 1 failed - app.log");
+        state.acknowledge_alerts();
+        assert_eq!(state.terminal_title(), "rtlog ✖ 1 failed - app.log");
+    }
+
+    #[test]
+    fn test_muted_alert_pattern() {
+        let mut state = AppState::new(None, vec![(AlertSeverity::Warn, "timeout".into()), (AlertSeverity::Info, "retry".into())]);
+        state.set_sources([(None, PathBuf::from("a.log"))]);
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        push(&mut state, "upstream timeout");
+        state.acknowledge_alerts();
+        assert!(state.alert_history[0].acknowledged && !state.alert_visible());
+        state.toggle_alert_panel();
+        state.open_mute_prompt();
+        state.submit_prompt();
+        push(&mut state, "upstream timeout");
+        assert_eq!((state.alert_history.len(), state.alert_mute(0).map(|m| m.suppressed)), (1, Some(1)));
+        // A line also matching an unmuted pattern still raises that one
+        push(&mut state, "timeout, will retry");
+        assert_eq!((state.alert_history[1].rule, state.alert_history[1].severity), (Some(1), AlertSeverity::Info));
+        assert_eq!(state.unacked_alerts(), 1);
+        // The panel lists the newest alert first
+        state.alert_panel_move(false);
+        state.unmute_selected_alert();
+        assert!(state.alert_mute(0).is_none());
+    }
+
+    #[test]
+    fn test_source_profiles() {
+        use crate::config::{RedactConfig, SourceProfileEntry};
+        use crate::profile::LineFormat;
+        let mut state = AppState::new(None, vec![(AlertSeverity::Critical, "OutOfMemoryError".into())]);
+        state.add_filter(FilterRule::simple("failed".into(), false));
+        let entry = |path: &str, format| SourceProfileEntry { path: path.into(), name: Some("api".into()), format, timestamp: None, multiline: Some(r"^\s+at |^Caused by".into()), redact: RedactConfig::default(), encoding: None };
+        state.profiles = Profiles::compile(&[entry("api-*.json", LineFormat::Json), entry("*.log", LineFormat::Plain)]).unwrap();
+        state.set_sources([(None, PathBuf::from("/var/log/api-1.json")), (Some("worker".into()), PathBuf::from("worker.log")), (None, PathBuf::from("db.txt"))]);
+        assert_eq!(state.sources.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["api", "worker", "db.txt"]);
+        let (api, worker) = (state.sources[0].id, state.sources[1].id);
+        // The level field decides, not the words in the message
+        state.push_line_for(api, LogLine::new(r#"{"level":"info","msg":"0 requests failed"}"#));
+        assert_eq!((state.sources[0].levels.info, state.sources[0].levels.error), (1, 0));
+        assert_eq!(state.line_level(&state.sources[0], r#"{"level":"info","msg":"error"}"#), Some(Level::Info));
+        let push = |state: &mut AppState, text: &str| state.push_line_for(worker, LogLine::new(text));
+        push(&mut state, "ERROR job 7 failed");
+        push(&mut state, "  at Worker.run(Worker.java:42)");
+        push(&mut state, "Caused by: java.lang.OutOfMemoryError");
+        push(&mut state, "INFO job 8 done");
+        let src = &state.sources[1];
+        assert_eq!(src.lines.len(), 2);
+        assert_eq!(&*src.lines[0], "ERROR job 7 failed\n  at Worker.run(Worker.java:42)\nCaused by: java.lang.OutOfMemoryError");
+        assert_eq!((src.shown, src.levels.error, src.levels.info), (1, 1, 1));
+        // The continuation's alert is raised all the same
+        assert_eq!(state.alert_history.len(), 1);
+    }
+
+    #[test]
+    fn test_timestamp_formats() {
+        let mut state = AppState::new(None, Vec::new());
+        state.timestamp_formats = vec![(Some("db.log".into()), "%d.%m.%Y %H:%M:%S".into()), (None, "[%Y/%m/%d %H:%M:%S]".into())];
+        state.set_sources([(None, PathBuf::from("db.log")), (None, PathBuf::from("app.log"))]);
+        assert_eq!(state.sources[0].timestamp_format.as_deref(), Some("%d.%m.%Y %H:%M:%S"));
+        let id = state.sources[1].id;
+        for text in ["[2025/09/17 11:59:52] started", "2025-09-17 11:59:53 not this format", "[2025/09/17 12:00:01] done"] {
+            state.push_line_for(id, LogLine::new(text));
+        }
+        let src = &state.sources[1];
+        assert_eq!((src.timestamps_parsed, src.timestamps_failed), (2, 1));
+        assert_eq!(src.timestamp(&src.lines[2]).map(|t| t.to_string()).as_deref(), Some("2025-09-17 12:00:01"));
+        // Without a format no line is counted
+        assert_eq!((state.sources[0].timestamps_parsed, state.sources[0].timestamps_failed), (0, 0));
+    }
+
+    #[test]
+    fn test_reloaded_alert_rules() {
+        let mut state = AppState::new(None, vec![(AlertSeverity::Warn, "timeout".into()), (AlertSeverity::Info, "retry".into())]);
+        state.set_sources([(None, PathBuf::from("a.log"))]);
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        push(&mut state, "upstream timeout");
+        push(&mut state, "will retry");
+        state.alert_mutes.insert(0, AlertMute { until_ms: u128::MAX, suppressed: 0 });
+        let mut oom = AlertRule::new("out of memory".into(), false, AlertSeverity::Critical);
+        (oom.name, oom.color) = (Some("OOM".into()), Some("magenta".into()));
+        state.set_alert_rules(vec![oom, AlertRule::new("timeout".into(), false, AlertSeverity::Critical)]);
+        // History and mutes follow "timeout" to its new position; "retry" is gone
+        assert_eq!(state.alert_history.iter().map(|e| e.rule).collect::<Vec<_>>(), [Some(1), None]);
+        assert!(state.alert_mute(1).is_some() && state.alert_mute(0).is_none());
+        assert_eq!(state.alert_severity("java: out of memory"), Some(AlertSeverity::Critical));
+        assert_eq!((state.alert_rules[0].label(), state.alert_rules[1].label()), ("OOM", "timeout"));
+        assert_eq!(state.alert_color("java: out of memory"), Some("magenta"));
+        assert_eq!(state.alert_color("upstream timeout"), None);
+    }
+
+    #[test]
+    fn test_plugin_annotations_survive_undo() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("a.log"))]);
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        let annotate = |state: &mut AppState, index: usize, text: &str| state.apply_plugin_action(PluginAction::Annotate { source: "a.log".into(), index, text: text.into() });
+        push(&mut state, "one");
+        push(&mut state, "two");
+        annotate(&mut state, 1, "second");
+        annotate(&mut state, 5, "out of range");
+        state.clear_focused_source();
+        push(&mut state, "three");
+        // Numbering continues after the cleared lines
+        annotate(&mut state, 0, "gone");
+        annotate(&mut state, 2, "third");
+        state.undo();
+        let notes: Vec<(usize, &str)> = state.sources[0].annotations.iter().map(|(id, n)| (id.0, n.as_str())).collect();
+        assert_eq!(notes, [(1, "second"), (2, "third")]);
+    }
+
+    #[test]
+    fn test_pipe_input_and_derived_output() {
+        let mut state = AppState::new(None, vec![(AlertSeverity::Warn, "ERROR".into())]);
+        state.set_sources([(None, PathBuf::from("a.log"))]);
+        let id = state.sources[0].id;
+        let push = |state: &mut AppState, id: usize, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        for text in ["ERROR one", "INFO two", "ERROR three", "INFO four"] { push(&mut state, id, text); }
+        state.acknowledge_alerts();
+        state.add_filter(FilterRule::simple("ERROR".into(), false));
+        assert_eq!(state.pipe_input(), ["ERROR one", "ERROR three"]);
+        state.context_radius = 1;
+        state.context_panel_open = true;
+        state.sources[0].selected_log = Some(LineId(3));
+        assert_eq!(state.pipe_input(), ["ERROR three", "INFO four"]);
+        // Output restating errors counts for its own source only
+        let out = state.add_source(Some("| uniq -c".into()), PathBuf::new(), Some("uniq -c".into()));
+        state.mark_derived_source(out);
+        push(&mut state, out, "2 ERROR");
+        assert_eq!((state.alert_history.len(), state.filters[0].match_count), (2, 0));
+        assert_eq!(state.sources[1].lines.len(), 1);
+    }
+
+    #[test]
+    fn test_range_selection() {
+        let mut state = AppState::new(None, Vec::new());
+        state.set_sources([(None, PathBuf::from("a.log"))]);
+        let id = state.sources[0].id;
+        for text in ["ERROR one", "INFO two", "ERROR three", "INFO four", "ERROR five"] {
+            state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        }
+        state.add_filter(FilterRule::simple("ERROR".into(), false));
+        state.sources[0].selected_log = Some(LineId(4));
+        state.toggle_range();
+        // Extending over the filtered gaps: hidden lines are left out unless asked for
+        state.move_log_selection_by(-2);
+        assert_eq!(state.range_lines(&state.sources[0]), Some(vec![0, 2, 4]));
+        state.toggle_range_hidden();
+        assert_eq!(state.range_lines(&state.sources[0]), Some(vec![0, 1, 2, 3, 4]));
+        assert_eq!(state.pipe_input().len(), 5);
+        state.copy_selection();
+        assert_eq!(state.clipboard.as_deref(), Some("ERROR one\nINFO two\nERROR three\nINFO four\nERROR five"));
+        assert_eq!(state.sources[0].range_anchor, None);
+        // Without a range the selected line is copied
+        state.copy_selection();
+        assert_eq!(state.clipboard.as_deref(), Some("ERROR one"));
+        // Exporting the range ends it; the next export takes the visible lines again
+        state.toggle_range();
+        state.move_log_selection_by(1);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("range.log");
+        state.export_selection(&path.display().to_string()).unwrap();
+        let exported = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(exported, "ERROR one\nERROR three\n");
+        state.export_selection(&path.display().to_string()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_filter_groups() {
+        let mut state = AppState::default();
+        for pattern in ["healthcheck", "payment", "metrics", "refund"] {
+            state.add_filter(FilterRule::simple(pattern.into(), false));
+        }
+        let group = |state: &mut AppState, index: usize, name: &str| {
+            (state.selected_filter, state.filter_header) = (index, false);
+            state.open_filter_group_prompt();
+            state.prompt.as_mut().unwrap().input = name.into();
+            state.submit_prompt();
+        };
+        group(&mut state, 0, "noise");
+        group(&mut state, 2, " noise ");
+        group(&mut state, 3, "payment-flow");
+        group(&mut state, 1, "payment-flow");
+        let group_row = |name: &str, members: Vec<usize>, collapsed: bool| FilterRow::Group { name: name.into(), members, collapsed };
+        assert_eq!(state.filter_rows(), [group_row("noise", vec![0, 2], false), FilterRow::Filter(0), FilterRow::Filter(2),
+            group_row("payment-flow", vec![1, 3], false), FilterRow::Filter(1), FilterRow::Filter(3)]);
+        // Space on a header switches the whole group, and undo switches it back
+        state.selected_filter = 0;
+        state.move_selection_up();
+        assert!(state.filter_header);
+        state.toggle_selected_filter();
+        assert_eq!(state.filters.iter().map(|f| f.enabled).collect::<Vec<_>>(), [false, true, false, true]);
+        state.remove_selected_filter();
+        assert_eq!(state.filters.len(), 4);
+        state.undo();
+        assert!(state.filters.iter().all(|f| f.enabled));
+        // A collapsed group is one row, selected through its header
+        state.toggle_filter_group_collapsed();
+        assert_eq!(state.filter_rows().len(), 4);
+        state.move_selection_down();
+        assert_eq!((state.selected_filter, state.filter_header), (1, true));
+        state.move_selection_down();
+        assert_eq!((state.selected_filter, state.filter_header), (1, false));
+        group(&mut state, 1, "");
+        assert_eq!(state.filter_rows()[0], FilterRow::Filter(1));
+    }
+
+    #[test]
+    fn test_replace_preview() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("api.log"))]);
+        let id = state.sources[0].id;
+        for text in ["GET /a user=alice took 12ms", "health check", "GET /b user=bob took 7ms"] {
+            state.push_line_for(id, LogLine::new(text));
+        }
+        state.toggle_replace_preview();
+        assert!(state.prompt.is_none() && state.replace_preview.is_none());
+        state.search_input = r"user=(?P<user>\w+) took (\d+)ms".into();
+        state.search_is_regex = true;
+        state.apply_search();
+        state.toggle_replace_preview();
+        state.prompt.as_mut().unwrap().input = "$user $3".into();
+        state.submit_prompt();
+        assert_eq!(state.prompt.as_ref().and_then(|p| p.error.as_deref()), Some("no group 3 in the search"));
+        state.prompt.as_mut().unwrap().input = "${user}=$2".into();
+        state.submit_prompt();
+        // Only the matching lines, replaced on screen and in exports; the buffer keeps the text
+        let src = &state.sources[0];
+        let shown: Vec<String> = state.shown_lines(src).into_iter().map(|i| state.display_log_text(&src.lines[i]).into_owned()).collect();
+        assert_eq!(shown, ["GET /a alice=12", "GET /b bob=7"]);
+        assert_eq!(&*src.lines[0], "GET /a user=alice took 12ms");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replaced.log");
+        state.export_visible(&path.display().to_string()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "GET /a alice=12\nGET /b bob=7\n");
+        state.toggle_replace_preview();
+        assert_eq!(state.shown_lines(&state.sources[0]).len(), 3);
+    }
+
+    #[test]
+    fn test_stop_reading_and_export_all() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("api.log")), (None, PathBuf::from("db.log"))]);
+        let (api, db) = (state.sources[0].id, state.sources[1].id);
+        state.push_line_for(api, LogLine::new("ERROR api down"));
+        state.push_line_for(db, LogLine::new("INFO db ready"));
+        state.push_line_for(db, LogLine::new("ERROR db full"));
+        state.set_source_error(api, "gone".into());
+        assert_eq!(state.lines_received(), 3);
+        state.stop_reading("10m elapsed (--duration)");
+        assert!(!state.sources[0].closed && state.sources[1].closed);
+        assert_eq!(state.current_message().map(|m| m.text.as_str()), Some("stopped reading: 10m elapsed (--duration)"));
+        state.add_filter(FilterRule::simple("ERROR".into(), false));
+        state.export_format = ExportFormat::Jsonl;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("all.jsonl");
+        assert_eq!(state.export_all(&path.display().to_string()), Ok(2));
+        let exported = std::fs::read_to_string(&path).unwrap();
+        let sources: Vec<&str> = exported.lines().map(|l| if l.contains(r#""source":"api.log""#) { "api" } else { "db" }).collect();
+        assert_eq!(sources, ["api", "db"]);
+    }
+
+    #[test]
+    fn test_selected_file_location() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("handler.rs"), "fn main() {}\n").unwrap();
+        let mut state = AppState::new(None, Vec::new());
+        state.file_regex = Some(regex::Regex::new(crate::editor::DEFAULT_FILE_PATTERN).unwrap());
+        state.set_sources([(None, dir.join("app.log"))]);
+        let id = state.sources[0].id;
+        for text in ["panicked at gone.rs:1:1, called from handler.rs:12:5", "no location", "at missing.rs:3"] {
+            state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        }
+        // A relative path that is not in the working directory is found next to the log file
+        state.sources[0].selected_log = Some(LineId(0));
+        let location = state.selected_file_location().unwrap();
+        assert_eq!((location.path, location.line, location.column), (dir.join("handler.rs"), 12, Some(5)));
+        state.sources[0].selected_log = Some(LineId(1));
+        assert_eq!(state.selected_file_location(), Err("no file:line in the selected line".into()));
+        state.sources[0].selected_log = Some(LineId(2));
+        assert_eq!(state.selected_file_location(), Err("missing.rs: no such file".into()));
+    }
+
+    #[test]
+    fn test_insert_marker() {
+        let mut state = AppState::default();
+        state.set_sources([(None, PathBuf::from("a.log")), (None, PathBuf::from("b.log"))]);
+        state.insert_marker(false);
+        assert!(state.sources[0].is_separator(0));
+        assert!(state.sources[1].lines.is_empty());
+        state.insert_marker(true);
+        assert_eq!((state.sources[0].lines.len(), state.sources[1].lines.len()), (2, 1));
+        assert_eq!(state.sources[1].unread, 1);
+        // Separators of --watch runs are markers too and count for nothing
+        state.push_line_for(state.sources[1].id, LogLine::separator("---- RUN 10:00:00 $ grep ERROR app.log ----"));
+        assert!(state.sources[1].is_separator(1));
+        assert_eq!(state.sources[1].levels, LevelCounts::default());
+        // Lines of the input shaped like a marker or a run separator are filtered and counted
+        // like any other
+        state.add_filter(FilterRule::simple("disk".into(), false));
+        for text in ["---- MARK 10:00:00 ERROR ----", "---- RUN 10:00:00 $ ERROR ----"] {
+            state.push_line_for(state.sources[1].id, LogLine { text: text.into(), class: LineClass { is_error: true, ..Default::default() } });
+        }
+        assert!(!state.sources[1].is_separator(2) && !state.sources[1].is_separator(3));
+        assert_eq!((state.sources[1].levels.error, state.shown_lines(&state.sources[1])), (2, vec![0, 1]));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut state = AppState::new(None, Vec::new());
+        state.set_sources([(None, PathBuf::from("a.log")), (None, PathBuf::from("b.log"))]);
+        let id = state.sources[0].id;
+        for text in ["2025-01-02 10:00:00 ERROR job 1 failed", "2025-01-02 10:00:30 job 2 done", "2025-01-02 10:04:00 ERROR job 3 failed"] {
+            state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        }
+        state.toggle_summary();
+        let view = state.summary.as_ref().unwrap();
+        assert_eq!(view.loading, 2);
+        let a = &view.sources[0];
+        assert_eq!((a.lines, a.levels.error, a.top[0].count), (3, 2, 2));
+        assert_eq!(a.span.map(|(first, last)| (last - first).num_seconds()), Some(240));
+        assert_eq!((view.sources[1].lines, view.sources[1].span), (0, None));
+        state.set_source_closed(state.sources[0].id);
+        state.toggle_summary();
+        state.toggle_summary();
+        state.set_source_closed(state.sources[1].id);
+        assert_eq!(state.summary.as_ref().unwrap().loading, 1);
+        state.toggle_summary();
+        assert!(state.summary.is_none());
+    }
+
+    #[test]
+    fn test_gap_separators() {
+        let mut state = AppState { gap_threshold: Some(chrono::TimeDelta::seconds(60)), ..Default::default() };
+        state.set_sources([(None, PathBuf::from("a.log"))]);
+        let id = state.sources[0].id;
+        for text in ["2025-01-02 10:00:00 start", "2025-01-02 10:00:05 ok", "no timestamp", "2025-01-02 10:06:37 restarted", "2025-01-02 10:06:00 late"] {
+            state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+        }
+        let lines: Vec<&str> = state.sources[0].lines.iter().map(|l| &l[..]).collect();
+        assert_eq!(lines[3], "---- GAP 6m 32s ----");
+        assert_eq!((lines.len(), state.sources[0].shown), (6, 6));
+        assert!(state.sources[0].is_separator(3));
+        // A line of the input shaped like one is not
+        state.add_filter(FilterRule::simple("restarted".into(), false));
+        state.push_line_for(id, LogLine::new("---- GAP 1h ----"));
+        assert!(!state.sources[0].is_separator(6));
+        assert_eq!(state.shown_lines(&state.sources[0]), [3, 4]);
+    }
+
+    #[test]
+    fn test_control_commands() {
+        let mut state = AppState::new(None, Vec::new());
+        state.set_sources([(None, PathBuf::from("/srv/api.log")), (None, PathBuf::from("/srv/db.log"))]);
+        let id = state.sources[1].id;
+        for text in ["connect timeout", "query ok"] { state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() }); }
+        assert!(state.apply_control(ControlCommand::Filter { pattern: "(".into(), regex: true }).is_err());
+        assert!(state.filters.is_empty());
+        state.apply_control(ControlCommand::Filter { pattern: "TIMEOUT".into(), regex: false }).unwrap();
+        assert_eq!(state.apply_control(ControlCommand::Focus { source: "nope.log".into() }), Err("no source named nope.log".into()));
+        state.apply_control(ControlCommand::Focus { source: "/srv/db.log".into() }).unwrap();
+        assert_eq!(state.focused, 1);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.csv");
+        state.apply_control(ControlCommand::Export { path: path.display().to_string(), format: Some("csv".into()) }).unwrap();
+        let exported = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(exported.lines().count(), 2);
+        assert!(exported.contains("connect timeout") && !exported.contains("query ok"));
+        assert_eq!(state.export_format, ExportFormat::Text);
+        state.raise_alert(AlertSeverity::Warn, "disk almost full".into());
+        let status = state.apply_control(ControlCommand::Status).unwrap();
+        assert_eq!((status["focused"].as_str(), status["unacked_alerts"].as_u64()), (Some("db.log"), Some(1)));
+        assert_eq!(status["sources"][1]["shown"], 1);
+        state.apply_control(ControlCommand::Ack).unwrap();
+        assert_eq!(state.unacked_alerts(), 0);
+    }
+
+    #[test]
+    fn test_disambiguated_names() {
+        let paths = [Path::new("/srv/api/app.log"), Path::new("/srv/web/app.log"), Path::new("/srv/web/db.log")];
+        assert_eq!(disambiguated_names(&paths), vec!["app.log (api)", "app.log (web)", "db.log"]);
+        let deep = [Path::new("/a/x/logs/app.log"), Path::new("/b/x/logs/app.log")];
+        assert_eq!(disambiguated_names(&deep), vec!["app.log (a/x/logs)", "app.log (b/x/logs)"]);
+    }
+}
@@ -0,0 +1,137 @@
+//! The status bar template (`status_bar` in the config file).
+//!
+//! The template is text with fields in braces, like `{lines} {rate}/s {filters} {mode}`; `{{` and
+//! `}}` stand for braces. Front ends render the fields from the state; notices (messages, source
+//! errors, modes like RANGE or REPLAY) are shown before the template whatever it says.
+
+use anyhow::{bail, Result};
+
+/// Template of the status bar without `status_bar` in the config file
+pub const DEFAULT_STATUS_TEMPLATE: &str = "{levels}{showing}  Scroll: {scroll}  Mode: {mode}  Filters: {filters}  {keys}  {flags}";
+
+/// What a field of the template shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusField {
+    /// Lines of the focused source
+    Lines,
+    /// Lines of the focused source the filters let through
+    Shown,
+    /// `Lines: N`, or `Showing N of M lines` while the filters hide some
+    Showing,
+    /// Lines per second the focused source received in the last second
+    Rate,
+    /// Number of active filters
+    Filters,
+    /// Following or paused, with the paused sources counted
+    Mode,
+    /// Name of the focused source
+    Source,
+    /// Number of sources
+    Sources,
+    /// Lines scrolled up from the bottom
+    Scroll,
+    /// Error, warning and info counts of all sources, colored
+    Levels,
+    Errors,
+    Warnings,
+    /// Key hints of the filter panel
+    Keys,
+    /// Flags of the filter input (regex, fuzzy, case, word, line)
+    Flags,
+    /// Local time of day
+    Time,
+}
+
+const FIELDS: [(&str, StatusField); 15] = [
+    ("lines", StatusField::Lines),
+    ("shown", StatusField::Shown),
+    ("showing", StatusField::Showing),
+    ("rate", StatusField::Rate),
+    ("filters", StatusField::Filters),
+    ("mode", StatusField::Mode),
+    ("source", StatusField::Source),
+    ("sources", StatusField::Sources),
+    ("scroll", StatusField::Scroll),
+    ("levels", StatusField::Levels),
+    ("errors", StatusField::Errors),
+    ("warnings", StatusField::Warnings),
+    ("keys", StatusField::Keys),
+    ("flags", StatusField::Flags),
+    ("time", StatusField::Time),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusPart {
+    Text(String),
+    Field(StatusField),
+}
+
+/// A parsed status bar template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusTemplate(Vec<StatusPart>);
+
+impl StatusTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => { chars.next(); text.push('{'); }
+                '}' if chars.peek() == Some(&'}') => { chars.next(); text.push('}'); }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("status_bar: unclosed {{{}", name),
+                        }
+                    }
+                    let Some(&(_, field)) = FIELDS.iter().find(|(n, _)| *n == name.trim()) else {
+                        let known: Vec<&str> = FIELDS.iter().map(|(n, _)| *n).collect();
+                        bail!("status_bar: unknown field {{{}}} (known: {})", name, known.join(", "));
+                    };
+                    if !text.is_empty() { parts.push(StatusPart::Text(std::mem::take(&mut text))); }
+                    parts.push(StatusPart::Field(field));
+                }
+                '}' => bail!("status_bar: unmatched }} (write }}}} for a brace)"),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() { parts.push(StatusPart::Text(text)); }
+        Ok(Self(parts))
+    }
+
+    pub fn parts(&self) -> &[StatusPart] {
+        &self.0
+    }
+}
+
+impl Default for StatusTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_STATUS_TEMPLATE).expect("default status template is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        let template = StatusTemplate::parse("{lines} {rate}/s {{raw}} [{ source }]").unwrap();
+        assert_eq!(template.parts(), [
+            StatusPart::Field(StatusField::Lines),
+            StatusPart::Text(" ".into()),
+            StatusPart::Field(StatusField::Rate),
+            StatusPart::Text("/s {raw} [".into()),
+            StatusPart::Field(StatusField::Source),
+            StatusPart::Text("]".into()),
+        ]);
+        let err = StatusTemplate::parse("{lines} {speed}").unwrap_err().to_string();
+        assert!(err.starts_with("status_bar: unknown field {speed} (known: lines, shown"), "{}", err);
+        assert!(StatusTemplate::parse("{lines").is_err() && StatusTemplate::parse("a } b").is_err());
+        assert_eq!(StatusTemplate::default().parts().len(), 12);
+    }
+}
@@ -0,0 +1,209 @@
+//! Reading a file as it grows, synchronously, a batch of lines at a time.
+//!
+//! This is the core of the file reader (`log::FileTail`, which runs it on the blocking pool) and
+//! of `--simple` (which runs it on the UI thread): where reading starts, the encoding, the
+//! `--resume` offsets, and with `follow` the file being truncated, rotated or deleted. The caller
+//! decides when to read again; `Tail` itself never waits.
+
+use std::fs::{self, File, Metadata};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::encoding::{read_line, Encoding, DETECT_BYTES};
+use crate::log::{describe_io_error, same_file, LogLine, SourceEvent};
+use crate::offsets::TrackedFile;
+
+/// A file read synchronously; with `follow` it is reopened when it is replaced or comes back
+pub struct Tail {
+    path: PathBuf,
+    follow: bool,
+    file: Option<(BufReader<File>, Metadata)>,
+    /// Offset of the next byte to read
+    pos: u64,
+    /// Only the first open of a followed file starts at its end; files that come back are read
+    /// from the start
+    from_end: bool,
+    waiting: bool,
+    /// Bytes of a line whose end has not been written yet
+    pending: Vec<u8>,
+    done: bool,
+    /// With `--resume`: where the last session stopped reading, and how far this one gets
+    pub tracked: Option<TrackedFile>,
+    /// Told from the start of the file when None
+    pub encoding: Option<Encoding>,
+    /// Encoding of the open file
+    reading: Encoding,
+}
+
+impl Tail {
+    pub fn new(path: PathBuf, follow: bool) -> Self {
+        Self { path, follow, file: None, pos: 0, from_end: follow, waiting: false, pending: Vec::new(), done: false, tracked: None, encoding: None, reading: Encoding::Utf8 }
+    }
+
+    /// Whether the file is missing and waited for (with `follow`)
+    pub fn is_waiting(&self) -> bool { self.waiting }
+
+    /// Read nothing more
+    pub fn stop(&mut self) { self.done = true; }
+
+    /// Read up to `batch` lines into `events`; true when there may be more right away. The file
+    /// ends with `Closed` without `follow`, or with `Error` when it cannot be read; a followed file
+    /// that is missing is reported `Waiting` once, and `Reopened` when it is back.
+    pub fn read(&mut self, events: &mut Vec<SourceEvent>, batch: usize) -> bool {
+        if self.done || self.file.is_none() && !self.open(events) { return false; }
+        let Some((reader, opened)) = self.file.as_mut() else { return false; };
+        let start = events.len();
+        while events.len() - start < batch {
+            match read_line(reader, self.reading, &mut self.pending) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.pos += n as u64;
+                    if !self.reading.ends_line(&self.pending) { continue; }
+                    events.push(take_line(&mut self.pending, self.reading));
+                    if let Some(tracked) = &self.tracked { tracked.record(opened, self.pos); }
+                }
+                Err(e) => {
+                    events.push(SourceEvent::Error(describe_io_error(&e, &self.path)));
+                    self.done = true;
+                    return false;
+                }
+            }
+        }
+        if events.len() - start >= batch { return true; }
+        if !self.follow {
+            if !self.pending.is_empty() { events.push(take_line(&mut self.pending, self.reading)); }
+            events.push(SourceEvent::Closed);
+            self.done = true;
+            return false;
+        }
+        // At the end: notice deletion, rotation and truncation
+        match fs::metadata(&self.path) {
+            Err(e) => {
+                self.file = None;
+                self.waiting = true;
+                events.push(SourceEvent::Waiting(describe_io_error(&e, &self.path)));
+                false
+            }
+            // Rotated: reopen right away
+            Ok(md) if !same_file(opened, &md) => {
+                self.file = None;
+                true
+            }
+            // Truncated in place (copytruncate): start over from the beginning
+            Ok(md) if md.len() < self.pos => {
+                self.pos = reader.seek(SeekFrom::Start(0)).unwrap_or(0);
+                self.pending.clear();
+                if let Some(tracked) = &self.tracked { tracked.record(opened, self.pos); }
+                true
+            }
+            Ok(_) => false,
+        }
+    }
+
+    /// Open the file, false when it cannot be (reported once while following, else an error)
+    fn open(&mut self, events: &mut Vec<SourceEvent>) -> bool {
+        let from_end = std::mem::replace(&mut self.from_end, false);
+        let opened = File::open(&self.path).and_then(|mut file| {
+            let encoding = match self.encoding {
+                Some(encoding) => encoding,
+                None => {
+                    let mut head = Vec::with_capacity(DETECT_BYTES);
+                    (&mut file).take(DETECT_BYTES as u64).read_to_end(&mut head)?;
+                    file.rewind()?;
+                    Encoding::detect(&head).unwrap_or_default()
+                }
+            };
+            let saved = match &self.tracked {
+                Some(tracked) if from_end => tracked.start(&file.metadata()?),
+                _ => None,
+            };
+            let pos = if from_end { file.seek(saved.map_or(SeekFrom::End(0), SeekFrom::Start))? } else { 0 };
+            Ok((file.metadata()?, file, pos, encoding))
+        });
+        match opened {
+            Ok((metadata, file, pos, encoding)) => {
+                self.reading = encoding;
+                if std::mem::take(&mut self.waiting) { events.push(SourceEvent::Reopened); }
+                // Where reading starts is recorded too, so a session that read nothing resumes from there
+                if let Some(tracked) = &self.tracked { tracked.record(&metadata, pos); }
+                self.file = Some((BufReader::new(file), metadata));
+                self.pos = pos;
+                self.pending.clear();
+                true
+            }
+            Err(e) if self.follow => {
+                if !self.waiting { events.push(SourceEvent::Waiting(describe_io_error(&e, &self.path))); }
+                self.waiting = true;
+                false
+            }
+            Err(e) => {
+                events.push(SourceEvent::Error(describe_io_error(&e, &self.path)));
+                self.done = true;
+                false
+            }
+        }
+    }
+}
+
+/// The line in `pending` without its terminator, decoded lossily
+fn take_line(pending: &mut Vec<u8>, encoding: Encoding) -> SourceEvent {
+    SourceEvent::Line(LogLine::new(encoding.decode(&std::mem::take(pending))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "old line\n").unwrap();
+        let texts = |events: &[SourceEvent]| events.iter().map(|ev| match ev {
+            SourceEvent::Line(line) => line.text.to_string(),
+            other => format!("{:?}", other),
+        }).collect::<Vec<_>>();
+        let mut events = Vec::new();
+        // Followed, the file is read from its end; a line is only sent once it is complete
+        let mut tail = Tail::new(path.clone(), true);
+        assert!(!tail.read(&mut events, 10) && events.is_empty());
+        fs::write(&path, "old line\nnew line\r\npart").unwrap();
+        tail.read(&mut events, 10);
+        assert_eq!(texts(&events), ["new line"]);
+        events.clear();
+        // Truncated: read again from the start
+        fs::write(&path, "after\n").unwrap();
+        while tail.read(&mut events, 10) {}
+        assert_eq!(texts(&events), ["after"]);
+        events.clear();
+        // Deleted: waiting, reported once, until it is back
+        fs::remove_file(&path).unwrap();
+        tail.read(&mut events, 10);
+        tail.read(&mut events, 10);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], SourceEvent::Waiting(reason) if reason.starts_with("not found: ")) && tail.is_waiting());
+        events.clear();
+        fs::write(&path, "back\n").unwrap();
+        tail.read(&mut events, 10);
+        assert_eq!(texts(&events), ["Reopened", "back"]);
+        events.clear();
+        // Not followed: everything in batches, the last line even without its newline, then closed
+        fs::write(&path, "a\nb\nc").unwrap();
+        let mut tail = Tail::new(path.clone(), false);
+        assert!(tail.read(&mut events, 2));
+        assert!(!tail.read(&mut events, 2));
+        assert_eq!(texts(&events), ["a", "b", "c", "Closed"]);
+        assert!(!tail.read(&mut events, 2) && events.len() == 4);
+        events.clear();
+        // UTF-16 is told from the byte order mark
+        let utf16: Vec<u8> = [0xFF, 0xFE].into_iter().chain("a\r\nb\n".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        fs::write(&path, utf16).unwrap();
+        let mut tail = Tail::new(path.clone(), false);
+        tail.read(&mut events, 10);
+        assert_eq!(texts(&events), ["a", "b", "Closed"]);
+        events.clear();
+        let mut tail = Tail::new(dir.path().join("missing.log"), false);
+        tail.read(&mut events, 10);
+        assert!(matches!(&events[..], [SourceEvent::Error(e)] if e.starts_with("not found: ")));
+    }
+}
@@ -0,0 +1,99 @@
+//! Copying received lines to files (`--tee`).
+//!
+//! Each tee file gets a writer task fed through a bounded channel; readers hand their lines to
+//! it in `EventSender::send`, so disk I/O never runs on the UI thread and a slow disk slows the
+//! readers down instead of growing memory. The writer flushes whenever it runs out of queued
+//! lines, so the file is current during bursts and complete once the app shuts down. Lines are
+//! redacted (see `crate::redact`) before they are queued.
+
+use std::borrow::Cow;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::task::JoinHandle;
+
+use crate::redact::Redactor;
+
+/// Handle for appending lines to one tee file
+#[derive(Clone)]
+pub struct Tee {
+    tx: Sender<Arc<str>>,
+    redactor: Arc<Redactor>,
+}
+
+impl Tee {
+    /// Queue a line; silently dropped once the writer has stopped (after a write error)
+    pub async fn write(&self, line: &Arc<str>) {
+        let line = match self.redactor.redact(line) {
+            Cow::Borrowed(_) => Arc::clone(line),
+            Cow::Owned(redacted) => redacted.into(),
+        };
+        let _ = self.tx.send(line).await;
+    }
+}
+
+/// The tee files from the command line: global ones receive every source, named ones only the
+/// source with that name
+pub struct Tees {
+    global: Vec<Tee>,
+    named: Vec<(String, Tee)>,
+    writers: Vec<(PathBuf, JoinHandle<io::Result<()>>)>,
+}
+
+impl Tees {
+    /// Open (append to) every `[SOURCE=]PATH` target and start its writer
+    pub async fn open(specs: &[(Option<String>, PathBuf)], redactor: Arc<Redactor>) -> Result<Self> {
+        let mut tees = Self { global: Vec::new(), named: Vec::new(), writers: Vec::new() };
+        for (source, path) in specs {
+            let file = OpenOptions::new().create(true).append(true).open(path).await
+                .with_context(|| format!("cannot open tee file {}", path.display()))?;
+            let (tx, rx) = mpsc::channel(1024);
+            tees.writers.push((path.clone(), tokio::spawn(write_lines(BufWriter::new(file), rx))));
+            let tee = Tee { tx, redactor: Arc::clone(&redactor) };
+            match source {
+                Some(name) => tees.named.push((name.clone(), tee)),
+                None => tees.global.push(tee),
+            }
+        }
+        Ok(tees)
+    }
+
+    /// Tees for a source known by any of `names` (label, display name, path as given)
+    pub fn for_source(&self, names: &[String]) -> Vec<Tee> {
+        let named = self.named.iter().filter(|(n, _)| names.contains(n)).map(|(_, t)| t.clone());
+        self.global.iter().cloned().chain(named).collect()
+    }
+
+    /// Source names of `--tee SOURCE=PATH` targets
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.named.iter().map(|(n, _)| n.as_str())
+    }
+
+    /// Wait until the writers have flushed everything queued. Readers must be stopped first,
+    /// since each writer ends once the last handle to it is dropped.
+    pub async fn finish(self) -> Vec<String> {
+        drop(self.global);
+        drop(self.named);
+        let mut errors = Vec::new();
+        for (path, writer) in self.writers {
+            if let Err(e) = writer.await.map_err(io::Error::other).and_then(|res| res) {
+                errors.push(format!("tee {}: {}", path.display(), e));
+            }
+        }
+        errors
+    }
+}
+
+async fn write_lines<W: tokio::io::AsyncWrite + Unpin>(mut out: BufWriter<W>, mut rx: Receiver<Arc<str>>) -> io::Result<()> {
+    while let Some(line) = rx.recv().await {
+        out.write_all(line.as_bytes()).await?;
+        out.write_all(b"\n").await?;
+        if rx.is_empty() { out.flush().await?; }
+    }
+    out.flush().await
+}
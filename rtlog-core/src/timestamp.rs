@@ -0,0 +1,255 @@
+//! Timestamp detection for free-text log lines.
+//!
+//! rtlog stays file-agnostic, so timestamps are found heuristically: the first ISO-8601-like
+//! (`2025-09-17T11:59:52.505Z`, `2025/09/17 11:59:52`) or syslog-like (`Sep 17 11:59:52`) token in
+//! a line is parsed. Values are kept as written (wall-clock), any trailing UTC offset is ignored.
+//!
+//! For display only, `--tz` converts that timestamp to another zone: a trailing offset (`Z`,
+//! `+02:00`, `-0500`) says which zone it was written in, and timestamps without one are taken as
+//! local time.
+
+use std::borrow::Cow;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeDelta, TimeZone, Utc};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+fn iso_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(\d{4})[-/](\d{2})[-/](\d{2})[T ](\d{2}):(\d{2}):(\d{2})(?:[.,](\d{1,9}))?( ?(?:Z|[+-]\d{2}:?\d{2}\b))?").unwrap()
+    })
+}
+
+fn syslog_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b(Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) +(\d{1,2}) (\d{2}):(\d{2}):(\d{2})\b").unwrap()
+    })
+}
+
+fn time_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(\d{1,2}):(\d{2})(?::(\d{2})(?:[.,](\d{1,9}))?)?$").unwrap())
+}
+
+/// Find and parse the first timestamp in `line`
+pub fn parse_timestamp(line: &str) -> Option<NaiveDateTime> {
+    if let Some(c) = iso_regex().captures(line) {
+        let num = |i: usize| c[i].parse::<u32>().ok();
+        let date = NaiveDate::from_ymd_opt(c[1].parse().ok()?, num(2)?, num(3)?)?;
+        let nanos = c.get(7).map(|m| frac_to_nanos(m.as_str())).unwrap_or(0);
+        let time = NaiveTime::from_hms_nano_opt(num(4)?, num(5)?, num(6)?, nanos)?;
+        return Some(date.and_time(time));
+    }
+    if let Some(c) = syslog_regex().captures(line) {
+        let month = month_number(&c[1])?;
+        let num = |i: usize| c[i].parse::<u32>().ok();
+        // Syslog omits the year; assume the current one
+        let year = chrono::Local::now().year();
+        let date = NaiveDate::from_ymd_opt(year, month, num(2)?)?;
+        let time = NaiveTime::from_hms_opt(num(3)?, num(4)?, num(5)?)?;
+        return Some(date.and_time(time));
+    }
+    None
+}
+
+/// Parse the first timestamp in `line` written in the chrono `format` (`%d/%b/%Y:%H:%M:%S %z`):
+/// tried wherever a word or punctuation starts, so it can follow a prefix such as a level. A
+/// UTC offset in the format is parsed but ignored, like in detected timestamps.
+pub fn parse_timestamp_with(line: &str, format: &str) -> Option<NaiveDateTime> {
+    let mut prev: Option<char> = None;
+    for (at, c) in line.char_indices() {
+        let mid_word = prev.is_some_and(char::is_alphanumeric) && c.is_alphanumeric();
+        prev = Some(c);
+        if mid_word { continue; }
+        if let Ok((ts, _)) = NaiveDateTime::parse_and_remainder(&line[at..], format) { return Some(ts); }
+    }
+    None
+}
+
+/// Whether `format` is a chrono format string without unknown or incomplete specifiers
+pub fn is_valid_format(format: &str) -> bool {
+    !format.is_empty() && !StrftimeItems::new(format).any(|item| item == Item::Error)
+}
+
+/// Zone timestamps are shown in (`--tz`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayZone {
+    Utc,
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+impl DisplayZone {
+    /// `UTC`, `local` or an IANA zone name such as `Europe/Berlin`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "utc" | "z" => Some(DisplayZone::Utc),
+            "local" => Some(DisplayZone::Local),
+            _ => s.parse().ok().map(DisplayZone::Named),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self { DisplayZone::Utc => "UTC".into(), DisplayZone::Local => "local".into(), DisplayZone::Named(tz) => tz.name().into() }
+    }
+
+    /// Wall-clock time and UTC offset of `t` in this zone
+    fn wall_clock(&self, t: DateTime<Utc>) -> (NaiveDateTime, FixedOffset) {
+        match self {
+            DisplayZone::Utc => (t.naive_utc(), Utc.fix()),
+            DisplayZone::Local => { let t = t.with_timezone(&Local); (t.naive_local(), t.offset().fix()) }
+            DisplayZone::Named(tz) => { let t = t.with_timezone(tz); (t.naive_local(), t.offset().fix()) }
+        }
+    }
+}
+
+/// `line` with its first timestamp converted to `zone`, written in the same format; borrowed
+/// when it has none
+pub fn convert_timestamp(line: &str, zone: DisplayZone) -> Cow<'_, str> {
+    if let Some(c) = iso_regex().captures(line)
+        && let Some(ts) = parse_timestamp(line)
+        && let Some(t) = to_utc(ts, c.get(8).map(|m| m.as_str().trim()))
+    {
+        let (wall, offset) = zone.wall_clock(t);
+        let whole = c.get(0).unwrap();
+        return Cow::Owned(format!("{}{}{}", &line[..whole.start()], format_iso(&c, wall, offset), &line[whole.end()..]));
+    }
+    if let Some(whole) = syslog_regex().find(line)
+        && let Some(ts) = parse_timestamp(line)
+        && let Some(t) = to_utc(ts, None)
+    {
+        let (wall, _) = zone.wall_clock(t);
+        return Cow::Owned(format!("{}{}{}", &line[..whole.start()], wall.format("%b %e %H:%M:%S"), &line[whole.end()..]));
+    }
+    Cow::Borrowed(line)
+}
+
+/// `ts` written with the UTC `offset` (`Z`, `+02:00`, `-0500`), or in local time without one
+fn to_utc(ts: NaiveDateTime, offset: Option<&str>) -> Option<DateTime<Utc>> {
+    let Some(offset) = offset else { return Local.from_local_datetime(&ts).earliest().map(|t| t.with_timezone(&Utc)); };
+    let secs = if offset == "Z" { 0 } else {
+        let digits = offset[1..].replace(':', "");
+        let minutes = digits[..2].parse::<i32>().ok()? * 60 + digits[2..].parse::<i32>().ok()?;
+        if offset.starts_with('-') { -minutes * 60 } else { minutes * 60 }
+    };
+    FixedOffset::east_opt(secs)?.from_local_datetime(&ts).single().map(|t| t.with_timezone(&Utc))
+}
+
+/// `wall` in the shape of the ISO timestamp captured in `c`: same separators, fraction digits
+/// and offset style (no offset when it had none)
+fn format_iso(c: &Captures, wall: NaiveDateTime, offset: FixedOffset) -> String {
+    let whole = &c[0];
+    let (date_sep, time_sep) = (&whole[4..5], &whole[10..11]);
+    let mut out = format!("{}{}{}{}{}", wall.format("%Y"), date_sep, wall.format("%m"), date_sep, wall.format("%d"));
+    out.push_str(time_sep);
+    out.push_str(&wall.format("%H:%M:%S").to_string());
+    if let Some(frac) = c.get(7) {
+        let at = frac.start() - c.get(0).unwrap().start();
+        let nanos = format!("{:09}", wall.and_utc().timestamp_subsec_nanos());
+        out.push_str(&whole[at - 1..at]);
+        out.push_str(&nanos[..frac.len()]);
+    }
+    if let Some(written) = c.get(8).map(|m| m.as_str()) {
+        let secs = offset.local_minus_utc();
+        let (sign, minutes) = (if secs < 0 { '-' } else { '+' }, secs.abs() / 60);
+        if written.starts_with(' ') { out.push(' '); }
+        let written = written.trim();
+        if written == "Z" && secs == 0 {
+            out.push('Z');
+        } else if written.contains(':') || written == "Z" {
+            out.push_str(&format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60));
+        } else {
+            out.push_str(&format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60));
+        }
+    }
+    out
+}
+
+/// A gap between timestamps as `45s`, `4m 32s`, `2h 05m` or `3d 4h` (`0.8s` below ten seconds)
+pub fn format_gap(gap: TimeDelta) -> String {
+    let secs = gap.num_seconds();
+    match secs {
+        ..10 => format!("{:.1}s", gap.num_milliseconds() as f64 / 1000.0),
+        10..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        3600..86400 => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// Parse a bare time of day such as `12:03` or `12:03:45.250`
+pub fn parse_time_of_day(input: &str) -> Option<NaiveTime> {
+    let c = time_regex().captures(input.trim())?;
+    let num = |i: usize| c.get(i).map(|m| m.as_str().parse::<u32>().ok()).unwrap_or(Some(0));
+    let nanos = c.get(4).map(|m| frac_to_nanos(m.as_str())).unwrap_or(0);
+    NaiveTime::from_hms_nano_opt(num(1)?, num(2)?, num(3)?, nanos)
+}
+
+fn frac_to_nanos(frac: &str) -> u32 {
+    let mut digits = frac.to_string();
+    while digits.len() < 9 { digits.push('0'); }
+    digits[..9].parse().unwrap_or(0)
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    MONTHS.iter().position(|m| *m == name).map(|i| i as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iso_variants() {
+        let ts = parse_timestamp("2025-09-16T22:50:04Z ERROR api: request failed").unwrap();
+        assert_eq!(ts.to_string(), "2025-09-16 22:50:04");
+        let ts = parse_timestamp("68547:2025-09-17 11:59:52.505 +02:00    DBG     x").unwrap();
+        assert_eq!(ts.to_string(), "2025-09-17 11:59:52.505");
+        assert!(parse_timestamp("no time here").is_none());
+    }
+
+    #[test]
+    fn test_convert_timestamp() {
+        let utc = DisplayZone::parse("utc").unwrap();
+        assert_eq!(convert_timestamp("2025-09-17 11:59:52.505 +02:00 DBG x", utc), "2025-09-17 09:59:52.505 +00:00 DBG x");
+        assert_eq!(convert_timestamp("at 2025/01/02T23:30:00-0500 ok", utc), "at 2025/01/03T04:30:00+0000 ok");
+        let tokyo = DisplayZone::parse("Asia/Tokyo").unwrap();
+        assert_eq!(convert_timestamp("2025-09-16T22:50:04Z ERROR", tokyo), "2025-09-17T07:50:04+09:00 ERROR");
+        let berlin = DisplayZone::parse("Europe/Berlin").unwrap();
+        assert_eq!(convert_timestamp("2025-07-01T12:00:00,25Z", berlin), "2025-07-01T14:00:00,25+02:00");
+        assert!(matches!(convert_timestamp("no time here", utc), Cow::Borrowed(_)));
+        assert!(DisplayZone::parse("Mars/Olympus").is_none());
+    }
+
+    #[test]
+    fn test_parse_with_format() {
+        let access = r#"10.0.0.1 - - [17/Sep/2025:11:59:52 +0200] "GET / HTTP/1.1" 200"#;
+        let ts = parse_timestamp_with(access, "%d/%b/%Y:%H:%M:%S %z").unwrap();
+        assert_eq!(ts.to_string(), "2025-09-17 11:59:52");
+        let ts = parse_timestamp_with("[2025.09.17 11:59:52.505] boom", "[%Y.%m.%d %H:%M:%S%.3f]").unwrap();
+        assert_eq!(ts.and_utc().timestamp_subsec_millis(), 505);
+        assert_eq!(parse_timestamp_with("no time here", "%Y-%m-%d %H:%M:%S"), None);
+        assert!(is_valid_format("%Y-%m-%d %H:%M:%S%.3f"));
+        assert!(!is_valid_format("%Y-%m-%d %Q") && !is_valid_format("%Y %") && !is_valid_format(""));
+    }
+
+    #[test]
+    fn test_format_gap() {
+        assert_eq!(format_gap(TimeDelta::milliseconds(2500)), "2.5s");
+        assert_eq!(format_gap(TimeDelta::seconds(272)), "4m 32s");
+        assert_eq!(format_gap(TimeDelta::seconds(7500)), "2h 05m");
+        assert_eq!(format_gap(TimeDelta::seconds(3 * 86400 + 4 * 3600 + 59)), "3d 4h");
+    }
+
+    #[test]
+    fn test_parse_syslog_and_time_of_day() {
+        let ts = parse_timestamp("Sep 17 11:59:52 host sshd[1]: ok").unwrap();
+        assert_eq!(ts.time().to_string(), "11:59:52");
+        assert_eq!(parse_time_of_day("12:03").unwrap().to_string(), "12:03:00");
+        assert_eq!(parse_time_of_day("12:03:45.25").unwrap().to_string(), "12:03:45.250");
+        assert!(parse_time_of_day("84213").is_none());
+    }
+}
@@ -0,0 +1,170 @@
+//! Per-line transforms scripted in Rhai (`[[transforms]]` in the config file).
+//!
+//! A transform is a script run for every line of the matching sources in the reader task, before
+//! the line is classified and displayed. The script sees the line as the variable `line` and the
+//! source's sidebar name as the constant `source`; whatever `line` holds afterwards replaces the
+//! line, and setting it to `()` drops the line. Several transforms for one source run in config
+//! order. Besides Rhai's built-ins, scripts can call `base64_decode(s)`, `regex_captures(s, re)`
+//! and `regex_replace(s, re, replacement)`.
+//!
+//! A script error leaves the line unchanged; the first error of each transform is reported.
+//! Runaway scripts are stopped after `MAX_OPERATIONS`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use regex::Regex;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, ImmutableString, Scope, AST};
+
+use crate::config::TransformEntry;
+
+/// Script operations allowed per line
+const MAX_OPERATIONS: u64 = 100_000;
+/// Patterns compiled by `regex_captures` / `regex_replace` kept for reuse
+const REGEX_CACHE: usize = 64;
+
+/// One compiled transform script
+pub struct Transform {
+    name: String,
+    ast: AST,
+    engine: Arc<Engine>,
+    /// An error was reported already
+    failed: AtomicBool,
+}
+
+/// Transforms that apply to one source, in order
+pub struct TransformChain {
+    source: ImmutableString,
+    steps: Vec<Arc<Transform>>,
+}
+
+impl TransformChain {
+    /// Run the transforms on `text`: the new text, or `None` if a script dropped the line. The
+    /// second value is an error to report (the failing script's first one).
+    pub fn apply(&self, text: &str) -> (Option<String>, Option<String>) {
+        let mut line = text.to_string();
+        let mut report = None;
+        for step in &self.steps {
+            match step.run(&line, &self.source) {
+                Ok(Some(next)) => line = next,
+                Ok(None) => return (None, report),
+                Err(e) => if !step.failed.swap(true, Ordering::Relaxed) {
+                    report = Some(format!("transform {}: {}", step.name, e));
+                },
+            }
+        }
+        (Some(line), report)
+    }
+}
+
+impl Transform {
+    fn run(&self, line: &str, source: &ImmutableString) -> Result<Option<String>, Box<EvalAltResult>> {
+        let mut scope = Scope::new();
+        scope.push("line", line.to_string());
+        scope.push_constant("source", source.clone());
+        self.engine.run_ast_with_scope(&mut scope, &self.ast)?;
+        let value = scope.get_value::<Dynamic>("line").unwrap_or_default();
+        Ok(if value.is_unit() { None } else { Some(value.to_string()) })
+    }
+}
+
+/// The configured transforms with the sources they apply to
+#[derive(Default)]
+pub struct Transforms {
+    /// Regex on the source's names (`None`: every source) and the compiled script
+    rules: Vec<(Option<Regex>, Arc<Transform>)>,
+}
+
+impl Transforms {
+    /// Compile the scripts (reading `file` ones) and `match` patterns
+    pub fn compile(entries: &[TransformEntry]) -> Result<Self> {
+        if entries.is_empty() { return Ok(Self::default()); }
+        let engine = Arc::new(engine());
+        let mut rules = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let (name, script) = match (&entry.script, &entry.file) {
+                (Some(script), None) => (format!("#{}", i + 1), script.clone()),
+                (None, Some(path)) => (path.display().to_string(), std::fs::read_to_string(path)
+                    .with_context(|| format!("reading transform script {}", path.display()))?),
+                _ => anyhow::bail!("transform #{}: set exactly one of script and file", i + 1),
+            };
+            let ast = engine.compile(&script).with_context(|| format!("transform {}", name))?;
+            let pattern = entry.pattern.as_deref().map(Regex::new).transpose()
+                .with_context(|| format!("transform {}: invalid match pattern", name))?;
+            rules.push((pattern, Arc::new(Transform { name, ast, engine: Arc::clone(&engine), failed: AtomicBool::new(false) })));
+        }
+        Ok(Self { rules })
+    }
+
+    /// Transforms for a source known by `names` (sidebar name first, then path, label, command)
+    pub fn for_source(&self, names: &[String]) -> Option<Arc<TransformChain>> {
+        let steps: Vec<Arc<Transform>> = self.rules.iter()
+            .filter(|(re, _)| re.as_ref().is_none_or(|re| names.iter().any(|n| re.is_match(n))))
+            .map(|(_, t)| Arc::clone(t))
+            .collect();
+        if steps.is_empty() { return None; }
+        let source = names.first().map(|n| n.as_str()).unwrap_or_default().into();
+        Some(Arc::new(TransformChain { source, steps }))
+    }
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    // Standard or URL-safe alphabet, padded or not; invalid input is returned unchanged
+    engine.register_fn("base64_decode", |s: &str| -> String {
+        use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+        [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD].iter()
+            .find_map(|e| e.decode(s).ok())
+            .map_or_else(|| s.to_string(), |bytes| String::from_utf8_lossy(&bytes).into_owned())
+    });
+    // Groups of the first match (whole match first), or an empty array
+    engine.register_fn("regex_captures", |s: &str, re: &str| -> Result<Array, Box<EvalAltResult>> {
+        let re = cached_regex(re)?;
+        Ok(re.captures(s).map(|caps| {
+            caps.iter().map(|m| m.map_or(Dynamic::UNIT, |m| m.as_str().to_string().into())).collect()
+        }).unwrap_or_default())
+    });
+    // Every match replaced; `$1` / `${name}` refer to groups
+    engine.register_fn("regex_replace", |s: &str, re: &str, rep: &str| -> Result<String, Box<EvalAltResult>> {
+        Ok(cached_regex(re)?.replace_all(s, rep).into_owned())
+    });
+    engine
+}
+
+fn cached_regex(pattern: &str) -> Result<Regex, Box<EvalAltResult>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+    let mut cache = CACHE.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(re) = cache.get(pattern) { return Ok(re.clone()); }
+    let re = Regex::new(pattern).map_err(|e| format!("invalid regex {:?}: {}", pattern, e))?;
+    if cache.len() >= REGEX_CACHE { cache.clear(); }
+    cache.insert(pattern.to_string(), re.clone());
+    Ok(re)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_chain() {
+        let entry = |pattern: Option<&str>, script: &str| TransformEntry { pattern: pattern.map(Into::into), script: Some(script.into()), file: None };
+        let transforms = Transforms::compile(&[
+            entry(None, r#"let m = regex_captures(line, "payload=(\\S+)"); if m.len() > 1 { line.replace(m[1], base64_decode(m[1])); }"#),
+            entry(Some("^api"), r#"let codes = #{ E42: "disk full" }; for k in codes.keys() { if line.contains(k) { line += " (" + codes[k] + ")"; } }"#),
+            entry(Some("^api"), r#"if line.contains("healthz") { line = (); }"#),
+        ]).unwrap();
+        let api = transforms.for_source(&["api".into()]).unwrap();
+        assert_eq!(api.apply("payload=aGVsbG8= code=E42").0.as_deref(), Some("payload=hello code=E42 (disk full)"));
+        assert_eq!(api.apply("GET /healthz").0, None);
+        let other = transforms.for_source(&["db".into()]).unwrap();
+        assert_eq!(other.apply("payload=!!! x").0.as_deref(), Some("payload=!!! x"));
+        // Errors leave the line alone and are reported once
+        let failing = Transforms::compile(&[entry(None, r#"line = regex_replace(line, "(", "")"#)]).unwrap().for_source(&["a".into()]).unwrap();
+        assert!(matches!(failing.apply("x"), (Some(ref l), Some(_)) if l == "x"));
+        assert!(matches!(failing.apply("y"), (Some(_), None)));
+    }
+}
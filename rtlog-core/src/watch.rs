@@ -0,0 +1,106 @@
+//! Filesystem change notifications for followed files.
+//!
+//! A single process-wide `notify` watcher (inotify, kqueue, FSEvents, ReadDirectoryChangesW) is
+//! shared by all followed files: each file subscribes to its parent directory, which also reports
+//! creation, deletion and rename of the file itself. When the platform watcher is unavailable or a
+//! directory cannot be watched (e.g. instance limits, missing directory), `FileWatch` falls back to
+//! plain polling, so callers never need to care which mode is active.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::Notify;
+
+/// Wakes a follower when its file (or the directory entry for it) changes
+pub struct FileWatch {
+    sub: Option<Subscription>,
+}
+
+struct Subscription {
+    target: PathBuf,
+    dir: PathBuf,
+    wake: Arc<Notify>,
+}
+
+/// Followed file path and the handle waking its reader
+type Subscribers = Arc<Mutex<Vec<(PathBuf, Arc<Notify>)>>>;
+
+struct Shared {
+    /// Directory -> number of subscriptions watching it
+    watcher: Mutex<(RecommendedWatcher, HashMap<PathBuf, usize>)>,
+    subs: Subscribers,
+}
+
+fn shared() -> Option<&'static Shared> {
+    static SHARED: OnceLock<Option<Shared>> = OnceLock::new();
+    SHARED.get_or_init(|| {
+        let subs: Subscribers = Arc::default();
+        let handler_subs = Arc::clone(&subs);
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else { return };
+            let subs = handler_subs.lock().unwrap_or_else(|e| e.into_inner());
+            for (target, wake) in subs.iter() {
+                // Events without paths (e.g. queue overflow) wake everyone
+                if event.paths.is_empty() || event.paths.iter().any(|p| p == target) { wake.notify_one(); }
+            }
+        }).ok()?;
+        Some(Shared { watcher: Mutex::new((watcher, HashMap::new())), subs })
+    }).as_ref()
+}
+
+impl FileWatch {
+    /// Subscribe to changes of `path`; falls back to polling when it cannot be watched
+    pub fn new(path: &Path) -> Self {
+        Self { sub: Self::subscribe(path) }
+    }
+
+    fn subscribe(path: &Path) -> Option<Subscription> {
+        let shared = shared()?;
+        let dir = match path.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d,
+            _ => Path::new("."),
+        };
+        // Event paths are absolute, so compare against the canonical directory
+        let dir = std::fs::canonicalize(dir).ok()?;
+        let target = dir.join(path.file_name()?);
+        {
+            let mut guard = shared.watcher.lock().unwrap_or_else(|e| e.into_inner());
+            let (watcher, dirs) = &mut *guard;
+            if !dirs.contains_key(&dir) { watcher.watch(&dir, RecursiveMode::NonRecursive).ok()?; }
+            *dirs.entry(dir.clone()).or_insert(0) += 1;
+        }
+        let wake = Arc::new(Notify::new());
+        shared.subs.lock().unwrap_or_else(|e| e.into_inner()).push((target.clone(), Arc::clone(&wake)));
+        Some(Subscription { target, dir, wake })
+    }
+
+    /// Wait for a change notification, at most `idle`; without notifications just sleep `poll`
+    pub async fn wait(&self, idle: Duration, poll: Duration) {
+        match &self.sub {
+            Some(sub) => { let _ = tokio::time::timeout(idle, sub.wake.notified()).await; }
+            None => tokio::time::sleep(poll).await,
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let Some(shared) = shared() else { return };
+        {
+            let mut subs = shared.subs.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(i) = subs.iter().position(|(t, w)| *t == self.target && Arc::ptr_eq(w, &self.wake)) { subs.swap_remove(i); }
+        }
+        let mut guard = shared.watcher.lock().unwrap_or_else(|e| e.into_inner());
+        let (watcher, dirs) = &mut *guard;
+        if let Some(count) = dirs.get_mut(&self.dir) {
+            *count -= 1;
+            if *count == 0 {
+                dirs.remove(&self.dir);
+                let _ = watcher.unwatch(&self.dir);
+            }
+        }
+    }
+}
@@ -5,34 +5,93 @@
 //! - Spawn log ingestion tasks (via the `log` module abstractions).
 //! - Drive the main event/render loop without blocking the UI.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use regex::Regex;
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use tokio::sync::mpsc;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::process::Command;
+use tokio::sync::mpsc::{self, Receiver};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use crossterm::event::{Event, EventStream};
+use futures_util::StreamExt;
 
+use crate::agent::{AgentClient, UplinkEvent};
+use crate::classify::{AlertRule, AlertSeverity, LevelOverrides, LevelRules, DEFAULT_ERROR_PATTERN, DEFAULT_INFO_PATTERN, DEFAULT_WARN_PATTERN};
+use crate::control::{self, ControlServer};
+use crate::editor::editor_command;
+use crate::email::EmailAlerts;
+use crate::history::state_dir;
+use crate::hotkeys;
+use crate::links::LinkKind;
 use crate::filter::build_filter;
-use crate::log::stream_file;
-use crate::state::{AppState, FilterFocus};
-use crate::ui::{poll_input, Ui, UiEvent};
+use regex::RegexBuilder;
+use crate::log::{oslog_command, parse_serial_spec, parse_ssh_spec, stream_agents, stream_and_report, stream_command, stream_fluent, stream_http, stream_pipe, stream_plugin, stream_serial, stream_ssh, stream_watch, EventSender, FileTail, SourceEvent};
+use crate::offsets::ReadOffsets;
+use crate::plugin::Plugin;
+use crate::profile::Profiles;
+use crate::replay::{self, ReplayControl, ReplayFile};
+use crate::session::{self, PlayedKey, RecordedSource, Recording, SessionRecorder};
+use crate::screenshot;
+use crate::shutdown::ShutdownSignals;
+use crate::state::{AppState, FilterFocus, FocusFollow, MessageLevel, PromptKind, Source};
+use crate::status::StatusTemplate;
+use crate::redact::Redactor;
+use crate::rewrite::Rewriter;
+use crate::tee::Tees;
+use crate::timestamp::format_gap;
+use crate::transform::Transforms;
+use crate::ui::{key_name, parse_key, translate_event, Ui, UiEvent};
+use crate::watch::FileWatch;
+use crate::web::WebServer;
 
-use crate::cli::Config;
+use crate::cli::{Config, InputSpec, ReportConfig};
+use crate::config::LevelsConfig;
 
-fn discover_files(inputs: &[PathBuf], recursive: bool) -> Vec<PathBuf> {
+/// Minimum time between two frames triggered by source events (~30fps)
+pub(crate) const DRAW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(33);
+
+/// Time between two batches of lines sent to `rtlog serve` clients and by `rtlog agent`
+const PUBLISH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// How often the config file is checked when change notifications are not available
+const CONFIG_POLL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long a changed config file must stay unchanged before it is read: editors save in steps
+const CONFIG_SETTLE: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Exit status of a session ended by `--exit-on-match` or failed by `--fail-on-alert` (errors
+/// exit with 1, usage errors with 2)
+pub const FAILURE_STATUS: u8 = 3;
+
+/// Resolve CLI inputs to files. Explicit inputs that cannot be inspected (missing, no permission)
+/// are kept so their reader reports the error in the UI; unreadable directories are returned as errors.
+fn discover_files(inputs: &[PathBuf], recursive: bool) -> (Vec<PathBuf>, Vec<String>) {
     let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for p in inputs {
+        if fs::metadata(p).is_err() { files.push(p.clone()); }
+    }
     let mut stack: Vec<PathBuf> = inputs.to_vec();
     while let Some(p) = stack.pop() {
         if let Ok(md) = fs::metadata(&p) {
-            if md.is_file() {
+            // Anything that is not a directory is read directly (regular files, FIFOs, devices)
+            if !md.is_dir() {
                 files.push(p);
-            } else if md.is_dir() {
-                if let Ok(rd) = fs::read_dir(&p) {
-                    for entry in rd.flatten() {
-                        let path = entry.path();
-                        if let Ok(md2) = entry.metadata() {
-                            if md2.is_file() { files.push(path); }
-                            else if md2.is_dir() && recursive { stack.push(path); }
-                        }
+            } else {
+                let rd = match fs::read_dir(&p) {
+                    Ok(rd) => rd,
+                    Err(e) => { errors.push(format!("{}: {}", p.display(), e)); continue; }
+                };
+                for entry in rd.flatten() {
+                    let path = entry.path();
+                    if let Ok(md2) = entry.metadata() {
+                        if md2.is_file() { files.push(path); }
+                        else if md2.is_dir() && recursive { stack.push(path); }
                     }
                 }
             }
@@ -40,109 +99,1096 @@ fn discover_files(inputs: &[PathBuf], recursive: bool) -> Vec<PathBuf> {
     }
     files.sort();
     files.dedup();
-    files
+    (files, errors)
 }
 
-/// Entry point for the async runtime loop.
-pub async fn run(config: Config) -> Result<()> {
-    // Build filter from config
-    let filter: Option<Regex> = build_filter(config.regex.as_deref())?;
+/// Resolve inputs to (label, file) pairs. A named file keeps its name; files found in a named
+/// directory are labelled `NAME/relative/path`. Unnamed files get automatic names later.
+fn resolve_inputs(inputs: &[InputSpec], recursive: bool) -> (Vec<(Option<String>, PathBuf)>, Vec<String>) {
+    let mut out: Vec<(Option<String>, PathBuf)> = Vec::new();
+    let mut errors = Vec::new();
+    for input in inputs {
+        let (files, errs) = discover_files(std::slice::from_ref(&input.path), recursive);
+        errors.extend(errs);
+        for f in files {
+            if out.iter().any(|(_, p)| *p == f) { continue; }
+            let label = input.name.as_ref().map(|name| match f.strip_prefix(&input.path) {
+                Ok(rel) if !rel.as_os_str().is_empty() => format!("{}/{}", name, rel.display()),
+                _ => name.clone(),
+            });
+            out.push((label, f));
+        }
+    }
+    (out, errors)
+}
+
+/// Reader of `src`'s file, in the encoding set for it and resuming with `--resume`
+fn file_tail(state: &AppState, src: &Source, follow: bool) -> FileTail {
+    FileTail { path: src.path.clone(), follow, offsets: state.offsets.clone(), encoding: state.encoding_for(src) }
+}
+
+fn spawn_file_reader(tail: FileTail, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let label = tail.path.clone();
+        let _ = stream_and_report(tail, &label, id, tx).await;
+    })
+}
+
+fn spawn_command_reader(command: String, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = stream_command(command, id, tx).await;
+    })
+}
+
+fn spawn_watch_reader(command: String, interval: std::time::Duration, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = stream_watch(command, interval, id, tx).await;
+    })
+}
+
+fn spawn_plugin(command: String, events: Receiver<String>, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = stream_plugin(command, events, id, tx).await;
+    })
+}
+
+fn spawn_ssh_reader(spec: String, follow: bool, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = stream_ssh(spec, follow, id, tx).await;
+    })
+}
+
+fn spawn_http_reader(url: String, config: &Config, id: usize, tx: EventSender) -> JoinHandle<()> {
+    let (follow, interval) = (config.follow, config.poll_interval);
+    tokio::spawn(async move {
+        let _ = stream_http(url, follow, interval, id, tx).await;
+    })
+}
 
+fn spawn_serial_reader(spec: String, follow: bool, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = stream_serial(spec, follow, id, tx).await;
+    })
+}
+
+fn spawn_fluent_listener(addr: String, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = stream_fluent(addr, id, tx).await;
+    })
+}
+
+fn spawn_agent_listener(addr: String, id: usize, tx: EventSender) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let _ = stream_agents(addr, id, tx).await;
+    })
+}
+
+/// Sidebar label for a URL: `host/file`, without query string
+fn url_label(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, r)| r);
+    let rest = rest.split(['?', '#']).next().unwrap_or(rest);
+    let host = rest.split('/').next().unwrap_or(rest);
+    match rest.rsplit('/').find(|seg| !seg.is_empty()) {
+        Some(file) if file != host => format!("{}/{}", host, file),
+        _ => host.to_string(),
+    }
+}
+
+/// Open the input of the "open source" prompt: a file or directory path, a URL, or `!command`.
+/// New sources are appended to the sidebar and the first one is focused.
+fn open_sources(state: &mut AppState, readers: &mut HashMap<usize, JoinHandle<()>>, input: &str, config: &Config, tx: &EventSender, tees: &Tees) -> Result<(), String> {
+    let input = input.trim();
+    if input.is_empty() { return Err("enter a path, URL or !command".into()); }
+    let first_new = state.sources.len();
+    if let Some(cmd) = input.strip_prefix('!') {
+        let cmd = cmd.trim().to_string();
+        if cmd.is_empty() { return Err("empty command".into()); }
+        let id = state.add_source(Some(cmd.clone()), PathBuf::new(), Some(cmd.clone()));
+        readers.insert(id, spawn_command_reader(cmd, id, source_sender(state, id, tx, tees)));
+    } else if input.starts_with("http://") || input.starts_with("https://") {
+        let id = state.add_source(Some(url_label(input)), PathBuf::from(input), None);
+        readers.insert(id, spawn_http_reader(input.to_string(), config, id, source_sender(state, id, tx, tees)));
+    } else {
+        let path = PathBuf::from(input);
+        // Without --follow a missing file would only produce an error source; reject it up front
+        if !config.follow && fs::metadata(&path).is_err() { return Err(format!("cannot open {}", path.display())); }
+        let (files, errors) = discover_files(&[path], config.recursive);
+        if files.is_empty() {
+            return Err(errors.into_iter().next().unwrap_or_else(|| "no files found".into()));
+        }
+        for f in files {
+            let id = state.add_source(None, f.clone(), None);
+            let src = state.sources.iter().find(|s| s.id == id).expect("source just added");
+            readers.insert(id, spawn_file_reader(file_tail(state, src, config.follow), id, source_sender(state, id, tx, tees)));
+        }
+    }
+    state.set_focus(first_new);
+    let opened = state.sources.len() - first_new;
+    state.notify(MessageLevel::Info, if opened == 1 { format!("opened {}", input) } else { format!("opened {} sources from {}", opened, input) });
+    Ok(())
+}
+
+/// Run `command` with the lines `state.pipe_input` picks on its stdin; its output becomes a new,
+/// focused source named `| command`
+fn open_pipe(state: &mut AppState, readers: &mut HashMap<usize, JoinHandle<()>>, command: &str, tx: &EventSender) -> Result<(), String> {
+    let command = command.trim().trim_start_matches('|').trim();
+    if command.is_empty() { return Err("empty command".into()); }
+    let input = state.pipe_input();
+    let count = input.len();
+    let id = state.add_source(Some(format!("| {}", command)), PathBuf::new(), Some(command.to_string()));
+    state.mark_derived_source(id);
+    // Derived output: not copied to tee files nor transformed again
+    let sender = tx.clone().with_levels(state.sources.iter().find(|s| s.id == id).and_then(|src| state.level_rules_for(src)));
+    let command = command.to_string();
+    readers.insert(id, tokio::spawn(async move {
+        let _ = stream_pipe(command, input, id, sender).await;
+    }));
+    state.set_focus(state.sources.len() - 1);
+    state.notify(MessageLevel::Info, format!("piped {} lines", count));
+    Ok(())
+}
+
+/// Event sender for source `id`, copying its lines to the global `--tee` files and those naming it
+/// and classifying levels with the source's patterns and profile
+fn source_sender(state: &AppState, id: usize, tx: &EventSender, tees: &Tees) -> EventSender {
+    let Some(src) = state.sources.iter().find(|s| s.id == id) else { return tx.clone(); };
+    tx.with_tees(tees.for_source(&src.names())).with_levels(state.level_rules_for(src)).with_transforms(state.transforms_for(src)).with_profile(src.profile.clone())
+}
+
+/// Compile the `[levels]` config: the default level patterns and the per-source overrides
+fn level_rules(config: &LevelsConfig) -> Result<(LevelRules, LevelOverrides)> {
+    let compile = |error: Option<&str>, warn: Option<&str>, info: Option<&str>| {
+        LevelRules::new(error.unwrap_or(DEFAULT_ERROR_PATTERN), warn.unwrap_or(DEFAULT_WARN_PATTERN), info.unwrap_or(DEFAULT_INFO_PATTERN))
+    };
+    let default = compile(config.error.as_deref(), config.warn.as_deref(), config.info.as_deref())
+        .context("invalid [levels] pattern")?;
+    let overrides = config.source.iter().map(|s| {
+        let rules = compile(
+            s.error.as_deref().or(config.error.as_deref()),
+            s.warn.as_deref().or(config.warn.as_deref()),
+            s.info.as_deref().or(config.info.as_deref()),
+        ).with_context(|| format!("invalid [[levels.source]] pattern for {}", s.pattern))?;
+        let re = Regex::new(&s.pattern).with_context(|| format!("invalid [[levels.source]] match: {}", s.pattern))?;
+        Ok((re, Arc::new(rules)))
+    }).collect::<Result<Vec<_>>>()?;
+    Ok((default, overrides))
+}
+
+/// The `status_bar` template of the config file, the default without one
+fn status_template(config: &Config) -> Result<StatusTemplate> {
+    config.status_bar.as_deref().map_or_else(|| Ok(StatusTemplate::default()), StatusTemplate::parse)
+}
+
+pub(crate) fn apply_source_event(state: &mut AppState, sid: usize, ev: SourceEvent) {
+    if state.recorder.is_some() {
+        match &ev {
+            SourceEvent::Line(line) => state.record_line(sid, None, &line.text),
+            SourceEvent::Record { tag, line } => state.record_line(sid, Some(tag), &line.text),
+            _ => {}
+        }
+    }
+    match ev {
+        SourceEvent::Line(line) => state.push_line_for(sid, line),
+        SourceEvent::Error(err) => state.set_source_error(sid, err),
+        SourceEvent::Closed => state.set_source_closed(sid),
+        SourceEvent::Waiting(reason) => state.set_source_waiting(sid, Some(reason)),
+        SourceEvent::Reopened => state.set_source_waiting(sid, None),
+        SourceEvent::Record { tag, line } => state.push_tagged_line(sid, &tag, line),
+        SourceEvent::Plugin(action) => state.apply_plugin_action(action),
+        SourceEvent::Notice(text) => state.notify(MessageLevel::Warn, text),
+    }
+}
+
+/// Create the sources of the session recorded at `path` and start playing it back (`--play`),
+/// paced like the `--replay` files; returns the player and the channel of its keys
+fn start_playback(state: &mut AppState, path: &Path, config: &Config, tx: &EventSender) -> Result<(JoinHandle<()>, Receiver<PlayedKey>)> {
+    let recording = Recording::load(path)?;
+    let sources = recording.sources.into_iter().map(|src| {
+        let id = state.add_source(Some(src.name.clone()), src.path, None);
+        (src.name, id)
+    }).collect();
+    let control = state.replay.get_or_insert_with(|| ReplayControl::new(config.speed)).clone();
+    let (keys_tx, keys) = mpsc::channel(1);
+    Ok((tokio::spawn(session::play(recording.entries, sources, tx.clone(), keys_tx, control)), keys))
+}
+
+/// Hand the terminal to a shell (interactive when `command` is None) and come back afterwards.
+/// Source events keep being applied while the child runs so follow-only inputs lose nothing.
+async fn shell_out(ui: &mut Ui, state: &mut AppState, rx: &mut Receiver<(usize, SourceEvent)>, events: &mut EventStream, signals: &mut ShutdownSignals, command: Option<&str>) -> Result<()> {
+    let cmd = match command {
+        Some(c) if cfg!(windows) => { let mut cmd = Command::new("cmd"); cmd.arg("/C").arg(format!("{} & pause", c)); cmd }
+        Some(c) => {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(format!("{}; printf '\\n[rtlog] press Enter to return '; read _", c));
+            cmd
+        }
+        None if cfg!(windows) => Command::new(std::env::var_os("COMSPEC").unwrap_or_else(|| "cmd".into())),
+        None => Command::new(std::env::var_os("SHELL").unwrap_or_else(|| "sh".into())),
+    };
+    let banner = command.is_none() && !cfg!(windows);
+    run_suspended(ui, state, rx, events, signals, cmd, banner.then_some("[rtlog] suspended; exit the shell to return")).await
+}
+
+/// Run `cmd` on the terminal while the UI is suspended, after printing `banner`, applying source
+/// events meanwhile
+async fn run_suspended(ui: &mut Ui, state: &mut AppState, rx: &mut Receiver<(usize, SourceEvent)>, events: &mut EventStream, signals: &mut ShutdownSignals, mut cmd: Command, banner: Option<&str>) -> Result<()> {
+    // Replacing the stream stops its background reader, so the child gets every keystroke; the
+    // new one starts reading when it is first polled after `resume`
+    *events = EventStream::new();
+    ui.suspend()?;
+    if let Some(banner) = banner { println!("{}", banner); }
+    match cmd.spawn() {
+        Ok(mut child) => loop {
+            tokio::select! {
+                _ = child.wait() => break,
+                Some((sid, ev)) = rx.recv() => apply_source_event(state, sid, ev),
+                _ = signals.interrupt() => {}
+            }
+        },
+        Err(e) => state.notify(MessageLevel::Error, format!("cannot run {}: {}", cmd.as_std().get_program().to_string_lossy(), e)),
+    }
+    ui.resume()?;
+    Ok(())
+}
+
+/// Hidden `--bench-ingest FILE` mode: read FILE through the regular reader, classification and
+/// state path without a terminal, then print the throughput.
+pub async fn bench_ingest(path: &Path, config: &Config) -> Result<()> {
+    let filter = build_filter(config.regex.as_deref())?;
+    let mut state = AppState::new(filter, Vec::new());
+    state.set_alert_rules(config.alerts.clone());
+    let (levels, level_overrides) = level_rules(&config.levels)?;
+    state.set_level_rules(levels, level_overrides);
+    state.transforms = Transforms::compile(&config.transforms)?;
+    state.profiles = Profiles::compile(&config.profiles)?;
+    state.timestamp_formats = config.timestamp_formats.clone();
+    state.encodings = config.encodings.clone();
+    state.set_sources([(None, path.to_path_buf())]);
+    let (raw_tx, mut rx) = mpsc::channel::<(usize, SourceEvent)>(1024);
+    let tx = EventSender::new(raw_tx, state.shared_classifier());
+    let bytes = fs::metadata(path)?.len();
+    let start = Instant::now();
+    let id = state.sources[0].id;
+    let _reader = spawn_file_reader(file_tail(&state, &state.sources[0], false), id, tx.with_levels(state.level_rules_for(&state.sources[0])).with_transforms(state.transforms_for(&state.sources[0])).with_profile(state.sources[0].profile.clone()));
+    while let Some((sid, ev)) = rx.recv().await {
+        match ev {
+            SourceEvent::Error(e) => anyhow::bail!(e),
+            SourceEvent::Closed => break,
+            ev => apply_source_event(&mut state, sid, ev),
+        }
+    }
+    let secs = start.elapsed().as_secs_f64().max(f64::EPSILON);
+    let lines = state.sources[0].lines.len();
+    println!(
+        "{} lines, {:.1} MB in {:.3}s: {:.0} lines/s, {:.1} MB/s",
+        lines, bytes as f64 / 1e6, secs, lines as f64 / secs, bytes as f64 / 1e6 / secs,
+    );
+    Ok(())
+}
+
+/// `rtlog report`: read the inputs to their end through the regular readers, transforms and
+/// classification, without a terminal, and write the report.
+pub async fn report(config: ReportConfig) -> Result<ExitCode> {
+    let filter = build_filter(config.regex.as_deref())?;
+    let mut state = AppState::new(filter, Vec::new());
+    state.set_alert_rules(config.alerts.clone());
+    state.exit_pattern = build_filter(config.exit_on_match.as_deref())?;
+    let (levels, level_overrides) = level_rules(&config.levels)?;
+    state.set_level_rules(levels, level_overrides);
+    state.transforms = Transforms::compile(&config.transforms)?;
+    state.profiles = Profiles::compile(&config.profiles)?;
+    state.timestamp_formats = config.timestamp_formats.clone();
+    state.encodings = config.encodings.clone();
+    let redactor = Redactor::new(&config.redact)?;
+    let (files, discover_errors) = resolve_inputs(&config.inputs, config.recursive);
+    for err in &discover_errors { eprintln!("rtlog report: {}", err); }
+    if files.is_empty() { anyhow::bail!("no input files to report on"); }
+    state.set_sources(files);
+    let (raw_tx, mut rx) = mpsc::channel::<(usize, SourceEvent)>(1024);
+    let tx = EventSender::new(raw_tx, state.shared_classifier());
+    for src in &state.sources {
+        spawn_file_reader(file_tail(&state, src, false), src.id, tx.clone().with_levels(state.level_rules_for(src)).with_transforms(state.transforms_for(src)).with_profile(src.profile.clone()));
+    }
+    // The channel closes once every reader has reached the end of its file
+    drop(tx);
+    while let Some((sid, ev)) = rx.recv().await { apply_source_event(&mut state, sid, ev); }
+    for src in state.sources.iter().filter(|s| s.error.is_some()) {
+        eprintln!("rtlog report: {}: {}", src.name, src.error.as_deref().unwrap_or_default());
+    }
+    let reports = crate::report::build(&state);
+    fs::write(&config.out, crate::report::render(&reports, config.format, &redactor))
+        .with_context(|| format!("cannot write {}", config.out.display()))?;
+    let lines: usize = reports.iter().map(|r| r.lines).sum();
+    println!("wrote {}: {} source(s), {} lines", config.out.display(), reports.len(), lines);
+    Ok(exit_status(&state, config.fail_on_alert))
+}
+
+/// What a viewer session runs on: the state, the channel its sources' events arrive on and the
+/// readers feeding it. Shared by the terminal UI and `rtlog serve`.
+struct Session {
+    state: AppState,
+    rx: Receiver<(usize, SourceEvent)>,
+    tx: EventSender,
+    readers: HashMap<usize, JoinHandle<()>>,
+    tees: Tees,
+}
+
+/// Set up the state from `config` and start reading every input
+async fn start_session(config: &Config) -> Result<Session> {
     // Resolve input files
-    let files = discover_files(&config.inputs, config.recursive);
+    let (files, discover_errors) = resolve_inputs(&config.inputs, config.recursive);
 
     // Channel for log lines tagged with source id
-    let (tx, mut rx) = mpsc::channel::<(usize, String)>(1024);
-
-    // Spawn log readers
-    for (i, path) in files.iter().cloned().enumerate() {
-        let txc = tx.clone();
-        let follow = config.follow;
-        tokio::spawn(async move {
-            let _ = stream_file(path, follow, i, txc).await;
+    let (raw_tx, rx) = mpsc::channel::<(usize, SourceEvent)>(1024);
+
+    let mut state = new_state(config)?;
+    // Readers classify lines with the state's current rules before sending them
+    let tx = EventSender::new(raw_tx, state.shared_classifier());
+    state.set_sources(files);
+    let tees = Tees::open(&config.tee, Arc::clone(&state.redactor)).await?;
+    state.email = config.email.as_ref().map(|email| EmailAlerts::start(email, Arc::clone(&state.redactor))).transpose()?;
+
+    // Spawn log readers, keyed by source id so a source can be closed at runtime
+    let mut readers: HashMap<usize, JoinHandle<()>> = HashMap::new();
+    for src in &state.sources {
+        readers.insert(src.id, spawn_file_reader(file_tail(&state, src, config.follow), src.id, source_sender(&state, src.id, &tx, &tees)));
+    }
+    for spec in &config.ssh {
+        // Sidebar shows `host:file`; the full spec stays in the path so groups follow the remote directory
+        let label = parse_ssh_spec(spec).map(|(target, path)| {
+            let host = target.rsplit('@').next().unwrap_or(&target);
+            let file = path.rsplit('/').next().filter(|f| !f.is_empty()).unwrap_or(&path);
+            if host.contains(':') { format!("[{}]:{}", host, file) } else { format!("{}:{}", host, file) }
         });
+        let id = state.add_source(label, PathBuf::from(spec), None);
+        readers.insert(id, spawn_ssh_reader(spec.clone(), config.follow, id, source_sender(&state, id, &tx, &tees)));
+    }
+    for addr in &config.fluent {
+        // Per-tag sources are created under the listener's path, so they share its sidebar group
+        let id = state.add_source(Some(format!("fluent {}", addr)), PathBuf::from("fluent").join(addr).join("listener"), None);
+        readers.insert(id, spawn_fluent_listener(addr.clone(), id, source_sender(&state, id, &tx, &tees)));
+    }
+    for addr in &config.agents {
+        // Remote sources are created as `agents/HOST/NAME`, so each agent gets a sidebar group
+        let id = state.add_source(Some(format!("agents {}", addr)), PathBuf::from("agents").join(addr), None);
+        readers.insert(id, spawn_agent_listener(addr.clone(), id, source_sender(&state, id, &tx, &tees)));
+    }
+    for predicate in &config.oslog {
+        let label = if predicate.trim().is_empty() { "oslog".to_string() } else { format!("oslog {}", predicate) };
+        let cmd = oslog_command(predicate);
+        let id = state.add_source(Some(label), PathBuf::new(), Some(cmd.clone()));
+        readers.insert(id, spawn_command_reader(cmd, id, source_sender(&state, id, &tx, &tees)));
+    }
+    for cmd in &config.watch {
+        let id = state.add_source(Some(format!("watch {}", cmd)), PathBuf::new(), Some(cmd.clone()));
+        readers.insert(id, spawn_watch_reader(cmd.clone(), config.watch_interval, id, source_sender(&state, id, &tx, &tees)));
+    }
+    for spec in &config.serial {
+        // Grouped by the device directory (/dev) like files; the sidebar shows the device name
+        let path = parse_serial_spec(spec).map(|(path, _)| PathBuf::from(path)).unwrap_or_default();
+        let id = state.add_source(None, path, None);
+        readers.insert(id, spawn_serial_reader(spec.clone(), config.follow, id, source_sender(&state, id, &tx, &tees)));
+    }
+    for url in &config.http {
+        let id = state.add_source(Some(url_label(url)), PathBuf::from(url), None);
+        readers.insert(id, spawn_http_reader(url.clone(), config, id, source_sender(&state, id, &tx, &tees)));
+    }
+    for cmd in &config.plugins {
+        let id = state.add_source(Some(format!("plugin {}", cmd)), PathBuf::new(), Some(cmd.clone()));
+        let (plugin, events) = Plugin::channel();
+        state.add_plugin(id, plugin);
+        readers.insert(id, spawn_plugin(cmd.clone(), events, id, source_sender(&state, id, &tx, &tees)));
+    }
+    if !config.replay.is_empty() {
+        let control = ReplayControl::new(config.speed);
+        let files: Vec<ReplayFile> = config.replay.iter().map(|path| {
+            let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+            let id = state.add_source(Some(format!("replay {}", name)), path.clone(), None);
+            ReplayFile { path: path.clone(), source_id: id, tx: source_sender(&state, id, &tx, &tees) }
+        }).collect();
+        // One reader plays all the files, kept with the first of their sources
+        readers.insert(files[0].source_id, tokio::spawn(replay::replay(files, control.clone())));
+        state.replay = Some(control);
+    }
+    let group_rules = config.groups.iter()
+        .map(|(name, re)| Ok((name.clone(), Regex::new(re)?)))
+        .collect::<Result<Vec<_>>>()?;
+    state.group_sources(&group_rules);
+    for err in discover_errors { state.raise_alert(AlertSeverity::Warn, err); }
+    for name in tees.names() {
+        if !state.sources.iter().any(|src| src.names().iter().any(|n| n == name)) {
+            state.notify(MessageLevel::Warn, format!("--tee {}=…: no source with that name", name));
+        }
+    }
+    apply_view_options(&mut state, config)?;
+    Ok(Session { state, rx, tx, readers, tees })
+}
+
+/// A state with the filter and rules of `config` (alerts, levels, transforms, profiles,
+/// redaction, rewrites) and no sources yet
+pub(crate) fn new_state(config: &Config) -> Result<AppState> {
+    let mut state = AppState::new(build_filter(config.regex.as_deref())?, Vec::new());
+    if let Some(dir) = state_dir() { state.load_saved(&dir); }
+    state.set_alert_rules(config.alerts.clone());
+    let (levels, level_overrides) = level_rules(&config.levels)?;
+    state.set_level_rules(levels, level_overrides);
+    state.transforms = Transforms::compile(&config.transforms)?;
+    state.profiles = Profiles::compile(&config.profiles)?;
+    state.timestamp_formats = config.timestamp_formats.clone();
+    state.encodings = config.encodings.clone();
+    state.redactor = Arc::new(Redactor::new(&config.redact)?);
+    state.rewriter = Rewriter::new(&config.rewrite)?;
+    state.smart_case = config.smart_case;
+    state.status_template = status_template(config)?;
+    state.hotkeys.configure(&config.hotkeys)?;
+    state.exit_pattern = build_filter(config.exit_on_match.as_deref())?;
+    if config.resume { state.offsets = Some(Arc::new(ReadOffsets::load(ReadOffsets::default_path()))); }
+    Ok(state)
+}
+
+/// Apply the viewer options of `config` once the sources are added
+pub(crate) fn apply_view_options(state: &mut AppState, config: &Config) -> Result<()> {
+    state.parse_timestamps = config.timestamps;
+    state.export_format = config.export_format;
+    state.filter_context = config.context;
+    state.page_step = config.page_step;
+    state.display_zone = config.tz;
+    state.gap_threshold = config.gap.and_then(|gap| chrono::TimeDelta::from_std(gap).ok());
+    if config.summary && !config.follow { state.toggle_summary(); }
+    state.compact = config.compact;
+    state.tab_width = config.tab_width;
+    state.set_terminal_title = !config.no_title;
+    if let Some(spec) = &config.focus_follow {
+        state.focus_follow = if spec == "alerts" { FocusFollow::Alerts } else { FocusFollow::Pattern(RegexBuilder::new(spec).build()?) };
+        state.focus_follow_on = true;
+    }
+    state.correlate_regex = if config.correlate.is_empty() { None } else { Some(RegexBuilder::new(&config.correlate).build()?) };
+    state.file_regex = if config.file_pattern.is_empty() { None } else { Some(RegexBuilder::new(&config.file_pattern).build()?) };
+    Ok(())
+}
+
+/// Stop the readers, then flush the tee files and the alerts held back by the email rate limit
+async fn finish_session(state: &mut AppState, readers: HashMap<usize, JoinHandle<()>>, tees: Tees) {
+    stop_readers(readers).await;
+    // Stopped readers released their tee handles, so the writers flush and finish
+    let tee_errors = tokio::time::timeout(std::time::Duration::from_secs(5), tees.finish()).await
+        .unwrap_or_else(|_| vec!["tee: timed out flushing".to_string()]);
+    for err in tee_errors { eprintln!("rtlog: {}", err); }
+    save_offsets(state);
+    // Alerts held back by the email rate limit go out now
+    if let Some(email) = state.email.take() {
+        let err = tokio::time::timeout(std::time::Duration::from_secs(35), email.finish()).await
+            .unwrap_or_else(|_| Some("timed out sending the last alerts".to_string()));
+        if let Some(err) = err { eprintln!("rtlog: email: {}", err); }
     }
+}
+
+/// Write the read offsets of followed files for the next `--resume` (the readers are stopped)
+pub(crate) fn save_offsets(state: &AppState) {
+    if let Some(Err(e)) = state.offsets.as_ref().map(|offsets| offsets.save()) { eprintln!("rtlog: read offsets not saved: {:#}", e); }
+}
+
+/// When `--duration` and `--until-idle` stop reading the sources
+pub(crate) struct StopTimer {
+    duration: Option<(Instant, std::time::Duration)>,
+    idle: Option<std::time::Duration>,
+    /// Lines received when new ones were last noticed, and when
+    seen: (u64, Instant),
+    pub(crate) stopped: bool,
+}
 
-    // Initialize UI and state
-    let mut state = AppState::new(filter, config.alerts.clone());
-    let sources_meta = files.iter().map(|p| {
-        let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("?").to_string();
-        (name, p.clone())
+impl StopTimer {
+    pub(crate) fn new(config: &Config) -> Self {
+        let now = Instant::now();
+        Self { duration: config.duration.map(|d| (now + d, d)), idle: config.until_idle, seen: (0, now), stopped: false }
+    }
+
+    /// Why reading stops now; None while it goes on, and once it stopped
+    pub(crate) fn check(&mut self, state: &AppState) -> Option<String> {
+        if self.stopped { return None; }
+        let now = Instant::now();
+        if state.lines_received() != self.seen.0 { self.seen = (state.lines_received(), now); }
+        let human = |d: std::time::Duration| format_gap(chrono::TimeDelta::from_std(d).unwrap_or_default());
+        let reason = match (self.duration, self.idle) {
+            (Some((end, d)), _) if now >= end => format!("{} elapsed (--duration)", human(d)),
+            (_, Some(idle)) if now - self.seen.1 >= idle => format!("no new lines for {} (--until-idle)", human(idle)),
+            _ => return None,
+        };
+        self.stopped = true;
+        Some(reason)
+    }
+}
+
+/// `--export` once reading stopped: write the lines of every source and say where
+pub(crate) fn export_capture(state: &AppState, path: &Path) -> Result<()> {
+    let count = state.export_all(&path.to_string_lossy()).map_err(|e| anyhow::anyhow!("export failed: {}", e))?;
+    println!("rtlog: exported {} lines to {} ({})", count, path.display(), state.export_format.name());
+    Ok(())
+}
+
+/// Exit status of a finished session, saying why on stderr when it is a failure: the line that
+/// matched `--exit-on-match`, or with `fail_on_alert` the alerts nobody acknowledged
+pub(crate) fn exit_status(state: &AppState, fail_on_alert: bool) -> ExitCode {
+    let reason = match &state.exit_match {
+        Some(line) => format!("line matched --exit-on-match: {}", line),
+        None if fail_on_alert && state.unacked_alerts() > 0 => {
+            let latest = state.alert_history.back().map_or("", |a| a.message.as_str());
+            format!("{} unacknowledged alert(s) with --fail-on-alert, latest: {}", state.unacked_alerts(), latest)
+        }
+        None => return ExitCode::SUCCESS,
+    };
+    eprintln!("rtlog: {}", reason);
+    ExitCode::from(FAILURE_STATUS)
+}
+
+/// Report changes of the config file at `path` (its size or modification time, including it
+/// appearing or going away) until the receiver is dropped. Without a path nothing is reported.
+fn watch_config(path: Option<PathBuf>) -> Receiver<()> {
+    let (tx, rx) = mpsc::channel(1);
+    let Some(path) = path else { return rx; };
+    tokio::spawn(async move {
+        let stamp = |path: &Path| fs::metadata(path).ok().map(|md| (md.len(), md.modified().ok()));
+        let watch = FileWatch::new(&path);
+        let mut seen = stamp(&path);
+        while !tx.is_closed() {
+            watch.wait(CONFIG_POLL, CONFIG_POLL).await;
+            if stamp(&path) == seen { continue; }
+            loop {
+                tokio::time::sleep(CONFIG_SETTLE).await;
+                let now = stamp(&path);
+                if std::mem::replace(&mut seen, now) == seen { break; }
+            }
+            let _ = tx.try_send(());
+        }
     });
-    state.set_sources(sources_meta);
+    rx
+}
+
+/// Read the config file again and apply what can change while running: alert rules, level
+/// patterns, rewrite rules, smart case, the status bar, the tab width and the hotkeys. Nothing
+/// is applied unless the whole file is valid.
+/// Returns a summary naming what changed, and what only takes effect after a restart.
+fn reload_config(state: &mut AppState, config: &mut Config) -> Result<String> {
+    let new = config.reload.reload(config)?;
+    let (levels, level_overrides) = level_rules(&new.levels)?;
+    let rewriter = Rewriter::new(&new.rewrite)?;
+    let template = status_template(&new)?;
+    // Settings only read at startup are checked too, so mistakes show now rather than on restart
+    Transforms::compile(&new.transforms)?;
+    Redactor::new(&new.redact)?;
+    Profiles::compile(&new.profiles)?;
+    hotkeys::compile(&new.hotkeys)?;
+    let alert_key = |a: &AlertRule| (a.rule.pattern.clone(), a.rule.is_regex, a.severity, a.name.clone(), a.color.clone());
+    let mut applied = Vec::new();
+    if !new.alerts.iter().map(alert_key).eq(config.alerts.iter().map(alert_key)) {
+        state.set_alert_rules(new.alerts.clone());
+        config.alerts = new.alerts;
+        applied.push("alerts");
+    }
+    if new.levels != config.levels {
+        state.set_level_rules(levels, level_overrides);
+        config.levels = new.levels;
+        applied.push("levels");
+    }
+    if new.rewrite != config.rewrite {
+        state.rewriter = rewriter;
+        config.rewrite = new.rewrite;
+        applied.push("rewrite");
+    }
+    if new.smart_case != config.smart_case {
+        (state.smart_case, config.smart_case) = (new.smart_case, new.smart_case);
+        applied.push("smart_case");
+    }
+    if new.status_bar != config.status_bar {
+        state.status_template = template;
+        config.status_bar = new.status_bar;
+        applied.push("status_bar");
+    }
+    if new.tab_width != config.tab_width {
+        (state.tab_width, config.tab_width) = (new.tab_width, new.tab_width);
+        applied.push("tab_width");
+    }
+    if new.hotkeys != config.hotkeys {
+        state.hotkeys.configure(&new.hotkeys)?;
+        config.hotkeys = new.hotkeys;
+        applied.push("hotkeys");
+    }
+    let restart: Vec<&str> = [
+        ("inputs", new.inputs != config.inputs),
+        ("email", new.email != config.email),
+        ("transforms", new.transforms != config.transforms),
+        ("redact", new.redact != config.redact),
+        ("sources", new.profiles != config.profiles),
+    ].into_iter().filter(|&(_, changed)| changed).map(|(name, _)| name).collect();
+    let mut parts = Vec::new();
+    if !applied.is_empty() { parts.push(format!("updated {}", applied.join(", "))); }
+    if !restart.is_empty() { parts.push(format!("restart to apply {}", restart.join(", "))); }
+    if parts.is_empty() { parts.push("no changes".to_string()); }
+    Ok(format!("config reloaded: {}", parts.join("; ")))
+}
+
+/// `rtlog serve`: follow the sources like the viewer does, without a terminal, and stream the
+/// lines passing the filters to the browsers connected to `addr`
+pub async fn serve(mut config: Config, addr: &str) -> Result<ExitCode> {
+    let Session { mut state, mut rx, readers, tees, .. } = start_session(&config).await?;
+    let mut server = WebServer::bind(addr).await?;
+    println!("rtlog: serving {} source(s) on http://{}/ (Ctrl-C stops)", state.sources.len(), server.local_addr());
+    let mut signals = ShutdownSignals::new()?;
+    let mut config_changes = watch_config(config.reload.config_path());
+    let mut ticker = tokio::time::interval(PUBLISH_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut stop = StopTimer::new(&config);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                server.publish(&state);
+                if let Some(reason) = stop.check(&state) {
+                    println!("rtlog: stopped reading: {}", reason);
+                    break;
+                }
+            }
+            Some(()) = config_changes.recv() => match reload_config(&mut state, &mut config) {
+                Ok(summary) => println!("rtlog: {}", summary),
+                Err(e) => eprintln!("rtlog: config not reloaded: {:#}", e),
+            },
+            Some((sid, ev)) = rx.recv() => {
+                apply_source_event(&mut state, sid, ev);
+                while let Ok((sid, ev)) = rx.try_recv() { apply_source_event(&mut state, sid, ev); }
+                if state.exit_match.is_some() { break; }
+            }
+            Some(reply) = server.next_request() => { let _ = reply.send(server.snapshot(&state)); }
+            _ = signals.recv() => break,
+        }
+    }
+    drop(server);
+    finish_session(&mut state, readers, tees).await;
+    if let Some(path) = config.export.as_deref().filter(|_| stop.stopped) { export_capture(&state, path)?; }
+    Ok(exit_status(&state, config.fail_on_alert))
+}
+
+/// Tell on stderr how the agent's connection to the viewer at `addr` is doing
+fn report_uplink(addr: &str, event: UplinkEvent) {
+    match event {
+        UplinkEvent::Unreachable(e) => eprintln!("rtlog: cannot reach {}: {} (retrying)", addr, e),
+        UplinkEvent::Connected { dropped } => {
+            eprintln!("rtlog: connected to {}", addr);
+            if dropped > 0 { eprintln!("rtlog: {} batches of lines were dropped while {} was unreachable", dropped, addr); }
+        }
+        UplinkEvent::Lost(e) => eprintln!("rtlog: connection to {} lost: {}", addr, e),
+    }
+}
+
+/// `rtlog agent`: follow the sources without a terminal and stream them to the viewer at `addr`
+pub async fn agent(config: Config, addr: String, host: String) -> Result<ExitCode> {
+    let Session { mut state, mut rx, readers, tees, .. } = start_session(&config).await?;
+    println!("rtlog: streaming {} source(s) to {} as {} (Ctrl-C stops)", state.sources.len(), addr, host);
+    let (mut client, mut uplink) = AgentClient::start(addr.clone(), host);
+    let mut signals = ShutdownSignals::new()?;
+    let mut ticker = tokio::time::interval(PUBLISH_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut stop = StopTimer::new(&config);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                client.publish(&state);
+                if let Some(reason) = stop.check(&state) {
+                    println!("rtlog: stopped reading: {}", reason);
+                    break;
+                }
+            }
+            Some((sid, ev)) = rx.recv() => {
+                apply_source_event(&mut state, sid, ev);
+                while let Ok((sid, ev)) = rx.try_recv() { apply_source_event(&mut state, sid, ev); }
+                if state.exit_match.is_some() { break; }
+            }
+            Some(event) = uplink.recv() => report_uplink(&addr, event),
+            _ = signals.recv() => break,
+        }
+    }
+    client.publish(&state);
+    client.close(std::time::Duration::from_secs(2)).await;
+    while let Ok(event) = uplink.try_recv() { report_uplink(&addr, event); }
+    finish_session(&mut state, readers, tees).await;
+    if let Some(path) = config.export.as_deref().filter(|_| stop.stopped) { export_capture(&state, path)?; }
+    Ok(exit_status(&state, config.fail_on_alert))
+}
+
+/// Entry point for the async runtime loop.
+pub async fn run(mut config: Config) -> Result<ExitCode> {
+    let Session { mut state, mut rx, tx, mut readers, tees } = start_session(&config).await?;
+    let mut control_socket = config.control.as_deref().map(ControlServer::bind).transpose()?;
+    let mut signals = ShutdownSignals::new()?;
+    let mut config_changes = watch_config(config.reload.config_path());
+    let (player, mut played_keys) = match config.play.as_deref() {
+        Some(path) => { let (player, keys) = start_playback(&mut state, path, &config, &tx)?; (Some(player), Some(keys)) }
+        None => (None, None),
+    };
+    if let Some(path) = &config.record {
+        let sources = state.sources.iter().map(|src| RecordedSource { name: src.name.clone(), path: src.path.clone() }).collect();
+        state.recorder = Some(SessionRecorder::create(path, sources)?);
+    }
     let mut ui = Ui::new()?;
 
-    // Main loop
-    let mut last_draw = std::time::Instant::now();
-    let draw_interval = std::time::Duration::from_millis(33); // ~30fps max
+    // Main loop: wake on terminal input, source events or the draw ticker, whichever comes first
+    let mut events = EventStream::new();
+    let mut ticker = tokio::time::interval(DRAW_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut dirty = true;
+    let mut animating = false;
+    let mut stop = StopTimer::new(&config);
 
     let res = loop {
-        // Drain any available lines without blocking
-        while let Ok((sid, line)) = rx.try_recv() {
-            state.push_line_for(sid, line);
-        }
-
-        // Handle user input
-        match poll_input(&state)? {
-            UiEvent::Quit => break Ok(()),
-            UiEvent::None => {}
-            UiEvent::ScrollUp(n) => state.scroll_up(n),
-            UiEvent::ScrollDown(n) => state.scroll_down(n),
-            UiEvent::Top => state.scroll_top(),
-            UiEvent::Bottom => state.scroll_bottom(),
-            UiEvent::ToggleAuto => state.toggle_auto_scroll(),
-
-            UiEvent::ToggleFilterPanel => { state.filter_panel_open = !state.filter_panel_open; },
-            UiEvent::ToggleContextPanel => {
-                // Initialize selection if needed, then toggle
-                state.ensure_log_selection();
-                state.context_panel_open = !state.context_panel_open;
-            }
-            UiEvent::InputChar(c) => {
-                if state.filter_panel_open && matches!(state.filter_focus, FilterFocus::Input) { state.filter_input.push(c); }
-            }
-            UiEvent::Backspace => {
-                if state.filter_panel_open && matches!(state.filter_focus, FilterFocus::Input) { state.filter_input.pop(); }
-            }
-            UiEvent::AddFilter => {
-                if state.filter_panel_open { state.add_filter_from_input(); }
-            }
-            UiEvent::ToggleInputRegex => { if state.filter_panel_open { state.input_is_regex = !state.input_is_regex; } }
-            UiEvent::ToggleInputCase => { if state.filter_panel_open { state.input_case_insensitive = !state.input_case_insensitive; } }
-            UiEvent::ToggleInputWord => { if state.filter_panel_open { state.input_whole_word = !state.input_whole_word; } }
-            UiEvent::ToggleInputLine => { if state.filter_panel_open { state.input_whole_line = !state.input_whole_line; } }
-            UiEvent::ToggleFilterEnabled => { if state.filter_panel_open { state.toggle_selected_filter(); } }
-            UiEvent::DeleteFilter => { if state.filter_panel_open { state.remove_selected_filter(); } }
-            UiEvent::FocusNext => { if state.filter_panel_open { state.filter_focus = match state.filter_focus { FilterFocus::Input => FilterFocus::List, FilterFocus::List => FilterFocus::Input }; } }
-            UiEvent::SelectUp => { if state.filter_panel_open { state.move_selection_up(); } else { state.move_log_selection_up(); } }
-            UiEvent::SelectDown => { if state.filter_panel_open { state.move_selection_down(); } else { state.move_log_selection_down(); } }
-            UiEvent::NextSource => { state.focus_next_source(); }
-            UiEvent::PrevSource => { state.focus_prev_source(); }
-
-            // Search controls
-            UiEvent::ToggleSearch => { state.open_search(); }
-            UiEvent::CloseSearch => { state.close_search(); }
-            UiEvent::SearchChar(c) => { state.search_push_char(c); }
-            UiEvent::SearchBackspace => { state.search_pop_char(); }
-            UiEvent::ApplySearch => { state.apply_search(); state.search_open = false; }
-            UiEvent::NextMatch => { let _ = state.jump_next_match(); }
-            UiEvent::PrevMatch => { let _ = state.jump_prev_match(); }
-            UiEvent::ToggleSearchRegex => { state.search_is_regex = !state.search_is_regex; }
-            UiEvent::ToggleSearchCase => { state.search_case_insensitive = !state.search_case_insensitive; }
-        }
-
-        // Draw at most 30fps
-        let should_draw = last_draw.elapsed() >= draw_interval;
-        if should_draw {
-            ui.draw(&state)?;
-            last_draw = std::time::Instant::now();
-        } else {
-            // small sleep to reduce CPU
-            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let (input, played) = tokio::select! {
+            _ = ticker.tick() => {
+                if let Some(reason) = stop.check(&state) {
+                    stop_readers(std::mem::take(&mut readers)).await;
+                    while let Ok((sid, ev)) = rx.try_recv() { apply_source_event(&mut state, sid, ev); }
+                    state.stop_reading(&reason);
+                    if config.export.is_some() { break Ok(()); }
+                    dirty = true;
+                }
+                // Source events are coalesced into at most one frame per tick; a running alert
+                // animation keeps drawing (plus one frame to clear it)
+                if let Some(err) = state.email.as_ref().and_then(|e| e.take_error()) {
+                    state.notify(MessageLevel::Error, format!("email: {}", err));
+                    dirty = true;
+                }
+                let was_animating = std::mem::replace(&mut animating, state.animating());
+                if dirty || animating || was_animating {
+                    state.refresh_patterns();
+                    state.refresh_histogram();
+                    state.refresh_summary();
+                    ui.draw(&state)?;
+                    if std::mem::take(&mut state.bell) { ui.bell()?; }
+                    dirty = false;
+                }
+                continue;
+            }
+            Some((sid, ev)) = rx.recv() => {
+                apply_source_event(&mut state, sid, ev);
+                while let Ok((sid, ev)) = rx.try_recv() {
+                    apply_source_event(&mut state, sid, ev);
+                }
+                if state.exit_match.is_some() { break Ok(()); }
+                dirty = true;
+                continue;
+            }
+            Some(()) = config_changes.recv() => {
+                match reload_config(&mut state, &mut config) {
+                    Ok(summary) => state.notify(MessageLevel::Info, summary),
+                    Err(e) => state.notify(MessageLevel::Error, format!("config not reloaded: {:#}", e)),
+                }
+                dirty = true;
+                continue;
+            }
+            Some(request) = control::next_request(&mut control_socket) => {
+                let _ = request.reply.send(state.apply_control(request.command));
+                dirty = true;
+                continue;
+            }
+            played = session::next_key(&mut played_keys) => {
+                let Some(PlayedKey { key, done }) = played else {
+                    state.notify(MessageLevel::Info, "playback finished");
+                    dirty = true;
+                    continue;
+                };
+                // The lines recorded before the key come first; the player sends the ones after
+                // it once `done` is dropped
+                while let Ok((sid, ev)) = rx.try_recv() { apply_source_event(&mut state, sid, ev); }
+                drop(done);
+                let input = parse_key(&key).map_or(UiEvent::None, |key| translate_event(&state, &Event::Key(key)));
+                (input, true)
+            }
+            event = events.next() => match event {
+                Some(Ok(event)) => {
+                    let input = translate_event(&state, &event);
+                    // The key that quits is left out, so the playback does not end the viewer
+                    if state.recorder.is_some() && !matches!(input, UiEvent::None | UiEvent::Quit)
+                        && let Event::Key(key) = &event && let Some(name) = key_name(key) { state.record_key(&name); }
+                    (input, false)
+                }
+                Some(Err(e)) => break Err(e.into()),
+                None => break Ok(()),
+            },
+            _ = signals.recv() => break Ok(()),
+        };
+
+        let handed_back = if played { apply_played_input(&mut state, input, ui.page_height()); None } else { apply_input(&mut state, input, ui.page_height()) };
+        // Inputs that need the runtime's readers, terminal or channels are handed back
+        match handed_back {
+            None => {}
+            Some(UiEvent::Quit) => break Ok(()),
+            Some(UiEvent::Suspend) => { shell_out(&mut ui, &mut state, &mut rx, &mut events, &mut signals, None).await?; }
+            Some(UiEvent::OpenLink(_)) => {
+                if let Some(link) = state.take_chosen_link()
+                    && let Err(e) = open_sources(&mut state, &mut readers, &link.target, &config, &tx, &tees) { state.notify(MessageLevel::Error, e); }
+            }
+            Some(UiEvent::OpenInEditor) => {
+                if let Ok(location) = state.selected_file_location() {
+                    let editor = Command::from(editor_command(&location));
+                    run_suspended(&mut ui, &mut state, &mut rx, &mut events, &mut signals, editor, None).await?;
+                }
+            }
+            Some(UiEvent::CloseSource) => {
+                if let Some(id) = state.remove_focused_source()
+                    && let Some(handle) = readers.remove(&id) { handle.abort(); }
+            }
+            Some(UiEvent::SubmitPrompt) => {
+                match state.prompt.as_ref().map(|p| (p.kind, p.input.clone())) {
+                    Some((PromptKind::OpenSource, input)) => match open_sources(&mut state, &mut readers, &input, &config, &tx, &tees) {
+                        Ok(()) => state.close_prompt(),
+                        Err(e) => state.set_prompt_error(e),
+                    },
+                    Some((PromptKind::Pipe, input)) => match open_pipe(&mut state, &mut readers, &input, &tx) {
+                        Ok(()) => state.close_prompt(),
+                        Err(e) => state.set_prompt_error(e),
+                    },
+                    Some((PromptKind::Shell, input)) => {
+                        state.close_prompt();
+                        let command = Some(input.trim()).filter(|c| !c.is_empty());
+                        shell_out(&mut ui, &mut state, &mut rx, &mut events, &mut signals, command).await?;
+                    }
+                    Some((PromptKind::Screenshot, input)) if input.trim().is_empty() => state.set_prompt_error("enter a file path".into()),
+                    Some((PromptKind::Screenshot, input)) => {
+                        // Closed first, so the prompt is not on the screen saved
+                        state.close_prompt();
+                        let (width, height) = ui.size()?;
+                        let path = input.trim();
+                        match screenshot::save(&state, width, height, Path::new(path)) {
+                            Ok(format) => state.notify(MessageLevel::Info, format!("saved the screen to {} ({})", path, format.name())),
+                            Err(e) => state.notify(MessageLevel::Error, format!("cannot write {}: {:#}", path, e)),
+                        }
+                    }
+                    _ => state.submit_prompt(),
+                }
+            }
+            Some(_) => continue,
         }
+
+
+        // Show the result of user input right away instead of waiting for the next tick
+        state.refresh_patterns();
+        state.refresh_histogram();
+        state.refresh_summary();
+        ui.draw(&state)?;
+        if std::mem::take(&mut state.bell) { ui.bell()?; }
+        if let Some(text) = state.clipboard.take() { ui.copy(&text)?; }
+        dirty = false;
     };
 
     // Ensure UI is restored even if error
     let _ = ui.restore();
-    res
+    if let Some(player) = player { player.abort(); }
+    finish_session(&mut state, readers, tees).await;
+    let res = match config.export.as_deref() {
+        Some(path) if stop.stopped => res.and_then(|()| export_capture(&state, path)),
+        _ => res,
+    };
+    res.map(|()| exit_status(&state, config.fail_on_alert))
+}
+
+/// Apply a user input that only changes the state, with `page_height` rows in the log panel.
+/// Inputs the runtime has to carry out (quitting, suspending, closing a source's reader, prompts
+/// that open sources or run commands) and `UiEvent::None` are handed back instead.
+/// Apply a key of a played back recording (`--play`). It only does to the view what it did when
+/// recorded: it never quits, suspends, runs commands, opens or closes sources, opens links or
+/// writes files on this machine (exports, saved bindings, sidebar preferences); the prompts of
+/// those are closed instead.
+pub fn apply_played_input(state: &mut AppState, input: UiEvent, page_height: usize) {
+    let input = match input {
+        UiEvent::OpenLink(_) => UiEvent::CloseLinks,
+        UiEvent::CycleSourceSort | UiEvent::TogglePinSource => return,
+        UiEvent::SubmitPrompt if matches!(state.prompt.as_ref().map(|p| p.kind), Some(PromptKind::Export | PromptKind::Hotkey)) => return state.close_prompt(),
+        input => input,
+    };
+    if let Some(UiEvent::SubmitPrompt) = apply_input(state, input, page_height) { state.close_prompt(); }
+}
+
+pub fn apply_input(state: &mut AppState, input: UiEvent, page_height: usize) -> Option<UiEvent> {
+    // Any key but a digit uses up the pending count
+    let count = if matches!(input, UiEvent::CountDigit(_)) { None } else { state.pending_count.take() };
+    match input {
+        UiEvent::Quit | UiEvent::None | UiEvent::Suspend | UiEvent::CloseSource => return Some(input),
+        // Sources are opened by the runtime, which owns the readers
+        UiEvent::OpenLink(as_source) => {
+            if as_source || state.chosen_link().is_some_and(|l| l.kind == LinkKind::Log) { return Some(input); }
+            if let Some(link) = state.take_chosen_link() {
+                match open::that_detached(&link.target) {
+                    Ok(()) => state.notify(MessageLevel::Info, format!("opened {}", state.display_text(&link.target))),
+                    Err(e) => state.notify(MessageLevel::Error, format!("cannot open {}: {}", state.display_text(&link.target), e)),
+                }
+            }
+        }
+        UiEvent::OpenLinks => state.open_link_chooser(),
+        UiEvent::CloseLinks => state.close_link_chooser(),
+        UiEvent::CopyLink => state.copy_chosen_link(),
+        // The runtime suspends the UI for the editor once there is a file to open
+        UiEvent::OpenInEditor => {
+            state.ensure_log_selection();
+            match state.selected_file_location() {
+                Ok(_) => return Some(input),
+                Err(e) => state.notify(MessageLevel::Warn, format!("open in editor: {}", e)),
+            }
+        }
+        UiEvent::SubmitPrompt if matches!(state.prompt.as_ref().map(|p| p.kind), Some(PromptKind::OpenSource | PromptKind::Pipe | PromptKind::Shell | PromptKind::Screenshot)) => return Some(input),
+        UiEvent::Resize => {}
+        UiEvent::ScrollUp(n) => state.scroll_up(n * count.unwrap_or(1)),
+        UiEvent::ScrollDown(n) => state.scroll_down(n * count.unwrap_or(1)),
+        UiEvent::CountDigit(digit) => state.push_count_digit(digit),
+        UiEvent::GotoCount => match count {
+            Some(n) => { state.goto_line(n); }
+            None => state.scroll_bottom(),
+        },
+        UiEvent::ScrollPage(halves) => {
+            let n = state.page_lines(page_height, halves.unsigned_abs());
+            if halves < 0 { state.scroll_up(n) } else { state.scroll_down(n) }
+        }
+        UiEvent::Top => state.scroll_top(),
+        UiEvent::Bottom => state.scroll_bottom(),
+        UiEvent::ToggleAuto => state.toggle_auto_scroll(),
+        UiEvent::ToggleAutoAll => state.toggle_auto_scroll_all(),
+        UiEvent::ToggleWrap => state.toggle_wrap(),
+        UiEvent::ToggleColumns => state.toggle_column_view(),
+        UiEvent::OpenFieldFilter => state.open_prompt(PromptKind::FieldFilter),
+        UiEvent::ToggleCompact => state.toggle_compact(),
+        UiEvent::ToggleControls => state.toggle_controls(),
+        UiEvent::ToggleShowAll => state.toggle_show_all(),
+        UiEvent::ToggleFocusFollow => state.toggle_focus_follow(),
+        UiEvent::CycleSourceSort => state.cycle_source_sort(),
+        UiEvent::TogglePinSource => state.toggle_pin_source(),
+        UiEvent::AdjustFilterContext(delta) => state.adjust_filter_context(delta),
+        UiEvent::ScrollHorizontal(delta) => state.scroll_horizontal(delta),
+        UiEvent::ScrollContext(delta) => state.scroll_context(delta),
+
+        UiEvent::ToggleFilterPanel => { state.filter_panel_open = !state.filter_panel_open; },
+        UiEvent::ToggleContextPanel => {
+            // Initialize selection if needed, then toggle
+            state.ensure_log_selection();
+            state.context_panel_open = !state.context_panel_open;
+            state.context_scroll = 0;
+        }
+        UiEvent::InputChar(c) => {
+            if state.filter_panel_open && matches!(state.filter_focus, FilterFocus::Input) { state.filter_history.reset(); state.filter_input.push(c); }
+        }
+        UiEvent::Backspace => {
+            if state.filter_panel_open && matches!(state.filter_focus, FilterFocus::Input) { state.filter_history.reset(); state.filter_input.pop(); }
+        }
+        UiEvent::FilterHistoryPrev => state.filter_history_prev(),
+        UiEvent::FilterHistoryNext => state.filter_history_next(),
+        UiEvent::AddFilter => {
+            if state.filter_panel_open { state.add_filter_from_input(); }
+        }
+        UiEvent::ToggleInputRegex => {
+            if state.filter_panel_open {
+                state.input_is_regex = !state.input_is_regex;
+                if state.input_is_regex { state.input_fuzzy = false; }
+            }
+        }
+        UiEvent::ToggleInputFuzzy => {
+            if state.filter_panel_open {
+                state.input_fuzzy = !state.input_fuzzy;
+                if state.input_fuzzy { state.input_is_regex = false; }
+            }
+        }
+        UiEvent::ToggleInputCase => { if state.filter_panel_open { state.input_case_insensitive = !state.input_case_insensitive; } }
+        UiEvent::ToggleInputWord => { if state.filter_panel_open { state.input_whole_word = !state.input_whole_word; } }
+        UiEvent::ToggleInputLine => { if state.filter_panel_open { state.input_whole_line = !state.input_whole_line; } }
+        UiEvent::ToggleFilterEnabled => { if state.filter_panel_open { state.toggle_selected_filter(); } }
+        UiEvent::DeleteFilter => { if state.filter_panel_open { state.remove_selected_filter(); } }
+        UiEvent::Undo => state.undo(),
+        UiEvent::AcknowledgeAlerts => state.acknowledge_alerts(),
+        UiEvent::ToggleAlertPanel => state.toggle_alert_panel(),
+        UiEvent::ToggleRange => state.toggle_range(),
+        UiEvent::ToggleRangeHidden => state.toggle_range_hidden(),
+        UiEvent::CopySelection => state.copy_selection(),
+        UiEvent::ToggleReveal => state.toggle_reveal(),
+        UiEvent::ToggleRewrites => state.toggle_rewrites(),
+        UiEvent::CycleAggregateField => state.cycle_aggregate_field(),
+        UiEvent::MuteAlert => state.open_mute_prompt(),
+        UiEvent::UnmuteAlert => state.unmute_selected_alert(),
+        UiEvent::Mark(all) => state.insert_marker(all),
+        UiEvent::ReplayPause => state.toggle_replay_pause(),
+        UiEvent::ReplayStep => state.replay_step(),
+        UiEvent::ReplaySpeed(by) => state.scale_replay_speed(by),
+        UiEvent::ClearSource => { if state.current_source().is_some() { state.open_prompt(PromptKind::ConfirmClear); } }
+        UiEvent::FocusNext => { if state.filter_panel_open { state.filter_focus = match state.filter_focus { FilterFocus::Input => FilterFocus::List, FilterFocus::List => FilterFocus::Input }; } }
+        UiEvent::SelectUp if state.link_chooser.is_some() => state.link_chooser_move(true),
+        UiEvent::SelectDown if state.link_chooser.is_some() => state.link_chooser_move(false),
+        UiEvent::SelectUp if state.query.is_some() => state.query_move(true),
+        UiEvent::SelectDown if state.query.is_some() => state.query_move(false),
+        UiEvent::SelectUp if state.patterns.is_some() => state.patterns_move(true),
+        UiEvent::SelectDown if state.patterns.is_some() => state.patterns_move(false),
+        UiEvent::SelectUp if state.alert_panel.is_some() => state.alert_panel_move(true),
+        UiEvent::SelectDown if state.alert_panel.is_some() => state.alert_panel_move(false),
+        UiEvent::SelectUp if state.correlation.is_some() => state.correlation_move(true),
+        UiEvent::SelectDown if state.correlation.is_some() => state.correlation_move(false),
+        UiEvent::SelectUp => { if state.filter_panel_open { state.move_selection_up(); } else { state.move_log_selection_by(-(count.unwrap_or(1) as isize)); } }
+        UiEvent::SelectDown => { if state.filter_panel_open { state.move_selection_down(); } else { state.move_log_selection_by(count.unwrap_or(1) as isize); } }
+        UiEvent::NextSource => { state.focus_next_source(); }
+        UiEvent::PrevSource => { state.focus_prev_source(); }
+        UiEvent::NextGroup => { state.focus_group(true); }
+        UiEvent::PrevGroup => { state.focus_group(false); }
+        UiEvent::ToggleGroup => { state.toggle_group_collapsed(); }
+
+        // Search controls
+        UiEvent::ToggleSearch => { state.open_search(); }
+        UiEvent::CloseSearch => { state.close_search(); }
+        UiEvent::SearchChar(c) => { state.search_push_char(c); }
+        UiEvent::SearchBackspace => { state.search_pop_char(); }
+        UiEvent::SearchHistoryPrev => { state.search_history_prev(); }
+        UiEvent::SearchHistoryNext => { state.search_history_next(); }
+        UiEvent::ApplySearch => { state.apply_search(); state.search_open = false; }
+        UiEvent::NextMatch => { if state.jump_next_match().is_none() { state.notify(MessageLevel::Warn, "no match"); } }
+        UiEvent::PrevMatch => { if state.jump_prev_match().is_none() { state.notify(MessageLevel::Warn, "no match"); } }
+        UiEvent::ToggleSearchRegex => { state.search_is_regex = !state.search_is_regex; }
+        UiEvent::ToggleSearchCase => { state.search_case_insensitive = !state.search_case_insensitive; }
+        UiEvent::ToggleSearchScope => { state.toggle_search_scope(); }
+        UiEvent::OpenReplace => { state.toggle_replace_preview(); }
+        UiEvent::OpenHotkey => { state.open_hotkey_prompt(); }
+        UiEvent::OpenFilterGroup => { state.open_filter_group_prompt(); }
+        UiEvent::ToggleFilterGroup => { state.toggle_filter_group_collapsed(); }
+        UiEvent::Hotkey(key) => { state.toggle_hotkey(key); }
+
+        UiEvent::CycleHistogram => state.cycle_histogram(),
+        UiEvent::HistogramMove(delta) => state.histogram_move(delta),
+
+        UiEvent::ToggleSummary => state.toggle_summary(),
+        UiEvent::SummaryScroll(up) => state.summary_scroll(up),
+
+        // Top patterns panel
+        UiEvent::TogglePatterns => state.toggle_patterns(),
+        UiEvent::OpenQuery => state.open_prompt(PromptKind::Query),
+        UiEvent::CloseQuery => state.close_query(),
+        UiEvent::PatternsWindow => state.cycle_patterns_window(),
+        UiEvent::PatternsJump => state.patterns_jump(),
+
+        // Correlation view
+        UiEvent::Correlate => { if let Err(e) = state.correlate_selected() { state.notify(MessageLevel::Warn, format!("correlate: {}", e)); } }
+        UiEvent::CloseCorrelation => state.close_correlation(),
+        UiEvent::CorrelationJump => state.correlation_jump(),
+
+        // Generic prompt
+        UiEvent::OpenGoto => { state.open_prompt(PromptKind::Goto); }
+        UiEvent::OpenPipe => { if state.current_source().is_some() { state.open_prompt(PromptKind::Pipe); } }
+        UiEvent::OpenExport => { if state.current_source().is_some() { state.open_prompt(PromptKind::Export); } }
+        UiEvent::OpenScreenshot => { state.open_prompt(PromptKind::Screenshot); }
+        UiEvent::CycleExportFormat => { state.export_format = state.export_format.next(); }
+        UiEvent::OpenSource => { state.open_prompt(PromptKind::OpenSource); }
+        UiEvent::Freeze => { let _ = state.freeze_focused(); }
+        UiEvent::OpenShell => { state.open_prompt(PromptKind::Shell); }
+        UiEvent::PromptChar(c) => { state.prompt_push_char(c); }
+        UiEvent::PromptBackspace => { state.prompt_pop_char(); }
+        UiEvent::SubmitPrompt => state.submit_prompt(),
+        UiEvent::ClosePrompt => { state.close_prompt(); }
+    }
+    None
+}
+
+/// Abort all reader tasks and wait (briefly) until they are gone, so the processes and
+/// connections they own are closed before the runtime shuts down
+async fn stop_readers(readers: HashMap<usize, JoinHandle<()>>) {
+    for handle in readers.values() { handle.abort(); }
+    let _ = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+        for handle in readers.into_values() { let _ = handle.await; }
+    }).await;
 }
@@ -1,60 +1,994 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use crate::classify::{AlertRule, AlertSeverity};
+use crate::config::{AlertEntry, EmailConfig, FileConfig, HotkeyEntry, InputEntry, LevelsConfig, RedactConfig, RedactRule, RewriteRule, SourceProfileEntry, TransformEntry};
+use crate::editor::DEFAULT_FILE_PATTERN;
+use crate::encoding::Encoding;
+use crate::export::ExportFormat;
+use crate::filter::{build_filter, DEFAULT_CORRELATE_PATTERN, TAB_WIDTH};
+use crate::log::{parse_serial_spec, parse_ssh_spec};
+use crate::replay::Speed;
+use crate::report::ReportFormat;
+use crate::timestamp::{is_valid_format, DisplayZone};
+use clap_complete::Shell;
+use ratatui::style::Color;
+use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// An input path with an optional user-assigned display name
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputSpec {
+    pub name: Option<String>,
+    pub path: PathBuf,
+}
 
 /// Immutable configuration used by the application runtime
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub inputs: Vec<PathBuf>,
+    pub inputs: Vec<InputSpec>,
     pub follow: bool,
+    /// Followed files start where the last session stopped reading them
+    pub resume: bool,
     pub regex: Option<String>,
+    /// A line matching it ends the session with a failure status
+    pub exit_on_match: Option<String>,
+    /// Alerts not acknowledged by the end make the exit status a failure
+    pub fail_on_alert: bool,
+    /// Lines shown around each filter match
+    pub context: usize,
     pub recursive: bool,
-    pub alerts: Vec<String>,
+    pub alerts: Vec<AlertRule>,
+    /// What the config file was merged with, to merge it again when the file changes
+    pub reload: ConfigReload,
+    pub timestamps: bool,
+    /// `--timestamp-format`, by source name (None for all sources)
+    pub timestamp_formats: Vec<(Option<String>, String)>,
+    /// `--encoding`, by source name (None for all sources)
+    pub encodings: Vec<(Option<String>, Encoding)>,
+    pub correlate: String,
+    /// Regex finding `path:line` locations for the open-in-editor action; empty disables it
+    pub file_pattern: String,
+    pub groups: Vec<(String, String)>,
+    /// Remote files as `[user@]host:/path`, read through the system ssh client
+    pub ssh: Vec<String>,
+    /// URLs polled for appended content
+    pub http: Vec<String>,
+    pub poll_interval: Duration,
+    /// Addresses to accept Fluent forward protocol connections on
+    pub fluent: Vec<String>,
+    /// Addresses to accept `rtlog agent` connections on
+    pub agents: Vec<String>,
+    /// macOS unified log predicates, each streamed as its own source
+    pub oslog: Vec<String>,
+    /// Serial devices as `PATH[:BAUD]`
+    pub serial: Vec<String>,
+    /// Shell commands rerun periodically, each run's output appended as a block
+    pub watch: Vec<String>,
+    /// Pause between the end of a watched command's run and the next
+    pub watch_interval: Duration,
+    /// Files receiving a copy of every line: of all sources, or of the named source
+    pub tee: Vec<(Option<String>, PathBuf)>,
+    /// Plugin commands (see `crate::plugin`)
+    pub plugins: Vec<String>,
+    /// Captured log files re-emitted according to their timestamps
+    pub replay: Vec<PathBuf>,
+    /// Pacing of the replayed files and of the played back session
+    pub speed: Speed,
+    /// File the session's lines and key presses are recorded to
+    pub record: Option<PathBuf>,
+    /// Session recorded with `--record` to play back
+    pub play: Option<PathBuf>,
+    /// Level classification patterns from the config file
+    pub levels: LevelsConfig,
+    /// Mailing alerts, from the config file
+    pub email: Option<EmailConfig>,
+    /// Line transform scripts, from the config file
+    pub transforms: Vec<TransformEntry>,
+    /// Redaction rules from the command line and the config file
+    pub redact: RedactConfig,
+    /// Display rewrite rules, from the config file
+    pub rewrite: Vec<RewriteRule>,
+    /// Parsing profiles of matching files, from the config file
+    pub profiles: Vec<SourceProfileEntry>,
+    /// Smart case for filters and searches typed in the TUI, from the config file
+    pub smart_case: bool,
+    /// Status bar template, from the config file
+    pub status_bar: Option<String>,
+    /// Columns between tab stops in log lines, from the config file
+    pub tab_width: usize,
+    /// Filters bound to Alt+1 … Alt+9, from the config file
+    pub hotkeys: Vec<HotkeyEntry>,
+    /// Initial format of the export prompt
+    pub export_format: ExportFormat,
+    /// Share of the visible height a page scroll moves
+    pub page_step: f64,
+    /// What moves the focus to the source it appeared in: alerts, or lines matching a regex
+    pub focus_follow: Option<String>,
+    /// Zone timestamps are shown in
+    pub tz: Option<DisplayZone>,
+    /// Timestamp gaps between consecutive lines longer than this get a separator
+    pub gap: Option<Duration>,
+    /// Open with the summary of all sources (when not following)
+    pub summary: bool,
+    /// Start in compact mode
+    pub compact: bool,
+    /// Reading stops this long after starting
+    pub duration: Option<Duration>,
+    /// Reading stops once no source had a line for this long
+    pub until_idle: Option<Duration>,
+    /// Where the lines are exported when reading stops, before exiting
+    pub export: Option<PathBuf>,
+    /// Leave the terminal title alone
+    pub no_title: bool,
+    /// Unix socket accepting JSON commands (see `crate::control`)
+    pub control: Option<PathBuf>,
+    /// Hidden benchmark mode: ingest this file without a UI and report throughput
+    pub bench_ingest: Option<PathBuf>,
+    /// View the single input synchronously, without the async runtime (see `crate::simple`)
+    pub simple: bool,
 }
 
-/// User-facing CLI arguments (kept private to the CLI layer)
+/// What rtlog was asked to do
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Open the viewer (`rtlog PATH...`)
+    View(Box<Config>),
+    /// Write a report without a terminal (`rtlog report PATH... --out FILE`)
+    Report(Box<ReportConfig>),
+    /// Follow the sources without a terminal and serve them to browsers on an address
+    /// (`rtlog serve --addr HOST:PORT PATH...`)
+    Serve(Box<Config>, String),
+    /// Follow the sources without a terminal and stream them to a viewer: the viewer's address
+    /// and the host name to show (`rtlog agent --connect HOST:PORT PATH...`)
+    Agent(Box<Config>, String, String),
+    /// Print a shell completion script (`rtlog completions SHELL`)
+    Completions(Shell),
+    /// Print the man page, or write all of them to a directory (`rtlog man [--dir DIR]`)
+    Man(Option<PathBuf>),
+    /// Print a commented config file (`rtlog --print-default-config`)
+    PrintDefaultConfig,
+}
+
+/// Configuration of `rtlog report`
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    pub inputs: Vec<InputSpec>,
+    pub recursive: bool,
+    pub out: PathBuf,
+    pub format: ReportFormat,
+    /// Only lines matching this count
+    pub regex: Option<String>,
+    pub alerts: Vec<AlertRule>,
+    pub levels: LevelsConfig,
+    pub transforms: Vec<TransformEntry>,
+    pub redact: RedactConfig,
+    pub profiles: Vec<SourceProfileEntry>,
+    pub timestamp_formats: Vec<(Option<String>, String)>,
+    pub encodings: Vec<(Option<String>, Encoding)>,
+    /// A line matching it makes the exit status a failure
+    pub exit_on_match: Option<String>,
+    /// Alerts make the exit status a failure
+    pub fail_on_alert: bool,
+}
+
+/// User-facing CLI arguments (kept private to the CLI layer). Without a subcommand the
+/// arguments are the viewer's, so `rtlog PATH...` is short for `rtlog view PATH...`.
 #[derive(Parser, Debug)]
-#[command(name = "rtlog", version, about = "Real-time log viewer")]
-struct Args {
+#[command(name = "rtlog", version, about = "Real-time log viewer", args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Sub>,
+
+    #[command(flatten)]
+    view: ViewArgs,
+
+    /// Print a commented config file with the built-in defaults and exit
+    #[arg(long = "print-default-config")]
+    print_default_config: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Sub {
+    /// Open the viewer on log files and other sources (what `rtlog PATH...` does)
+    View(ViewArgs),
+    /// Open the viewer following the files for appended lines (`rtlog view -f`)
+    Follow(ViewArgs),
+    /// Write a static report on log files: levels, top messages, error timeline and alert matches
+    Report(ReportArgs),
+    /// Follow the sources without a terminal and show them live in browsers
+    Serve(ServeArgs),
+    /// Follow the sources without a terminal and stream them to a viewer started with --agents
+    Agent(AgentArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+    /// Print the man page (roff)
+    Man(ManArgs),
+}
+
+/// Where lines come from, shared by the viewer and `rtlog report`
+#[derive(clap::Args, Debug)]
+struct SourceArgs {
     /// Paths to log files or directories to read
-    #[arg(value_name = "PATH", num_args = 1.., required=true)]
+    #[arg(value_name = "PATH")]
     inputs: Vec<PathBuf>,
 
-    /// Follow the files for appended lines (like tail -f)
-    #[arg(short = 'f', long = "follow")]
-    follow: bool,
-
-    /// Regex filter to highlight matches (case-insensitive)
-    #[arg(short = 'r', long = "regex")]
-    regex: Option<String>,
+    /// Named input: NAME=PATH shows PATH as NAME in the sidebar (repeatable)
+    #[arg(long = "input", value_name = "NAME=PATH", value_parser = parse_named_input)]
+    named_inputs: Vec<(String, PathBuf)>,
 
     /// Recurse into directories when PATH is a directory
     #[arg(short = 'R', long = "recursive")]
     recursive: bool,
 
-    /// Patterns that trigger visual alerts (repeatable). Defaults to ERROR and FATAL if none provided.
-    #[arg(long = "alert")]
-    alerts: Vec<String>,
+    /// Config file (default: $XDG_CONFIG_HOME/rtlog/config.toml when present)
+    #[arg(long = "config", value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Read timestamps in the chrono FORMAT (e.g. '%Y-%m-%d %H:%M:%S%.3f') instead of detecting
+    /// them; with SOURCE= only for that source (sidebar name, label or input as given).
+    /// Repeatable; implies -t
+    #[arg(long = "timestamp-format", value_name = "[SOURCE=]FORMAT", value_parser = parse_timestamp_format)]
+    timestamp_formats: Vec<(Option<String>, String)>,
+
+    /// Read files in ENCODING (utf-8, utf-16le, utf-16be, latin1) instead of telling it from
+    /// their start; with SOURCE= only for that source. Repeatable
+    #[arg(long = "encoding", value_name = "[SOURCE=]ENCODING", value_parser = parse_encoding)]
+    encodings: Vec<(Option<String>, Encoding)>,
+}
+
+/// Which lines count and which raise alerts, shared by the viewer and `rtlog report`
+#[derive(clap::Args, Debug)]
+struct FilterArgs {
+    /// Regex filter (case-insensitive): only matching lines are shown, or counted in reports
+    #[arg(short = 'r', long = "regex")]
+    regex: Option<String>,
+
+    /// Pattern that triggers an alert, optionally prefixed with its severity info:, warn: (default)
+    /// or critical: (repeatable). Defaults to ERROR and FATAL if none provided.
+    #[arg(long = "alert", value_name = "[SEVERITY:]PAT", value_parser = parse_alert)]
+    alerts: Vec<(AlertSeverity, String)>,
 
-    /// Disable alerts entirely (no red highlights, no banner)
+    /// Disable alerts entirely (no red highlights, no banner, no alert matches in reports)
     #[arg(long = "no-alerts", alias = "no-alert")]
     no_alerts: bool,
+
+    /// Mask sensitive text on screen, in tee files, exports, reports and emails: a built-in rule
+    /// set (email, ip, token) or a regex whose matches become *** (repeatable; R reveals)
+    #[arg(long = "redact", value_name = "SET|REGEX")]
+    redact: Vec<String>,
+
+    /// Stop at the first line matching REGEX (case-insensitive) and exit with status 3, for
+    /// scripts and tests waiting for a failure; reports are written in full first
+    #[arg(long = "exit-on-match", value_name = "REGEX", value_parser = parse_regex)]
+    exit_on_match: Option<String>,
+
+    /// Exit with status 3 when alerts were raised and not acknowledged
+    #[arg(long = "fail-on-alert")]
+    fail_on_alert: bool,
+}
+
+/// Arguments of the viewer (`rtlog view`, `rtlog follow`, or none)
+#[derive(clap::Args, Debug)]
+struct ViewArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Remote file over SSH: [USER@]HOST:/PATH, tailed with the system ssh client (repeatable)
+    #[arg(long = "ssh", value_name = "[USER@]HOST:PATH", value_parser = parse_ssh)]
+    ssh: Vec<String>,
+
+    /// HTTP(S) URL polled for new content with Range requests (repeatable; follows with -f)
+    #[arg(long = "http", value_name = "URL", value_parser = parse_url)]
+    http: Vec<String>,
+
+    /// Poll interval for --http sources, e.g. 500ms, 5s, 1m
+    #[arg(long = "poll-interval", value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+    poll_interval: Duration,
+
+    /// Accept Fluentd/Fluent Bit forward protocol connections on [HOST:]PORT (port alone binds 127.0.0.1);
+    /// each tag becomes a source
+    #[arg(long = "fluent", value_name = "[HOST:]PORT", value_parser = parse_listen_addr)]
+    fluent: Vec<String>,
+
+    /// Accept `rtlog agent` connections on [HOST:]PORT (port alone binds 127.0.0.1); each remote
+    /// source becomes a source HOST/NAME
+    #[arg(long = "agents", value_name = "[HOST:]PORT", value_parser = parse_agents_addr)]
+    agents: Vec<String>,
+
+    /// Stream the macOS unified log (`log stream`) filtered by PREDICATE, e.g. 'process == "Finder"' (repeatable; '' for everything)
+    #[arg(long = "oslog", value_name = "PREDICATE", value_parser = parse_oslog)]
+    oslog: Vec<String>,
+
+    /// Serial device to read, PATH[:BAUD] (default baud 115200), e.g. /dev/ttyUSB0:115200 (repeatable)
+    #[arg(long = "serial", value_name = "PATH[:BAUD]", value_parser = parse_serial)]
+    serial: Vec<String>,
+
+    /// Run CMD repeatedly like watch(1), appending each run's output after a timestamped
+    /// separator (repeatable)
+    #[arg(long = "watch", value_name = "CMD")]
+    watch: Vec<String>,
+
+    /// Pause between --watch runs, e.g. 500ms, 5s, 1m
+    #[arg(long = "watch-interval", visible_alias = "interval", value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+    watch_interval: Duration,
+
+    /// Append every received line to PATH; with SOURCE= only lines of that source (sidebar name,
+    /// label or input as given). Repeatable
+    #[arg(long = "tee", value_name = "[SOURCE=]PATH", value_parser = parse_tee)]
+    tee: Vec<(Option<String>, PathBuf)>,
+
+    /// Replay a captured log FILE as if live, paced by its timestamps (repeatable; p pauses, . steps)
+    #[arg(long = "replay", value_name = "FILE")]
+    replay: Vec<PathBuf>,
+
+    /// Replay speed: a factor on the recorded gaps (2x, 0.5x) or a fixed rate (100/s)
+    #[arg(long = "speed", value_name = "SPEED", default_value = "1x", value_parser = parse_speed)]
+    speed: Speed,
+
+    /// Record the lines received and the keys pressed, with their timing, to FILE for --play
+    #[arg(long = "record", value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Play back a session recorded with --record: its sources, lines and keys, paced by --speed
+    #[arg(long = "play", value_name = "FILE")]
+    play: Option<PathBuf>,
+
+    /// Run CMD as a plugin exchanging JSON lines with rtlog: it receives lines, alerts and filter
+    /// changes, and can annotate lines, add filters and emit derived lines (repeatable)
+    #[arg(long = "plugin", value_name = "CMD")]
+    plugins: Vec<String>,
+
+    /// Format of exported lines (`e`): text, jsonl or csv; Tab switches it in the export prompt
+    #[arg(long = "export-format", value_name = "FORMAT", default_value = "text", value_parser = parse_export_format)]
+    export_format: ExportFormat,
+
+    /// PageUp/PageDown scroll this many visible heights, Ctrl-U/Ctrl-D half as many (e.g. 0.5)
+    #[arg(long = "page-step", value_name = "FACTOR", default_value_t = 1.0, value_parser = parse_page_step)]
+    page_step: f64,
+
+    /// Move the focus to the source that last raised an alert ("alerts") or printed a line
+    /// matching REGEX; L switches it on and off
+    #[arg(long = "focus-follow", value_name = "alerts|REGEX")]
+    focus_follow: Option<String>,
+
+    /// Accept JSON commands (add a filter, focus a source, export, acknowledge alerts) on a Unix
+    /// socket created at PATH, so scripts and editors can drive the viewer
+    #[arg(long = "control", value_name = "PATH")]
+    control: Option<PathBuf>,
+
+    /// Ingest FILE without a terminal and print lines/sec (for performance work)
+    #[arg(long = "bench-ingest", value_name = "FILE", hide = true)]
+    bench_ingest: Option<PathBuf>,
+
+    /// View a single file without the async runtime, for the quickest start on small systems:
+    /// no other kinds of sources, tee files, plugins, control socket, email or config reload
+    #[arg(long = "simple", conflicts_with_all = ["ssh", "http", "fluent", "agents", "oslog", "serial", "watch", "replay", "record", "play", "plugins", "tee", "control", "bench_ingest"])]
+    simple: bool,
+
+    /// Follow the files for appended lines (like tail -f)
+    #[arg(short = 'f', long = "follow")]
+    follow: bool,
+
+    /// With -f, start each file where the last session stopped reading it instead of at its end,
+    /// so lines written while rtlog was not running are not missed
+    #[arg(long = "resume")]
+    resume: bool,
+
+    /// Stop reading the sources DURATION after starting, e.g. 10m, for unattended captures
+    #[arg(long = "duration", value_name = "DURATION", value_parser = parse_duration)]
+    duration: Option<Duration>,
+
+    /// Stop reading the sources once none has had a new line for DURATION, e.g. 60s
+    #[arg(long = "until-idle", value_name = "DURATION", value_parser = parse_duration)]
+    until_idle: Option<Duration>,
+
+    /// When --duration or --until-idle stops reading, write the lines of every source that pass
+    /// the filters to FILE (in --export-format) and exit
+    #[arg(long = "export", value_name = "FILE")]
+    export: Option<PathBuf>,
+
+    /// Without -f, open with a summary of each source (lines, levels, time span, top patterns);
+    /// any key closes it and I shows it again
+    #[arg(long = "summary")]
+    summary: bool,
+
+    /// Start in compact mode: only the log lines and the status bar, for small panes (Z toggles)
+    #[arg(long = "compact")]
+    compact: bool,
+
+    /// Leave the terminal title alone (by default it names the focused source and counts
+    /// unacknowledged alerts)
+    #[arg(long = "no-title")]
+    no_title: bool,
+
+    /// Show N lines before and after each filter match, dimmed, like grep -C (+/- change it)
+    #[arg(short = 'C', long = "context", value_name = "N", default_value_t = 0)]
+    context: usize,
+
+    /// Parse timestamps in lines (enables jumping to a time with ':')
+    #[arg(short = 't', long = "timestamps")]
+    timestamps: bool,
+
+    /// Show timestamps converted to ZONE: UTC, local or a name like Europe/Berlin (lines
+    /// without a UTC offset are taken as local time)
+    #[arg(long = "tz", value_name = "ZONE", value_parser = parse_tz)]
+    tz: Option<DisplayZone>,
+
+    /// Insert a "---- GAP 4m 32s ----" separator where the timestamps of consecutive lines of a
+    /// source are further apart than DURATION (e.g. 30s, 5m, 1h)
+    #[arg(long = "gap", value_name = "DURATION", value_parser = parse_duration)]
+    gap: Option<Duration>,
+
+    /// Regex extracting a request/trace ID for the correlate action ('c'); first capture group is the ID
+    #[arg(long = "correlate", value_name = "REGEX", default_value = DEFAULT_CORRELATE_PATTERN, hide_default_value = true)]
+    correlate: String,
+
+    /// Regex finding file locations for opening them in $EDITOR ('E'): groups named file, line
+    /// and col, or else the first two groups as file and line
+    #[arg(long = "file-pattern", value_name = "REGEX", default_value = DEFAULT_FILE_PATTERN, hide_default_value = true)]
+    file_pattern: String,
+
+    /// Group sources in the sidebar: NAME=REGEX matched against the path (repeatable; first match wins)
+    #[arg(long = "group", value_name = "NAME=REGEX", value_parser = parse_group)]
+    groups: Vec<(String, String)>,
+}
+
+/// Arguments of `rtlog report`
+#[derive(clap::Args, Debug)]
+struct ReportArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Report file to write
+    #[arg(short = 'o', long = "out", value_name = "FILE")]
+    out: PathBuf,
+
+    /// md or html (default: from the --out extension)
+    #[arg(long = "format", value_name = "FORMAT", value_parser = parse_report_format)]
+    format: Option<ReportFormat>,
+}
+
+/// Arguments of `rtlog serve`
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// Address to serve the web view on, [HOST:]PORT (port alone binds 127.0.0.1)
+    #[arg(long = "addr", value_name = "[HOST:]PORT", default_value = "127.0.0.1:8080", value_parser = parse_serve_addr)]
+    addr: String,
+
+    #[command(flatten)]
+    view: ViewArgs,
+}
+
+/// Arguments of `rtlog agent`
+#[derive(clap::Args, Debug)]
+struct AgentArgs {
+    /// Viewer to stream to, HOST[:PORT] (its --agents address)
+    #[arg(long = "connect", value_name = "HOST[:PORT]", value_parser = parse_connect_addr)]
+    connect: String,
+
+    /// Name the viewer shows this machine's sources under (default: the host name)
+    #[arg(long = "name", value_name = "NAME")]
+    name: Option<String>,
+
+    #[command(flatten)]
+    view: ViewArgs,
+}
+
+/// Arguments of `rtlog completions`
+#[derive(clap::Args, Debug)]
+struct CompletionsArgs {
+    /// Shell to complete for
+    #[arg(value_name = "SHELL")]
+    shell: Shell,
+}
+
+/// Arguments of `rtlog man`
+#[derive(clap::Args, Debug)]
+struct ManArgs {
+    /// Write rtlog.1 and a page per subcommand (rtlog-report.1, ...) to DIR instead
+    #[arg(long = "dir", value_name = "DIR")]
+    dir: Option<PathBuf>,
+}
+
+/// Write the completion script for `shell` to `out`
+pub fn write_completions(shell: Shell, out: &mut dyn Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "rtlog", out);
+}
+
+/// Write the man page (roff) to `out`
+pub fn write_man(out: &mut dyn Write) -> std::io::Result<()> {
+    clap_mangen::Man::new(Cli::command()).render(out)
+}
+
+/// Write the man pages of rtlog and its subcommands to `dir`
+pub fn write_man_pages(dir: &std::path::Path) -> std::io::Result<()> {
+    clap_mangen::generate_to(Cli::command(), dir)
+}
+
+/// The command line options the config file complements, kept to merge the file again when it
+/// changes while running
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    /// `--config`; None for the default location
+    pub config: Option<PathBuf>,
+    inputs: Vec<InputSpec>,
+    alerts: Vec<(AlertSeverity, String)>,
+    no_alerts: bool,
+    redact: Vec<String>,
+}
+
+impl ConfigReload {
+    /// The config file to watch for changes (it may not exist yet)
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.config.clone().or_else(crate::config::default_path)
+    }
+
+    /// `config` with the settings that come from the config file read again
+    pub fn reload(&self, config: &Config) -> anyhow::Result<Config> {
+        let file = FileConfig::load(self.config.as_deref())?;
+        let tab_width = tab_width(&file)?;
+        let mut inputs = self.inputs.clone();
+        inputs.extend(file.inputs.into_iter().map(|e| InputSpec { name: e.name, path: e.path }));
+        Ok(Config {
+            inputs,
+            alerts: merge_alerts(self.alerts.clone(), file.alerts, self.no_alerts)?,
+            levels: file.levels,
+            email: file.email,
+            transforms: file.transforms,
+            redact: merge_redact(file.redact, self.redact.clone()),
+            rewrite: file.rewrite,
+            profiles: file.sources,
+            smart_case: file.smart_case.unwrap_or(true),
+            status_bar: file.status_bar,
+            tab_width,
+            hotkeys: file.hotkeys,
+            ..config.clone()
+        })
+    }
+}
+
+/// `tab_width` from the config file, 8 without it
+fn tab_width(file: &FileConfig) -> anyhow::Result<usize> {
+    match file.tab_width {
+        Some(width) if !(1..=32).contains(&width) => anyhow::bail!("tab_width must be between 1 and 32, not {}", width),
+        width => Ok(width.unwrap_or(TAB_WIDTH)),
+    }
+}
+
+/// Exit with a usage error, showing the usage of `subcommand` (of the bare invocation for None)
+fn usage_error(subcommand: Option<&str>, kind: ErrorKind, message: &str) -> ! {
+    let mut cmd = Cli::command();
+    cmd.build();
+    match subcommand.and_then(|name| cmd.find_subcommand_mut(name)) {
+        Some(sub) => sub.error(kind, message).exit(),
+        None => cmd.error(kind, message).exit(),
+    }
+}
+
+/// Parse CLI options (merged with the config file)
+pub fn parse() -> anyhow::Result<Command> {
+    let cli = Cli::parse();
+    if cli.print_default_config { return Ok(Command::PrintDefaultConfig); }
+    Ok(match cli.command {
+        None => Command::View(Box::new(parse_view(cli.view, false, None)?)),
+        Some(Sub::View(args)) => Command::View(Box::new(parse_view(args, false, Some("view"))?)),
+        Some(Sub::Follow(args)) => Command::View(Box::new(parse_view(args, true, Some("follow"))?)),
+        Some(Sub::Report(args)) => Command::Report(Box::new(parse_report(args)?)),
+        Some(Sub::Serve(args)) => Command::Serve(Box::new(parse_view(args.view, true, Some("serve"))?), args.addr),
+        Some(Sub::Agent(args)) => {
+            let host = args.name.unwrap_or_else(|| hostname::get().map(|h| h.to_string_lossy().into_owned()).unwrap_or_else(|_| "agent".into()));
+            Command::Agent(Box::new(parse_view(args.view, true, Some("agent"))?), args.connect, host)
+        }
+        Some(Sub::Completions(args)) => Command::Completions(args.shell),
+        Some(Sub::Man(args)) => Command::Man(args.dir),
+    })
+}
+
+/// Inputs from PATH arguments, `--input` and the config file
+fn collect_inputs(source: SourceArgs, file: Vec<InputEntry>) -> Vec<InputSpec> {
+    let mut inputs: Vec<InputSpec> = source.inputs.into_iter().map(|path| InputSpec { name: None, path }).collect();
+    inputs.extend(source.named_inputs.into_iter().map(|(name, path)| InputSpec { name: Some(name), path }));
+    inputs.extend(file.into_iter().map(|e| InputSpec { name: e.name, path: e.path }));
+    inputs
+}
+
+fn parse_view(args: ViewArgs, follow: bool, subcommand: Option<&str>) -> anyhow::Result<Config> {
+    let file = FileConfig::load(args.source.config.as_deref())?;
+    let tab_width = tab_width(&file)?;
+    let config_path = args.source.config.clone();
+    let recursive = args.source.recursive;
+    let timestamp_formats = args.source.timestamp_formats.clone();
+    let encodings = args.source.encodings.clone();
+    let from_file = file.inputs.len();
+    let inputs = collect_inputs(args.source, file.inputs);
+    if args.bench_ingest.is_none() && inputs.is_empty() && args.ssh.is_empty() && args.http.is_empty() && args.fluent.is_empty() && args.agents.is_empty() && args.oslog.is_empty() && args.serial.is_empty() && args.watch.is_empty() && args.replay.is_empty() && args.play.is_none() {
+        usage_error(subcommand, ErrorKind::MissingRequiredArgument, "no inputs: pass PATH..., --input NAME=PATH, --ssh HOST:PATH, --http URL, --fluent PORT, --agents PORT, --serial DEV, --watch CMD, --replay FILE, --play FILE, or list inputs in the config file");
+    }
+    if args.simple {
+        if matches!(subcommand, Some("serve" | "agent")) {
+            usage_error(subcommand, ErrorKind::ArgumentConflict, "--simple only applies to the viewer");
+        }
+        if inputs.len() != 1 || inputs[0].path.is_dir() {
+            usage_error(subcommand, ErrorKind::ValueValidation, "--simple reads exactly one file");
+        }
+    }
+    if args.resume && !(follow || args.follow) {
+        usage_error(subcommand, ErrorKind::MissingRequiredArgument, "--resume continues followed files; add -f");
+    }
+    if args.export.is_some() && args.duration.is_none() && args.until_idle.is_none() {
+        usage_error(subcommand, ErrorKind::MissingRequiredArgument, "--export needs --duration or --until-idle to know when to write");
+    }
+    let filter = args.filter;
+    let reload = ConfigReload {
+        config: config_path,
+        inputs: inputs[..inputs.len() - from_file].to_vec(),
+        alerts: filter.alerts.clone(),
+        no_alerts: filter.no_alerts,
+        redact: filter.redact.clone(),
+    };
+    Ok(Config {
+        inputs,
+        follow: follow || args.follow,
+        resume: args.resume,
+        regex: filter.regex,
+        exit_on_match: filter.exit_on_match,
+        fail_on_alert: filter.fail_on_alert,
+        context: args.context,
+        recursive,
+        alerts: merge_alerts(filter.alerts, file.alerts, filter.no_alerts)?,
+        reload,
+        timestamps: args.timestamps || !timestamp_formats.is_empty(),
+        timestamp_formats,
+        encodings,
+        correlate: args.correlate,
+        file_pattern: args.file_pattern,
+        groups: args.groups,
+        ssh: args.ssh,
+        http: args.http,
+        poll_interval: args.poll_interval,
+        fluent: args.fluent,
+        agents: args.agents,
+        oslog: args.oslog,
+        serial: args.serial,
+        watch: args.watch,
+        watch_interval: args.watch_interval,
+        tee: args.tee,
+        plugins: args.plugins,
+        replay: args.replay,
+        speed: args.speed,
+        record: args.record,
+        play: args.play,
+        levels: file.levels,
+        email: file.email,
+        transforms: file.transforms,
+        redact: merge_redact(file.redact, filter.redact),
+        rewrite: file.rewrite,
+        profiles: file.sources,
+        smart_case: file.smart_case.unwrap_or(true),
+        status_bar: file.status_bar,
+        tab_width,
+        hotkeys: file.hotkeys,
+        export_format: args.export_format,
+        page_step: args.page_step,
+        focus_follow: args.focus_follow,
+        tz: args.tz,
+        gap: args.gap,
+        summary: args.summary,
+        compact: args.compact,
+        duration: args.duration,
+        until_idle: args.until_idle,
+        export: args.export,
+        no_title: args.no_title,
+        control: args.control,
+        bench_ingest: args.bench_ingest,
+        simple: args.simple,
+    })
+}
+
+fn parse_report(args: ReportArgs) -> anyhow::Result<ReportConfig> {
+    let file = FileConfig::load(args.source.config.as_deref())?;
+    let Some(format) = args.format.or_else(|| ReportFormat::of_path(&args.out)) else {
+        usage_error(Some("report"), ErrorKind::ValueValidation, "cannot tell the report format from the --out extension: pass --format md or --format html");
+    };
+    let recursive = args.source.recursive;
+    let timestamp_formats = args.source.timestamp_formats.clone();
+    let encodings = args.source.encodings.clone();
+    let inputs = collect_inputs(args.source, file.inputs);
+    if inputs.is_empty() {
+        usage_error(Some("report"), ErrorKind::MissingRequiredArgument, "no inputs: pass PATH..., --input NAME=PATH, or list inputs in the config file");
+    }
+    Ok(ReportConfig {
+        inputs,
+        recursive,
+        out: args.out,
+        format,
+        regex: args.filter.regex,
+        alerts: merge_alerts(args.filter.alerts, file.alerts, args.filter.no_alerts)?,
+        levels: file.levels,
+        transforms: file.transforms,
+        redact: merge_redact(file.redact, args.filter.redact),
+        profiles: file.sources,
+        timestamp_formats,
+        encodings,
+        exit_on_match: args.filter.exit_on_match,
+        fail_on_alert: args.filter.fail_on_alert,
+    })
 }
 
-/// Parse CLI options into an application Config
-pub fn parse() -> Config {
-    let args = Args::parse();
-    let alerts = if args.no_alerts {
-        Vec::new()
-    } else if args.alerts.is_empty() {
-        vec!["ERROR".into(), "FATAL".into()]
-    } else {
-        args.alerts
+/// Alert rules from the command line, then the config file; ERROR and FATAL without any
+fn merge_alerts(cli: Vec<(AlertSeverity, String)>, file: Vec<AlertEntry>, no_alerts: bool) -> anyhow::Result<Vec<AlertRule>> {
+    if no_alerts { return Ok(Vec::new()); }
+    let mut alerts: Vec<AlertRule> = cli.into_iter().map(|(severity, pattern)| AlertRule::new(pattern, false, severity)).collect();
+    for entry in file {
+        let mut alert = AlertRule::new(entry.pattern, entry.regex, entry.severity);
+        if alert.rule.compiled.is_none() {
+            let err = alert.rule.compile().err().map(|e| e.to_string()).unwrap_or_default();
+            anyhow::bail!("alert pattern '{}': {}", alert.rule.pattern, err);
+        }
+        if let Some(color) = &entry.color {
+            Color::from_str(color).map_err(|_| anyhow::anyhow!("alert pattern '{}': unknown color '{}'", alert.rule.pattern, color))?;
+        }
+        (alert.name, alert.color) = (entry.name, entry.color);
+        alerts.push(alert);
+    }
+    if alerts.is_empty() {
+        alerts = ["ERROR", "FATAL"].into_iter().map(|p| AlertRule::new(p.into(), false, AlertSeverity::Warn)).collect();
+    }
+    Ok(alerts)
+}
+
+/// `[redact]` from the config file plus the `--redact` rule sets and regexes
+fn merge_redact(mut redact: RedactConfig, rules: Vec<String>) -> RedactConfig {
+    for rule in rules {
+        if crate::redact::BUILTIN.contains(&rule.as_str()) {
+            redact.builtin.push(rule);
+        } else {
+            redact.rules.push(RedactRule { pattern: rule, replace: crate::redact::DEFAULT_REPLACEMENT.to_string() });
+        }
+    }
+    redact
+}
+
+fn parse_report_format(s: &str) -> Result<ReportFormat, String> {
+    ReportFormat::parse(s).ok_or_else(|| "expected md or html".into())
+}
+
+fn parse_named_input(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => Ok((name.to_string(), PathBuf::from(path))),
+        _ => Err("expected NAME=PATH".into()),
+    }
+}
+
+/// `critical:disk full` → (Critical, "disk full"); without a known severity prefix the whole
+/// argument is a warn pattern
+fn parse_alert(s: &str) -> Result<(AlertSeverity, String), String> {
+    let parsed = s.split_once(':').and_then(|(sev, pat)| Some((AlertSeverity::parse(sev)?, pat)));
+    match parsed {
+        Some((severity, pat)) if !pat.is_empty() => Ok((severity, pat.to_string())),
+        Some(_) => Err("empty alert pattern".into()),
+        None if !s.is_empty() => Ok((AlertSeverity::Warn, s.to_string())),
+        None => Err("empty alert pattern".into()),
+    }
+}
+
+fn parse_tee(s: &str) -> Result<(Option<String>, PathBuf), String> {
+    match s.split_once('=') {
+        Some((source, path)) if !source.is_empty() && !path.is_empty() => Ok((Some(source.to_string()), PathBuf::from(path))),
+        None if !s.is_empty() => Ok((None, PathBuf::from(s))),
+        _ => Err("expected [SOURCE=]PATH".into()),
+    }
+}
+
+/// `[SOURCE=]FORMAT`; a format is told from a source name by its `%`
+fn parse_timestamp_format(s: &str) -> Result<(Option<String>, String), String> {
+    let (source, format) = match s.split_once('=') {
+        Some((source, format)) if !source.contains('%') => (Some(source.to_string()), format),
+        _ => (None, s),
     };
-    Config {
-        inputs: args.inputs,
-        follow: args.follow,
-        regex: args.regex,
-        recursive: args.recursive,
-        alerts,
+    if source.as_deref() == Some("") || !is_valid_format(format) {
+        return Err("expected [SOURCE=]FORMAT with a chrono format like '%Y-%m-%d %H:%M:%S'".into());
+    }
+    Ok((source, format.to_string()))
+}
+
+/// `[SOURCE=]ENCODING`; an encoding name has no `=`
+fn parse_encoding(s: &str) -> Result<(Option<String>, Encoding), String> {
+    match s.rsplit_once('=') {
+        Some(("", _)) => Err("expected [SOURCE=]ENCODING".into()),
+        Some((source, encoding)) => Ok((Some(source.to_string()), encoding.parse()?)),
+        None => Ok((None, s.parse()?)),
+    }
+}
+
+fn parse_regex(s: &str) -> Result<String, String> {
+    build_filter(Some(s)).map(|_| s.to_string()).map_err(|e| e.to_string())
+}
+
+fn parse_speed(s: &str) -> Result<Speed, String> {
+    Speed::parse(s).ok_or_else(|| "expected a factor like 2x or a rate like 100/s".into())
+}
+
+fn parse_export_format(s: &str) -> Result<ExportFormat, String> {
+    ExportFormat::parse(s).ok_or_else(|| "expected text, jsonl or csv".into())
+}
+
+fn parse_tz(s: &str) -> Result<DisplayZone, String> {
+    DisplayZone::parse(s).ok_or_else(|| "expected UTC, local or a zone name like Europe/Berlin".into())
+}
+
+fn parse_page_step(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        _ => Err("expected a positive factor like 1 or 0.5".into()),
+    }
+}
+
+fn parse_ssh(s: &str) -> Result<String, String> {
+    match parse_ssh_spec(s) {
+        Some(_) => Ok(s.to_string()),
+        None => Err("expected [USER@]HOST:PATH".into()),
+    }
+}
+
+fn parse_url(s: &str) -> Result<String, String> {
+    if s.starts_with("http://") || s.starts_with("https://") { Ok(s.to_string()) } else { Err("expected an http:// or https:// URL".into()) }
+}
+
+/// Parse `500ms`, `2s`, `1m`, `1h` or a plain number of seconds
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (num, unit) = s.find(|c: char| !c.is_ascii_digit() && c != '.').map_or((s, "s"), |i| s.split_at(i));
+    let value: f64 = num.parse().map_err(|_| format!("invalid duration: {}", s))?;
+    let secs = match unit {
+        "ms" => value / 1000.0,
+        "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return Err(format!("invalid duration unit: {}", unit)),
+    };
+    if secs <= 0.0 { return Err("duration must be positive".into()); }
+    Ok(Duration::from_secs_f64(secs))
+}
+
+/// `[HOST:]PORT` as `HOST:PORT`, the host defaulting to 127.0.0.1
+fn listen_addr(s: &str) -> Option<String> {
+    if let Ok(port) = s.parse::<u16>() { return Some(format!("127.0.0.1:{}", port)); }
+    match s.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_listen_addr(s: &str) -> Result<String, String> {
+    listen_addr(s).ok_or_else(|| format!("expected [HOST:]PORT (the forward protocol default is {})", crate::fluent::DEFAULT_PORT))
+}
+
+fn parse_serve_addr(s: &str) -> Result<String, String> {
+    listen_addr(s).ok_or_else(|| "expected [HOST:]PORT, e.g. 8080 or 0.0.0.0:8080".into())
+}
+
+fn parse_agents_addr(s: &str) -> Result<String, String> {
+    listen_addr(s).ok_or_else(|| format!("expected [HOST:]PORT (agents connect to port {} by default)", crate::agent::DEFAULT_PORT))
+}
+
+/// `HOST[:PORT]` as `HOST:PORT`, the port defaulting to the agent port
+fn parse_connect_addr(s: &str) -> Result<String, String> {
+    match s.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(s.to_string()),
+        None if !s.is_empty() => Ok(format!("{}:{}", s, crate::agent::DEFAULT_PORT)),
+        _ => Err("expected HOST[:PORT]".into()),
+    }
+}
+
+fn parse_oslog(s: &str) -> Result<String, String> {
+    if cfg!(target_os = "macos") { Ok(s.to_string()) } else { Err("the unified log is only available on macOS".into()) }
+}
+
+fn parse_serial(s: &str) -> Result<String, String> {
+    match parse_serial_spec(s) {
+        Some(_) => Ok(s.to_string()),
+        None => Err("expected PATH[:BAUD]".into()),
+    }
+}
+
+fn parse_group(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, re)) if !name.is_empty() && !re.is_empty() => Ok((name.to_string(), re.to_string())),
+        _ => Err("expected NAME=REGEX".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(argv: &[&str]) -> Config {
+        let cli = Cli::try_parse_from(argv).unwrap();
+        match cli.command {
+            None => parse_view(cli.view, false, None),
+            Some(Sub::View(args)) => parse_view(args, false, Some("view")),
+            Some(Sub::Follow(args)) => parse_view(args, true, Some("follow")),
+            Some(other) => panic!("not a viewer command: {:?}", other),
+        }.unwrap()
+    }
+
+    #[test]
+    fn test_subcommands() {
+        Cli::command().debug_assert();
+        let bare = view(&["rtlog", "--config", "/dev/null", "-r", "timeout", "a.log", "b.log"]);
+        assert_eq!((bare.inputs.len(), bare.follow, bare.regex.as_deref()), (2, false, Some("timeout")));
+        let viewed = view(&["rtlog", "view", "--config", "/dev/null", "-f", "a.log"]);
+        assert!(viewed.follow);
+        let followed = view(&["rtlog", "follow", "--config", "/dev/null", "--no-alerts", "--input", "api=a.log"]);
+        assert!(followed.follow && followed.alerts.is_empty());
+        assert_eq!(followed.inputs[0].name.as_deref(), Some("api"));
+        let Some(Sub::Report(args)) = Cli::try_parse_from(["rtlog", "report", "--config", "/dev/null", "-R", "logs", "-o", "r.html", "--alert", "critical:oom", "--fail-on-alert"]).unwrap().command else { panic!("not a report") };
+        let report = parse_report(args).unwrap();
+        assert_eq!((report.format, report.recursive, report.alerts.len(), report.fail_on_alert), (ReportFormat::Html, true, 1, true));
+        let served = Cli::try_parse_from(["rtlog", "serve", "--config", "/dev/null", "--exit-on-match", "panic|segfault", "a.log"]).unwrap();
+        assert!(matches!(served.command, Some(Sub::Serve(args)) if args.view.filter.exit_on_match.as_deref() == Some("panic|segfault")));
+        assert!(Cli::try_parse_from(["rtlog", "--exit-on-match", "(unclosed", "a.log"]).is_err());
+        assert!(Cli::try_parse_from(["rtlog", "report", "a.log"]).is_err());
+        assert!(view(&["rtlog", "--config", "/dev/null", "--simple", "a.log"]).simple);
+        let capture = view(&["rtlog", "--config", "/dev/null", "-f", "--until-idle", "1m", "--export", "out.jsonl", "a.log"]);
+        assert_eq!((capture.until_idle, capture.duration), (Some(Duration::from_secs(60)), None));
+        assert!(view(&["rtlog", "follow", "--config", "/dev/null", "--resume", "a.log"]).resume);
+        assert!(Cli::try_parse_from(["rtlog", "--simple", "--ssh", "host:/var/log/syslog", "a.log"]).is_err());
+        assert!(matches!(Cli::try_parse_from(["rtlog", "completions", "zsh"]).unwrap().command, Some(Sub::Completions(CompletionsArgs { shell: Shell::Zsh }))));
+    }
+
+    #[test]
+    fn test_timestamp_formats() {
+        let config = view(&["rtlog", "--config", "/dev/null", "--timestamp-format", "%d/%b/%Y:%H:%M:%S", "--timestamp-format", "api=%s.%f", "a.log"]);
+        assert_eq!(config.timestamp_formats, [(None, "%d/%b/%Y:%H:%M:%S".to_string()), (Some("api".into()), "%s.%f".into())]);
+        assert!(config.timestamps);
+        // A `=` in the format itself does not make a source name
+        assert_eq!(parse_timestamp_format("%Y-%m-%d time=%H:%M:%S"), Ok((None, "%Y-%m-%d time=%H:%M:%S".into())));
+        assert!(parse_timestamp_format("api=%Y-%m-%d %Q").is_err() && parse_timestamp_format("=%Y").is_err());
+        let config = view(&["rtlog", "--config", "/dev/null", "--encoding", "utf-16", "--encoding", "svc=latin1", "a.log"]);
+        assert_eq!(config.encodings, [(None, Encoding::Utf16Le), (Some("svc".into()), Encoding::Latin1)]);
+        assert!(parse_encoding("svc=ebcdic").is_err() && parse_encoding("=utf-8").is_err());
+    }
+
+    #[test]
+    fn test_config_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[[alerts]]\npattern = \"oom\"\n").unwrap();
+        let config = view(&["rtlog", "--config", path.to_str().unwrap(), "--alert", "disk", "a.log"]);
+        assert_eq!(config.alerts.len(), 2);
+        std::fs::write(&path, "smart_case = false\nstatus_bar = '{lines}'\ntab_width = 4\n[[inputs]]\npath = \"b.log\"\n[[rewrite]]\npattern = 'x'\n[[hotkeys]]\nkey = 3\npattern = 'y'\n").unwrap();
+        let reloaded = config.reload.reload(&config);
+        std::fs::write(&path, "smart_case = maybe\n").unwrap();
+        let broken = config.reload.reload(&config);
+        // Command line options are merged with the file again, the rest is kept
+        let reloaded = reloaded.unwrap();
+        assert_eq!(reloaded.alerts.iter().map(|a| a.label()).collect::<Vec<_>>(), ["disk"]);
+        assert_eq!(reloaded.inputs.iter().map(|i| i.path.to_str().unwrap()).collect::<Vec<_>>(), ["a.log", "b.log"]);
+        assert!(!reloaded.smart_case && reloaded.rewrite.len() == 1 && !reloaded.follow);
+        assert_eq!((reloaded.status_bar.as_deref(), reloaded.tab_width), (Some("{lines}"), 4));
+        assert_eq!(reloaded.hotkeys.iter().map(|h| h.key).collect::<Vec<_>>(), [3]);
+        assert!(broken.unwrap_err().to_string().starts_with("parsing config"));
+    }
+
+    #[test]
+    fn test_merge_alerts() {
+        let entry = |pattern: &str, regex, color: Option<&str>| AlertEntry { name: Some("n".into()), pattern: pattern.into(), regex, severity: AlertSeverity::Critical, color: color.map(String::from) };
+        let alerts = merge_alerts(vec![(AlertSeverity::Info, "oom".into())], vec![entry(r"took \d+s", true, Some("#ff8800"))], false).unwrap();
+        assert_eq!(alerts.iter().map(|a| a.label()).collect::<Vec<_>>(), ["oom", "n"]);
+        assert!(alerts[1].rule.compiled.as_ref().is_some_and(|re| re.is_match("query took 12s")));
+        assert_eq!(merge_alerts(Vec::new(), Vec::new(), false).unwrap().len(), 2);
+        assert!(merge_alerts(Vec::new(), vec![entry("(", true, None)], false).is_err());
+        let err = merge_alerts(Vec::new(), vec![entry("disk", false, Some("blurple"))], false).unwrap_err();
+        assert_eq!(err.to_string(), "alert pattern 'disk': unknown color 'blurple'");
     }
 }
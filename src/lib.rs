@@ -0,0 +1,14 @@
+//! rtlog library crate: the CLI, the TUI and the headless front ends, so the binary (`main.rs`)
+//! stays thin. The engine lives in `rtlog-core`; its modules are re-exported here so the front
+//! ends and benchmarks refer to them as `crate::state`, `rtlog::filter` and so on.
+
+pub use rtlog_core::{agent, classify, config, control, editor, email, encoding, export, filter, fluent, histogram, history, hotkeys, links, log, net, offsets, patterns, plugin, profile, query, redact, replace, replay, rewrite, session, sidebar, state, status, tail, tee, timestamp, transform, watch};
+
+pub mod app;
+pub mod cli;
+pub mod report;
+pub mod screenshot;
+pub mod shutdown;
+pub mod simple;
+pub mod ui;
+pub mod web;
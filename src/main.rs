@@ -1,17 +1,39 @@
-//! rtlog entry point: parses CLI and starts the async application runtime.
-//! The main function is intentionally thin and delegates to the runtime in `app`.
+//! rtlog entry point: parses CLI and starts the async application runtime (except for
+//! `--simple`, which runs without it). The main function is intentionally thin and delegates to
+//! the runtime in `app`.
 
-mod app;
-mod cli;
-mod filter;
-mod log;
-mod state;
-mod ui;
+use std::io::Write;
+use std::process::ExitCode;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use rtlog::{app, cli, config, simple};
+use rtlog::cli::Command;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let config = cli::parse();
-    app::run(config).await
+fn main() -> Result<ExitCode> {
+    let command = cli::parse()?;
+    match command {
+        Command::View(config) if config.simple => simple::run(*config),
+        command => tokio::runtime::Builder::new_multi_thread().enable_all().build()?.block_on(run(command)),
+    }
+}
+
+async fn run(command: Command) -> Result<ExitCode> {
+    let done = Ok(ExitCode::SUCCESS);
+    match command {
+        Command::Report(config) => app::report(*config).await,
+        Command::Serve(config, addr) => app::serve(*config, &addr).await,
+        Command::Agent(config, addr, host) => app::agent(*config, addr, host).await,
+        Command::Completions(shell) => { cli::write_completions(shell, &mut std::io::stdout()); done }
+        Command::Man(None) => { cli::write_man(&mut std::io::stdout())?; done }
+        Command::Man(Some(dir)) => {
+            cli::write_man_pages(&dir).with_context(|| format!("cannot write man pages to {}", dir.display()))?;
+            println!("wrote man pages to {}", dir.display());
+            done
+        }
+        Command::PrintDefaultConfig => { std::io::stdout().write_all(config::DEFAULT_CONFIG.as_bytes())?; done }
+        Command::View(config) => match &config.bench_ingest {
+            Some(path) => { app::bench_ingest(path, &config).await?; done }
+            None => app::run(*config).await,
+        },
+    }
 }
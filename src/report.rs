@@ -0,0 +1,265 @@
+//! Static reports on finished log files (`rtlog report`).
+//!
+//! The files are read through the viewer's readers, transforms, classification and filters,
+//! without a terminal. The report then gives, per source, the level breakdown, the most frequent
+//! message templates (see `patterns`), a timeline of error lines and the lines matching each
+//! alert pattern, as Markdown or as a self-contained HTML page. Like exports, reports are
+//! redacted.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::classify::{AlertSeverity, Level, LevelCounts};
+use crate::histogram::{histogram, Bucket, BucketSize};
+use crate::patterns::{top_patterns, PatternCount};
+use crate::redact::Redactor;
+use crate::state::AppState;
+use crate::timestamp::format_gap;
+
+/// Message templates listed per source
+const TOP_PATTERNS: usize = 10;
+
+/// Matching lines quoted per alert pattern
+const ALERT_EXAMPLES: usize = 10;
+
+/// Widest timeline bar, in characters (Markdown) or pixels / 4 (HTML)
+const BAR_WIDTH: u64 = 40;
+
+/// Error timelines spanning more than this many hours count per hour instead of per minute
+const MINUTE_TIMELINE_HOURS: i64 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(ReportFormat::Markdown),
+            "html" | "htm" => Some(ReportFormat::Html),
+            _ => None,
+        }
+    }
+
+    /// Format named by the extension of `path`
+    pub fn of_path(path: &Path) -> Option<Self> {
+        path.extension().and_then(|e| e.to_str()).and_then(Self::parse)
+    }
+}
+
+/// What the report says about one source
+#[derive(Debug)]
+pub struct SourceReport {
+    pub name: String,
+    /// Lines counted: those passing the filters, separators excluded
+    pub lines: usize,
+    pub levels: LevelCounts,
+    pub span: Option<(NaiveDateTime, NaiveDateTime)>,
+    pub patterns: Vec<PatternCount>,
+    pub timeline_size: BucketSize,
+    /// Error lines per bucket, empty buckets left out
+    pub timeline: Vec<Bucket>,
+    pub alerts: Vec<AlertMatches>,
+    /// Why the source could not be read completely
+    pub error: Option<String>,
+}
+
+/// Lines of a source matching one alert pattern
+#[derive(Debug)]
+pub struct AlertMatches {
+    pub severity: AlertSeverity,
+    pub pattern: String,
+    pub count: usize,
+    /// Line numbers (from 1) and text of the first matches
+    pub examples: Vec<(usize, String)>,
+}
+
+/// Collect the report on every source of `state`, counting the lines that pass its filters
+pub fn build(state: &AppState) -> Vec<SourceReport> {
+    state.sources.iter().map(|src| {
+        let counted: Vec<usize> = state.shown_lines(src).into_iter().filter(|&i| !src.is_separator(i)).collect();
+        let lines = || counted.iter().map(|&i| (i, &src.lines[i]));
+        let mut levels = LevelCounts::default();
+        let mut errors = Vec::new();
+        for (i, line) in lines() {
+            match state.line_level(src, line) {
+                Some(Level::Error) => { levels.error += 1; errors.push(i); }
+                Some(Level::Warn) => levels.warn += 1,
+                Some(Level::Info) => levels.info += 1,
+                None => {}
+            }
+        }
+        let first = lines().find_map(|(_, l)| src.timestamp(l));
+        let last = lines().rev().find_map(|(_, l)| src.timestamp(l));
+        let span = first.zip(last);
+        let (mut patterns, _) = top_patterns(lines(), None);
+        patterns.truncate(TOP_PATTERNS);
+        let timeline_size = match span {
+            Some((first, last)) if (last - first).num_hours() > MINUTE_TIMELINE_HOURS => BucketSize::Hour,
+            _ => BucketSize::Minute,
+        };
+        let mut timeline = histogram(errors.iter().map(|&i| (i, &src.lines[i])), timeline_size, |l| src.timestamp(l));
+        timeline.retain(|b| b.count > 0);
+        let alerts = state.alert_rules.iter().filter_map(|a| {
+            let re = a.rule.compiled.as_ref()?;
+            let matching: Vec<(usize, &str)> = lines().filter(|(_, l)| re.is_match(l)).map(|(i, l)| (i, &l[..])).collect();
+            if matching.is_empty() { return None; }
+            let examples = matching.iter().take(ALERT_EXAMPLES).map(|&(i, l)| (src.line_id(i).0 + 1, l.to_string())).collect();
+            Some(AlertMatches { severity: a.severity, pattern: a.rule.pattern.clone(), count: matching.len(), examples })
+        }).collect();
+        SourceReport { name: src.name.clone(), lines: counted.len(), levels, span, patterns, timeline_size, timeline, alerts, error: src.error.clone() }
+    }).collect()
+}
+
+/// The report as a Markdown document or an HTML page; line text is redacted with `redactor`
+pub fn render(reports: &[SourceReport], format: ReportFormat, redactor: &Redactor) -> String {
+    let total: usize = reports.iter().map(|r| r.lines).sum();
+    let generated = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    let mut out = String::new();
+    match format {
+        ReportFormat::Markdown => {
+            let _ = writeln!(out, "# rtlog report\n\nGenerated {}: {} source(s), {} lines.", generated, reports.len(), total);
+            for r in reports { markdown_source(&mut out, r, redactor); }
+        }
+        ReportFormat::Html => {
+            out.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>rtlog report</title><style>\n");
+            out.push_str("body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin:.5em 0 1.5em}td,th{border:1px solid #ccc;padding:2px 8px;text-align:left}code{white-space:pre-wrap}.n{text-align:right}.bar{display:inline-block;height:.8em;background:#c33}.error{color:#c33}\n");
+            out.push_str("</style></head><body>\n");
+            let _ = writeln!(out, "<h1>rtlog report</h1>\n<p>Generated {}: {} source(s), {} lines.</p>", generated, reports.len(), total);
+            for r in reports { html_source(&mut out, r, redactor); }
+            out.push_str("</body></html>\n");
+        }
+    }
+    out
+}
+
+fn span_text(span: Option<(NaiveDateTime, NaiveDateTime)>) -> String {
+    span.map_or("no timestamps".to_string(), |(first, last)| {
+        format!("{} → {} ({})", first.format("%Y-%m-%d %H:%M:%S"), last.format("%Y-%m-%d %H:%M:%S"), format_gap(last - first))
+    })
+}
+
+fn share(n: usize, total: usize) -> f64 {
+    n as f64 * 100.0 / total.max(1) as f64
+}
+
+/// Level rows: name, lines; lines without a level last
+fn level_rows(r: &SourceReport) -> [(&'static str, usize); 4] {
+    let other = r.lines - r.levels.error - r.levels.warn - r.levels.info;
+    [("error", r.levels.error), ("warn", r.levels.warn), ("info", r.levels.info), ("other", other)]
+}
+
+fn bar_length(count: u64, max: u64) -> u64 {
+    (count * BAR_WIDTH).div_ceil(max.max(1))
+}
+
+/// `s` as text of a Markdown table cell
+fn md_cell(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('|', "\\|").replace('`', "\\`").replace('<', "&lt;")
+}
+
+fn markdown_source(out: &mut String, r: &SourceReport, redactor: &Redactor) {
+    let _ = writeln!(out, "\n## {}\n\n{} lines, {}.", md_cell(&r.name), r.lines, span_text(r.span));
+    if let Some(err) = &r.error { let _ = writeln!(out, "\n**Read error:** {}", md_cell(err)); }
+    out.push_str("\n### Levels\n\n| Level | Lines | Share |\n|---|---:|---:|\n");
+    for (name, n) in level_rows(r) { let _ = writeln!(out, "| {} | {} | {:.1}% |", name, n, share(n, r.lines)); }
+    if !r.patterns.is_empty() {
+        out.push_str("\n### Top messages\n\n| Lines | Share | Message |\n|---:|---:|---|\n");
+        for p in &r.patterns { let _ = writeln!(out, "| {} | {:.1}% | {} |", p.count, share(p.count, r.lines), md_cell(&redactor.redact(&p.template))); }
+    }
+    if !r.timeline.is_empty() {
+        let _ = writeln!(out, "\n### Errors per {}\n\n| Time | Errors | |\n|---|---:|---|", r.timeline_size.name());
+        let max = r.timeline.iter().map(|b| b.count).max().unwrap_or(1);
+        for b in &r.timeline {
+            let _ = writeln!(out, "| {} | {} | {} |", b.start.format("%Y-%m-%d %H:%M"), b.count, "█".repeat(bar_length(b.count, max) as usize));
+        }
+    }
+    if !r.alerts.is_empty() {
+        out.push_str("\n### Alert matches\n");
+        for a in &r.alerts {
+            let _ = writeln!(out, "\n**{}** `{}`: {} line(s)\n", a.severity.name(), a.pattern.replace('`', "'"), a.count);
+            for (n, line) in &a.examples { let _ = writeln!(out, "- {}: {}", n, md_cell(&redactor.redact(line))); }
+            if a.count > a.examples.len() { let _ = writeln!(out, "- … {} more", a.count - a.examples.len()); }
+        }
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn html_source(out: &mut String, r: &SourceReport, redactor: &Redactor) {
+    let _ = writeln!(out, "<h2>{}</h2>\n<p>{} lines, {}.</p>", html_escape(&r.name), r.lines, html_escape(&span_text(r.span)));
+    if let Some(err) = &r.error { let _ = writeln!(out, "<p class=\"error\"><b>Read error:</b> {}</p>", html_escape(err)); }
+    out.push_str("<h3>Levels</h3>\n<table><tr><th>Level</th><th>Lines</th><th>Share</th></tr>\n");
+    for (name, n) in level_rows(r) { let _ = writeln!(out, "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{:.1}%</td></tr>", name, n, share(n, r.lines)); }
+    out.push_str("</table>\n");
+    if !r.patterns.is_empty() {
+        out.push_str("<h3>Top messages</h3>\n<table><tr><th>Lines</th><th>Share</th><th>Message</th></tr>\n");
+        for p in &r.patterns {
+            let _ = writeln!(out, "<tr><td class=\"n\">{}</td><td class=\"n\">{:.1}%</td><td><code>{}</code></td></tr>", p.count, share(p.count, r.lines), html_escape(&redactor.redact(&p.template)));
+        }
+        out.push_str("</table>\n");
+    }
+    if !r.timeline.is_empty() {
+        let _ = writeln!(out, "<h3>Errors per {}</h3>\n<table><tr><th>Time</th><th>Errors</th><th></th></tr>", r.timeline_size.name());
+        let max = r.timeline.iter().map(|b| b.count).max().unwrap_or(1);
+        for b in &r.timeline {
+            let _ = writeln!(out, "<tr><td>{}</td><td class=\"n\">{}</td><td><span class=\"bar\" style=\"width:{}px\"></span></td></tr>", b.start.format("%Y-%m-%d %H:%M"), b.count, bar_length(b.count, max) * 4);
+        }
+        out.push_str("</table>\n");
+    }
+    if !r.alerts.is_empty() {
+        out.push_str("<h3>Alert matches</h3>\n");
+        for a in &r.alerts {
+            let _ = writeln!(out, "<p><b>{}</b> <code>{}</code>: {} line(s)</p>\n<ul>", a.severity.name(), html_escape(&a.pattern), a.count);
+            for (n, line) in &a.examples { let _ = writeln!(out, "<li>{}: <code>{}</code></li>", n, html_escape(&redactor.redact(line))); }
+            if a.count > a.examples.len() { let _ = writeln!(out, "<li>… {} more</li>", a.count - a.examples.len()); }
+            out.push_str("</ul>\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::LineClass;
+    use crate::config::RedactConfig;
+    use crate::log::LogLine;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_report() {
+        let mut state = AppState::new(None, vec![(AlertSeverity::Critical, "disk full".into())]);
+        state.set_sources([(None, PathBuf::from("app.log"))]);
+        let id = state.sources[0].id;
+        let lines = [
+            "2025-01-02 10:00:00 INFO user 1 <admin> logged in from bob@example.com",
+            "2025-01-02 10:00:30 ERROR disk full on /var",
+            "2025-01-02 10:01:10 ERROR job 7 failed",
+            "2025-01-02 10:01:20 WARN slow | 900ms",
+            "2025-01-02 10:03:00 INFO user 2 <admin> logged in from bob@example.com",
+        ];
+        for text in lines { state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() }); }
+        let reports = build(&state);
+        let r = &reports[0];
+        assert_eq!((r.lines, r.levels.error, r.levels.warn, r.levels.info), (5, 2, 1, 2));
+        assert_eq!(r.patterns[0].count, 2);
+        assert_eq!(r.timeline.iter().map(|b| b.count).collect::<Vec<_>>(), [1, 1]);
+        assert_eq!((r.alerts[0].count, r.alerts[0].examples[0].0), (1, 2));
+        let redactor = Redactor::new(&RedactConfig { builtin: vec!["email".into()], rules: Vec::new() }).unwrap();
+        let md = render(&reports, ReportFormat::Markdown, &redactor);
+        assert!(md.contains("| error | 2 | 40.0% |"));
+        assert!(md.contains("| 2 | 40.0% | &lt;*> &lt;*>:&lt;*>:&lt;*> INFO user &lt;*> &lt;admin> logged in from &lt;email> |"));
+        assert!(md.contains("slow \\| &lt;*>"));
+        assert!(md.contains("**critical** `disk full`: 1 line(s)\n\n- 2: 2025-01-02 10:00:30 ERROR disk full on /var"));
+        let html = render(&reports, ReportFormat::Html, &redactor);
+        assert!(html.contains("<code>&lt;*&gt; &lt;*&gt;:&lt;*&gt;:&lt;*&gt; INFO user &lt;*&gt; &lt;admin&gt; logged in from &lt;email&gt;</code>"));
+        assert!(!html.contains("example.com"));
+        assert_eq!(ReportFormat::of_path(Path::new("out/report.HTML")), Some(ReportFormat::Html));
+    }
+}
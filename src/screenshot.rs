@@ -0,0 +1,227 @@
+//! The screen saved to a file (`Ctrl-S`), for pasting the exact view into incident notes.
+//!
+//! The frame is rendered again off screen at the terminal's size, without the prompt asking for
+//! the file, and written in the format the file extension names: `.ans` / `.ansi` keep the colors
+//! as ANSI escape sequences (for `cat`, `less -R` or an asciinema-style terminal), `.svg` draws
+//! the cells as an image for documents that take no terminal text, anything else is plain text.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use ratatui::backend::TestBackend;
+use ratatui::buffer::{Buffer, Cell};
+use ratatui::style::{Color, Modifier};
+use ratatui::Terminal;
+
+use crate::filter::display_width;
+use crate::state::AppState;
+use crate::ui::render;
+
+/// SVG cell size in pixels, for a 14px monospace font
+const CELL_WIDTH: f64 = 8.4;
+const CELL_HEIGHT: f64 = 18.0;
+/// SVG colors of the terminal's defaults and of the 16 named colors (a dark theme)
+const SVG_FOREGROUND: &str = "#cccccc";
+const SVG_BACKGROUND: &str = "#1e1e1e";
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+    "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenFormat {
+    Text,
+    Ansi,
+    Svg,
+}
+
+impl ScreenFormat {
+    /// The format `path`'s extension names
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("ans" | "ansi") => Self::Ansi,
+            Some("svg") => Self::Svg,
+            _ => Self::Text,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self { Self::Text => "text", Self::Ansi => "ANSI", Self::Svg => "SVG" }
+    }
+}
+
+/// Write the frame `state` shows on a `width`×`height` terminal to `path`; returns the format
+pub fn save(state: &AppState, width: u16, height: u16, path: &Path) -> anyhow::Result<ScreenFormat> {
+    let format = ScreenFormat::of(path);
+    let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+    terminal.draw(|frame| { render(frame, state); })?;
+    fs::write(path, write_screen(terminal.backend().buffer(), format))?;
+    Ok(format)
+}
+
+/// `buffer` in `format`
+pub fn write_screen(buffer: &Buffer, format: ScreenFormat) -> String {
+    let rows = (0..buffer.area.height).map(|y| runs(buffer, y));
+    match format {
+        ScreenFormat::Text => rows.map(|runs| {
+            let row: String = runs.into_iter().map(|run| run.text).collect();
+            format!("{}\n", row.trim_end())
+        }).collect(),
+        ScreenFormat::Ansi => rows.map(|runs| {
+            let mut row = String::new();
+            for run in &runs { let _ = write!(row, "\x1b[{}m{}", sgr(run), run.text); }
+            if !runs.is_empty() { row.push_str("\x1b[0m"); }
+            row + "\n"
+        }).collect(),
+        ScreenFormat::Svg => svg(buffer.area.width, rows.collect()),
+    }
+}
+
+/// Cells of one style in a row
+struct Run {
+    /// Column of the first cell
+    x: u16,
+    /// Columns covered, wide characters counting twice
+    width: u16,
+    fg: Color,
+    bg: Color,
+    modifier: Modifier,
+    text: String,
+}
+
+/// The runs of row `y`, up to its last cell with text or a background
+fn runs(buffer: &Buffer, y: u16) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    let mut x = 0;
+    while x < buffer.area.width {
+        let cell: &Cell = &buffer[(x, y)];
+        // The cells a wide character covers hold a blank that is not shown
+        let width = display_width(cell.symbol()).clamp(1, (buffer.area.width - x) as usize) as u16;
+        match runs.last_mut() {
+            Some(run) if (run.fg, run.bg, run.modifier) == (cell.fg, cell.bg, cell.modifier) => {
+                run.text.push_str(cell.symbol());
+                run.width += width;
+            }
+            _ => runs.push(Run { x, width, fg: cell.fg, bg: cell.bg, modifier: cell.modifier, text: cell.symbol().to_string() }),
+        }
+        x += width;
+    }
+    // Trailing blanks on the default background
+    while let Some(run) = runs.last_mut().filter(|run| run.bg == Color::Reset) {
+        let trimmed = run.text.trim_end_matches(' ').len();
+        run.width -= (run.text.len() - trimmed) as u16;
+        run.text.truncate(trimmed);
+        if !run.text.is_empty() { break; }
+        runs.pop();
+    }
+    runs
+}
+
+/// The Select Graphic Rendition parameters of a run's style
+fn sgr(run: &Run) -> String {
+    let mut params = vec!["0".to_string()];
+    for (modifier, param) in [(Modifier::BOLD, "1"), (Modifier::DIM, "2"), (Modifier::ITALIC, "3"), (Modifier::UNDERLINED, "4"),
+                              (Modifier::SLOW_BLINK, "5"), (Modifier::RAPID_BLINK, "6"), (Modifier::REVERSED, "7"), (Modifier::HIDDEN, "8"), (Modifier::CROSSED_OUT, "9")] {
+        if run.modifier.contains(modifier) { params.push(param.to_string()); }
+    }
+    for (color, base) in [(run.fg, 30), (run.bg, 40)] {
+        let param = match color {
+            Color::Reset => continue,
+            Color::Indexed(n) => format!("{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+            named => {
+                let n = named_index(named).unwrap_or(7);
+                if n < 8 { (base + n).to_string() } else { (base + 60 + n - 8).to_string() }
+            }
+        };
+        params.push(param);
+    }
+    params.join(";")
+}
+
+/// Position of a named color in the 16-color palette
+fn named_index(color: Color) -> Option<u8> {
+    Some(match color {
+        Color::Black => 0, Color::Red => 1, Color::Green => 2, Color::Yellow => 3,
+        Color::Blue => 4, Color::Magenta => 5, Color::Cyan => 6, Color::Gray => 7,
+        Color::DarkGray => 8, Color::LightRed => 9, Color::LightGreen => 10, Color::LightYellow => 11,
+        Color::LightBlue => 12, Color::LightMagenta => 13, Color::LightCyan => 14, Color::White => 15,
+        _ => return None,
+    })
+}
+
+/// `color` for SVG, or `default` for the terminal's own
+fn svg_color(color: Color, default: &str) -> String {
+    match color {
+        Color::Reset => default.to_string(),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::Indexed(n) if n < 16 => PALETTE[n as usize].to_string(),
+        // The 6×6×6 color cube, then 24 grays
+        Color::Indexed(n) if n < 232 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            format!("#{:02x}{:02x}{:02x}", level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        Color::Indexed(n) => { let v = 8 + (n - 232) * 10; format!("#{:02x}{:02x}{:02x}", v, v, v) }
+        named => PALETTE[named_index(named).unwrap_or(7) as usize].to_string(),
+    }
+}
+
+fn svg(width: u16, rows: Vec<Vec<Run>>) -> String {
+    let (w, h) = (f64::from(width) * CELL_WIDTH, rows.len() as f64 * CELL_HEIGHT);
+    let mut out = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.1}\" height=\"{h:.1}\" viewBox=\"0 0 {w:.1} {h:.1}\">\n");
+    let _ = writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", SVG_BACKGROUND);
+    out.push_str("<g font-family=\"ui-monospace, Menlo, Consolas, 'DejaVu Sans Mono', monospace\" font-size=\"14\" xml:space=\"preserve\">\n");
+    for (y, runs) in rows.iter().enumerate() {
+        let top = y as f64 * CELL_HEIGHT;
+        for run in runs {
+            let (mut fg, mut bg) = (svg_color(run.fg, SVG_FOREGROUND), svg_color(run.bg, SVG_BACKGROUND));
+            if run.modifier.contains(Modifier::REVERSED) { std::mem::swap(&mut fg, &mut bg); }
+            let (x, cols) = (f64::from(run.x) * CELL_WIDTH, f64::from(run.width) * CELL_WIDTH);
+            if bg != SVG_BACKGROUND {
+                let _ = writeln!(out, "<rect x=\"{x:.1}\" y=\"{top:.1}\" width=\"{cols:.1}\" height=\"{CELL_HEIGHT:.1}\" fill=\"{bg}\"/>");
+            }
+            if run.text.trim().is_empty() || run.modifier.contains(Modifier::HIDDEN) { continue; }
+            let mut attrs = format!("fill=\"{}\"", fg);
+            for (modifier, attr) in [(Modifier::BOLD, " font-weight=\"bold\""), (Modifier::ITALIC, " font-style=\"italic\""),
+                                     (Modifier::DIM, " opacity=\"0.6\""), (Modifier::UNDERLINED, " text-decoration=\"underline\""),
+                                     (Modifier::CROSSED_OUT, " text-decoration=\"line-through\"")] {
+                if run.modifier.contains(modifier) { attrs.push_str(attr); }
+            }
+            // Stretched to its cells, so the columns line up whatever the font
+            let _ = writeln!(out, "<text x=\"{x:.1}\" y=\"{:.1}\" textLength=\"{cols:.1}\" lengthAdjust=\"spacingAndGlyphs\" {attrs}>{}</text>", top + CELL_HEIGHT * 0.75, escape_xml(&run.text));
+        }
+    }
+    out.push_str("</g>\n</svg>\n");
+    out
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+
+    #[test]
+    fn test_write_screen() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 12, 2));
+        buffer.set_string(0, 0, "ERROR", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD));
+        buffer.set_string(6, 0, "日<1>", Style::default());
+        buffer.set_string(0, 1, "ok", Style::default().bg(Color::Indexed(196)));
+        assert_eq!(write_screen(&buffer, ScreenFormat::Text), "ERROR 日<1>\nok\n");
+        assert_eq!(write_screen(&buffer, ScreenFormat::Ansi), "\x1b[0;1;31mERROR\x1b[0m 日<1>\x1b[0m\n\x1b[0;48;5;196mok\x1b[0m\n");
+        let svg = write_screen(&buffer, ScreenFormat::Svg);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100.8\" height=\"36.0\""), "{}", svg);
+        assert!(svg.contains("<text x=\"0.0\" y=\"13.5\" textLength=\"42.0\" lengthAdjust=\"spacingAndGlyphs\" fill=\"#cd3131\" font-weight=\"bold\">ERROR</text>"), "{}", svg);
+        // The wide character takes two columns of the run
+        assert!(svg.contains("<text x=\"42.0\" y=\"13.5\" textLength=\"50.4\" lengthAdjust=\"spacingAndGlyphs\" fill=\"#cccccc\"> 日&lt;1&gt;</text>"), "{}", svg);
+        assert!(svg.contains("<rect x=\"0.0\" y=\"18.0\" width=\"16.8\" height=\"18.0\" fill=\"#ff0000\"/>"), "{}", svg);
+        assert_eq!(ScreenFormat::of(Path::new("view.ANS")), ScreenFormat::Ansi);
+        assert_eq!(ScreenFormat::of(Path::new("notes/view")), ScreenFormat::Text);
+    }
+}
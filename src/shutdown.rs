@@ -0,0 +1,54 @@
+//! Termination signals.
+//!
+//! SIGINT, SIGTERM and SIGHUP (Ctrl-C / Ctrl-Break on Windows) are turned into a normal quit of the
+//! main loop instead of killing the process, so the terminal is restored and reader tasks (and the
+//! processes they own) are stopped on the way out.
+
+use std::io;
+
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+pub struct ShutdownSignals {
+    #[cfg(unix)]
+    interrupt: Signal,
+    #[cfg(unix)]
+    terminate: Signal,
+    #[cfg(unix)]
+    hangup: Signal,
+}
+
+impl ShutdownSignals {
+    /// Install the handlers; from here on these signals no longer terminate the process
+    pub fn new() -> io::Result<Self> {
+        #[cfg(unix)]
+        return Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+            hangup: signal(SignalKind::hangup())?,
+        });
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// Wait for any termination request
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = self.interrupt.recv() => {}
+            _ = self.terminate.recv() => {}
+            _ = self.hangup.recv() => {}
+        }
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+    }
+
+    /// Wait for SIGINT only. While a child process owns the terminal, Ctrl-C is meant for the
+    /// child but reaches us too; consuming it here keeps it from quitting rtlog afterwards.
+    pub async fn interrupt(&mut self) {
+        #[cfg(unix)]
+        self.interrupt.recv().await;
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
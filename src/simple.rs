@@ -0,0 +1,151 @@
+//! `--simple`: the viewer for a single file, without the async runtime.
+//!
+//! The file is read on the UI thread between key presses, a batch of lines at a time so a large
+//! file does not hold up the screen, and polled for new lines with `-f`. The reading is the
+//! regular reader's (`tail::Tail`), run without a task. Everything else is the regular viewer:
+//! the same state, rendering and key handling. What needs background tasks is left out: other
+//! kinds of sources, tee files, plugins, the control socket, email and config reload. Inputs
+//! that would use them (suspending, opening or closing sources, piping) only show a notice.
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use crossterm::event;
+
+use crate::app::{apply_input, apply_source_event, apply_view_options, exit_status, export_capture, new_state, save_offsets, StopTimer, DRAW_INTERVAL};
+use crate::cli::Config;
+use crate::editor::{editor_command, FileLocation};
+use crate::log::{LogLine, SourceEvent, POLL_INTERVAL};
+use crate::profile::Profile;
+use crate::state::{AppState, MessageLevel, PromptKind};
+use crate::tail::Tail;
+use crate::transform::TransformChain;
+use crate::ui::{translate_event, Ui, UiEvent};
+
+/// Lines read before the screen and the keyboard get their turn again
+const READ_BATCH: usize = 20_000;
+
+const NOT_AVAILABLE: &str = "not available with --simple";
+
+/// View `config`'s only input until the user quits
+pub fn run(config: Config) -> Result<ExitCode> {
+    let input = &config.inputs[0];
+    let mut state = new_state(&config)?;
+    state.set_sources([(input.name.clone(), input.path.clone())]);
+    apply_view_options(&mut state, &config)?;
+    let src = &state.sources[0];
+    let lines = Lines { id: src.id, profile: src.profile.clone(), transforms: state.transforms_for(src) };
+    let mut tail = Tail::new(input.path.clone(), config.follow);
+    tail.tracked = state.offsets.as_ref().map(|offsets| offsets.track(&input.path));
+    tail.encoding = state.encoding_for(&state.sources[0]);
+    let mut stop = StopTimer::new(&config);
+    let mut ui = Ui::new()?;
+    let res = view(&mut ui, &mut state, &mut tail, &lines, &mut stop, config.export.is_some());
+    let _ = ui.restore();
+    save_offsets(&state);
+    let res = match config.export.as_deref() {
+        Some(path) if stop.stopped => res.and_then(|()| export_capture(&state, path)),
+        _ => res,
+    };
+    res.map(|()| exit_status(&state, config.fail_on_alert))
+}
+
+/// Run the viewer until the user quits; with `exit_on_stop` also when `stop` ends reading
+fn view(ui: &mut Ui, state: &mut AppState, tail: &mut Tail, lines: &Lines, stop: &mut StopTimer, exit_on_stop: bool) -> Result<()> {
+    let mut events = Vec::new();
+    let mut next_read = Instant::now();
+    let mut last_draw: Option<Instant> = None;
+    let mut dirty = true;
+    let mut animating = false;
+    loop {
+        let now = Instant::now();
+        if now >= next_read {
+            let more = tail.read(&mut events, READ_BATCH);
+            dirty |= !events.is_empty();
+            for ev in events.drain(..) { lines.apply(state, ev); }
+            if state.exit_match.is_some() { return Ok(()); }
+            next_read = if more { now } else { now + POLL_INTERVAL };
+        }
+        if let Some(reason) = stop.check(state) {
+            tail.stop();
+            state.stop_reading(&reason);
+            if exit_on_stop { return Ok(()); }
+            dirty = true;
+        }
+        // New lines are drawn at most once per interval, like the regular viewer's ticker
+        let was_animating = std::mem::replace(&mut animating, state.animating());
+        let due = last_draw.is_none_or(|t| t.elapsed() >= DRAW_INTERVAL);
+        if (dirty || animating || was_animating) && due {
+            draw(ui, state)?;
+            (last_draw, dirty) = (Some(Instant::now()), false);
+        }
+        let timeout = next_read.saturating_duration_since(Instant::now()).min(DRAW_INTERVAL);
+        if !event::poll(timeout)? { continue; }
+        let input = translate_event(state, &event::read()?);
+        match apply_input(state, input, ui.page_height()) {
+            None => {}
+            Some(UiEvent::Quit) => return Ok(()),
+            Some(UiEvent::SubmitPrompt) if state.prompt.as_ref().is_some_and(|p| matches!(p.kind, PromptKind::OpenSource | PromptKind::Pipe | PromptKind::Shell)) => {
+                state.set_prompt_error(NOT_AVAILABLE.to_string());
+            }
+            Some(UiEvent::Suspend | UiEvent::CloseSource | UiEvent::OpenLink(_)) => {
+                state.close_link_chooser();
+                state.notify(MessageLevel::Warn, NOT_AVAILABLE);
+            }
+            Some(UiEvent::OpenInEditor) => {
+                if let Ok(location) = state.selected_file_location() { open_editor(ui, state, &location)?; }
+            }
+            Some(_) => continue,
+        }
+        // Show the result of user input right away
+        draw(ui, state)?;
+        (last_draw, dirty) = (Some(Instant::now()), false);
+    }
+}
+
+fn draw(ui: &mut Ui, state: &mut AppState) -> Result<()> {
+    state.refresh_patterns();
+    state.refresh_histogram();
+    state.refresh_summary();
+    ui.draw(state)?;
+    if std::mem::take(&mut state.bell) { ui.bell()?; }
+    if let Some(text) = state.clipboard.take() { ui.copy(&text)?; }
+    Ok(())
+}
+
+/// Run the editor on `location` with the UI suspended (the file is not read meanwhile)
+fn open_editor(ui: &mut Ui, state: &mut AppState, location: &FileLocation) -> Result<()> {
+    let mut cmd = editor_command(location);
+    ui.suspend()?;
+    let status = cmd.status();
+    ui.resume()?;
+    if let Err(e) = status { state.notify(MessageLevel::Error, format!("cannot run {}: {}", cmd.get_program().to_string_lossy(), e)); }
+    Ok(())
+}
+
+/// What the readers' `EventSender` does to lines in the regular viewer, apart from tee files:
+/// profile redaction and transforms. Lines are classified when they are added to the state.
+struct Lines {
+    id: usize,
+    profile: Option<Arc<Profile>>,
+    transforms: Option<Arc<TransformChain>>,
+}
+
+impl Lines {
+    fn apply(&self, state: &mut AppState, ev: SourceEvent) {
+        let SourceEvent::Line(line) = ev else { return apply_source_event(state, self.id, ev); };
+        let mut text = match &self.profile {
+            Some(profile) => profile.redact(&line.text).into_owned(),
+            None => line.text.to_string(),
+        };
+        if let Some(transforms) = &self.transforms {
+            let (transformed, error) = transforms.apply(&text);
+            if let Some(error) = error { state.notify(MessageLevel::Warn, error); }
+            let Some(transformed) = transformed else { return; };
+            text = transformed;
+        }
+        state.push_line_for(self.id, LogLine::new(text));
+    }
+}
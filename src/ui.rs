@@ -1,206 +1,702 @@
 //! TUI layer: rendering and input handling built on ratatui and crossterm.
 //! The UI reads state immutably and emits `UiEvent` to keep concerns separated.
 
-use crate::filter::{highlight_line, line_matches};
-use crate::state::{AppState, FilterFocus};
-use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crate::classify::{AlertSeverity, LevelCounts};
+use crate::filter::{char_width, control_symbol, display_width, expand_tabs_from, fit_width, CompiledFilterSet};
+use crate::histogram::BucketSize;
+use crate::links::LinkKind;
+use crate::query::Value;
+use crate::sidebar::SourceSort;
+use crate::state::{AppState, Correlation, FieldAggregate, FilterFocus, FilterRow, FocusFollow, HistogramView, LinkChooser, MessageLevel, PatternView, Prompt, PromptKind, QueryView, Source, SummaryView};
+use crate::status::{StatusField, StatusPart};
+use crate::timestamp::format_gap;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Style, Modifier, Color};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Paragraph, Wrap, List, ListItem, Sparkline, Clear};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap, List, ListItem, ListState, Sparkline, SparklineBar, Clear, Scrollbar, ScrollbarOrientation, ScrollbarState};
 use ratatui::Terminal;
+use std::borrow::Cow;
 use std::io;
+use std::sync::{Arc, Once, OnceLock};
+use std::thread::ThreadId;
 
 /// TUI façade over ratatui/crossterm. Owns the terminal and provides a `draw` method.
+/// The terminal is restored when the `Ui` is dropped, and by a panic hook if the UI thread panics.
 pub struct Ui {
     terminal: Terminal<CrosstermBackend<io::Stdout>>,
+    /// Raw mode and alternate screen are active
+    active: bool,
+    /// Rows of log lines the log panel showed in the last frame
+    log_height: usize,
+    /// Terminal title last set; None while the terminal's own title is shown
+    title: Option<String>,
+}
+
+/// Push the terminal's title on its title stack (xterm, and the terminals and tmux following it)
+const SAVE_TITLE: &str = "\x1b[22;0t";
+/// Pop the title pushed by `SAVE_TITLE`
+const RESTORE_TITLE: &str = "\x1b[23;0t";
+
+/// Thread running the UI; panics elsewhere (reader tasks) leave the terminal alone
+static UI_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// Leave raw mode and the alternate screen
+fn restore_terminal() -> io::Result<()> {
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(io::stdout(), crossterm::terminal::LeaveAlternateScreen, crossterm::cursor::Show)
+}
+
+/// Restore the terminal before the panic message is printed, so it is readable
+fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if UI_THREAD.get() == Some(&std::thread::current().id()) { let _ = restore_terminal(); }
+            previous(info);
+        }));
+    });
 }
 
 impl Ui {
     pub fn new() -> anyhow::Result<Self> {
+        let _ = UI_THREAD.set(std::thread::current().id());
+        install_panic_hook();
         crossterm::terminal::enable_raw_mode()?;
         let mut stdout = io::stdout();
         crossterm::execute!(stdout, crossterm::terminal::EnterAlternateScreen)?;
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
-        Ok(Self { terminal })
+        Ok(Self { terminal, active: true, log_height: 0, title: None })
     }
 
     pub fn restore(&mut self) -> anyhow::Result<()> {
-        crossterm::terminal::disable_raw_mode()?;
-        crossterm::execute!(
-            self.terminal.backend_mut(),
-            crossterm::terminal::LeaveAlternateScreen,
-            crossterm::cursor::Show
-        )?;
+        if !self.active { return Ok(()); }
+        self.active = false;
+        if self.title.take().is_some() {
+            crossterm::execute!(self.terminal.backend_mut(), crossterm::style::Print(RESTORE_TITLE))?;
+            if is_iterm() { set_iterm_badge(self.terminal.backend_mut(), "")?; }
+        }
+        restore_terminal()?;
         self.terminal.show_cursor()?;
         Ok(())
     }
 
+    /// Ring the terminal bell
+    pub fn bell(&mut self) -> anyhow::Result<()> {
+        crossterm::execute!(self.terminal.backend_mut(), crossterm::style::Print('\x07'))?;
+        Ok(())
+    }
+
+    /// Put `text` on the system clipboard with the OSC 52 escape sequence, which the terminal
+    /// (also across SSH, and in tmux with `set-clipboard on`) passes to the clipboard
+    pub fn copy(&mut self, text: &str) -> anyhow::Result<()> {
+        use base64::Engine;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+        crossterm::execute!(self.terminal.backend_mut(), crossterm::style::Print(format!("\x1b]52;c;{}\x07", encoded)))?;
+        Ok(())
+    }
+
+    /// Give the terminal back (e.g. to a shell) without tearing down the UI
+    pub fn suspend(&mut self) -> anyhow::Result<()> {
+        self.restore()
+    }
+
+    /// Re-enter raw mode and the alternate screen after `suspend`, forcing a full redraw
+    pub fn resume(&mut self) -> anyhow::Result<()> {
+        crossterm::terminal::enable_raw_mode()?;
+        crossterm::execute!(self.terminal.backend_mut(), crossterm::terminal::EnterAlternateScreen)?;
+        self.active = true;
+        self.terminal.clear()?;
+        Ok(())
+    }
+
+    /// Rows of the log panel, which a page scroll moves by
+    pub fn page_height(&self) -> usize {
+        self.log_height
+    }
+
+    /// Columns and rows of the terminal
+    pub fn size(&self) -> anyhow::Result<(u16, u16)> {
+        let size = self.terminal.size()?;
+        Ok((size.width, size.height))
+    }
+
     pub fn draw(&mut self, state: &AppState) -> anyhow::Result<()> {
-        let filter_regs = state.enabled_regexes();
-        let highlights = state.active_highlight_regexes();
-        let alert_regs = state.alert_enabled_regexes();
-        let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
-        let blink_on = (now_ms / 400) % 2 == 0;
-        self.terminal.draw(|frame| {
-            let area = frame.area();
-
-            // Split horizontally: left sidebar (sources), right main panels
-            let cols = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Length(22), Constraint::Min(10)])
-                .split(area);
-
-            // Sidebar: list all sources, highlight focused
-            let side_items: Vec<ListItem> = state.sources.iter().enumerate().map(|(i, s)| {
-                let mut line = Line::from(s.name.clone());
-                if i == state.focused {
+        let mut log_height = self.log_height;
+        self.terminal.draw(|frame| if let Some(height) = render(frame, state) { log_height = height; })?;
+        self.log_height = log_height;
+        if state.set_terminal_title { self.update_title(state)?; }
+        Ok(())
+    }
+
+    /// Show `state`'s terminal title (in tmux: the pane title), and the unacknowledged alerts as
+    /// the badge in iTerm2; the terminal's own title is saved first and put back by `restore`
+    fn update_title(&mut self, state: &AppState) -> anyhow::Result<()> {
+        let title = state.terminal_title();
+        if self.title.as_ref() == Some(&title) { return Ok(()); }
+        let out = self.terminal.backend_mut();
+        if self.title.is_none() { crossterm::queue!(out, crossterm::style::Print(SAVE_TITLE))?; }
+        crossterm::queue!(out, crossterm::terminal::SetTitle(&title))?;
+        if is_iterm() {
+            let alerts = state.unacked_alerts();
+            set_iterm_badge(out, &if alerts > 0 { format!("⚠ {}", alerts) } else { String::new() })?;
+        }
+        io::Write::flush(out)?;
+        self.title = Some(title);
+        Ok(())
+    }
+}
+
+/// Draw the whole screen for `state` into `frame`; returns the rows of log lines the log panel
+/// shows, None when the terminal is too small for it. `Ui::draw` renders to the terminal with it,
+/// tests to a `TestBackend`.
+pub fn render(frame: &mut ratatui::Frame<'_>, state: &AppState) -> Option<usize> {
+    let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let blink_on = (now_ms / 400).is_multiple_of(2);
+    let area = frame.area();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        draw_too_small(frame, area);
+        return None;
+    }
+
+    // Split horizontally: left sidebar (sources), right main panels; narrow terminals and
+    // compact mode leave the whole width to the logs
+    let main = if !state.compact && area.width >= SIDEBAR_MIN_WIDTH {
+        let cols = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(30), Constraint::Min(10)])
+            .split(area);
+        draw_sidebar(frame, cols[0], state);
+        cols[1]
+    } else {
+        area
+    };
+
+    // Right area: logs, status, stats, and optional context/filter panels
+    // Increase stats panel height to show more filter summaries; short terminals skip it
+    let show_stats = !state.compact && area.height >= STATS_MIN_HEIGHT;
+    let mut constraints = vec![Constraint::Min(1), Constraint::Length(1), Constraint::Length(if show_stats { 10 } else { 0 })];
+    if state.context_panel_open {
+        let h = (state.context_radius * 2 + 3) as u16;
+        constraints.push(Constraint::Length(h.max(5)));
+    }
+    if state.filter_panel_open { constraints.push(Constraint::Length(10)); }
+    let chunks = Layout::default().direction(Direction::Vertical).constraints(constraints).split(main);
+
+    // Optional histogram strip on top of the log panel
+    let log_area = match &state.histogram {
+        Some(view) => {
+            let parts = Layout::default().direction(Direction::Vertical).constraints([Constraint::Length(6), Constraint::Min(1)]).split(chunks[0]);
+            draw_histogram(frame, parts[0], view);
+            parts[1]
+        }
+        None => chunks[0],
+    };
+
+    // Determine visible slice from the focused source
+    let borders = if state.compact { 0 } else { 2 };
+    let height = log_area.height.saturating_sub(borders) as usize;
+    let content_width = log_area.width.saturating_sub(borders);
+    let mut lines: Vec<Line> = Vec::new();
+    let mut rows = 0;
+    let (total, scroll_offset, selected_log) = if let Some(src) = state.current_source() {
+        (src.lines.len(), src.scroll_offset, src.selected_index())
+    } else { (0, 0, None) };
+    let range = state.current_source().and_then(|src| state.range_bounds(src));
+    // Build a filtered viewport: collect matching indices (and context lines around
+    // them) from the end so that the Logs panel shows a continuous stream of matching
+    // lines, unaffected by interleaved non-matching lines.
+    // Newest line in view, for the position readout
+    let mut bottom_line = None;
+    let mut columns: Vec<(Arc<str>, usize)> = Vec::new();
+    if let Some(src) = state.current_source() {
+        let desired = height.saturating_add(scroll_offset);
+        let mut match_indices: Vec<(usize, bool)> = state.shown_lines_rev(src).take(desired).collect();
+        // We collected from newest to oldest; reverse to chronological order
+        match_indices.reverse();
+        // Apply scroll_offset: drop the last `scroll_offset` matches
+        let visible_len = match_indices.len().saturating_sub(scroll_offset);
+        let start_vis = 0;
+        let end_vis = visible_len;
+        let window = &match_indices[start_vis..end_vis];
+        bottom_line = window.last().map(|&(i, _)| i);
+        // Column view: each field as wide as its name or its widest value in view
+        if state.column_view {
+            columns = state.field_names().into_iter().map(|name| {
+                let values = window.iter().filter(|&&(i, _)| !src.is_separator(i)).filter_map(|&(i, _)| state.field_value(&src.lines[i], &name));
+                let widest = values.map(display_width).chain([display_width(&name)]).max().unwrap_or(0);
+                (name, widest.min(FIELD_COLUMN_WIDTH))
+            }).collect();
+        }
+
+        // Newest line at the bottom: take lines backwards until their (wrapped) rows fill
+        // the viewport; a partly visible top line is scrolled off below
+        for &(i, context) in window.iter().rev() {
+            if rows >= height { break; }
+            let text = &src.lines[i];
+            let mut line = if src.is_separator(i) {
+                // Full-width bar so the separation stands out
+                let style = Style::default().fg(Color::Black).bg(Color::Magenta).add_modifier(Modifier::BOLD);
+                Line::from(Span::styled(fit_width(text, log_area.width.saturating_sub(2) as usize), style))
+            } else {
+                display_line(state, text, state.filter_match_set(), state.search_compiled.as_ref())
+            };
+            // If this line matches an alert pattern, colorize it by severity or the rule's color
+            if let Some(severity) = state.alert_severity(text) {
+                let color = state.alert_color(text).and_then(|c| c.parse().ok()).unwrap_or(severity_color(severity));
+                // Optionally flashing reverse during active blink window
+                line = apply_line_color(line, color);
+                if severity == AlertSeverity::Critical { line = apply_line_modifier(line, Modifier::BOLD); }
+                if severity.blink_ms() > 0 && now_ms < state.alert_blink_deadline_ms && blink_on {
                     line = apply_line_modifier(line, Modifier::REVERSED);
                 }
-                ListItem::new(line)
-            }).collect();
-            let side = List::new(side_items)
-                .block(Block::default().borders(Borders::ALL).title("Sources (Tab/Shift-Tab, [/]): switch"));
-            frame.render_widget(side, cols[0]);
-
-            // Right area: logs, status, stats, and optional context/filter panels
-            // Increase stats panel height to show more filter summaries
-            let mut constraints = vec![Constraint::Min(1), Constraint::Length(1), Constraint::Length(10)];
-            if state.context_panel_open {
-                let h = (state.context_radius * 2 + 3) as u16;
-                constraints.push(Constraint::Length(h.max(5)));
             }
-            if state.filter_panel_open { constraints.push(Constraint::Length(10)); }
-            let chunks = Layout::default().direction(Direction::Vertical).constraints(constraints).split(cols[1]);
-
-            // Determine visible slice from the focused source
-            let height = chunks[0].height as usize - 2; // borders
-            let mut lines: Vec<Line> = Vec::new();
-            let (total, scroll_offset, selected_log) = if let Some(src) = state.current_source() {
-                (src.lines.len(), src.scroll_offset, src.selected_log)
-            } else { (0, 0, None) };
-            // Build a filtered viewport: collect matching indices from the end so that
-            // the Logs panel shows a continuous stream of matching lines, unaffected by
-            // interleaved non-matching lines.
-            let mut match_indices: Vec<usize> = Vec::new();
-            if let Some(src) = state.current_source() {
-                let desired = height.saturating_add(scroll_offset);
-                let mut i = total;
-                while i > 0 {
-                    i -= 1;
-                    let text = &src.lines[i];
-                    if line_matches(text, &filter_regs) {
-                        match_indices.push(i);
-                        if match_indices.len() >= desired { break; }
-                    }
-                }
-                // We collected from newest to oldest; reverse to chronological order
-                match_indices.reverse();
-                // Apply scroll_offset: drop the last `scroll_offset` matches
-                let visible_len = match_indices.len().saturating_sub(scroll_offset);
-                let start_vis = 0;
-                let end_vis = visible_len;
-                let window = &match_indices[start_vis..end_vis];
-
-                for &i in window.iter().rev().take(height).rev() { // ensure we only render up to viewport height
-                    let text = &src.lines[i];
-                    let mut line = highlight_line(text, &highlights);
-                    // If this line matches an alert pattern, colorize it strongly
-                    if !alert_regs.is_empty() && line_matches(text, &alert_regs) {
-                        // Make it red and optionally flashing reverse during active blink window
-                        line = apply_line_color(line, Color::Red);
-                        if now_ms < state.alert_blink_deadline_ms && blink_on {
-                            line = apply_line_modifier(line, Modifier::REVERSED);
-                        }
-                    }
-                    if let Some(sel) = selected_log { if sel == i { line = apply_line_modifier(line, Modifier::REVERSED); }}
-                    lines.push(line);
-                }
+            if let Some(note) = src.annotations.get(&src.line_id(i)) {
+                line.spans.push(Span::styled(format!("  « {} »", state.display_text(note)), Style::default().fg(Color::Magenta).add_modifier(Modifier::ITALIC)));
             }
-
-            let title = if let Some(src) = state.current_source() { format!("Logs - {} (Enter:Context, j/k:select)", src.name) } else { "Logs".to_string() };
-            let para = Paragraph::new(lines)
-                .block(Block::default().borders(Borders::ALL).title(title))
-                .style(Style::default())
-                .wrap(Wrap { trim: false });
-            frame.render_widget(para, chunks[0]);
-
-            // Status bar: show active filters count and flags of input
-            let active = filter_regs.len();
-            let (auto, so) = if let Some(src) = state.current_source() { (src.auto_scroll, src.scroll_offset) } else { (true, 0) };
-            let status = format!(
-                "Lines: {}  Scroll: {}  Mode: {}  Filters: {}  [/] Filter Panel  Enter:{}  r:regex={} i:case={} w:word={} x:line={}",
-                total,
-                so,
-                if auto { "Auto" } else { "Paused" },
-                active,
-                if state.filter_panel_open { "Add Filter" } else { "Toggle Context" },
-                state.input_is_regex,
-                state.input_case_insensitive,
-                state.input_whole_word,
-                state.input_whole_line,
-            );
-            let status_para = Paragraph::new(status)
-                .block(Block::default().borders(Borders::TOP))
-                .wrap(Wrap { trim: true });
-            frame.render_widget(status_para, chunks[1]);
-
-            // Summary / Stats panel
-            draw_stats_panel(frame, chunks[2], state);
-
-            let mut next_chunk = 3;
-            if state.context_panel_open {
-                if let Some(sel) = selected_log {
-                    draw_context_panel(frame, chunks[next_chunk], state, sel);
-                } else {
-                    let empty = Paragraph::new("No selection").block(Block::default().borders(Borders::ALL).title("Context"));
-                    frame.render_widget(empty, chunks[next_chunk]);
-                }
-                next_chunk += 1;
+            if !columns.is_empty() && !src.is_separator(i) {
+                let cells = columns.iter().map(|(name, width)| fit_width(state.field_value(text, name).unwrap_or("-"), *width));
+                let cells = cells.collect::<Vec<_>>().join(" ");
+                line.spans.insert(0, Span::styled(format!("{} │ ", cells), Style::default().fg(Color::Cyan)));
             }
-
-            if state.filter_panel_open {
-                draw_filter_panel(frame, chunks[next_chunk], state);
+            if context { line = apply_line_modifier(line, Modifier::DIM); }
+            if range.is_some_and(|(first, last)| (first..=last).contains(&i)) { line = apply_line_bg(line, RANGE_BG); }
+            if let Some(sel) = selected_log && sel == i { line = apply_line_modifier(line, Modifier::REVERSED); }
+            if state.no_wrap {
+                rows += 1;
+                lines.push(line);
+            } else {
+                let wrapped = wrap_line(line, content_width as usize);
+                rows += wrapped.len();
+                lines.extend(wrapped.into_iter().rev());
             }
+        }
+        lines.reverse();
+    }
 
-            // Search overlay input (temporary)
-            if state.search_open {
-                let w = (area.width.saturating_sub(10)).min(60);
-                let h = 3;
-                let x = area.x + (area.width - w) / 2;
-                let y = area.y + (area.height - h) / 2;
-                let popup = Rect::new(x, y, w, h);
-                frame.render_widget(Clear, popup);
-                let title = format!("Search (r:{} i:{}) - Enter:apply Esc:close", state.search_is_regex, state.search_case_insensitive);
-                let input = Paragraph::new(state.search_input.clone())
-                    .block(Block::default().borders(Borders::ALL).title(title))
-                    .wrap(Wrap { trim: false });
-                frame.render_widget(input, popup);
+    let wrap_hint = if state.no_wrap { " [no wrap, ←/→ scroll]" } else { "" };
+    let mut title = if let Some(src) = state.current_source() { format!("Logs - {}{} (Enter:Context, j/k:select)", src.name, wrap_hint) } else { "Logs".to_string() };
+    // The column names head the columns from the top border
+    if !columns.is_empty() {
+        let names: Vec<String> = columns.iter().map(|(name, width)| fit_width(name, *width)).collect();
+        title = format!("{} │ {}", names.join(" "), title);
+    }
+    // Compact mode: no border, so no title or position either
+    let mut block = if state.compact { Block::default() } else { Block::default().borders(Borders::ALL).title(title) };
+    if let Some(bottom) = bottom_line && !state.compact {
+        let position = format!("line {} / {} ({}%)", group_digits(bottom + 1), group_digits(total), (bottom + 1) * 100 / total);
+        block = block.title(Line::from(position).right_aligned());
+    }
+    let para = Paragraph::new(lines)
+        .block(block)
+        .style(Style::default());
+    let para = if state.no_wrap {
+        para.scroll((0, state.h_scroll))
+    } else {
+        para.scroll((rows.saturating_sub(height) as u16, 0))
+    };
+    if let Some(view) = &state.summary {
+        draw_summary_panel(frame, log_area, state, view);
+    } else if let Some(view) = &state.query {
+        draw_query_panel(frame, log_area, state, view);
+    } else if let Some(corr) = &state.correlation {
+        draw_correlation_panel(frame, log_area, state, corr);
+    } else if let Some(view) = &state.patterns {
+        draw_patterns_panel(frame, log_area, state, view);
+    } else if let Some(selected) = state.alert_panel {
+        draw_alert_panel(frame, log_area, state, selected, now_ms);
+    } else {
+        frame.render_widget(para, log_area);
+        // Scrollbar over the lines the panel can show, when they don't fit
+        let shown = state.current_source().map_or(0, |s| if state.show_all { s.lines.len() } else { s.shown });
+        let content = shown.max(scroll_offset + height);
+        if content > height && !state.compact {
+            let mut scrollbar = ScrollbarState::new(content - height + 1).viewport_content_length(height).position(content - height - scroll_offset.min(content - height));
+            let area = log_area.inner(ratatui::layout::Margin { vertical: 1, horizontal: 0 });
+            frame.render_stateful_widget(Scrollbar::new(ScrollbarOrientation::VerticalRight).begin_symbol(None).end_symbol(None), area, &mut scrollbar);
+        }
+    }
+
+    // Status bar: notices, then the fields of the status bar template
+    let active = state.filter_match_set().len();
+    let (auto, so, shown) = if let Some(src) = state.current_source() { (src.auto_scroll, src.scroll_offset, src.shown) } else { (true, 0, 0) };
+    // Say how much the filters hide, so an empty-looking panel is not mistaken for no input
+    let lines_label = if shown < total && !state.show_all {
+        format!("Showing {} of {} lines (v:all)", group_digits(shown), group_digits(total))
+    } else {
+        format!("Lines: {}", group_digits(total))
+    };
+    // Pausing one source says nothing about the others, so count them all
+    let paused = state.sources.iter().filter(|s| !s.auto_scroll).count();
+    let follow_label = match (auto, state.sources.len()) {
+        (_, n) if n > 1 && paused > 0 => format!("{} ({}/{} paused, S:all)", if auto { "Following" } else { "Paused" }, paused, n),
+        (true, _) => "Following".to_string(),
+        (false, _) => "Paused".to_string(),
+    };
+    let levels = state.level_counts();
+    let mut status = Vec::new();
+    for part in state.status_template.parts() {
+        let text = match part {
+            StatusPart::Text(text) => text.clone(),
+            StatusPart::Field(StatusField::Levels) => {
+                status.extend(level_spans(&levels, ["E:", "W:", "I:"]));
+                continue;
             }
+            StatusPart::Field(field) => match field {
+                StatusField::Lines => group_digits(total),
+                StatusField::Shown => group_digits(shown),
+                StatusField::Showing => lines_label.clone(),
+                StatusField::Rate => state.current_source().map_or(0, |s| s.lines_per_sec()).to_string(),
+                StatusField::Filters => active.to_string(),
+                StatusField::Mode => follow_label.clone(),
+                StatusField::Source => state.current_source().map_or_else(String::new, |s| s.name.clone()),
+                StatusField::Sources => state.sources.len().to_string(),
+                StatusField::Scroll => so.to_string(),
+                StatusField::Errors => compact_count(levels.error as u64),
+                StatusField::Warnings => compact_count(levels.warn as u64),
+                StatusField::Keys => format!("[/] Filter Panel  Enter:{}", if state.filter_panel_open { "Add Filter" } else { "Toggle Context" }),
+                StatusField::Flags => format!(
+                    "r:regex={} f:fuzzy={} i:case={} w:word={} x:line={}",
+                    state.input_is_regex,
+                    state.input_fuzzy,
+                    case_flag(state.input_case_insensitive, state.smart_case),
+                    state.input_whole_word,
+                    state.input_whole_line,
+                ),
+                StatusField::Time => chrono::Local::now().format("%H:%M:%S").to_string(),
+                StatusField::Levels => unreachable!("rendered as spans above"),
+            },
+        };
+        status.push(Span::raw(text));
+    }
+    let mut status_spans = Vec::new();
+    // A count being typed (vim style)
+    if let Some(count) = state.pending_count {
+        status_spans.push(Span::styled(format!("{}  ", count), Style::default().add_modifier(Modifier::BOLD)));
+    }
+    // Source problems go first so they are not cut off on narrow terminals
+    if let Some(err) = state.current_source().and_then(|s| s.error.as_ref()) {
+        status_spans.push(Span::styled(format!("Source error: {}  ", err), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+    } else if let Some(reason) = state.current_source().and_then(|s| s.waiting.as_ref()) {
+        status_spans.push(Span::styled(format!("Waiting ({}), retrying…  ", reason), Style::default().fg(Color::Yellow)));
+    } else if state.current_source().is_some_and(|s| s.snapshot) {
+        status_spans.push(Span::styled("SNAPSHOT (read-only)  ", Style::default().fg(Color::Cyan)));
+    } else if state.current_source().is_some_and(|s| s.closed) {
+        status_spans.push(Span::raw("EOF  "));
+    }
+    if let Some(src) = state.current_source() && let Some((first, last)) = range {
+        let count = state.range_lines(src).map_or(0, |lines| lines.len());
+        let span = last - first + 1;
+        let lines = if state.range_hidden {
+            format!("{} lines incl. hidden (h)", group_digits(span))
+        } else if count < span {
+            format!("{} lines, {} hidden left out (h)", group_digits(count), group_digits(span - count))
+        } else {
+            format!("{} lines", group_digits(count))
+        };
+        status_spans.push(Span::styled(format!("RANGE {} y:copy e:export V:cancel  ", lines), Style::default().fg(Color::White).bg(RANGE_BG).add_modifier(Modifier::BOLD)));
+    }
+    if state.revealed {
+        status_spans.push(Span::styled("UNREDACTED (R)  ", Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD)));
+    }
+    if state.focus_follow_on {
+        let on = match &state.focus_follow { FocusFollow::Alerts => "alerts".to_string(), FocusFollow::Pattern(re) => format!("/{}/", re.as_str()) };
+        status_spans.push(Span::styled(format!("FOCUS-FOLLOW {} (L)  ", on), Style::default().fg(Color::Black).bg(Color::Magenta).add_modifier(Modifier::BOLD)));
+    }
+    if let Some(replacement) = state.replace_preview.as_ref().filter(|_| state.search_compiled.is_some()) {
+        status_spans.push(Span::styled(format!("REPLACE /{}/ → {:?} (s)  ", state.search_input, replacement), Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD)));
+    }
+    if state.show_all && shown < total {
+        status_spans.push(Span::styled(format!("ALL LINES, {} filtered out (v)  ", group_digits(total - shown)), Style::default().fg(Color::Black).bg(Color::Cyan).add_modifier(Modifier::BOLD)));
+    }
+    if let Some(zone) = state.display_zone {
+        status_spans.push(Span::styled(format!("TZ {}  ", zone.name()), Style::default().fg(Color::Cyan)));
+    }
+    if let Some(replay) = &state.replay {
+        let text = if replay.is_paused() { "REPLAY paused (.:step p:resume)  ".to_string() } else { format!("REPLAY {}  ", replay.speed()) };
+        status_spans.push(Span::styled(text, Style::default().fg(Color::Cyan)));
+    }
+    if let Some(msg) = state.current_message() {
+        let color = match msg.level { MessageLevel::Info => Color::LightGreen, MessageLevel::Warn => Color::Yellow, MessageLevel::Error => Color::LightRed };
+        status_spans.push(Span::styled(format!("{}  ", msg.text), Style::default().fg(color).add_modifier(Modifier::BOLD)));
+    }
+    let failed = state.failed_source_count();
+    if failed > 0 {
+        status_spans.push(Span::styled(format!("{} source(s) failed  ", failed), Style::default().fg(Color::Red)));
+    }
+    status_spans.extend(status);
+    // Single row without a border so the text is actually visible
+    let status_para = Paragraph::new(Line::from(status_spans))
+        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    frame.render_widget(status_para, chunks[1]);
+
+    // Summary / Stats panel
+    if show_stats { draw_stats_panel(frame, chunks[2], state); }
+
+    let mut next_chunk = 3;
+    if state.context_panel_open {
+        if let Some(sel) = selected_log {
+            draw_context_panel(frame, chunks[next_chunk], state, sel);
+        } else {
+            let empty = Paragraph::new("No selection").block(Block::default().borders(Borders::ALL).title("Context"));
+            frame.render_widget(empty, chunks[next_chunk]);
+        }
+        next_chunk += 1;
+    }
+
+    if state.filter_panel_open {
+        draw_filter_panel(frame, chunks[next_chunk], state);
+    }
+
+    // Search overlay input (temporary)
+    if state.search_open {
+        let popup = centered_rect(area, area.width.saturating_sub(10).min(60), 3);
+        frame.render_widget(Clear, popup);
+        // The scope first, so a narrow popup still shows what is searched
+        let scope = if state.search_all_lines { "all lines" } else if state.show_all || state.filter_match_set().is_empty() { "shown lines" } else { "filtered lines" };
+        let title = format!("Search {} (r:{} i:{}) - Tab:scope Enter:apply Up/Down:history Esc:close", scope, state.search_is_regex, case_flag(state.search_case_insensitive, state.smart_case));
+        let input = Paragraph::new(state.search_input.clone())
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(input, popup);
+    }
+
+    if let Some(prompt) = &state.prompt {
+        draw_prompt(frame, area, prompt, state);
+    }
+
+    if let Some(chooser) = &state.link_chooser {
+        draw_link_chooser(frame, area, state, chooser);
+    }
 
-            // Alert popup/banner (non-blocking)
-            if state.alert_deadline_ms > now_ms {
-                let msg = state.alert_message.clone().unwrap_or_else(|| "Alert".into());
-                let blink_active = now_ms < state.alert_blink_deadline_ms && blink_on;
-                let content = if blink_active { format!("⚠ ALERT: {}", msg) } else { format!("ALERT: {}", msg) };
-                let w = (area.width.saturating_sub(10)).min(60);
-                let h = 3;
-                let x = area.x + (area.width - w) / 2;
-                let y = area.y + 1; // near top
-                let popup = Rect::new(x, y, w, h);
-                frame.render_widget(Clear, popup);
-                let style = if blink_active { Style::default().fg(Color::Black).bg(Color::Red).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::Red).add_modifier(Modifier::BOLD) };
-                let para = Paragraph::new(content)
-                    .block(Block::default().borders(Borders::ALL).title("ALERT"))
-                    .style(style)
-                    .wrap(Wrap { trim: true });
-                frame.render_widget(para, popup);
+    // Alert popup/banner (non-blocking)
+    if state.alert_visible() {
+        let msg = state.alert_message.as_deref().map_or(Cow::Borrowed("Alert"), |m| state.display_text(m));
+        let blink_active = now_ms < state.alert_blink_deadline_ms && blink_on;
+        let content = if blink_active { format!("⚠ ALERT: {}", msg) } else { format!("ALERT: {}", msg) };
+        let color = severity_color(state.alert_severity);
+        let popup = Rect { y: area.y + 1, ..centered_rect(area, area.width.saturating_sub(10).min(60), 3) }; // near top
+        frame.render_widget(Clear, popup);
+        let style = if blink_active || state.alert_unacked > 0 { Style::default().fg(Color::Black).bg(color).add_modifier(Modifier::BOLD) } else { Style::default().fg(color).add_modifier(Modifier::BOLD) };
+        let title = if state.alert_unacked > 0 { format!("CRITICAL ({}) a:acknowledge", state.alert_unacked) } else { "ALERT a:acknowledge A:history".to_string() };
+        let para = Paragraph::new(content)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(style)
+            .wrap(Wrap { trim: true });
+        frame.render_widget(para, popup);
+    }
+    Some(height)
+}
+
+/// Whether the terminal is iTerm2, which shows badges
+fn is_iterm() -> bool {
+    std::env::var("TERM_PROGRAM").is_ok_and(|t| t == "iTerm.app")
+}
+
+/// Set iTerm2's badge, the large text in the corner of the session; empty removes it
+fn set_iterm_badge(out: &mut impl io::Write, text: &str) -> io::Result<()> {
+    use base64::Engine;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    crossterm::execute!(out, crossterm::style::Print(format!("\x1b]1337;SetBadgeFormat={}\x07", encoded)))
+}
+
+impl Drop for Ui {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+/// The i flag of the filter input or search as shown: "smart" when smart case may still make
+/// the pattern match case
+fn case_flag(insensitive: bool, smart_case: bool) -> &'static str {
+    match (insensitive, smart_case) {
+        (true, true) => "smart",
+        (true, false) => "true",
+        (false, _) => "false",
+    }
+}
+
+/// Below this size only a "terminal too small" notice is drawn
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
+/// Narrower terminals hide the sources sidebar
+const SIDEBAR_MIN_WIDTH: u16 = 80;
+/// Shorter terminals hide the stats panel
+const STATS_MIN_HEIGHT: u16 = 24;
+
+/// Background of the lines in the range selection
+const RANGE_BG: Color = Color::Blue;
+
+/// A `w`×`h` rectangle centered in `area`, shrunk to fit
+fn centered_rect(area: Rect, w: u16, h: u16) -> Rect {
+    let (w, h) = (w.min(area.width), h.min(area.height));
+    Rect::new(area.x + (area.width - w) / 2, area.y + (area.height - h) / 2, w, h)
+}
+
+fn draw_too_small(frame: &mut ratatui::Frame<'_>, area: Rect) {
+    let text = vec![
+        Line::styled("Terminal too small", Style::default().add_modifier(Modifier::BOLD)),
+        Line::from(format!("{}x{}, need at least {}x{}", area.width, area.height, MIN_WIDTH, MIN_HEIGHT)),
+        Line::from("q: quit"),
+    ];
+    let para = Paragraph::new(text).alignment(ratatui::layout::Alignment::Center).wrap(Wrap { trim: true });
+    frame.render_widget(para, centered_rect(area, area.width, 3));
+}
+
+fn severity_color(severity: AlertSeverity) -> Color {
+    match severity { AlertSeverity::Info => Color::Cyan, AlertSeverity::Warn => Color::Red, AlertSeverity::Critical => Color::LightRed }
+}
+
+fn draw_sidebar(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState) {
+    // Flat list when there is a single group; otherwise a collapsible tree of groups
+    let mut items: Vec<ListItem> = Vec::new();
+    let mut selected_row = None;
+    if state.groups.len() <= 1 {
+        for i in state.sidebar_order() {
+            if i == state.focused { selected_row = Some(items.len()); }
+            items.push(ListItem::new(source_line(&state.sources[i], "")));
+        }
+    } else {
+        let focused_group = state.current_source().map(|s| s.group);
+        for (gi, g) in state.groups.iter().enumerate() {
+            let marker = if g.collapsed { '▸' } else { '▾' };
+            if g.collapsed && focused_group == Some(gi) { selected_row = Some(items.len()); }
+            items.push(ListItem::new(Line::from(Span::styled(
+                format!("{} {} ({})", marker, g.name, g.members.len()),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ))));
+            if g.collapsed { continue; }
+            for i in state.group_members(gi) {
+                if i == state.focused { selected_row = Some(items.len()); }
+                items.push(ListItem::new(source_line(&state.sources[i], "  ")));
             }
-        })?;
-        Ok(())
+        }
+    }
+    let title = match state.sidebar.sort {
+        SourceSort::Input => "Sources ([/] {/} z o X O *)".to_string(),
+        sort => format!("Sources by {} ([/] {{/}} z o X O *)", sort.name()),
+    };
+    let side = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut list_state = ListState::default().with_selected(selected_row);
+    frame.render_stateful_widget(side, area, &mut list_state);
+}
+
+/// Sidebar row: follow indicator, name, error badge, unread count, total lines and rate
+fn source_line<'a>(s: &Source, indent: &str) -> Line<'a> {
+    let follow = if s.auto_scroll {
+        Span::styled("▶ ", Style::default().fg(Color::Green))
+    } else {
+        Span::styled("‖ ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+    };
+    let mut spans = vec![Span::raw(indent.to_string()), follow, Span::raw(s.name.clone())];
+    if s.error.is_some() {
+        spans.push(Span::styled(" ✖", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+    } else if s.waiting.is_some() {
+        spans.push(Span::styled(" …", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
     }
+    if s.unread > 0 {
+        spans.push(Span::styled(format!(" +{}", compact_count(s.unread as u64)), Style::default().fg(Color::Yellow)));
+    }
+    let rate = s.lines_per_sec();
+    let meta = if rate > 0 { format!(" {} {}/s", compact_count(s.lines.len() as u64), compact_count(rate as u64)) } else { format!(" {}", compact_count(s.lines.len() as u64)) };
+    spans.push(Span::styled(meta, Style::default().fg(Color::DarkGray)));
+    Line::from(spans)
+}
+
+/// Colored per-level counts, each preceded by its label
+fn level_spans(levels: &LevelCounts, labels: [&str; 3]) -> Vec<Span<'static>> {
+    let counts = [(levels.error, Color::LightRed), (levels.warn, Color::Yellow), (levels.info, Color::LightGreen)];
+    labels.iter().zip(counts).map(|(label, (n, color))| {
+        Span::styled(format!("{}{}  ", label, compact_count(n as u64)), Style::default().fg(color))
+    }).collect()
+}
+
+/// Short human-readable count: 950, 1.2k, 3.4M
+/// Integers as such, other values with two decimals
+fn format_number(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e15 { format!("{}", v as i64) } else { format!("{:.2}", v) }
+}
+
+/// `n` with thousands separators: 98,765
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) { out.push(','); }
+        out.push(c);
+    }
+    out
+}
+
+fn compact_count(n: u64) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        1_000..=999_999 => format!("{:.1}k", n as f64 / 1_000.0),
+        _ => format!("{:.1}M", n as f64 / 1_000_000.0),
+    }
+}
+
+fn draw_prompt(frame: &mut ratatui::Frame<'_>, area: Rect, prompt: &Prompt, state: &AppState) {
+    // Queries and pipelines get room for longer input
+    let (w, h) = match prompt.kind {
+        PromptKind::Query => (area.width.saturating_sub(10).min(100), 5),
+        PromptKind::Pipe => (area.width.saturating_sub(10).min(100), 3),
+        _ => (area.width.saturating_sub(10).min(60), 3),
+    };
+    let popup = centered_rect(area, w, h);
+    frame.render_widget(Clear, popup);
+    let range = state.current_source().and_then(|src| state.range_lines(src)).map(|lines| lines.len());
+    let export_label = match range {
+        Some(n) => format!("Export {} selected lines as {} to file", group_digits(n), state.export_format.name()),
+        None => format!("Export as {} to file", state.export_format.name()),
+    };
+    let (label, keys) = match prompt.kind {
+        PromptKind::Goto => ("Goto line or time", "Enter:apply Esc:close"),
+        PromptKind::OpenSource => ("Open path or !command", "Enter:apply Esc:close"),
+        PromptKind::Shell => ("Shell command (empty: interactive shell)", "Enter:apply Esc:close"),
+        PromptKind::ConfirmClear => ("Clear the focused source's buffer?", "y/Enter:clear n/Esc:cancel"),
+        PromptKind::MuteAlert => ("Mute alert pattern for minutes (empty: 10)", "Enter:apply Esc:close"),
+        PromptKind::Query => ("SQL query", "Enter:run Esc:close"),
+        PromptKind::Export => (export_label.as_str(), "Tab:format Enter:save Esc:close"),
+        PromptKind::Pipe if range.is_some() => ("Pipe selected lines to command", "Enter:run Esc:close"),
+        PromptKind::Pipe if state.pipe_context() => ("Pipe context lines to command", "Enter:run Esc:close"),
+        PromptKind::Pipe => ("Pipe visible lines to command", "Enter:run Esc:close"),
+        PromptKind::Replace => ("Replace matches with ($1, ${name})", "Enter:preview Esc:close"),
+        PromptKind::Hotkey => ("Bind the search to Alt+1…9", "Enter:save Esc:close"),
+        PromptKind::FilterGroup => ("Group of the filter (empty: none)", "Enter:apply Esc:close"),
+        PromptKind::Screenshot => ("Save screen as .txt, .ans or .svg", "Enter:save Esc:close"),
+        PromptKind::FieldFilter => ("Field filter like ms > 500 (empty: none)", "Enter:apply Esc:close"),
+    };
+    let (title, style) = match &prompt.error {
+        Some(err) => (format!("{} - {}", label, err), Style::default().fg(Color::Red)),
+        None => (format!("{} - {}", label, keys), Style::default()),
+    };
+    let input = Paragraph::new(prompt.input.clone())
+        .block(Block::default().borders(Borders::ALL).title(title).border_style(style))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(input, popup);
+}
+
+fn draw_link_chooser(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState, chooser: &LinkChooser) {
+    let height = chooser.links.len().min(10);
+    let popup = centered_rect(area, area.width.saturating_sub(10).min(100), height as u16 + 2);
+    frame.render_widget(Clear, popup);
+    let start = (chooser.selected + 1).saturating_sub(height);
+    let lines: Vec<Line> = chooser.links.iter().enumerate().skip(start).take(height).map(|(n, link)| {
+        let action = match link.kind { LinkKind::Url => "browser", LinkKind::Log => "source", LinkKind::File => "open" };
+        let line = Line::from(vec![
+            Span::styled(format!("{:<8}", action), Style::default().fg(Color::Cyan)),
+            Span::raw(state.display_text(&link.target).into_owned()),
+        ]);
+        if n == chooser.selected { apply_line_modifier(line, Modifier::REVERSED) } else { line }
+    }).collect();
+    let title = "Open link - Enter:open o:as source y:copy Esc:close";
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), popup);
 }
 
 fn draw_filter_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState) {
@@ -210,32 +706,49 @@ fn draw_filter_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppStat
         .split(area);
 
     // Input line with flags
-    let input_title = format!("Filter Input (focus={}): r={} i={} w={} x={}",
+    let input_title = format!("Filter Input (focus={}): r={} f={} i={} w={} x={}",
         match state.filter_focus { FilterFocus::Input => "input", FilterFocus::List => "list" },
-        state.input_is_regex, state.input_case_insensitive, state.input_whole_word, state.input_whole_line);
+        state.input_is_regex, state.input_fuzzy, case_flag(state.input_case_insensitive, state.smart_case), state.input_whole_word, state.input_whole_line);
     let input = Paragraph::new(state.filter_input.clone())
         .block(Block::default().borders(Borders::ALL).title(input_title))
         .wrap(Wrap { trim: false });
     frame.render_widget(input, rows[0]);
 
-    // Filters list
-    let items: Vec<ListItem> = state.filters.iter().enumerate().map(|(i, f)| {
-        let sel = if i == state.selected_filter { ">" } else { " " };
-        let chk = if f.enabled { "[x]" } else { "[ ]" };
-        let flags = format!("{}{}{}{}",
-            if f.is_regex { 'r' } else { '-' },
-            if f.case_insensitive { 'i' } else { '-' },
-            if f.whole_word { 'w' } else { '-' },
-            if f.whole_line { 'x' } else { '-' },
-        );
-        ListItem::new(Line::from(vec![
-            Span::raw(format!("{} {} {} ", sel, chk, flags)),
-            Span::styled(f.pattern.clone(), Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(format!("  ({} matches)", f.match_count)),
-        ]))
+    // Filters list, grouped filters under their group's header
+    let filter_rows = state.filter_rows();
+    let selected = state.selected_filter_row(&filter_rows);
+    let items: Vec<ListItem> = filter_rows.iter().enumerate().map(|(row, item)| {
+        let sel = if Some(row) == selected { ">" } else { " " };
+        match item {
+            FilterRow::Group { name, members, collapsed } => {
+                let on = members.iter().filter(|&&i| state.filters[i].enabled).count();
+                let chk = match on { 0 => "[ ]", n if n == members.len() => "[x]", _ => "[-]" };
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{} {} {} ", sel, chk, if *collapsed { "▸" } else { "▾" })),
+                    Span::styled(name.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(format!("  {}/{} on", on, members.len())),
+                ]))
+            }
+            &FilterRow::Filter(i) => {
+                let f = &state.filters[i];
+                let chk = if f.enabled { "[x]" } else { "[ ]" };
+                let indent = if f.group.is_some() { "  " } else { "" };
+                let hotkey = state.hotkeys.key_of(f).map(|key| format!("  Alt+{}", key)).unwrap_or_default();
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{} {}{} {} ", sel, indent, chk, f.flags())),
+                    Span::styled(f.pattern.clone(), if f.enabled { rule_style(i) } else { Style::default().add_modifier(Modifier::BOLD) }),
+                    Span::raw(format!("  ({} matches){}", f.match_count, hotkey)),
+                ]))
+            }
+        }
     }).collect();
+    let mut title = "Active Filters (Space:toggle, d:delete, g:group, z:collapse, Tab:switch focus)".to_string();
+    if !state.field_filters.is_empty() {
+        let fields: Vec<String> = state.field_filters.iter().map(ToString::to_string).collect();
+        title = format!("{} - fields: {}", title, fields.join(", "));
+    }
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Active Filters (Space:toggle, d:delete, Tab:switch focus)"));
+        .block(Block::default().borders(Borders::ALL).title(title));
     frame.render_widget(list, rows[1]);
 }
 
@@ -252,6 +765,20 @@ fn draw_stats_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState
         format!("Total lines: {}", state.current_source().map(|s| s.lines.len()).unwrap_or(0)), 
         Style::default().fg(Color::White).add_modifier(Modifier::BOLD),
     )]));
+    if let Some(src) = state.current_source() {
+        let mut spans = vec![Span::raw("Levels: ")];
+        spans.extend(level_spans(&src.levels, ["ERROR ", "WARN ", "INFO "]));
+        lines.push(Line::from(spans));
+        // How well the configured timestamp format fits the lines
+        if let Some(format) = &src.timestamp_format {
+            let failed = if src.timestamps_failed > 0 { Style::default().fg(Color::Yellow) } else { Style::default() };
+            lines.push(Line::from(vec![
+                Span::raw(format!("Timestamps: {} parsed, ", src.timestamps_parsed)),
+                Span::styled(format!("{} unparsed", src.timestamps_failed), failed),
+                Span::styled(format!(" ({})", format), Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+    }
 
     // Show counts for enabled filters only
     if state.filters.is_empty() {
@@ -263,6 +790,20 @@ fn draw_stats_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState
                 Span::styled(f.pattern.clone(), Style::default().fg(Color::Cyan)),
                 Span::raw(format!(": {}", f.match_count)),
             ]));
+            // Named groups of regex filters, summarized for the focused source
+            let names = f.compiled.iter().filter(|_| f.is_regex).flat_map(|re| re.capture_names().flatten());
+            for name in names {
+                let stats = state.current_source().and_then(|s| s.fields.get(name)).filter(|s| s.count > 0);
+                let summary = match stats {
+                    Some(s) => format!("min {}  avg {}  max {}  ({} values)", format_number(s.min), format_number(s.avg()), format_number(s.max), s.count),
+                    None => "no values".to_string(),
+                };
+                lines.push(Line::from(vec![
+                    Span::raw("  └ "),
+                    Span::styled(format!("{}: ", name), Style::default().fg(Color::Magenta)),
+                    Span::raw(summary),
+                ]));
+            }
         }
     }
 
@@ -271,11 +812,19 @@ fn draw_stats_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState
         .wrap(Wrap { trim: true });
     frame.render_widget(text, cols[0]);
 
-    // Right: error/warn sparklines stacked
+    // Right: error/warn sparklines stacked, with the aggregated field beside them
+    let spark_area = match state.field_aggregate() {
+        Some((name, aggregate)) => {
+            let parts = Layout::default().direction(Direction::Horizontal).constraints([Constraint::Min(10), Constraint::Length(26)]).split(cols[1]);
+            draw_field_aggregate(frame, parts[1], name, aggregate.as_ref());
+            parts[0]
+        }
+        None => cols[1],
+    };
     let rows = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(cols[1]);
+        .split(spark_area);
 
     let err_data: Vec<u64> = state.err_buckets.iter().map(|&v| v as u64).collect();
     let warn_data: Vec<u64> = state.warn_buckets.iter().map(|&v| v as u64).collect();
@@ -293,6 +842,164 @@ fn draw_stats_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState
     frame.render_widget(warn, rows[1]);
 }
 
+fn draw_field_aggregate(frame: &mut ratatui::Frame<'_>, area: Rect, name: &str, aggregate: Option<&FieldAggregate>) {
+    let label = |text: &str| Span::styled(format!("{:<7}", text), Style::default().fg(Color::Magenta));
+    let lines: Vec<Line> = match aggregate {
+        Some(a) => vec![
+            Line::from(vec![label("count"), Span::raw(a.count.to_string())]),
+            Line::from(vec![label("p50"), Span::raw(format_number(a.p50))]),
+            Line::from(vec![label("p95"), Span::raw(format_number(a.p95))]),
+            Line::from(vec![label("max"), Span::raw(format_number(a.max))]),
+        ],
+        None => vec![Line::from("no values")],
+    };
+    let para = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(format!("{} 60s f:next", name)));
+    frame.render_widget(para, area);
+}
+
+/// Colors of the filter rules' matches, by position in the filter list (repeating)
+const RULE_COLORS: [Color; 6] = [Color::Yellow, Color::Cyan, Color::Magenta, Color::Green, Color::LightBlue, Color::LightRed];
+
+/// Style of the matches of the filter rule at `rule` in the list
+pub fn rule_style(rule: usize) -> Style {
+    Style::default().fg(RULE_COLORS[rule % RULE_COLORS.len()]).add_modifier(Modifier::BOLD)
+}
+
+/// Style of the search matches, drawn over the filter matches
+fn search_style() -> Style {
+    Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD)
+}
+
+/// `text` with the matches of the `filters` colored by rule and those of `search` on top. Where
+/// two rules' matches overlap, the rule earlier in the list wins.
+pub fn highlight_line<'a>(text: &'a str, filters: &CompiledFilterSet, search: Option<&regex::Regex>) -> Line<'a> {
+    let mut ranges: Vec<(std::ops::Range<usize>, Style)> = filters.matches(text).into_iter().rev()
+        .map(|m| (m.range, rule_style(m.rule)))
+        .collect();
+    if let Some(re) = search {
+        ranges.extend(re.find_iter(text).filter(|m| !m.is_empty()).map(|m| (m.range(), search_style())));
+    }
+    if ranges.is_empty() {
+        return Line::from(text);
+    }
+    // Style of each byte, later ranges painted over earlier ones; ranges start and end on
+    // character boundaries, so runs of one style do as well
+    let mut styles: Vec<Option<Style>> = vec![None; text.len()];
+    for (range, style) in ranges {
+        styles[range].fill(Some(style));
+    }
+    let mut spans: Vec<Span> = Vec::new();
+    let mut start = 0;
+    for end in 1..=text.len() {
+        if end == text.len() || styles[end] != styles[start] {
+            spans.push(match styles[start] {
+                Some(style) => Span::styled(&text[start..end], style),
+                None => Span::raw(&text[start..end]),
+            });
+            start = end;
+        }
+    }
+    Line::from(spans)
+}
+
+/// `line` with tabs expanded to tab stops every `tab_width` columns across its spans. The
+/// terminal would drop a raw tab, shifting every styled range after it. With `visible` (`T`),
+/// tabs start with `→` and other control characters, which the terminal would drop as well,
+/// are shown as symbols (see `control_symbol`).
+pub fn expand_tabs(line: Line<'_>, tab_width: usize, visible: bool) -> Line<'_> {
+    let special = |c: char| c == '\t' || visible && control_symbol(c).is_some();
+    if !line.spans.iter().any(|s| s.content.contains(special)) {
+        return line;
+    }
+    let mut column = 0;
+    let mut spans: Vec<Span> = Vec::new();
+    for span in line.spans {
+        if !span.content.contains(special) {
+            column += display_width(&span.content);
+            spans.push(span);
+            continue;
+        }
+        if !visible {
+            let (text, next) = expand_tabs_from(&span.content, column, tab_width);
+            column = next;
+            spans.push(Span::styled(text, span.style));
+            continue;
+        }
+        // Symbols get a style of their own, so `^M` is not taken for text
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let symbol = match c {
+                '\t' => format!("→{:1$}", "", tab_width - 1 - column % tab_width),
+                c => match control_symbol(c) {
+                    Some(symbol) => symbol,
+                    None => { text.push(c); column += char_width(c); continue; }
+                },
+            };
+            if !text.is_empty() { spans.push(Span::styled(std::mem::take(&mut text), span.style)); }
+            column += display_width(&symbol);
+            spans.push(Span::styled(symbol, span.style.patch(control_style())));
+        }
+        if !text.is_empty() { spans.push(Span::styled(text, span.style)); }
+    }
+    Line { spans, ..line }
+}
+
+/// Style of the symbols standing for tabs and control characters
+fn control_style() -> Style {
+    Style::default().fg(Color::DarkGray)
+}
+
+/// `line` with the line breaks of joined multi-line entries (`multiline` in a source profile)
+/// shown as ` ⏎ `, since the terminal would drop them
+fn mark_line_breaks(line: Line<'_>) -> Line<'_> {
+    if !line.spans.iter().any(|s| s.content.contains('\n')) {
+        return line;
+    }
+    let spans: Vec<Span> = line.spans.into_iter()
+        .map(|span| if span.content.contains('\n') { Span::styled(span.content.replace('\n', " ⏎ "), span.style) } else { span })
+        .collect();
+    Line { spans, ..line }
+}
+
+/// `highlight_line` on the text as shown on screen (see `AppState::display_log_text`), tabs expanded
+fn display_line<'a>(state: &AppState, text: &'a str, filters: &CompiledFilterSet, search: Option<&regex::Regex>) -> Line<'a> {
+    expand_tabs(mark_line_breaks(match state.display_log_text(text) {
+        Cow::Borrowed(text) => highlight_line(text, filters, search),
+        Cow::Owned(text) => Line::from(highlight_line(&text, filters, search).spans.into_iter()
+            .map(|s| Span::styled(s.content.into_owned(), s.style))
+            .collect::<Vec<_>>()),
+    }), state.tab_width, state.show_controls)
+}
+
+/// `line` broken into rows of `width` columns, by character so the rows are known before drawing;
+/// a wide character that does not fit at the end of a row starts the next one
+fn wrap_line(line: Line<'_>, width: usize) -> Vec<Line<'_>> {
+    let mut rows = vec![Line::default().style(line.style)];
+    if width == 0 { return vec![line]; }
+    let mut used = 0;
+    for span in line.spans {
+        if used + display_width(&span.content) <= width {
+            used += display_width(&span.content);
+            rows.last_mut().unwrap().spans.push(span);
+            continue;
+        }
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let w = char_width(c);
+            if used + w > width && used > 0 {
+                if !text.is_empty() { rows.last_mut().unwrap().spans.push(Span::styled(std::mem::take(&mut text), span.style)); }
+                rows.push(Line::default().style(line.style));
+                used = 0;
+            }
+            text.push(c);
+            used += w;
+        }
+        if !text.is_empty() { rows.last_mut().unwrap().spans.push(Span::styled(text, span.style)); }
+    }
+    rows
+}
+
 fn apply_line_modifier(line: Line<'_>, modifier: Modifier) -> Line<'_> {
     // Apply a modifier to all spans in the line while preserving their colors/styles
     let spans = line.spans.into_iter().map(|mut s| {
@@ -304,6 +1011,15 @@ fn apply_line_modifier(line: Line<'_>, modifier: Modifier) -> Line<'_> {
     Line::from(spans)
 }
 
+fn apply_line_bg(line: Line<'_>, color: Color) -> Line<'_> {
+    // Apply a background color to all spans, preserving foreground colors and modifiers
+    let spans = line.spans.into_iter().map(|mut s| {
+        s.style = s.style.bg(color);
+        s
+    }).collect::<Vec<_>>();
+    Line::from(spans)
+}
+
 fn apply_line_color(line: Line<'_>, color: Color) -> Line<'_> {
     // Apply a foreground color to all spans, preserving modifiers
     let spans = line.spans.into_iter().map(|mut s| {
@@ -315,18 +1031,186 @@ fn apply_line_color(line: Line<'_>, color: Color) -> Line<'_> {
     Line::from(spans)
 }
 
+fn draw_correlation_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState, corr: &Correlation) {
+    let height = area.height.saturating_sub(2) as usize;
+    // Keep the selected hit visible, preferring to show the newest hits
+    let start = (corr.selected + 1).saturating_sub(height);
+    let id_re = regex::Regex::new(&regex::escape(&corr.id)).ok();
+    let mut lines: Vec<Line> = Vec::new();
+    for (n, &(si, li)) in corr.hits.iter().enumerate().skip(start).take(height) {
+        let Some(src) = state.sources.get(si) else { continue; };
+        let Some(text) = src.line_index(li).and_then(|i| src.lines.get(i)) else { continue; };
+        let mut spans = vec![Span::styled(format!("[{}] ", src.name), Style::default().fg(Color::Cyan))];
+        spans.extend(display_line(state, text, &CompiledFilterSet::default(), id_re.as_ref()).spans);
+        let mut line = Line::from(spans);
+        if n == corr.selected { line = apply_line_modifier(line, Modifier::REVERSED); }
+        lines.push(line);
+    }
+    let sources = {
+        let mut ids: Vec<usize> = corr.hits.iter().map(|h| h.0).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.len()
+    };
+    let title = format!("Correlate: {} ({} lines across {} sources) Enter:jump Esc:close", corr.id, corr.hits.len(), sources);
+    let para = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(para, area);
+}
+
+fn draw_summary_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState, view: &SummaryView) {
+    let mut lines: Vec<Line> = Vec::new();
+    for s in &view.sources {
+        let mut header = vec![
+            Span::styled(s.name.clone(), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("  {} lines  ", group_digits(s.lines))),
+        ];
+        header.extend(level_spans(&s.levels, ["E:", "W:", "I:"]));
+        if let Some((first, last)) = s.span {
+            let span = format!("{} → {} ({})", first.format("%Y-%m-%d %H:%M:%S"), last.format("%Y-%m-%d %H:%M:%S"), format_gap(last - first));
+            header.push(Span::styled(span, Style::default().fg(Color::Cyan)));
+        }
+        lines.push(Line::from(header));
+        for p in &s.top {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:>10}  ", group_digits(p.count)), Style::default().fg(Color::DarkGray)),
+                Span::raw(state.display_text(&p.template)),
+            ]));
+        }
+        lines.push(Line::from(""));
+    }
+    let total: usize = view.sources.iter().map(|s| s.lines).sum();
+    let loading = if view.loading > 0 { format!(", {} still loading", view.loading) } else { String::new() };
+    let title = format!("Summary: {} sources, {} lines{} (j/k:scroll, any key:close, I:reopen)", view.sources.len(), group_digits(total), loading);
+    let para = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .scroll((view.scroll.min(u16::MAX as usize) as u16, 0));
+    frame.render_widget(para, area);
+}
+
+fn draw_patterns_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState, view: &PatternView) {
+    let height = area.height.saturating_sub(2) as usize;
+    let start = (view.selected + 1).saturating_sub(height);
+    let mut lines: Vec<Line> = Vec::new();
+    for (n, p) in view.patterns.iter().enumerate().skip(start).take(height) {
+        let share = p.count as f64 * 100.0 / view.total.max(1) as f64;
+        let mut line = Line::from(vec![
+            Span::styled(format!("{:>8} {:>5.1}%  ", p.count, share), Style::default().fg(Color::Cyan)),
+            Span::raw(state.display_text(&p.template)),
+        ]);
+        if n == view.selected { line = apply_line_modifier(line, Modifier::REVERSED); }
+        lines.push(line);
+    }
+    let name = state.current_source().map_or("", |s| s.name.as_str());
+    let window = view.window_minutes().map_or("whole buffer".to_string(), |m| format!("last {}m", m));
+    let title = format!("Top patterns: {} ({} lines, {}, {} patterns) w:window Enter:jump Esc:close", name, view.total, window, view.patterns.len());
+    let para = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(para, area);
+}
+
+/// Longest field value shown in full in the column view
+const FIELD_COLUMN_WIDTH: usize = 24;
+
+/// Longest cell shown in full in a query column other than the last
+const QUERY_COLUMN_WIDTH: usize = 40;
+
+fn draw_query_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState, view: &QueryView) {
+    let result = &view.result;
+    let height = area.height.saturating_sub(3) as usize;
+    let start = (view.selected + 1).saturating_sub(height);
+    let visible = &result.rows[start.min(result.rows.len())..(start + height).min(result.rows.len())];
+    let text = |v: &Value| state.display_text(&v.to_string()).into_owned();
+    let cells: Vec<Vec<String>> = visible.iter().map(|row| row.iter().map(text).collect()).collect();
+    let last = result.columns.len().saturating_sub(1);
+    let widths: Vec<usize> = result.columns.iter().enumerate().map(|(c, name)| {
+        let widest = cells.iter().map(|row| display_width(&row[c])).chain([display_width(name)]).max().unwrap_or(0);
+        if c == last { widest } else { widest.min(QUERY_COLUMN_WIDTH) }
+    }).collect();
+    let format_row = |row: &[String]| -> String {
+        row.iter().zip(&widths).map(|(cell, &w)| fit_width(cell, w)).collect::<Vec<_>>().join("  ")
+    };
+    let mut lines: Vec<Line> = vec![Line::styled(format_row(&result.columns), Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED))];
+    for (n, row) in cells.iter().enumerate() {
+        // Numbers stand out from text
+        let numeric = visible[n].iter().all(|v| matches!(v, Value::Num(_) | Value::Null));
+        let mut line = Line::styled(format_row(row), Style::default().fg(if numeric { Color::Cyan } else { Color::Reset }));
+        if start + n == view.selected { line = apply_line_modifier(line, Modifier::REVERSED); }
+        lines.push(line);
+    }
+    let more = if result.truncated { format!(", first {}", result.rows.len()) } else { String::new() };
+    let title = format!("Query: {} rows{} ({} lines matched, {} ms) Q:edit Esc:close", result.rows.len(), more, result.matched, view.elapsed_ms);
+    let para = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(para, area);
+}
+
+fn draw_alert_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState, selected: usize, now_ms: u128) {
+    let height = area.height.saturating_sub(2) as usize;
+    let start = (selected + 1).saturating_sub(height);
+    let mut lines: Vec<Line> = Vec::new();
+    // Newest first
+    for (n, entry) in state.alert_history.iter().rev().enumerate().skip(start).take(height) {
+        let color = severity_color(entry.severity);
+        let mut spans = vec![
+            Span::raw(format!("{}  ", entry.time.format("%H:%M:%S"))),
+            Span::styled(format!("{:<9}", entry.severity.name()), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+        ];
+        if let Some(rule) = entry.rule {
+            spans.push(Span::styled(format!("[{}] ", state.alert_rules[rule].label()), Style::default().fg(Color::Cyan)));
+        }
+        spans.push(Span::raw(state.display_text(&entry.message)));
+        if let Some(mute) = entry.rule.and_then(|r| state.alert_mute(r)) {
+            let left = mute.until_ms.saturating_sub(now_ms).div_ceil(60_000);
+            spans.push(Span::styled(format!("  muted {}m left, {} suppressed", left, mute.suppressed), Style::default().fg(Color::DarkGray)));
+        }
+        let mut line = Line::from(spans);
+        if !entry.acknowledged { line = apply_line_modifier(line, Modifier::BOLD); }
+        if n == selected { line = apply_line_modifier(line, Modifier::REVERSED); }
+        lines.push(line);
+    }
+    let title = format!("Alert history ({} alerts, {} unacknowledged) a:acknowledge m:mute pattern u:unmute Esc:close", state.alert_history.len(), state.unacked_alerts());
+    let para = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(para, area);
+}
+
+fn draw_histogram(frame: &mut ratatui::Frame<'_>, area: Rect, view: &HistogramView) {
+    let width = area.width.saturating_sub(2) as usize;
+    // Show the newest buckets unless the cursor is further back
+    let start = view.buckets.len().saturating_sub(width).min(view.selected);
+    let bars = view.buckets.iter().enumerate().skip(start).take(width).map(|(i, b)| {
+        let bar = SparklineBar::from(b.count);
+        if i == view.selected { bar.style(Some(Style::default().fg(Color::Yellow))) } else { bar }
+    });
+    let fmt = match view.size { BucketSize::Minute => "%Y-%m-%d %H:%M", BucketSize::Hour => "%Y-%m-%d %H:00" };
+    let title = match view.buckets.get(view.selected) {
+        Some(b) => format!("Lines per {}: {} ({} lines) ←/→:jump H:{}", view.size.name(), b.start.format(fmt), b.count, if view.size == BucketSize::Minute { "hourly" } else { "hide" }),
+        None => "Lines per minute: no timestamps found H:hide".to_string(),
+    };
+    let chart = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .data(bars)
+        .style(Style::default().fg(Color::Cyan));
+    frame.render_widget(chart, area);
+}
+
 fn draw_context_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppState, sel: usize) {
     let Some(src) = state.current_source() else { return; };
     let total = src.lines.len();
     if total == 0 { return; }
     let radius = state.context_radius;
-    let from = sel.saturating_sub(radius);
-    let to = (sel + radius + 1).min(total);
+    // The window around the selection (centred when the panel is squeezed), moved by the
+    // panel's own scrolling and kept within the source
+    let height = (area.height.saturating_sub(2) as usize).max(1);
+    let start = sel as isize - radius.min(height / 2) as isize + state.context_scroll;
+    let from = start.clamp(0, total.saturating_sub(height) as isize) as usize;
+    let to = (from + height).min(total);
 
     let mut lines: Vec<Line> = Vec::new();
     for i in from..to {
-        let content = src.lines[i].clone();
-        let mut line = Line::from(content);
+        let mut line = display_line(state, &src.lines[i], &CompiledFilterSet::default(), None);
         if i == sel {
             // Highlight selected line distinctly in context view
             line = apply_line_modifier(line, Modifier::BOLD);
@@ -337,7 +1221,11 @@ fn draw_context_panel(frame: &mut ratatui::Frame<'_>, area: Rect, state: &AppSta
         lines.push(line);
     }
 
-    let title = format!("Context (±{} lines around selected)", radius);
+    let scrolled = match state.context_scroll {
+        0 => String::new(),
+        n => format!(", scrolled {:+}", n),
+    };
+    let title = format!("Context (±{} lines around selected{}) J/K:scroll n/N:next match", radius, scrolled);
     let para = Paragraph::new(lines)
         .block(Block::default().borders(Borders::ALL).title(title))
         .wrap(Wrap { trim: false });
@@ -349,9 +1237,40 @@ pub enum UiEvent {
     None,
     ScrollUp(usize),
     ScrollDown(usize),
+    /// Scroll by this many half pages; negative is up
+    ScrollPage(isize),
+    /// Digit of a count for the next navigation key (`25j`)
+    CountDigit(u32),
+    /// Select the line given by the count (`100G`), or go to the bottom without one
+    GotoCount,
     Top,
     Bottom,
     ToggleAuto,
+    /// Pause or resume following for every source
+    ToggleAutoAll,
+    ToggleWrap,
+    /// Show the fields of the lines in columns, or not
+    ToggleColumns,
+    /// Ask for a condition on a field
+    OpenFieldFilter,
+    /// Hide or show everything but the log lines and the status bar
+    ToggleCompact,
+    /// Show tabs and control characters as symbols, or not
+    ToggleControls,
+    /// Show the lines the filters hide as well
+    ToggleShowAll,
+    /// Switch focus-follow on or off
+    ToggleFocusFollow,
+    /// Sort the sidebar by the next order
+    CycleSourceSort,
+    /// Pin the focused source to the top of the sidebar, or unpin it
+    TogglePinSource,
+    /// Show more or fewer context lines around filter matches
+    AdjustFilterContext(isize),
+    /// Scroll unwrapped lines sideways by this many columns
+    ScrollHorizontal(i16),
+    /// Scroll the context panel by this many lines
+    ScrollContext(isize),
 
     ToggleFilterPanel,
     ToggleContextPanel,
@@ -362,84 +1281,519 @@ pub enum UiEvent {
     ToggleInputCase,
     ToggleInputWord,
     ToggleInputLine,
+    ToggleInputFuzzy,
     ToggleFilterEnabled,
     DeleteFilter,
     FocusNext,
     SelectUp,
     SelectDown,
+    FilterHistoryPrev,
+    FilterHistoryNext,
     NextSource,
     PrevSource,
+    NextGroup,
+    PrevGroup,
+    ToggleGroup,
 
     // Search
     ToggleSearch,
     CloseSearch,
     SearchChar(char),
     SearchBackspace,
+    SearchHistoryPrev,
+    SearchHistoryNext,
     ApplySearch,
     NextMatch,
     PrevMatch,
     ToggleSearchRegex,
     ToggleSearchCase,
+    ToggleSearchScope,
+    /// Preview replacing the search matches, or end the preview
+    OpenReplace,
+    /// Bind the applied search to a hotkey
+    OpenHotkey,
+    /// Ask for the group of the selected filter
+    OpenFilterGroup,
+    /// Collapse or expand the selected filter's group
+    ToggleFilterGroup,
+    /// Alt and a digit: switch the filter bound to it
+    Hotkey(u8),
+
+    /// Cycle the histogram: per minute, per hour, hidden
+    CycleHistogram,
+    /// Move the histogram cursor by this many buckets
+    HistogramMove(isize),
+
+    /// Open or close the summary of all sources
+    ToggleSummary,
+    /// Scroll the summary up (true) or down a line
+    SummaryScroll(bool),
+
+    // Top patterns panel
+    TogglePatterns,
+    PatternsWindow,
+    PatternsJump,
+
+    // Query results panel
+    OpenQuery,
+    CloseQuery,
+
+    // Correlation view
+    Correlate,
+    CloseCorrelation,
+    CorrelationJump,
+
+    // Generic prompt
+    OpenGoto,
+    OpenExport,
+    OpenPipe,
+    /// Ask for a file to save the screen to
+    OpenScreenshot,
+    /// Switch the export prompt to the next file format
+    CycleExportFormat,
+    OpenSource,
+    CloseSource,
+    OpenShell,
+    /// Open the file location in the selected line in the editor
+    OpenInEditor,
+
+    // Link chooser
+    OpenLinks,
+    CloseLinks,
+    /// Open the chosen link the way its kind says, or as a new source (`true`)
+    OpenLink(bool),
+    CopyLink,
+
+    Suspend,
+    Freeze,
+    PromptChar(char),
+    PromptBackspace,
+    SubmitPrompt,
+    ClosePrompt,
+
+    Undo,
+    ClearSource,
+    /// Insert a marker line into the focused source (`true`: into all sources)
+    Mark(bool),
+
+    // Replay controls
+    ReplayPause,
+    ReplayStep,
+    /// Multiply the replay speed by this factor
+    ReplaySpeed(f64),
+
+    /// Acknowledge all alerts: stop blinking and dismiss the banner
+    AcknowledgeAlerts,
+    // Alert history panel
+    ToggleAlertPanel,
+    MuteAlert,
+    UnmuteAlert,
+
+    /// Start or drop a range selection in the log panel
+    ToggleRange,
+    /// Take the lines the filters hide into the range selection, or leave them out
+    ToggleRangeHidden,
+    /// Copy the range selection or the selected line to the clipboard
+    CopySelection,
+
+    /// Show or redact sensitive text on screen
+    ToggleReveal,
+    /// Show log lines rewritten or as received
+    ToggleRewrites,
+    /// Aggregate the next field next to the sparklines
+    CycleAggregateField,
+
+    /// Terminal size changed; redraw right away
+    Resize,
 }
 
-pub fn poll_input(state: &AppState) -> anyhow::Result<UiEvent> {
-    if event::poll(std::time::Duration::from_millis(10))? {
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                if state.search_open {
-                    return Ok(match key.code {
-                        KeyCode::Esc => UiEvent::CloseSearch,
-                        KeyCode::Enter => UiEvent::ApplySearch,
-                        KeyCode::Backspace => UiEvent::SearchBackspace,
-                        KeyCode::Char('r') => UiEvent::ToggleSearchRegex,
-                        KeyCode::Char('i') => UiEvent::ToggleSearchCase,
-                        KeyCode::Char(c) if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT => UiEvent::SearchChar(c),
-                        _ => UiEvent::None,
-                    });
-                }
+/// Translate a terminal event into a `UiEvent`
+pub fn translate_event(state: &AppState, event: &Event) -> UiEvent {
+    match event {
+        // Raw mode turns Ctrl-C into a key press instead of SIGINT
+        Event::Key(key) if key.kind == KeyEventKind::Press && key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('c') => UiEvent::Quit,
+        Event::Key(key) if key.kind == KeyEventKind::Press => map_key(state, key),
+        Event::Resize(_, _) => UiEvent::Resize,
+        _ => UiEvent::None,
+    }
+}
 
-                // Check if we're in input mode for filter input
-                let in_filter_input = state.filter_panel_open && matches!(state.filter_focus, FilterFocus::Input);
-
-                return Ok(match key.code {
-                    // Always handle Esc to quit, but only handle 'q' to quit if not in input mode
-                    KeyCode::Esc => UiEvent::Quit,
-                    KeyCode::Char('q') if !in_filter_input => UiEvent::Quit,
-                    
-                    KeyCode::Up => UiEvent::ScrollUp(1),
-                    KeyCode::Down => UiEvent::ScrollDown(1),
-                    KeyCode::PageUp => UiEvent::ScrollUp(10),
-                    KeyCode::PageDown => UiEvent::ScrollDown(10),
-                    KeyCode::Home => UiEvent::Top,
-                    KeyCode::End => UiEvent::Bottom,
-                    KeyCode::Char(' ') if key.modifiers.is_empty() => { if state.filter_panel_open && matches!(state.filter_focus, FilterFocus::List) { UiEvent::ToggleFilterEnabled } else { UiEvent::ToggleAuto } },
-
-                    KeyCode::Char('/') if !in_filter_input => UiEvent::ToggleFilterPanel,
-                    KeyCode::Char('?') if !in_filter_input => UiEvent::ToggleSearch,
-                    KeyCode::Enter => { if state.filter_panel_open { UiEvent::AddFilter } else { UiEvent::ToggleContextPanel } },
-                    KeyCode::Backspace => UiEvent::Backspace,
-                    KeyCode::Tab => UiEvent::FocusNext,
-                    KeyCode::BackTab => UiEvent::PrevSource,
-                    KeyCode::Char(']') if !in_filter_input => UiEvent::NextSource,
-                    KeyCode::Char('[') if !in_filter_input => UiEvent::PrevSource,
-                    
-                    // Only handle these shortcuts if NOT in filter input mode
-                    KeyCode::Char('r') if !in_filter_input => UiEvent::ToggleInputRegex,
-                    KeyCode::Char('i') if !in_filter_input => UiEvent::ToggleInputCase,
-                    KeyCode::Char('w') if !in_filter_input => UiEvent::ToggleInputWord,
-                    KeyCode::Char('x') if !in_filter_input => UiEvent::ToggleInputLine,
-                    KeyCode::Char('d') if !in_filter_input => UiEvent::DeleteFilter,
-                    KeyCode::Char('k') if !in_filter_input => UiEvent::SelectUp,
-                    KeyCode::Char('j') if !in_filter_input => UiEvent::SelectDown,
-                    KeyCode::Char('n') if key.modifiers.is_empty() && !in_filter_input => UiEvent::NextMatch,
-                    KeyCode::Char('N') if !in_filter_input => UiEvent::PrevMatch,
-                    
-                    // Handle all other characters as input when in appropriate modes
-                    KeyCode::Char(c) if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT => UiEvent::InputChar(c),
-                    _ => UiEvent::None,
-                });
+/// Keys with a name in `key_name`
+const NAMED_KEYS: [(KeyCode, &str); 15] = [
+    (KeyCode::Enter, "Enter"), (KeyCode::Tab, "Tab"), (KeyCode::BackTab, "BackTab"), (KeyCode::Backspace, "Backspace"),
+    (KeyCode::Esc, "Esc"), (KeyCode::Up, "Up"), (KeyCode::Down, "Down"), (KeyCode::Left, "Left"), (KeyCode::Right, "Right"),
+    (KeyCode::Home, "Home"), (KeyCode::End, "End"), (KeyCode::PageUp, "PageUp"), (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Delete, "Delete"), (KeyCode::Insert, "Insert"),
+];
+
+/// A key press as text for `--record`: the character (`j`, `G`), or its name and modifiers in
+/// angle brackets (`<Enter>`, `<C-d>`, `<A-1>`, `<S-Up>`, `<lt>` for `<`); None for keys rtlog
+/// does not use
+pub fn key_name(key: &KeyEvent) -> Option<String> {
+    let mut mods = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) { mods.push_str("C-"); }
+    if key.modifiers.contains(KeyModifiers::ALT) { mods.push_str("A-"); }
+    let name = match key.code {
+        KeyCode::Char('<') => "lt".to_string(),
+        KeyCode::Char(c) if mods.is_empty() => return Some(c.to_string()),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        code => {
+            // Shift is part of the character, but not of a named key
+            if key.modifiers.contains(KeyModifiers::SHIFT) { mods.push_str("S-"); }
+            NAMED_KEYS.iter().find(|(named, _)| *named == code)?.1.to_string()
+        }
+    };
+    Some(format!("<{}{}>", mods, name))
+}
+
+/// The key press `key_name` wrote as `name`
+pub fn parse_key(name: &str) -> Option<KeyEvent> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        let shift = if c.is_uppercase() { KeyModifiers::SHIFT } else { KeyModifiers::NONE };
+        return Some(KeyEvent::new(KeyCode::Char(c), shift));
+    }
+    let mut rest = name.strip_prefix('<')?.strip_suffix('>')?;
+    let mut modifiers = KeyModifiers::NONE;
+    for (prefix, modifier) in [("C-", KeyModifiers::CONTROL), ("A-", KeyModifiers::ALT), ("S-", KeyModifiers::SHIFT)] {
+        if rest.len() > prefix.len() && let Some(r) = rest.strip_prefix(prefix) { rest = r; modifiers |= modifier; }
+    }
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        _ if rest == "lt" => KeyCode::Char('<'),
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.strip_prefix('F').and_then(|n| n.parse().ok()) {
+            Some(n) => KeyCode::F(n),
+            None => NAMED_KEYS.iter().find(|(_, named)| *named == rest)?.0,
+        },
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+fn map_key(state: &AppState, key: &KeyEvent) -> UiEvent {
+    if state.prompt.as_ref().is_some_and(|p| p.kind == PromptKind::ConfirmClear) {
+        return match key.code {
+            KeyCode::Enter | KeyCode::Char('y') => UiEvent::SubmitPrompt,
+            KeyCode::Esc | KeyCode::Char('n') => UiEvent::ClosePrompt,
+            _ => UiEvent::None,
+        };
+    }
+    if state.prompt.is_some() {
+        return match key.code {
+            KeyCode::Esc => UiEvent::ClosePrompt,
+            KeyCode::Enter => UiEvent::SubmitPrompt,
+            KeyCode::Backspace => UiEvent::PromptBackspace,
+            KeyCode::Tab if state.prompt.as_ref().is_some_and(|p| p.kind == PromptKind::Export) => UiEvent::CycleExportFormat,
+            KeyCode::Char(c) if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT => UiEvent::PromptChar(c),
+            _ => UiEvent::None,
+        };
+    }
+
+    if state.link_chooser.is_some() {
+        return match key.code {
+            KeyCode::Esc | KeyCode::Char('U') | KeyCode::Char('q') => UiEvent::CloseLinks,
+            KeyCode::Enter => UiEvent::OpenLink(false),
+            KeyCode::Char('o') => UiEvent::OpenLink(true),
+            KeyCode::Char('y') => UiEvent::CopyLink,
+            KeyCode::Up | KeyCode::Char('k') => UiEvent::SelectUp,
+            KeyCode::Down | KeyCode::Char('j') => UiEvent::SelectDown,
+            _ => UiEvent::None,
+        };
+    }
+
+    if state.query.is_some() {
+        return match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => UiEvent::CloseQuery,
+            KeyCode::Char('Q') => UiEvent::OpenQuery,
+            KeyCode::Up | KeyCode::Char('k') => UiEvent::SelectUp,
+            KeyCode::Down | KeyCode::Char('j') => UiEvent::SelectDown,
+            _ => UiEvent::None,
+        };
+    }
+
+    if state.summary.is_some() {
+        return match key.code {
+            KeyCode::Up | KeyCode::Char('k') => UiEvent::SummaryScroll(true),
+            KeyCode::Down | KeyCode::Char('j') => UiEvent::SummaryScroll(false),
+            _ => UiEvent::ToggleSummary,
+        };
+    }
+
+    if state.patterns.is_some() {
+        return match key.code {
+            KeyCode::Esc | KeyCode::Char('P') | KeyCode::Char('q') => UiEvent::TogglePatterns,
+            KeyCode::Enter => UiEvent::PatternsJump,
+            KeyCode::Char('w') => UiEvent::PatternsWindow,
+            KeyCode::Up | KeyCode::Char('k') => UiEvent::SelectUp,
+            KeyCode::Down | KeyCode::Char('j') => UiEvent::SelectDown,
+            KeyCode::Char(']') | KeyCode::Tab => UiEvent::NextSource,
+            KeyCode::Char('[') | KeyCode::BackTab => UiEvent::PrevSource,
+            _ => UiEvent::None,
+        };
+    }
+
+    if state.alert_panel.is_some() {
+        return match key.code {
+            KeyCode::Esc | KeyCode::Char('A') | KeyCode::Char('q') => UiEvent::ToggleAlertPanel,
+            KeyCode::Char('a') => UiEvent::AcknowledgeAlerts,
+            KeyCode::Char('m') => UiEvent::MuteAlert,
+            KeyCode::Char('u') => UiEvent::UnmuteAlert,
+            KeyCode::Up | KeyCode::Char('k') => UiEvent::SelectUp,
+            KeyCode::Down | KeyCode::Char('j') => UiEvent::SelectDown,
+            _ => UiEvent::None,
+        };
+    }
+
+    if state.correlation.is_some() {
+        return match key.code {
+            KeyCode::Esc | KeyCode::Char('c') | KeyCode::Char('q') => UiEvent::CloseCorrelation,
+            KeyCode::Enter => UiEvent::CorrelationJump,
+            KeyCode::Up | KeyCode::Char('k') => UiEvent::SelectUp,
+            KeyCode::Down | KeyCode::Char('j') => UiEvent::SelectDown,
+            _ => UiEvent::None,
+        };
+    }
+
+    if state.search_open {
+        return match key.code {
+            KeyCode::Esc => UiEvent::CloseSearch,
+            KeyCode::Enter => UiEvent::ApplySearch,
+            KeyCode::Backspace => UiEvent::SearchBackspace,
+            KeyCode::Up => UiEvent::SearchHistoryPrev,
+            KeyCode::Down => UiEvent::SearchHistoryNext,
+            KeyCode::Char('r') => UiEvent::ToggleSearchRegex,
+            KeyCode::Char('i') => UiEvent::ToggleSearchCase,
+            KeyCode::Tab => UiEvent::ToggleSearchScope,
+            KeyCode::Char(c) if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT => UiEvent::SearchChar(c),
+            _ => UiEvent::None,
+        };
+    }
+
+    // Check if we're in input mode for filter input
+    let in_filter_input = state.filter_panel_open && matches!(state.filter_focus, FilterFocus::Input);
+    let in_filter_list = state.filter_panel_open && matches!(state.filter_focus, FilterFocus::List);
+    let in_range = state.current_source().is_some_and(|s| s.range_anchor.is_some());
+
+    match key.code {
+        // Always handle Esc to quit (after dropping a range selection), but only handle 'q' to
+        // quit if not in input mode
+        KeyCode::Esc if in_range => UiEvent::ToggleRange,
+        KeyCode::Esc => UiEvent::Quit,
+        KeyCode::Char('q') if !in_filter_input => UiEvent::Quit,
+        
+        KeyCode::Up if in_filter_input => UiEvent::FilterHistoryPrev,
+        KeyCode::Down if in_filter_input => UiEvent::FilterHistoryNext,
+        KeyCode::Char('K') if !in_filter_input && state.context_panel_open => UiEvent::ScrollContext(-1),
+        KeyCode::Char('J') if !in_filter_input && state.context_panel_open => UiEvent::ScrollContext(1),
+        KeyCode::Up if state.context_panel_open && key.modifiers.contains(KeyModifiers::SHIFT) => UiEvent::ScrollContext(-1),
+        KeyCode::Down if state.context_panel_open && key.modifiers.contains(KeyModifiers::SHIFT) => UiEvent::ScrollContext(1),
+        KeyCode::PageUp if state.context_panel_open && key.modifiers.contains(KeyModifiers::SHIFT) => UiEvent::ScrollContext(-10),
+        KeyCode::PageDown if state.context_panel_open && key.modifiers.contains(KeyModifiers::SHIFT) => UiEvent::ScrollContext(10),
+        KeyCode::Up => UiEvent::ScrollUp(1),
+        KeyCode::Down => UiEvent::ScrollDown(1),
+        KeyCode::PageUp => UiEvent::ScrollPage(-2),
+        KeyCode::PageDown => UiEvent::ScrollPage(2),
+        KeyCode::Char('u') if key.modifiers == KeyModifiers::CONTROL => UiEvent::ScrollPage(-1),
+        KeyCode::Char('d') if key.modifiers == KeyModifiers::CONTROL => UiEvent::ScrollPage(1),
+        KeyCode::Home => UiEvent::Top,
+        KeyCode::End => UiEvent::Bottom,
+        // Typed as is in the filter input, where patterns like fuzzy `conn timeout db` need it
+        KeyCode::Char(' ') if key.modifiers.is_empty() && !in_filter_input => { if in_filter_list { UiEvent::ToggleFilterEnabled } else { UiEvent::ToggleAuto } },
+
+        KeyCode::Char('/') if !in_filter_input => UiEvent::ToggleFilterPanel,
+        KeyCode::Char('?') if !in_filter_input => UiEvent::ToggleSearch,
+        KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => UiEvent::OpenScreenshot,
+        KeyCode::Char('s') if !in_filter_input => UiEvent::OpenReplace,
+        KeyCode::Char('b') if !in_filter_input => UiEvent::OpenHotkey,
+        KeyCode::Char(':') if !in_filter_input => UiEvent::OpenGoto,
+        KeyCode::Char('e') if !in_filter_input => UiEvent::OpenExport,
+        KeyCode::Char('|') if !in_filter_input => UiEvent::OpenPipe,
+        KeyCode::Char('o') if !in_filter_input => UiEvent::OpenSource,
+        KeyCode::Char('z') if key.modifiers == KeyModifiers::CONTROL => UiEvent::Suspend,
+        KeyCode::Char('!') if !in_filter_input => UiEvent::OpenShell,
+        KeyCode::Char('E') if !in_filter_input => UiEvent::OpenInEditor,
+        KeyCode::Char('U') if !in_filter_input => UiEvent::OpenLinks,
+        KeyCode::Char('F') if !in_filter_input => UiEvent::Freeze,
+        KeyCode::Char('X') if !in_filter_input => UiEvent::CloseSource,
+        KeyCode::Char('c') if !in_filter_input => UiEvent::Correlate,
+        KeyCode::Char('P') if !in_filter_input => UiEvent::TogglePatterns,
+        KeyCode::Char('I') if !in_filter_input => UiEvent::ToggleSummary,
+        KeyCode::Char('Q') if !in_filter_input => UiEvent::OpenQuery,
+        KeyCode::Char('a') if !in_filter_input && state.unacked_alerts() > 0 => UiEvent::AcknowledgeAlerts,
+        KeyCode::Char('A') if !in_filter_input => UiEvent::ToggleAlertPanel,
+        KeyCode::Char('H') if !in_filter_input => UiEvent::CycleHistogram,
+        KeyCode::Char('R') if !in_filter_input => UiEvent::ToggleReveal,
+        KeyCode::Char('D') if !in_filter_input => UiEvent::ToggleRewrites,
+        KeyCode::Char('f') if !in_filter_input && state.filter_panel_open => UiEvent::ToggleInputFuzzy,
+        KeyCode::Char('f') if !in_filter_input => UiEvent::CycleAggregateField,
+        KeyCode::Left if state.histogram.is_some() => UiEvent::HistogramMove(if key.modifiers.contains(KeyModifiers::SHIFT) { -10 } else { -1 }),
+        KeyCode::Right if state.histogram.is_some() => UiEvent::HistogramMove(if key.modifiers.contains(KeyModifiers::SHIFT) { 10 } else { 1 }),
+        KeyCode::Left if state.no_wrap => UiEvent::ScrollHorizontal(if key.modifiers.contains(KeyModifiers::SHIFT) { -40 } else { -8 }),
+        KeyCode::Right if state.no_wrap => UiEvent::ScrollHorizontal(if key.modifiers.contains(KeyModifiers::SHIFT) { 40 } else { 8 }),
+        KeyCode::Char('W') if !in_filter_input => UiEvent::ToggleWrap,
+        KeyCode::Char('t') if !in_filter_input => UiEvent::ToggleColumns,
+        KeyCode::Char('=') if !in_filter_input => UiEvent::OpenFieldFilter,
+        KeyCode::Char('Z') if !in_filter_input => UiEvent::ToggleCompact,
+        KeyCode::Char('T') if !in_filter_input => UiEvent::ToggleControls,
+        KeyCode::Char('S') if !in_filter_input => UiEvent::ToggleAutoAll,
+        KeyCode::Char('L') if !in_filter_input => UiEvent::ToggleFocusFollow,
+        KeyCode::Char('O') if !in_filter_input => UiEvent::CycleSourceSort,
+        KeyCode::Char('*') if !in_filter_input => UiEvent::TogglePinSource,
+        KeyCode::Char('v') if !in_filter_input => UiEvent::ToggleShowAll,
+        KeyCode::Char('V') if !in_filter_input => UiEvent::ToggleRange,
+        KeyCode::Char('h') if !in_filter_input && in_range => UiEvent::ToggleRangeHidden,
+        KeyCode::Char('y') if !in_filter_input => UiEvent::CopySelection,
+        KeyCode::Char('+') if !in_filter_input => UiEvent::AdjustFilterContext(1),
+        KeyCode::Char('-') if !in_filter_input => UiEvent::AdjustFilterContext(-1),
+        KeyCode::Enter => { if state.filter_panel_open { UiEvent::AddFilter } else { UiEvent::ToggleContextPanel } },
+        KeyCode::Backspace => UiEvent::Backspace,
+        KeyCode::Tab => UiEvent::FocusNext,
+        KeyCode::BackTab => UiEvent::PrevSource,
+        KeyCode::Char(']') if !in_filter_input => UiEvent::NextSource,
+        KeyCode::Char('[') if !in_filter_input => UiEvent::PrevSource,
+        KeyCode::Char('}') if !in_filter_input => UiEvent::NextGroup,
+        KeyCode::Char('{') if !in_filter_input => UiEvent::PrevGroup,
+        KeyCode::Char('z') if in_filter_list => UiEvent::ToggleFilterGroup,
+        KeyCode::Char('g') if in_filter_list => UiEvent::OpenFilterGroup,
+        KeyCode::Char('z') if !in_filter_input => UiEvent::ToggleGroup,
+        
+        // Only handle these shortcuts if NOT in filter input mode
+        KeyCode::Char('r') if !in_filter_input => UiEvent::ToggleInputRegex,
+        KeyCode::Char('i') if !in_filter_input => UiEvent::ToggleInputCase,
+        KeyCode::Char('w') if !in_filter_input => UiEvent::ToggleInputWord,
+        KeyCode::Char('x') if !in_filter_input => UiEvent::ToggleInputLine,
+        KeyCode::Char('d') if !in_filter_input => UiEvent::DeleteFilter,
+        KeyCode::Char('u') if !in_filter_input => UiEvent::Undo,
+        KeyCode::Char('C') if !in_filter_input => UiEvent::ClearSource,
+        KeyCode::Char('m') if !in_filter_input => UiEvent::Mark(false),
+        KeyCode::Char('M') if !in_filter_input => UiEvent::Mark(true),
+        KeyCode::Char('p') if !in_filter_input && state.replay.is_some() => UiEvent::ReplayPause,
+        KeyCode::Char('.') if !in_filter_input && state.replay.is_some() => UiEvent::ReplayStep,
+        KeyCode::Char('>') if !in_filter_input && state.replay.is_some() => UiEvent::ReplaySpeed(2.0),
+        KeyCode::Char('<') if !in_filter_input && state.replay.is_some() => UiEvent::ReplaySpeed(0.5),
+        KeyCode::Char('k') if !in_filter_input => UiEvent::SelectUp,
+        KeyCode::Char('j') if !in_filter_input => UiEvent::SelectDown,
+        KeyCode::Char('n') if key.modifiers.is_empty() && !in_filter_input => UiEvent::NextMatch,
+        KeyCode::Char('N') if !in_filter_input => UiEvent::PrevMatch,
+        KeyCode::Char(c @ '1'..='9') if key.modifiers == KeyModifiers::ALT => UiEvent::Hotkey(c as u8 - b'0'),
+        KeyCode::Char(c @ '1'..='9') if !in_filter_input => UiEvent::CountDigit(c as u32 - '0' as u32),
+        KeyCode::Char('0') if !in_filter_input && state.pending_count.is_some() => UiEvent::CountDigit(0),
+        KeyCode::Char('G') if !in_filter_input => UiEvent::GotoCount,
+        
+        // Handle all other characters as input when in appropriate modes
+        KeyCode::Char(c) if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT => UiEvent::InputChar(c),
+        _ => UiEvent::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{FilterRule, TAB_WIDTH};
+    use proptest::prelude::*;
+
+    fn line_to_string(line: &Line<'_>) -> String {
+        line.spans.iter().map(|s| s.content.to_string()).collect::<Vec<_>>().join("")
+    }
+
+    #[test]
+    fn test_highlight_preserves_full_text() {
+        let text = "68547:2025-09-17 11:59:52.505 +02:00    DBG     AIS.CometYxlon.CA20.LineConnect.Kernel.LineConnectDriver_       Transmit message to device: oSTART:XXXX_XXX_XXX@Substrate-CARRIER123456789.02_1,38@Substrate-CARRIER123456789.02_2,37";
+        let rule = FilterRule { pattern: "LineConnectDriver_".into(), is_regex: false, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None };
+        let line = highlight_line(text, &CompiledFilterSet::new(&[rule]), None);
+        let rebuilt = line_to_string(&line);
+        assert_eq!(rebuilt, text);
+    }
+
+    #[test]
+    fn test_expand_tabs_keeps_highlights() {
+        let rule = FilterRule { pattern: "错误".into(), is_regex: false, fuzzy: false, case_insensitive: true, whole_word: false, whole_line: false, exclude: false, enabled: true, compiled: None, match_count: 0, group: None };
+        // The wide characters before the tab take four columns, so it pads to column 8
+        let line = expand_tabs(highlight_line("日本\t错误\tend", &CompiledFilterSet::new(std::slice::from_ref(&rule)), None), TAB_WIDTH, false);
+        assert_eq!(line_to_string(&line), "日本    错误    end");
+        assert_eq!(line.spans[1].content, "错误");
+        // Shown: the tab is an arrow padded to the stop, the carriage return a symbol of its own
+        let line = expand_tabs(highlight_line("日本\t错误\r", &CompiledFilterSet::new(&[rule]), None), 4, true);
+        assert_eq!(line_to_string(&line), "日本→   错误^M");
+        assert_eq!(line.spans.iter().map(|s| (&*s.content, s.style == control_style())).collect::<Vec<_>>(), [("日本", false), ("→   ", true), ("错误", false), ("^M", true)]);
+    }
+
+    #[test]
+    fn test_wrap_line() {
+        let line = Line::from(vec![Span::raw("ab"), Span::styled("cd日e", Style::default().fg(Color::Red))]);
+        let rows = wrap_line(line, 5);
+        // The wide character does not fit after four columns, so it starts the second row
+        assert_eq!(rows.iter().map(line_to_string).collect::<Vec<_>>(), ["abcd", "日e"]);
+        assert_eq!(rows[1].spans[0].style.fg, Some(Color::Red));
+        assert_eq!(wrap_line(Line::from("abcde"), 5).len(), 1);
+        assert_eq!(wrap_line(Line::default(), 5).len(), 1);
+        // Narrower than a wide character: one a row
+        assert_eq!(wrap_line(Line::from("日本"), 1).iter().map(line_to_string).collect::<Vec<_>>(), ["日", "本"]);
+    }
+
+    #[test]
+    fn test_key_names() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        for (event, name) in [
+            (key(KeyCode::Char('j'), KeyModifiers::NONE), "j"),
+            (key(KeyCode::Char('G'), KeyModifiers::SHIFT), "G"),
+            (key(KeyCode::Char('d'), KeyModifiers::CONTROL), "<C-d>"),
+            (key(KeyCode::Char('1'), KeyModifiers::ALT), "<A-1>"),
+            (key(KeyCode::Char('<'), KeyModifiers::NONE), "<lt>"),
+            (key(KeyCode::Enter, KeyModifiers::NONE), "<Enter>"),
+            (key(KeyCode::Up, KeyModifiers::SHIFT), "<S-Up>"),
+            (key(KeyCode::F(5), KeyModifiers::NONE), "<F5>"),
+        ] {
+            assert_eq!(key_name(&event).as_deref(), Some(name));
+            assert_eq!(parse_key(name), Some(event), "{}", name);
+        }
+        assert_eq!(key_name(&key(KeyCode::CapsLock, KeyModifiers::NONE)), None);
+        assert_eq!((parse_key("<Nope>"), parse_key("jk")), (None, None));
+    }
+
+    #[test]
+    fn test_highlight_colors_by_rule() {
+        let rules = [FilterRule::simple("disk".into(), false), FilterRule::simple(r"disk \w+".into(), true), FilterRule::simple("var".into(), false)];
+        let search = regex::Regex::new("full").unwrap();
+        let line = highlight_line("ERROR disk full on /var", &CompiledFilterSet::new(&rules), Some(&search));
+        let spans: Vec<(&str, Style)> = line.spans.iter().map(|s| (&*s.content, s.style)).collect();
+        // The first rule wins over the second where they overlap, the search over both
+        assert_eq!(spans, [
+            ("ERROR ", Style::default()),
+            ("disk", rule_style(0)),
+            (" ", rule_style(1)),
+            ("full", search_style()),
+            (" on /", Style::default()),
+            ("var", rule_style(2)),
+        ]);
+        assert_ne!(rule_style(0), rule_style(1));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_highlight_reconstructs_text(
+            text in "[abAB .*é漢\t]{0,24}",
+            rules in prop::collection::vec(("[ab.*|é]{0,3}", any::<bool>()), 0..4),
+            search in proptest::option::of("[ab.]{1,2}"),
+        ) {
+            let rules: Vec<FilterRule> = rules.into_iter().map(|(pattern, is_regex)| FilterRule::simple(pattern, is_regex)).collect();
+            let search = search.and_then(|s| regex::Regex::new(&s).ok());
+            let line = highlight_line(&text, &CompiledFilterSet::new(&rules), search.as_ref());
+            prop_assert_eq!(line_to_string(&line), text.as_str());
+            // Each byte has the style of the search or else the first rule matching it
+            let mut expected = vec![Style::default(); text.len()];
+            for (i, rule) in rules.iter().enumerate().rev() {
+                let Ok(re) = rule.compile() else { continue; };
+                for m in re.find_iter(&text) { expected[m.range()].fill(rule_style(i)); }
+            }
+            for m in search.iter().flat_map(|re| re.find_iter(&text)) { expected[m.range()].fill(search_style()); }
+            let mut at = 0;
+            for span in &line.spans {
+                let end = at + span.content.len();
+                prop_assert!(expected[at..end].iter().all(|&style| style == span.style), "span {:?} at {}", span.content, at);
+                at = end;
             }
         }
     }
-    Ok(UiEvent::None)
 }
@@ -0,0 +1,284 @@
+//! Web view (`rtlog serve`): the live, filtered lines of every source in a browser.
+//!
+//! A small HTTP server answers `/` with a self-contained page (`web.html`) and upgrades `/ws` to
+//! a websocket on which the page receives JSON messages: `sources` (the source list, with the
+//! active filters) and `lines` (a batch of a source's new lines that pass the filters, with level
+//! and alert severity). A new client first gets the last `SNAPSHOT_LINES` lines of each source.
+//! Lines are sent as they are shown on screen: converted with `--tz`, rewritten and always
+//! redacted. Clients only watch; the filters are the ones rtlog was started with. Browsers may
+//! open the websocket only from the page itself: one whose `Origin` is another site is refused.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
+
+use crate::net::serve_connections;
+use crate::state::{AppState, LineId, Source};
+
+/// The page served at `/`
+const PAGE: &str = include_str!("web.html");
+
+/// Lines per source a client receives when it connects
+const SNAPSHOT_LINES: usize = 2000;
+
+/// Messages buffered per client; a client further behind skips lines (and is told so)
+const CLIENT_BACKLOG: usize = 1024;
+
+/// Largest request head read from a browser
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+
+/// A client asking for the current lines, answered by the runtime that owns the state
+pub type SnapshotRequest = oneshot::Sender<Vec<Utf8Bytes>>;
+
+pub struct WebServer {
+    local_addr: SocketAddr,
+    updates: broadcast::Sender<Utf8Bytes>,
+    requests: mpsc::Receiver<SnapshotRequest>,
+    /// Per source id, the first line not yet sent to clients
+    published: HashMap<usize, LineId>,
+    /// Last `sources` message sent
+    sources_sent: Option<Utf8Bytes>,
+    accept_task: JoinHandle<()>,
+}
+
+impl WebServer {
+    /// Listen on `addr` (`HOST:PORT`) and serve clients until dropped
+    pub async fn bind(addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await.with_context(|| format!("cannot listen on {}", addr))?;
+        let local_addr = listener.local_addr()?;
+        let (updates, _) = broadcast::channel(CLIENT_BACKLOG);
+        let (request_tx, requests) = mpsc::channel(16);
+        let clients = updates.clone();
+        let accept_task = tokio::spawn(serve_connections(listener, move |stream, _| {
+            let (updates, requests) = (clients.clone(), request_tx.clone());
+            async move { let _ = handle(stream, updates, requests).await; }
+        }));
+        Ok(Self { local_addr, updates, requests, published: HashMap::new(), sources_sent: None, accept_task })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Next client waiting for its snapshot
+    pub async fn next_request(&mut self) -> Option<SnapshotRequest> {
+        self.requests.recv().await
+    }
+
+    /// What a new client starts with: the source list and the recent lines already published
+    pub fn snapshot(&self, state: &AppState) -> Vec<Utf8Bytes> {
+        let mut messages = vec![sources_message(state)];
+        for src in &state.sources {
+            let end = self.published.get(&src.id).copied().unwrap_or(src.first_id);
+            let mut lines: Vec<usize> = state.shown_lines_rev(src).map(|(i, _)| i)
+                .filter(|&i| src.line_id(i) < end)
+                .take(SNAPSHOT_LINES)
+                .collect();
+            lines.reverse();
+            if let Some(msg) = lines_message(state, src, &lines) { messages.push(msg); }
+        }
+        messages
+    }
+
+    /// Send clients the sources when they changed and every source's lines received since the
+    /// last call
+    pub fn publish(&mut self, state: &AppState) {
+        let sources = sources_message(state);
+        if self.sources_sent.as_ref() != Some(&sources) {
+            let _ = self.updates.send(sources.clone());
+            self.sources_sent = Some(sources);
+        }
+        for src in &state.sources {
+            let end = src.line_id(src.lines.len());
+            let start = self.published.insert(src.id, end).unwrap_or(src.first_id);
+            if end <= start { continue; }
+            let mut lines: Vec<usize> = state.shown_lines_rev(src).map(|(i, _)| i).take_while(|&i| src.line_id(i) >= start).collect();
+            lines.reverse();
+            if let Some(msg) = lines_message(state, src, &lines) { let _ = self.updates.send(msg); }
+        }
+    }
+}
+
+impl Drop for WebServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+fn sources_message(state: &AppState) -> Utf8Bytes {
+    let sources: Vec<Value> = state.sidebar_order().into_iter().map(|i| {
+        let src = &state.sources[i];
+        json!({
+            "id": src.id,
+            "name": src.name,
+            "group": state.groups.get(src.group).map(|g| g.name.as_str()),
+            "error": src.error,
+            "waiting": src.waiting,
+        })
+    }).collect();
+    let filters: Vec<&str> = state.filters.iter().filter(|f| f.enabled).map(|f| f.pattern.as_str()).collect();
+    json!({ "type": "sources", "sources": sources, "filters": filters }).to_string().into()
+}
+
+/// `lines` (indices into `src.lines`) as a `lines` message, or None when there are none
+fn lines_message(state: &AppState, src: &Source, lines: &[usize]) -> Option<Utf8Bytes> {
+    if lines.is_empty() { return None; }
+    let lines: Vec<Value> = lines.iter().map(|&i| {
+        let text = &src.lines[i];
+        json!({
+            "n": src.line_id(i).0 + 1,
+            "text": state.display_log_text(text),
+            "level": state.line_level(src, text).map(|l| l.name()),
+            "alert": state.alert_severity(text).map(|s| s.name()),
+            "marker": src.is_separator(i),
+        })
+    }).collect();
+    Some(json!({ "type": "lines", "source": src.id, "lines": lines }).to_string().into())
+}
+
+/// Serve the page, or stream lines when the browser opens the websocket
+async fn handle(mut stream: TcpStream, updates: broadcast::Sender<Utf8Bytes>, requests: mpsc::Sender<SnapshotRequest>) -> Result<()> {
+    // Peek, so that the websocket handshake still reads the whole request
+    let mut start = [0u8; 256];
+    let n = stream.peek(&mut start).await?;
+    let request_line = String::from_utf8_lossy(&start[..n]);
+    let path = request_line.split(' ').nth(1).unwrap_or("/").split('?').next().unwrap_or("/");
+    if path == "/ws" { return stream_lines(stream, updates, requests).await; }
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 { return Ok(()); }
+        head.extend_from_slice(&buf[..n]);
+    }
+    let (status, content_type, body) = match path {
+        "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", PAGE),
+        _ => ("404 Not Found", "text/plain; charset=utf-8", "not found\n"),
+    };
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}", status, content_type, body.len(), body);
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Refuse the websocket to pages of other sites, which a browser would otherwise let connect to
+/// a server on localhost: their `Origin` must name the host the page was served from. Clients
+/// that are not browsers send no `Origin` and are let in.
+// The error type is the one the handshake callback must return
+#[allow(clippy::result_large_err)]
+fn check_origin(request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    let header = |name: &str| request.headers().get(name).and_then(|v| v.to_str().ok());
+    let Some(origin) = header("origin") else { return Ok(response); };
+    let origin_host = origin.split_once("://").map_or(origin, |(_, host)| host);
+    if header("host").is_some_and(|host| host.eq_ignore_ascii_case(origin_host)) { return Ok(response); }
+    let mut refusal = ErrorResponse::new(Some(format!("websocket from origin {} refused\n", origin)));
+    *refusal.status_mut() = StatusCode::FORBIDDEN;
+    Err(refusal)
+}
+
+async fn stream_lines(stream: TcpStream, updates: broadcast::Sender<Utf8Bytes>, requests: mpsc::Sender<SnapshotRequest>) -> Result<()> {
+    let mut ws = tokio_tungstenite::accept_hdr_async(stream, check_origin).await?;
+    // Subscribe before asking for the snapshot so no line falls in between; the page drops the
+    // lines it receives twice
+    let mut rx = updates.subscribe();
+    let (reply, snapshot) = oneshot::channel();
+    requests.send(reply).await?;
+    for msg in snapshot.await? { ws.send(Message::Text(msg)).await?; }
+    loop {
+        tokio::select! {
+            update = rx.recv() => match update {
+                Ok(msg) => ws.send(Message::Text(msg)).await?,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    ws.send(Message::text(json!({ "type": "lagged", "skipped": skipped }).to_string())).await?;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = ws.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = ws.close(None).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::{AlertSeverity, LineClass};
+    use crate::config::RedactConfig;
+    use crate::filter::build_filter;
+    use crate::log::LogLine;
+    use crate::redact::Redactor;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn parse(msg: &Utf8Bytes) -> Value {
+        serde_json::from_str(msg.as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_publish_and_snapshot() {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let mut state = AppState::new(build_filter(Some("disk|mail")).unwrap(), vec![(AlertSeverity::Critical, "disk full".into())]);
+            state.set_sources([(None, PathBuf::from("app.log"))]);
+            state.redactor = Arc::new(Redactor::new(&RedactConfig { builtin: vec!["email".into()], rules: Vec::new() }).unwrap());
+            let id = state.sources[0].id;
+            let push = |state: &mut AppState, text: &str| state.push_line_for(id, LogLine { text: text.into(), class: LineClass::default() });
+            push(&mut state, "INFO started");
+            push(&mut state, "ERROR disk full on /var");
+            let mut server = WebServer::bind("127.0.0.1:0").await.unwrap();
+            let mut rx = server.updates.subscribe();
+            server.publish(&state);
+            assert_eq!(parse(&rx.try_recv().unwrap())["sources"][0]["name"], "app.log");
+            let lines = parse(&rx.try_recv().unwrap());
+            assert_eq!(lines["lines"].as_array().unwrap().len(), 1);
+            assert_eq!(lines["lines"][0]["n"], 2);
+            assert_eq!((lines["lines"][0]["level"].as_str(), lines["lines"][0]["alert"].as_str()), (Some("error"), Some("critical")));
+            push(&mut state, "WARN mail to bob@example.com bounced");
+            push(&mut state, "INFO idle");
+            server.publish(&state);
+            // Unchanged sources are not sent again, and only the new line that passes the filter is
+            let lines = parse(&rx.try_recv().unwrap());
+            assert_eq!((lines["lines"][0]["n"].as_u64(), lines["lines"][0]["text"].as_str()), (Some(3), Some("WARN mail to <email> bounced")));
+            assert!(rx.try_recv().is_err());
+            push(&mut state, "ERROR disk full again");
+            let snapshot = server.snapshot(&state);
+            assert_eq!(snapshot.len(), 2);
+            let ns: Vec<u64> = parse(&snapshot[1])["lines"].as_array().unwrap().iter().filter_map(|l| l["n"].as_u64()).collect();
+            assert_eq!(ns, [2, 3]);
+        });
+    }
+
+    #[test]
+    fn test_websocket_origin() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let server = WebServer::bind("127.0.0.1:0").await.unwrap();
+            let addr = server.local_addr();
+            let connect = |origin: Option<String>| async move {
+                let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+                if let Some(origin) = origin { request.headers_mut().insert("Origin", origin.parse().unwrap()); }
+                let stream = TcpStream::connect(addr).await.unwrap();
+                tokio_tungstenite::client_async(request, stream).await.map(|_| ())
+            };
+            assert!(connect(None).await.is_ok());
+            assert!(connect(Some(format!("http://{}", addr))).await.is_ok());
+            let refused = connect(Some("https://evil.example".into())).await.unwrap_err();
+            assert!(matches!(refused, tokio_tungstenite::tungstenite::Error::Http(ref r) if r.status() == StatusCode::FORBIDDEN), "{:?}", refused);
+        });
+    }
+}
@@ -0,0 +1,161 @@
+//! Drives the TUI without a terminal: an `AppState` fed with synthetic sources, scripted key
+//! presses going through the same translation and handling as the real event loop, and frames
+//! rendered into a ratatui `TestBackend` whose buffer the tests assert on.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Cell;
+use ratatui::Terminal;
+use rtlog::app::{apply_input, apply_played_input};
+use rtlog::classify::AlertSeverity;
+use rtlog::log::LogLine;
+use rtlog::state::AppState;
+use rtlog::ui::{parse_key, render, translate_event, UiEvent};
+
+pub struct Harness {
+    pub state: AppState,
+    terminal: Terminal<TestBackend>,
+    /// Rows of the log panel in the last frame, used for page scrolling like `Ui::page_height`
+    page_height: usize,
+}
+
+impl Harness {
+    /// A `width`×`height` screen with no sources
+    pub fn new(width: u16, height: u16) -> Self {
+        Self::with_alerts(width, height, Vec::new())
+    }
+
+    pub fn with_alerts(width: u16, height: u16, alerts: Vec<(AlertSeverity, String)>) -> Self {
+        let state = AppState::new(None, alerts);
+        let terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        Self { state, terminal, page_height: height as usize }
+    }
+
+    /// Add a source named `name` holding `lines`, and focus it
+    pub fn source(&mut self, name: &str, lines: &[&str]) -> &mut Self {
+        let id = self.state.add_source(None, PathBuf::from(name), None);
+        for &text in lines {
+            self.state.push_line_for(id, LogLine::new(text));
+        }
+        self.state.focused = self.state.sources.iter().position(|s| s.id == id).unwrap();
+        self
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) -> &mut Self {
+        self.terminal.backend_mut().resize(width, height);
+        self
+    }
+
+    /// Press the keys of `script` in order, drawing a frame after each like the event loop does.
+    /// Characters are typed as they are; `<Name>` is a special key (`<Enter>`, `<Esc>`, `<Tab>`,
+    /// `<BackTab>`, `<Backspace>`, `<Up>`, `<Down>`, `<Left>`, `<Right>`, `<PageUp>`,
+    /// `<PageDown>`, `<Home>`, `<End>`, `<Space>`, `<Lt>`), `<C-x>` is Ctrl+x and `<A-x>` Alt+x.
+    pub fn keys(&mut self, script: &str) -> &mut Self {
+        let mut rest = script;
+        while let Some(c) = rest.chars().next() {
+            let (key, len) = match rest.find('>').filter(|_| c == '<') {
+                Some(end) => (parse_special(&rest[1..end]), end + 1),
+                None => (KeyEvent::new(KeyCode::Char(c), shift_for(c)), c.len_utf8()),
+            };
+            rest = &rest[len..];
+            self.press(key);
+        }
+        self
+    }
+
+    /// Translate `key` as the UI would and apply what it means to the state
+    pub fn press(&mut self, key: KeyEvent) -> Option<UiEvent> {
+        self.draw();
+        let input = translate_event(&self.state, &Event::Key(key));
+        let unhandled = apply_input(&mut self.state, input, self.page_height);
+        self.draw();
+        unhandled
+    }
+
+    /// Press the key named `name` (`j`, `<Enter>`) as a played back recording does (`--play`)
+    pub fn play(&mut self, name: &str) -> &mut Self {
+        self.draw();
+        let input = translate_event(&self.state, &Event::Key(parse_key(name).unwrap()));
+        apply_played_input(&mut self.state, input, self.page_height);
+        self.draw()
+    }
+
+    pub fn draw(&mut self) -> &mut Self {
+        let state = &self.state;
+        let mut page_height = None;
+        self.terminal.draw(|frame| page_height = render(frame, state)).unwrap();
+        if let Some(height) = page_height { self.page_height = height; }
+        self
+    }
+
+    /// The screen as text, one line per row with trailing blanks trimmed
+    pub fn screen(&mut self) -> Vec<String> {
+        self.draw();
+        let buffer = self.terminal.backend().buffer();
+        (0..buffer.area.height).map(|y| row_text(buffer, y)).collect()
+    }
+
+    pub fn row(&mut self, y: u16) -> String {
+        self.draw();
+        row_text(self.terminal.backend().buffer(), y)
+    }
+
+    /// Whether some row contains `text`
+    pub fn shows(&mut self, text: &str) -> bool {
+        self.screen().iter().any(|row| row.contains(text))
+    }
+
+    /// Position of the first occurrence of `text` on screen
+    pub fn find(&mut self, text: &str) -> Option<(u16, u16)> {
+        self.screen().iter().enumerate().find_map(|(y, row)| {
+            // Columns count cells, which is characters for the ASCII the tests look for
+            row.find(text).map(|at| (row[..at].chars().count() as u16, y as u16))
+        })
+    }
+
+    pub fn cell(&mut self, x: u16, y: u16) -> Cell {
+        self.draw();
+        self.terminal.backend().buffer()[(x, y)].clone()
+    }
+}
+
+fn row_text(buffer: &ratatui::buffer::Buffer, y: u16) -> String {
+    let row: String = (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect();
+    row.trim_end().to_string()
+}
+
+fn shift_for(c: char) -> KeyModifiers {
+    if c.is_ascii_uppercase() { KeyModifiers::SHIFT } else { KeyModifiers::NONE }
+}
+
+fn parse_special(name: &str) -> KeyEvent {
+    if let Some(c) = name.strip_prefix("C-").and_then(|c| c.chars().next()) {
+        return KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    }
+    if let Some(c) = name.strip_prefix("A-").and_then(|c| c.chars().next()) {
+        return KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+    }
+    let code = match name {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Tab" => KeyCode::Tab,
+        "BackTab" => return KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT),
+        "Backspace" => KeyCode::Backspace,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "Space" => KeyCode::Char(' '),
+        "Lt" => KeyCode::Char('<'),
+        _ => panic!("unknown key <{}>", name),
+    };
+    KeyEvent::new(code, KeyModifiers::NONE)
+}
@@ -0,0 +1,381 @@
+//! End-to-end UI tests: scripted keys against synthetic sources, asserting on rendered frames
+
+mod harness;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use harness::Harness;
+use ratatui::style::{Color, Modifier};
+use rtlog::config::HotkeyEntry;
+use rtlog::hotkeys::Hotkeys;
+use rtlog::screenshot::{self, ScreenFormat};
+use rtlog::session::{Entry, RecordedSource, Recording, SessionRecorder};
+use rtlog::status::StatusTemplate;
+use rtlog::ui::UiEvent;
+
+fn numbered(n: usize) -> Vec<String> {
+    (1..=n).map(|i| format!("INFO request {}", i)).collect()
+}
+
+fn two_sources() -> Harness {
+    let mut h = Harness::new(100, 30);
+    h.source("app.log", &["INFO started", "WARN upstream timeout", "ERROR disk full"]);
+    h.source("db.log", &["INFO ready"]);
+    h.state.focused = 0;
+    h
+}
+
+#[test]
+fn test_layout() {
+    let mut h = two_sources();
+    let screen = h.screen();
+    assert!(screen[0].starts_with("┌Sources"));
+    assert!(screen[0].contains("Logs - app.log"));
+    assert!(screen[0].ends_with("line 3 / 3 (100%)┐"));
+    assert!(screen[1].contains("app.log 3") && screen[2].contains("db.log +1 1"));
+    assert_eq!(h.find("WARN upstream timeout"), Some((31, 2)));
+    assert!(h.shows("Lines: 3  Scroll: 0  Mode: Following"));
+    assert!(h.shows("Summary / Stats"));
+}
+
+#[test]
+fn test_status_bar_template() {
+    let mut h = two_sources();
+    h.resize(140, 30);
+    h.state.status_template = StatusTemplate::parse("{lines} lines, {shown} shown  {filters} filters  {mode}  [{source}] {{{sources}}}").unwrap();
+    h.keys("/timeout<Enter><Tab>/");
+    assert!(h.shows("3 lines, 1 shown  1 filters  Following  [app.log] {2}"));
+    assert!(!h.shows("Scroll:") && !h.shows("E:1"));
+}
+
+#[test]
+fn test_filter_added_with_keys() {
+    let mut h = two_sources();
+    h.keys("/timeout<Enter>");
+    assert_eq!(h.state.filters.len(), 1);
+    assert!(h.shows("[x] -i-- timeout"));
+    assert!(h.shows("Showing 1 of 3 lines"));
+    assert!(!h.shows("INFO started") && !h.shows("ERROR disk full"));
+    // The part that matched is highlighted, the rest of the line is not
+    let (x, y) = h.find("timeout").unwrap();
+    let cell = h.cell(x, y);
+    assert_eq!(cell.fg, Color::Yellow);
+    assert!(cell.modifier.contains(Modifier::BOLD));
+    assert_ne!(h.cell(x - 2, y).fg, Color::Yellow);
+    // Disabling the filter from the list brings the other lines back
+    h.keys("<Tab><Space>");
+    assert!(h.shows("[ ] -i-- timeout"));
+    assert!(h.shows("INFO started"));
+}
+
+#[test]
+fn test_field_columns() {
+    let mut h = Harness::new(140, 30);
+    h.source("api.log", &["GET /a status=200 took 12ms", "GET /b status=503 took 950ms", "health ok"]);
+    h.state.input_is_regex = true;
+    h.keys(r"/status=(?P<Lt>status>\d+) took (?P<Lt>ms>\d+)ms<Enter><Tab>/t");
+    // Each column as wide as its name or its widest value, the names in the top border
+    assert!(h.shows("status ms  │ Logs - api.log"), "{:#?}", h.screen());
+    assert!(h.shows("200    12  │ GET /a status=200 took 12ms") && h.shows("503    950 │ GET /b"));
+    h.keys("=ms > 100<Enter>");
+    assert!(h.shows("Showing 1 of 3 lines") && h.shows("503    950 │") && !h.shows("GET /a"));
+    h.keys("=user = bob<Enter>");
+    assert!(h.shows("no field user"));
+    h.keys("<Esc>/");
+    assert!(h.shows("fields: ms > 100"));
+    h.keys("/=<Enter>");
+    assert!(h.shows("field filters removed") && h.shows("200    12  │ GET /a"));
+    h.keys("t");
+    assert!(h.shows("column view off") && !h.shows("│ GET /a"));
+}
+
+#[test]
+fn test_scrolling() {
+    let mut h = Harness::new(100, 30);
+    let lines = numbered(200);
+    h.source("app.log", &lines.iter().map(String::as_str).collect::<Vec<_>>());
+    assert!(h.row(0).ends_with("line 200 / 200 (100%)┐"));
+    assert!(h.shows("Mode: Following"));
+    h.keys("<Home>");
+    assert!(h.row(0).contains("line 1 / 200"));
+    // The line rate shows once the second the lines came in is over
+    assert!(h.row(1).starts_with("│‖ app.log 200 "), "{}", h.row(1));
+    assert!(h.row(1).ends_with("││INFO request 1                                                      █"), "{}", h.row(1));
+    assert!(h.shows("Mode: Paused"));
+    h.keys("<PageDown>");
+    assert!(!h.row(1).contains("INFO request 1 "));
+    assert!(!h.row(0).contains("line 1 / 200"));
+    h.keys("<End>");
+    assert!(h.row(0).ends_with("line 200 / 200 (100%)┐"));
+    assert!(h.shows("INFO request 200"));
+}
+
+#[test]
+fn test_wrapped_lines_fit() {
+    let mut h = Harness::new(60, 20);
+    let lines: Vec<String> = (1..=20).map(|n| format!("INFO request {} {} 日本 end {}", n, "x".repeat(70), n)).collect();
+    h.source("app.log", &lines.iter().map(String::as_str).collect::<Vec<_>>());
+    // Two rows a line: the newest ends on the last row, the top line shows only its second row
+    assert!(h.row(18).starts_with("└"));
+    assert!(h.row(17).contains("end 20") && h.row(16).starts_with("│INFO request 20 x"));
+    assert!(h.row(1).contains("end 12") && !h.shows("request 12"));
+    h.keys("W");
+    assert!(h.row(17).starts_with("│INFO request 20 x") && !h.shows("end 20"));
+}
+
+#[test]
+fn test_played_keys_write_no_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let (recorded, played) = (dir.path().join("recorded.log"), dir.path().join("played.log"));
+    let path = dir.path().join("session.rt");
+    let mut recorder = SessionRecorder::create(&path, vec![RecordedSource { name: "app.log".into(), path: "app.log".into() }]).unwrap();
+    let mut keys = vec!["e".to_string()];
+    keys.extend(played.display().to_string().chars().map(String::from));
+    keys.extend(["<Enter>", "O", "*", "X"].map(String::from));
+    for key in &keys { recorder.key(key).unwrap(); }
+    drop(recorder);
+    let mut h = two_sources();
+    // Pressed, the keys export the lines
+    h.keys(&format!("e{}<Enter>", recorded.display()));
+    assert!(recorded.exists());
+    // Played back, the export prompt is closed without saving, the sidebar and sources stay
+    for (_, entry) in Recording::load(&path).unwrap().entries {
+        let Entry::Key { key } = entry else { continue; };
+        h.play(&key);
+    }
+    assert!(!played.exists() && h.state.prompt.is_none());
+    assert!(h.shows("Sources (") && h.state.sources.len() == 2 && h.state.sources[0].name == "app.log");
+}
+
+#[test]
+fn test_compact_mode() {
+    let mut h = two_sources();
+    h.keys("Z");
+    assert!(!h.shows("Sources") && !h.shows("Summary / Stats") && !h.shows("Logs - app.log"));
+    // The lines start at the top left corner, the status bar is on the last row
+    assert!(h.row(0).starts_with("INFO started"));
+    assert!(h.row(2).starts_with("ERROR disk full"));
+    assert!(h.row(29).contains("compact mode on (Z)") && h.row(29).contains("Lines: 3"));
+    h.keys("Z");
+    assert!(h.shows("Sources") && h.shows("Logs - app.log"));
+}
+
+#[test]
+fn test_control_characters() {
+    let mut h = Harness::new(100, 30);
+    h.source("app.log", &["ERROR\tdisk\x1b[31m full\r", "a\tb"]);
+    // Passed through as they are, for the terminal to act on (or drop)
+    assert!(h.shows("ERROR   disk\x1b[31m full\r") && h.shows("a       b"));
+    h.keys("T");
+    assert!(h.shows("ERROR→  disk^[[31m full^M") && h.shows("tabs and control characters shown (T)"));
+    let (x, y) = h.find("^M").unwrap();
+    assert_eq!(h.cell(x, y).fg, Color::DarkGray);
+    h.state.tab_width = 4;
+    assert!(h.shows("a→  b"));
+    h.keys("T");
+    assert!(h.shows("a   b"));
+}
+
+#[test]
+fn test_search_scope() {
+    let mut h = Harness::new(120, 30);
+    h.source("app.log", &["ERROR disk full", "INFO disk ok", "ERROR db down"]);
+    h.keys("/ERROR<Enter><Tab>/?");
+    assert!(h.shows("Search filtered lines (r:false i:smart) - Tab:scope"));
+    // Only the lines shown are searched: the hidden "disk ok" is skipped
+    h.keys("ok<Enter>");
+    assert_eq!(h.state.current_source().unwrap().selected_index(), None);
+    h.keys("?<Tab>");
+    assert!(h.shows("Search all lines (r:false i:smart)"));
+    h.keys("ok<Enter>");
+    assert_eq!(h.state.current_source().unwrap().selected_index(), Some(1));
+    assert!(h.shows("showing all lines for a match the filters hide (v)") && h.shows("INFO disk ok"));
+}
+
+#[test]
+fn test_replace_preview() {
+    let mut h = Harness::new(120, 30);
+    h.source("app.log", &["login name=alice", "health ok", "logout name=bob"]);
+    h.keys("?r");
+    h.keys("name=(\\w+)<Enter>s");
+    assert!(h.shows("Replace matches with ($1, ${name}) - Enter:preview"));
+    h.keys("who: $1<Enter>");
+    assert!(h.shows("login who: alice") && h.shows("logout who: bob") && !h.shows("health ok"));
+    assert!(h.shows("REPLACE /name=(\\w+)/ → \"who: $1\" (s)"));
+    h.keys("s");
+    assert!(h.shows("login name=alice") && h.shows("health ok") && h.shows("replace preview off"));
+}
+
+#[test]
+fn test_hotkeys() {
+    let mut h = Harness::new(120, 30);
+    h.source("app.log", &["GET /a 200", "GET /b 503", "connection refused"]);
+    h.state.hotkeys.configure(&[HotkeyEntry { key: 1, pattern: r"\b5\d\d\b".into(), regex: true }]).unwrap();
+    h.keys("<A-1>");
+    assert!(h.shows("filter added: \\b5\\d\\d\\b") && h.shows("GET /b 503") && !h.shows("GET /a 200"));
+    h.keys("<A-1>");
+    assert!(h.shows("filter disabled") && h.shows("GET /a 200"));
+    // The applied search saved to a key, shown next to its filter
+    h.keys("?conn<Enter>b");
+    assert!(h.shows("Bind the search to Alt+1…9 - Enter:save"));
+    h.keys("0<Enter>");
+    assert!(h.shows("enter a digit from 1 to 9"));
+    h.keys("<Backspace>2<Enter><A-2>/");
+    assert!(h.shows("[x] -i-- conn  (0 matches)  Alt+2") && h.shows("[ ] ri-- \\b5\\d\\d\\b  (0 matches)  Alt+1"));
+    h.keys("<Tab>/<A-3>");
+    assert!(h.shows("nothing bound to Alt+3"));
+    // A binding that cannot be saved says so
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("state"), "").unwrap();
+    h.state.hotkeys = Hotkeys::load(Some(dir.path().join("state/hotkeys")));
+    h.keys("b4<Enter>");
+    assert!(h.shows("Alt+4 filters for conn until rtlog quits: cannot write"));
+}
+
+#[test]
+fn test_filter_groups() {
+    let mut h = Harness::new(120, 30);
+    h.source("app.log", &["GET /healthz 200", "payment 42 captured", "GET /metrics 200"]);
+    h.keys("/healthz<Enter>metrics<Enter>payment<Enter><Tab>gnoise<Enter>kkkgnoise<Enter>kk");
+    assert!(h.shows("> [x] ▾ noise  2/2 on") && h.shows("    [x] -i-- healthz") && h.shows("  [x] -i-- payment"));
+    // Space on the header switches the group, z collapses it
+    h.keys("<Space>");
+    assert!(h.shows("> [ ] ▾ noise  0/2 on") && h.shows("group noise disabled (2 filters)"));
+    assert!(h.shows("payment 42 captured") && !h.shows("GET /healthz 200"));
+    h.keys("z");
+    assert!(h.shows("> [ ] ▸ noise  0/2 on") && !h.shows("-i-- healthz"));
+    h.keys("u");
+    assert!(h.shows("[x] ▸ noise  2/2 on") && h.shows("GET /healthz 200"));
+}
+
+#[test]
+fn test_screenshot() {
+    let mut h = two_sources();
+    h.keys("/disk<Enter><Tab>/<C-s>");
+    assert!(h.shows("Save screen as .txt, .ans or .svg - Enter:save Esc:close"));
+    h.keys("view.txt");
+    // Saved by the runtime, which knows the terminal's size, once the prompt is closed
+    assert!(matches!(h.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)), Some(UiEvent::SubmitPrompt)));
+    h.state.close_prompt();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("screen.txt");
+    assert_eq!(screenshot::save(&h.state, 100, 30, &path).unwrap(), ScreenFormat::Text);
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(saved.lines().collect::<Vec<_>>(), h.screen());
+    assert!(saved.contains("ERROR disk full") && !saved.contains("INFO started"));
+    let ansi = path.with_extension("ans");
+    assert_eq!(screenshot::save(&h.state, 100, 30, &ansi).unwrap(), ScreenFormat::Ansi);
+    assert!(std::fs::read_to_string(&ansi).unwrap().contains("\x1b[0;1;33mdisk"));
+}
+
+#[test]
+fn test_switch_source() {
+    let mut h = two_sources();
+    h.keys("]");
+    assert!(h.row(0).contains("Logs - db.log"));
+    assert!(h.shows("INFO ready"));
+    assert!(!h.shows("WARN upstream timeout"));
+    h.keys("[");
+    assert!(h.row(0).contains("Logs - app.log"));
+}
+
+#[test]
+fn test_small_terminals() {
+    let mut h = two_sources();
+    // Too narrow for the sidebar
+    h.resize(60, 30);
+    assert!(!h.shows("Sources"));
+    assert!(h.row(0).starts_with("┌Logs - app.log"));
+    // Too small for anything but a notice
+    h.resize(30, 8);
+    assert!(h.shows("Terminal too small"));
+    assert!(h.shows("30x8, need at least 40x10"));
+    h.resize(100, 30);
+    assert!(h.shows("Sources"));
+}
+
+#[test]
+fn test_tiny_terminals() {
+    let mut h = two_sources();
+    // Drawn without panicking, also with panels and popups open and in compact mode
+    h.resize(10, 3);
+    assert_eq!(h.row(0).trim(), "Terminal");
+    h.keys("/disk<Tab>:");
+    assert_eq!(h.screen().len(), 3);
+    h.resize(1, 1);
+    h.keys("<Esc><Esc>Z");
+    assert_eq!(h.screen(), ["T"]);
+    h.resize(100, 30);
+    assert!(h.shows("ERROR disk full"));
+}
+
+#[test]
+fn test_runtime_inputs_handed_back() {
+    let mut h = two_sources();
+    h.keys("/");
+    assert!(h.state.filter_panel_open);
+    // `q` is typed into the filter input rather than quitting
+    h.keys("q");
+    assert_eq!(h.state.filter_input, "q");
+    h.keys("<Backspace><Tab>/");
+    assert!(!h.state.filter_panel_open);
+    let quit = h.press(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE));
+    assert!(matches!(quit, Some(UiEvent::Quit)));
+}
+
+#[test]
+fn test_fuzzy_filter() {
+    let mut h = Harness::new(100, 30);
+    h.source("app.log", &["Connection to db-1 refused", "db connection ok", "INFO idle"]);
+    h.keys("/<Tab>f<Tab>conn db<Enter>");
+    assert!(h.shows("[x] fi-- conn db"));
+    assert!(h.shows("Connection to db-1 refused"));
+    assert!(!h.shows("db connection ok") && !h.shows("INFO idle"));
+    // Only the characters matched are highlighted: "Conn" and the "d" and "b" of "db-1"
+    let (x, y) = h.find("Connection").unwrap();
+    assert_eq!(h.cell(x, y).fg, Color::Yellow);
+    assert_ne!(h.cell(x + 4, y).fg, Color::Yellow);
+    assert_eq!(h.cell(x + 14, y).fg, Color::Yellow);
+}
+
+#[test]
+fn test_range_selection_keys() {
+    let mut h = Harness::new(120, 30);
+    h.source("app.log", &["ERROR one", "INFO two", "ERROR three"]);
+    h.keys("/ERROR<Enter><Tab>/");
+    h.keys("Vk");
+    assert!(h.shows("RANGE 2 lines, 1 hidden left out (h)"));
+    let (x, y) = h.find("ERROR one").unwrap();
+    assert_eq!(h.cell(x, y).bg, Color::Blue);
+    h.keys("h");
+    assert!(h.shows("RANGE 3 lines incl. hidden (h)"));
+    // Esc drops the range instead of quitting
+    assert!(h.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)).is_none());
+    assert!(!h.shows("RANGE"));
+    assert_ne!(h.cell(x, y).bg, Color::Blue);
+    h.keys("Vjy");
+    assert_eq!(h.state.clipboard.as_deref(), Some("ERROR one\nERROR three"));
+    assert!(h.shows("copied 2 lines to the clipboard"));
+}
+
+#[test]
+fn test_link_chooser() {
+    let mut h = Harness::new(120, 30);
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("linked.log");
+    std::fs::write(&log, "").unwrap();
+    let line = format!("ERROR see https://status.example.com/incidents/42 and {}", log.display());
+    h.source("app.log", &[&line, "INFO idle"]);
+    h.keys("U");
+    assert!(h.shows("no URL or file path in the selected line"));
+    h.keys("kU");
+    assert!(h.shows("Open link - Enter:open o:as source y:copy Esc:close"));
+    assert!(h.shows("browser https://status.example.com/incidents/42"));
+    assert!(h.shows(&format!("source  {}", log.display())));
+    h.keys("jy");
+    assert!(h.state.link_chooser.is_none());
+    assert_eq!(h.state.clipboard, Some(log.display().to_string()));
+    // Log files are opened as sources by the runtime
+    h.keys("U<Down>");
+    let open = h.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    assert!(matches!(open, Some(UiEvent::OpenLink(false))));
+}